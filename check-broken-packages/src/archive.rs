@@ -0,0 +1,195 @@
+//! Analysis of built package archives, not installed yet, against the libraries of the system, so
+//! that AUR maintainers can validate a build, and AUR helpers warn about broken installs, before
+//! installing them
+
+use std::collections::BTreeSet;
+use std::env;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use crate::elf::Resolver;
+use crate::report::Finding;
+use crate::system::SystemProvider;
+
+/// Metadata file of package archives
+const PKGINFO_FILENAME: &str = ".PKGINFO";
+
+/// Temporary directory removed when dropped
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Get package name of .PKGINFO content
+fn pkginfo_name(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .find(|(k, _v)| k.trim() == "pkgname")
+        .map(|(_k, v)| v.trim())
+}
+
+/// Return true if a library missing from the system is in an extracted package, in one of the
+/// directories searched for it
+fn is_bundled(extract_dir: &Path, searched: &[PathBuf], soname: &str) -> bool {
+    searched.iter().any(|d| {
+        extract_dir
+            .join(d.strip_prefix("/").unwrap_or(d))
+            .join(soname)
+            .is_file()
+    })
+}
+
+/// Package archive extracted to a temporary directory
+struct ExtractedPackage {
+    name: String,
+    dir: TempDir,
+}
+
+/// Extract a package archive (any compression bsdtar supports) to a temporary directory
+fn extract(archive: &Path, index: usize) -> Result<ExtractedPackage, Box<dyn error::Error>> {
+    let extract_dir = TempDir(env::temp_dir().join(format!(
+        "{}-analyze-{}-{}",
+        env!("CARGO_PKG_NAME"),
+        process::id(),
+        index
+    )));
+    fs::create_dir_all(&extract_dir.0)?;
+    let output = Command::new("bsdtar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(&extract_dir.0)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to extract package archive {:?}: {}",
+            archive,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    let pkginfo = fs::read_to_string(extract_dir.0.join(PKGINFO_FILENAME))
+        .map_err(|e| format!("Not a package archive {:?}: {}", archive, e))?;
+    let name = pkginfo_name(&pkginfo)
+        .ok_or_else(|| format!("No package name in {:?}", archive))?
+        .to_string();
+    Ok(ExtractedPackage {
+        name,
+        dir: extract_dir,
+    })
+}
+
+/// Get files of package archives missing a shared library on the system at a root directory,
+/// once installed together, their files being parsed by a system provider
+pub fn analyze(
+    archives: &[PathBuf],
+    root: &Path,
+    system: &dyn SystemProvider,
+) -> Result<Vec<Finding>, Box<dyn error::Error>> {
+    let packages = archives
+        .iter()
+        .enumerate()
+        .map(|(i, a)| extract(a, i))
+        .collect::<Result<Vec<_>, _>>()?;
+    let resolver = Resolver::with_root(root);
+    let mut findings = Vec::new();
+    for package in &packages {
+        findings.extend(analyze_package(package, &packages, &resolver, system)?);
+    }
+    Ok(findings)
+}
+
+/// Get sonames that package archives need, and that neither the system at a root directory nor
+/// the archives themselves provide: installing them gives immediately broken files
+pub fn missing_sonames(
+    archives: &[PathBuf],
+    root: &Path,
+    system: &dyn SystemProvider,
+) -> Result<BTreeSet<String>, Box<dyn error::Error>> {
+    Ok(analyze(archives, root, system)?
+        .into_iter()
+        .filter_map(|f| match f {
+            Finding::MissingDependency { dependency, .. } => Some(dependency),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Get files of an extracted package missing a shared library, neither on the system nor in the
+/// packages installed with it
+fn analyze_package(
+    package: &ExtractedPackage,
+    packages: &[ExtractedPackage],
+    resolver: &Resolver,
+    system: &dyn SystemProvider,
+) -> Result<Vec<Finding>, Box<dyn error::Error>> {
+    let extract_dir = &package.dir;
+    let pattern = format!("{}/**/*", extract_dir.0.display());
+    let mut findings = Vec::new();
+    for extracted_filepath in glob::glob(&pattern)?.flatten() {
+        let metadata = match fs::symlink_metadata(&extracted_filepath) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let elf_info = match system.elf_info(&extracted_filepath) {
+            Ok(Some(i)) => i,
+            _ => continue,
+        };
+        // Path once installed, to resolve against system libraries
+        let filepath = Path::new("/").join(extracted_filepath.strip_prefix(&extract_dir.0)?);
+        for resolution in resolver.resolve(&elf_info, &filepath) {
+            let searched: Vec<PathBuf> = resolution
+                .searched
+                .iter()
+                .map(|(_s, d)| d.clone())
+                .collect();
+            if resolution.found.is_some()
+                || packages
+                    .iter()
+                    .any(|p| is_bundled(&p.dir.0, &searched, &resolution.soname))
+            {
+                continue;
+            }
+            findings.push(Finding::MissingDependency {
+                package: package.name.clone(),
+                file: filepath.to_string_lossy().to_string(),
+                dependency: resolution.soname,
+                held_back: None,
+                chain: Vec::new(),
+                transitive: None,
+                unshipped: false,
+            });
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_helpers() {
+        assert_eq!(
+            pkginfo_name("# Generated by makepkg\npkgname = foo-bin\npkgbase = foo\n"),
+            Some("foo-bin")
+        );
+        assert_eq!(pkginfo_name("pkgver = 1.0-1\n"), None);
+
+        let tmp_dir = tempdir::TempDir::new("").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("opt/foo/lib")).unwrap();
+        fs::write(tmp_dir.path().join("opt/foo/lib/libfoo.so.1"), b"").unwrap();
+        let searched = [PathBuf::from("/opt/foo/lib"), PathBuf::from("/usr/lib")];
+        assert!(is_bundled(tmp_dir.path(), &searched, "libfoo.so.1"));
+        assert!(!is_bundled(tmp_dir.path(), &searched, "libbar.so.1"));
+    }
+}
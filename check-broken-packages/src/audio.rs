@@ -0,0 +1,43 @@
+//! Audio plugins (LV2, VST, LADSPA...)
+
+use std::path::Path;
+
+/// Standard audio plugin directories, with their plugin format
+const AUDIO_PLUGIN_DIRS: [(&str, &str); 7] = [
+    ("/usr/lib/clap", "CLAP"),
+    ("/usr/lib/dssi", "DSSI"),
+    ("/usr/lib/ladspa", "LADSPA"),
+    ("/usr/lib/lv2", "LV2"),
+    ("/usr/lib/lxvst", "VST"),
+    ("/usr/lib/vst", "VST"),
+    ("/usr/lib/vst3", "VST3"),
+];
+
+/// Get audio plugin format of a file, if it is in a standard audio plugin directory
+pub fn plugin_format(path: &Path) -> Option<&'static str> {
+    AUDIO_PLUGIN_DIRS
+        .iter()
+        .find(|(dir, _)| path.starts_with(dir))
+        .map(|(_, format)| *format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_format() {
+        assert_eq!(
+            plugin_format(Path::new("/usr/lib/lv2/foo.lv2/foo.so")),
+            Some("LV2")
+        );
+        assert_eq!(
+            plugin_format(Path::new(
+                "/usr/lib/vst3/foo.vst3/Contents/x86_64-linux/foo.so"
+            )),
+            Some("VST3")
+        );
+        assert_eq!(plugin_format(Path::new("/usr/lib/vst3foo/foo.so")), None);
+        assert_eq!(plugin_format(Path::new("/usr/lib/libfoo.so")), None);
+    }
+}
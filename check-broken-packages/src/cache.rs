@@ -0,0 +1,34 @@
+//! Persistent cache of check results between runs
+
+use std::error;
+
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::format::{self, FormatError};
+use crate::paths::cache_dir;
+
+/// Load cached value stored in a format version, if any. Values stored in other versions are
+/// discarded rather than migrated, since they can be computed again.
+pub fn load<T: DeserializeOwned>(name: &str, format_version: u32) -> Option<T> {
+    let filepath = cache_dir()?.join(format!("{}.json", name));
+    match format::read(&filepath, format_version, |_, _| None) {
+        Ok(v) => Some(v),
+        Err(FormatError::Io(_)) => None,
+        Err(err) => {
+            debug!("Ignoring invalid cache file {:?}: {}", filepath, err);
+            None
+        }
+    }
+}
+
+/// Store value in cache, in a format version
+pub fn store<T: Serialize>(
+    name: &str,
+    format_version: u32,
+    value: &T,
+) -> Result<(), Box<dyn error::Error>> {
+    let dir = cache_dir().ok_or_else(|| simple_error::SimpleError::new("No cache directory"))?;
+    format::write(&dir.join(format!("{}.json", name)), format_version, value)
+}
@@ -0,0 +1,100 @@
+//! Categories of findings, encoded in the exit code with --exit-bitmask so that scripts can react
+//! differently to stale Python or Perl directories and to missing libraries, for example
+
+use crate::report::Finding;
+
+/// Category of findings, by check (code family)
+pub struct Category {
+    /// Bit of the exit code, above those of the other exit codes (1 to 3)
+    pub bit: i32,
+    /// Checks of the category, empty for the category of all other checks
+    checks: &'static [&'static str],
+}
+
+/// All categories, the last one has the findings of checks not in the others
+pub const CATEGORIES: [Category; 5] = [
+    // Missing libraries
+    Category {
+        bit: 4,
+        checks: &["lib", "aud", "dep", "ldr", "wine", "gpu"],
+    },
+    // Stale Python and Perl directories
+    Category {
+        bit: 8,
+        checks: &["py", "pl"],
+    },
+    // Broken references of system files (units, modules, hooks...) to programs
+    Category {
+        bit: 16,
+        checks: &[
+            "sd", "nmh", "pam", "nss", "init", "cron", "xdg", "lnk", "java", "unit", "polkit",
+            "dbus", "udev", "gfx", "ld", "preload", "env",
+        ],
+    },
+    // Damaged, unreadable or misplaced package files, and stale caches
+    Category {
+        bit: 32,
+        checks: &["bin", "perm", "cap", "arch", "cache", "gs"],
+    },
+    // Build quality, package age and timed out checks
+    Category {
+        bit: 64,
+        checks: &[],
+    },
+];
+
+/// Get category of a finding
+pub fn category(finding: &Finding) -> &'static Category {
+    let check = finding.check();
+    CATEGORIES
+        .iter()
+        .find(|c| c.checks.contains(&check.as_str()))
+        .unwrap_or(&CATEGORIES[CATEGORIES.len() - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category() {
+        let stale = Finding::StalePythonDir {
+            package: "python-foo".to_string(),
+            dir: "/usr/lib/python3.12".to_string(),
+            file_count: 3,
+        };
+        assert_eq!(category(&stale).bit, 8);
+        let stale = Finding::StalePerlLeftovers {
+            dir: "/usr/lib/perl5/5.38".to_string(),
+            file_count: 2,
+            removable: vec!["/usr/lib/perl5/5.38".to_string()],
+        };
+        assert_eq!(category(&stale).bit, 8);
+        let timed_out = Finding::CheckTimedOut {
+            check: "debug-info".to_string(),
+            timeout: 60,
+        };
+        assert_eq!(category(&timed_out).bit, 64);
+
+        // Every check is in a single category, and bits do not overlap
+        for (code, _description) in Finding::CODES.iter() {
+            let check = code
+                .trim_end_matches(|c: char| c.is_ascii_digit())
+                .to_ascii_lowercase();
+            assert!(
+                CATEGORIES
+                    .iter()
+                    .filter(|c| c.checks.contains(&check.as_str()))
+                    .count()
+                    <= 1,
+                "{}",
+                code
+            );
+        }
+        let bits = CATEGORIES.iter().fold(0, |m, c| {
+            assert_eq!(m & c.bit, 0);
+            m | c.bit
+        });
+        assert!(bits < 128);
+    }
+}
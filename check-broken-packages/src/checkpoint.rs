@@ -0,0 +1,164 @@
+//! Checkpoint of the package dependency analysis progress, so that a long analysis interrupted (for
+//! example by a reboot) resumes where it stopped on the next run
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::format;
+use crate::report::Finding;
+
+/// Format version of the checkpoint file
+const FORMAT_VERSION: u32 = 1;
+
+/// Minimum interval between two checkpoint file writes
+const WRITE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Checkpoint file content
+#[derive(Serialize, Deserialize, Default)]
+struct CheckpointData {
+    /// Packages whose files have all been analyzed
+    done_packages: HashSet<String>,
+
+    /// Findings of done packages
+    findings: Vec<Finding>,
+}
+
+struct CheckpointState {
+    data: CheckpointData,
+
+    /// Findings of packages being analyzed, only saved when the package is done
+    pending_findings: HashMap<String, Vec<Finding>>,
+
+    last_write: Instant,
+}
+
+pub struct Checkpoint {
+    filepath: PathBuf,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// Load checkpoint file if it exists and is newer than a date (like the last package
+    /// database change), otherwise start an empty checkpoint
+    pub fn load(filepath: &Path, valid_after: SystemTime) -> Checkpoint {
+        let is_valid = fs::metadata(filepath)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t > valid_after);
+        let data = if is_valid {
+            // Checkpoints written before formats were versioned have the same content
+            format::read(filepath, FORMAT_VERSION, |version, value| {
+                (version == 0).then_some(value)
+            })
+            .unwrap_or_else(|err| {
+                eprintln!("Ignoring checkpoint file {:?}: {}", filepath, err);
+                CheckpointData::default()
+            })
+        } else {
+            CheckpointData::default()
+        };
+        Checkpoint {
+            filepath: filepath.to_path_buf(),
+            state: Mutex::new(CheckpointState {
+                data,
+                pending_findings: HashMap::new(),
+                last_write: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn done_packages(&self) -> HashSet<String> {
+        self.state.lock().unwrap().data.done_packages.clone()
+    }
+
+    /// Get findings of packages analyzed by previous runs
+    pub fn findings(&self) -> Vec<Finding> {
+        self.state.lock().unwrap().data.findings.clone()
+    }
+
+    pub fn add_finding(&self, package: &str, finding: &Finding) {
+        self.state
+            .lock()
+            .unwrap()
+            .pending_findings
+            .entry(package.to_string())
+            .or_default()
+            .push(finding.clone());
+    }
+
+    /// Mark package as done, and write checkpoint file if the last write is old enough
+    pub fn package_done(&self, package: &str) {
+        let mut state = self.state.lock().unwrap();
+        let findings = state.pending_findings.remove(package).unwrap_or_default();
+        state.data.findings.extend(findings);
+        state.data.done_packages.insert(package.to_string());
+        if state.last_write.elapsed() >= WRITE_INTERVAL {
+            if let Err(err) = self.write_data(&state.data) {
+                eprintln!(
+                    "Failed to write checkpoint file {:?}: {}",
+                    self.filepath, err
+                );
+            }
+            state.last_write = Instant::now();
+        }
+    }
+
+    /// Write checkpoint file
+    pub fn write(&self) -> Result<(), Box<dyn error::Error>> {
+        let state = self.state.lock().unwrap();
+        self.write_data(&state.data)
+    }
+
+    fn write_data(&self, data: &CheckpointData) -> Result<(), Box<dyn error::Error>> {
+        format::write(&self.filepath, FORMAT_VERSION, data)
+    }
+
+    /// Remove checkpoint file, once the analysis completed
+    pub fn remove(&self) {
+        let _ = fs::remove_file(&self.filepath);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoint() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let filepath = tmp_dir.path().join("checkpoint.json");
+        let finding = |package: &str| Finding::DamagedFile {
+            package: package.to_string(),
+            file: "/usr/bin/foo".to_string(),
+            damage: crate::report::FileDamage::Empty,
+        };
+
+        let checkpoint = Checkpoint::load(&filepath, SystemTime::UNIX_EPOCH);
+        checkpoint.add_finding("foo", &finding("foo"));
+        checkpoint.add_finding("bar", &finding("bar"));
+        checkpoint.package_done("foo");
+        checkpoint.write().unwrap();
+
+        // Only findings of done packages are saved
+        let checkpoint = Checkpoint::load(&filepath, SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            checkpoint.done_packages(),
+            ["foo".to_string()].iter().cloned().collect()
+        );
+        assert_eq!(checkpoint.findings(), [finding("foo")]);
+
+        // Discarded if older than the given date
+        let checkpoint = Checkpoint::load(&filepath, SystemTime::now() + Duration::from_secs(60));
+        assert!(checkpoint.done_packages().is_empty());
+
+        checkpoint.remove();
+        assert!(!filepath.exists());
+    }
+}
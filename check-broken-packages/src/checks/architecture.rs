@@ -0,0 +1,268 @@
+//! ELF files of foreign packages built for another architecture than the package one, which usually
+//! means a binary package was repackaged from the wrong architecture, and ELF files of packages
+//! declared architecture independent
+
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::cross;
+use crate::report::Finding;
+
+/// Architecture of packages that are architecture independent
+const ANY_ARCH: &str = "any";
+
+/// ELF machine of x86-64
+const EM_X86_64: u16 = 62;
+
+/// ELF machine of i386, allowed for 32-bit libraries
+const EM_386: u16 = 3;
+
+/// ELF machine of eBPF programs, loaded by the kernel whatever the architecture
+const EM_BPF: u16 = 247;
+
+/// Directory of 32-bit libraries
+const LIB32_DIR: &str = "/usr/lib32/";
+
+/// Directories of files not run by the system processor
+const FOREIGN_ARCH_DIRS: [&str; 2] = ["/usr/lib/firmware/", "/usr/share/"];
+
+/// ELF machines of the package architectures of Arch Linux and its ports
+const PACKAGE_ARCH_MACHINES: [(&str, u16); 10] = [
+    ("x86_64", EM_X86_64),
+    ("x86_64_v3", EM_X86_64),
+    ("i686", EM_386),
+    ("pentium4", EM_386),
+    ("aarch64", 183),
+    ("armv7h", 40),
+    ("armv6h", 40),
+    ("riscv64", 243),
+    ("loong64", 258),
+    ("powerpc64le", 21),
+];
+
+/// Names of usual ELF machines
+const MACHINE_NAMES: [(u16, &str); 10] = [
+    (EM_386, "i686"),
+    (8, "mips"),
+    (20, "powerpc"),
+    (21, "powerpc64"),
+    (22, "s390"),
+    (40, "arm"),
+    (EM_X86_64, "x86_64"),
+    (183, "aarch64"),
+    (243, "riscv"),
+    (258, "loongarch"),
+];
+
+pub struct ArchitectureCheck;
+
+impl Check for ArchitectureCheck {
+    fn name(&self) -> &'static str {
+        "architecture"
+    }
+
+    fn description(&self) -> &'static str {
+        "ELF files of foreign packages built for another architecture"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let package_archs: HashMap<String, String> = ctx
+            .system
+            .local_packages()?
+            .into_iter()
+            .map(|p| (p.name, p.arch))
+            .collect();
+        // Packages of unknown architecture are expected to be built for the system one
+        let system_machine = PACKAGE_ARCH_MACHINES
+            .iter()
+            .find(|(a, _m)| *a == std::env::consts::ARCH)
+            .map(|(_a, m)| *m);
+
+        // First ELF file and count of ELF files of architecture independent packages
+        let mut any_arch_files: BTreeMap<&str, (&str, u16, usize)> = BTreeMap::new();
+        for (package, file) in ctx.foreign_package_files() {
+            if FOREIGN_ARCH_DIRS.iter().any(|d| file.starts_with(d))
+                || cross::is_cross_toolchain_file(file)
+            {
+                continue;
+            }
+            let package_arch = package_archs.get(package).map_or("", String::as_str);
+            let expected_machine = match package_arch {
+                ANY_ARCH => None,
+                "" => system_machine,
+                a => match PACKAGE_ARCH_MACHINES.iter().find(|(pa, _m)| *pa == a) {
+                    Some((_a, m)) => Some(*m),
+                    None => continue,
+                },
+            };
+            if (package_arch != ANY_ARCH) && expected_machine.is_none() {
+                continue;
+            }
+            let machine = match elf_machine(ctx, Path::new(file)) {
+                Some(m) => m,
+                None => continue,
+            };
+            let expected_machine = match expected_machine {
+                Some(m) => m,
+                None => {
+                    // Only machines that run programs, not firmware or microcontroller files
+                    if MACHINE_NAMES.iter().any(|(m, _n)| *m == machine) {
+                        any_arch_files
+                            .entry(package)
+                            .or_insert((file, machine, 0))
+                            .2 += 1;
+                    }
+                    continue;
+                }
+            };
+            let allowed = (machine == expected_machine)
+                || (machine == EM_BPF)
+                || ((expected_machine == EM_X86_64)
+                    && (machine == EM_386)
+                    && file.starts_with(LIB32_DIR));
+            if !allowed {
+                report(Finding::ForeignArchitecture {
+                    package: package.to_string(),
+                    file: file.to_string(),
+                    architecture: machine_name(machine),
+                    package_architecture: Some(package_arch.to_string()).filter(|a| !a.is_empty()),
+                });
+            }
+        }
+
+        for (package, (file, machine, file_count)) in any_arch_files {
+            report(Finding::ArchitectureSpecificFiles {
+                package: package.to_string(),
+                file: file.to_string(),
+                architecture: machine_name(machine),
+                file_count,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Get ELF machine of a file, None if it is not an ELF file
+fn elf_machine(ctx: &Context<'_>, path: &Path) -> Option<u16> {
+    let metadata = ctx.system.metadata(path).ok()?;
+    if !metadata.is_file {
+        return None;
+    }
+    let head = ctx.system.read_head(path, 20).ok()?;
+    if (head.len() < 20) || !head.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let bytes = [head[18], head[19]];
+    // EI_DATA, 2 for big endian
+    Some(if head[5] == 2 {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn machine_name(machine: u16) -> String {
+    MACHINE_NAMES
+        .iter()
+        .find(|(m, _n)| *m == machine)
+        .map(|(_m, n)| n.to_string())
+        .unwrap_or_else(|| format!("machine {}", machine))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_architecture_check() {
+        let elf = |machine: u16| {
+            let mut header = b"\x7fELF\x02\x01\x01".to_vec();
+            header.resize(18, 0);
+            header.extend(machine.to_le_bytes());
+            header
+        };
+        let system = MockSystem::default()
+            .package(
+                "foo-bin",
+                true,
+                &[
+                    "/opt/foo/foo",
+                    "/opt/foo/libfoo.so",
+                    "/usr/lib32/libfoo.so",
+                    "/usr/aarch64-linux-gnu/lib/libc.so.6",
+                    "/opt/foo/README",
+                ],
+            )
+            .file("/opt/foo/foo", 0o755, &elf(183), &[])
+            .file("/opt/foo/libfoo.so", 0o755, &elf(EM_386), &[])
+            .file("/usr/lib32/libfoo.so", 0o755, &elf(EM_386), &[])
+            .file(
+                "/usr/aarch64-linux-gnu/lib/libc.so.6",
+                0o755,
+                &elf(183),
+                &[],
+            )
+            .file("/opt/foo/README", 0o644, b"foo", &[])
+            .package("bar", true, &["/usr/lib/bar/a.so", "/usr/lib/bar/b.so"])
+            .arch("bar", "any")
+            .file("/usr/lib/bar/a.so", 0o755, &elf(EM_X86_64), &[])
+            .file("/usr/lib/bar/b.so", 0o755, &elf(EM_X86_64), &[])
+            .package("baz-bin", true, &["/opt/baz/baz", "/opt/baz/baz-helper"])
+            .arch("baz-bin", "aarch64")
+            .file("/opt/baz/baz", 0o755, &elf(183), &[])
+            .file("/opt/baz/baz-helper", 0o755, &elf(EM_X86_64), &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        ArchitectureCheck
+            .run(&ctx, &mut |f| findings.push(f))
+            .unwrap();
+        let (foo_findings, findings): (Vec<_>, Vec<_>) =
+            findings.into_iter().partition(|f| f.subject() == "foo-bin");
+        assert_eq!(
+            findings,
+            [
+                Finding::ForeignArchitecture {
+                    package: "baz-bin".to_string(),
+                    file: "/opt/baz/baz-helper".to_string(),
+                    architecture: "x86_64".to_string(),
+                    package_architecture: Some("aarch64".to_string()),
+                },
+                Finding::ArchitectureSpecificFiles {
+                    package: "bar".to_string(),
+                    file: "/usr/lib/bar/a.so".to_string(),
+                    architecture: "x86_64".to_string(),
+                    file_count: 2,
+                },
+            ]
+        );
+        if std::env::consts::ARCH == "x86_64" {
+            assert_eq!(
+                foo_findings,
+                [
+                    Finding::ForeignArchitecture {
+                        package: "foo-bin".to_string(),
+                        file: "/opt/foo/foo".to_string(),
+                        architecture: "aarch64".to_string(),
+                        package_architecture: None,
+                    },
+                    Finding::ForeignArchitecture {
+                        package: "foo-bin".to_string(),
+                        file: "/opt/foo/libfoo.so".to_string(),
+                        architecture: "i686".to_string(),
+                        package_architecture: None,
+                    },
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! XDG autostart entries, programs started with graphical sessions
+
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_command, ini_values, Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+use crate::system::SystemProvider;
+
+/// System wide autostart entries directory
+const AUTOSTART_DIR: &str = "/etc/xdg/autostart";
+
+pub struct AutostartCheck;
+
+impl Check for AutostartCheck {
+    fn name(&self) -> &'static str {
+        "autostart"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken XDG autostart entries"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        check_autostart_dir(Path::new(AUTOSTART_DIR), ctx, report);
+        Ok(())
+    }
+}
+
+/// Check programs of all desktop entries in a directory
+fn check_autostart_dir(dir: &Path, ctx: &Context<'_>, report: &mut dyn FnMut(Finding)) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    let mut entry_filepaths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "desktop"))
+        .collect();
+    entry_filepaths.sort_unstable();
+
+    for entry_filepath in entry_filepaths {
+        let content = match fs::read_to_string(&entry_filepath) {
+            Ok(c) => c,
+            Err(err) => {
+                eprintln!("Failed to read {:?}: {}", entry_filepath, err);
+                continue;
+            }
+        };
+        for (target, problem) in check_entry(ctx.system, &content) {
+            report(Finding::BrokenReference {
+                kind: ReferenceKind::AutostartEntry,
+                package: ctx.owner(&entry_filepath),
+                source: entry_filepath.to_string_lossy().to_string(),
+                target,
+                problem,
+            });
+        }
+    }
+}
+
+/// Get problems with the program of a desktop entry, entries that are hidden or whose TryExec
+/// program is missing are skipped by session managers, and are not checked
+fn check_entry(system: &dyn SystemProvider, content: &str) -> Vec<(String, TargetProblem)> {
+    if ini_values(content, "Hidden").contains(&"true") {
+        return Vec::new();
+    }
+    if ini_values(content, "TryExec")
+        .iter()
+        .any(|p| !check_command(system, p).is_empty())
+    {
+        return Vec::new();
+    }
+    ini_values(content, "Exec")
+        .first()
+        .map(|c| check_command(system, c))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_check_entry() {
+        assert_eq!(
+            check_entry(
+                &MockSystem::default(),
+                "[Desktop Entry]\nType=Application\nExec=/doesnotexist/foo --tray %u\n"
+            ),
+            [("/doesnotexist/foo".to_string(), TargetProblem::Missing)]
+        );
+        assert_eq!(
+            check_entry(
+                &MockSystem::default(),
+                "[Desktop Entry]\nExec=doesnotexist-foo\n"
+            ),
+            [(
+                "doesnotexist-foo".to_string(),
+                TargetProblem::NotFound("/usr/local/sbin:/usr/local/bin:/usr/bin".to_string())
+            )]
+        );
+        assert!(check_entry(
+            &MockSystem::default(),
+            "[Desktop Entry]\nExec=/doesnotexist/foo\nHidden=true\n"
+        )
+        .is_empty());
+        assert!(check_entry(
+            &MockSystem::default(),
+            "[Desktop Entry]\nExec=/doesnotexist/foo\nTryExec=/doesnotexist/foo\n"
+        )
+        .is_empty());
+    }
+}
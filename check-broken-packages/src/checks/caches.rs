@@ -0,0 +1,166 @@
+//! Font, icon and MIME caches, that packages must update with hooks when installing files they
+//! index
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::{Check, Context};
+use crate::report::Finding;
+
+/// Font directory
+const FONTS_DIR: &str = "/usr/share/fonts";
+
+/// Fontconfig cache directory
+const FONTCONFIG_CACHE_DIR: &str = "/var/cache/fontconfig";
+
+/// Icon themes directory
+const ICONS_DIR: &str = "/usr/share/icons";
+
+/// Icon cache filename, in each theme directory
+const ICON_CACHE_FILENAME: &str = "icon-theme.cache";
+
+/// Shared MIME info directories of sources, and of the cache
+const MIME_PACKAGES_DIR: &str = "/usr/share/mime/packages";
+const MIME_DIR: &str = "/usr/share/mime";
+
+pub struct CacheCheck;
+
+impl Check for CacheCheck {
+    fn name(&self) -> &'static str {
+        "caches"
+    }
+
+    fn description(&self) -> &'static str {
+        "stale font, icon and MIME caches"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let files = ctx.foreign_package_files();
+        let mut add_findings = |cache: &str, command: String, counts: Vec<(String, usize)>| {
+            for (package, file_count) in counts {
+                report(Finding::StaleCache {
+                    package,
+                    cache: cache.to_string(),
+                    command: command.clone(),
+                    file_count,
+                });
+            }
+        };
+
+        // Fontconfig has a cache file per font directory, use the newest one
+        let font_cache_mtime = fs::read_dir(FONTCONFIG_CACHE_DIR)
+            .ok()
+            .and_then(|e| e.flatten().filter_map(|e| mtime(&e.path())).max());
+        if let Some(font_cache_mtime) = font_cache_mtime {
+            add_findings(
+                FONTCONFIG_CACHE_DIR,
+                "fc-cache".to_string(),
+                newer_file_counts(&files, FONTS_DIR, font_cache_mtime),
+            );
+        }
+
+        // Icon caches, the update tool skips themes without a cache
+        let theme_dirs: Vec<String> = files
+            .iter()
+            .filter_map(|(_p, f)| f.strip_prefix(ICONS_DIR)?.strip_prefix('/'))
+            .filter_map(|f| f.split_once('/').map(|(theme, _)| theme))
+            .map(|theme| format!("{}/{}", ICONS_DIR, theme))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        for theme_dir in theme_dirs {
+            ctx.check_deadline()?;
+            let cache = format!("{}/{}", theme_dir, ICON_CACHE_FILENAME);
+            if let Some(cache_mtime) = mtime(Path::new(&cache)) {
+                let counts = newer_file_counts(&files, &theme_dir, cache_mtime);
+                add_findings(
+                    &cache,
+                    format!("gtk-update-icon-cache {}", theme_dir),
+                    counts,
+                );
+            }
+        }
+
+        // Shared MIME info cache
+        ctx.check_deadline()?;
+        let mime_cache = format!("{}/mime.cache", MIME_DIR);
+        if let Some(mime_cache_mtime) = mtime(Path::new(&mime_cache)) {
+            add_findings(
+                &mime_cache,
+                format!("update-mime-database {}", MIME_DIR),
+                newer_file_counts(&files, MIME_PACKAGES_DIR, mime_cache_mtime),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Get modification time of a path, without following links
+pub fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::symlink_metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Count files under a directory newer than a cache, for each package, excluding the cache itself
+pub fn newer_file_counts(
+    files: &[(&str, &str)],
+    dir: &str,
+    cache_mtime: SystemTime,
+) -> Vec<(String, usize)> {
+    let prefix = format!("{}/", dir);
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (package, file) in files {
+        if !file.starts_with(&prefix) || file.ends_with(ICON_CACHE_FILENAME) {
+            continue;
+        }
+        if mtime(Path::new(file)).is_some_and(|m| m > cache_mtime) {
+            *counts.entry(package).or_insert(0) += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(p, c)| (p.to_string(), c))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_newer_file_counts() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let dir = tmp_dir.path().join("icons");
+        fs::create_dir(&dir).unwrap();
+        let path = |f: &str| dir.join(f).to_str().unwrap().to_string();
+        let cache_mtime = SystemTime::now() - Duration::from_secs(3600);
+        for (filename, age) in &[("a.png", 60), ("b.png", 7200), (ICON_CACHE_FILENAME, 0)] {
+            let file = fs::File::create(path(filename)).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(*age))
+                .unwrap();
+        }
+
+        let (a, b, cache) = (path("a.png"), path("b.png"), path(ICON_CACHE_FILENAME));
+        let files = [
+            ("foo", a.as_str()),
+            ("foo", b.as_str()),
+            ("foo", cache.as_str()),
+        ];
+        assert_eq!(
+            newer_file_counts(&files, dir.to_str().unwrap(), cache_mtime),
+            [("foo".to_string(), 1)]
+        );
+        assert!(newer_file_counts(&files, "/usr/share/fonts", cache_mtime).is_empty());
+    }
+}
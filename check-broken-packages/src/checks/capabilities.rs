@@ -0,0 +1,233 @@
+//! File capabilities (like those of ping), that are lost when files are copied or restored without
+//! extended attributes
+
+use std::error;
+use std::ffi::CString;
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{Check, Context};
+use crate::pacman;
+use crate::report::Finding;
+use crate::tools::{self, Tool};
+
+/// Pacman package cache directory
+const PACKAGE_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+/// Extended attribute storing file capabilities
+const CAPABILITY_XATTR: &str = "security.capability";
+
+/// Tar block size
+const BLOCK_SIZE: usize = 512;
+
+pub struct CapabilityCheck;
+
+impl Check for CapabilityCheck {
+    fn name(&self) -> &'static str {
+        "capabilities"
+    }
+
+    fn description(&self) -> &'static str {
+        "lost file capabilities"
+    }
+
+    fn required_tools(&self) -> &'static [Tool] {
+        &[tools::BSDTAR]
+    }
+
+    /// Reads all cached archives of installed packages, which is slow
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, version) in pacman::get_installed_packages()? {
+            ctx.check_deadline()?;
+            // Capabilities are only known from the package archive, which is not always cached
+            let archive = match cached_archive(&package, &version) {
+                Some(a) => a,
+                None => continue,
+            };
+            let files = match archive_capability_files(&archive) {
+                Ok(f) => f,
+                Err(err) => {
+                    eprintln!("Failed to read package archive {:?}: {}", archive, err);
+                    continue;
+                }
+            };
+            for file in files {
+                let path = Path::new("/").join(&file);
+                if ctx.config.ignore_path.iter().any(|p| p.matches(&path)) {
+                    continue;
+                }
+                if path.is_file() && !has_capabilities(&path) {
+                    report(Finding::LostCapabilities {
+                        package: package.clone(),
+                        file: path.to_string_lossy().to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find the cached archive of a package version
+fn cached_archive(package: &str, version: &str) -> Option<PathBuf> {
+    let pattern = format!("{}/{}-{}-*.pkg.tar*", PACKAGE_CACHE_DIR, package, version);
+    glob::glob(&pattern)
+        .ok()?
+        .flatten()
+        .find(|p| !p.to_string_lossy().ends_with(".sig"))
+}
+
+/// Get paths of files with capabilities in a package archive
+fn archive_capability_files(archive: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+    // Let bsdtar decompress whatever the compression is, writing extended attributes as pax headers
+    let mut child = Command::new("bsdtar")
+        .args(["-cf", "-", "--format=pax"])
+        .arg(format!("@{}", archive.display()))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let files = parse_pax_capability_files(io::BufReader::new(child.stdout.take().unwrap()));
+    child.wait()?;
+    Ok(files?)
+}
+
+/// Get paths of entries with capabilities in a pax tar stream
+fn parse_pax_capability_files<R: Read>(mut reader: R) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut header = [0; BLOCK_SIZE];
+    let mut pax_path: Option<String> = None;
+    let mut pax_has_capabilities = false;
+    loop {
+        if reader.read_exact(&mut header).is_err() || header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = parse_octal(&header[124..136]);
+        let mut data = vec![0; size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE];
+        reader.read_exact(&mut data)?;
+        match header[156] {
+            b'x' => {
+                for (key, value) in parse_pax_records(&data[..size]) {
+                    if key == "path" {
+                        pax_path = Some(String::from_utf8_lossy(value).to_string());
+                    } else if key.ends_with(&format!("xattr.{}", CAPABILITY_XATTR)) {
+                        pax_has_capabilities = true;
+                    }
+                }
+            }
+            b'g' => {}
+            _ => {
+                if pax_has_capabilities {
+                    let path = pax_path.take().unwrap_or_else(|| {
+                        let name = nul_terminated(&header[0..100]);
+                        let prefix = nul_terminated(&header[345..500]);
+                        if prefix.is_empty() {
+                            name
+                        } else {
+                            format!("{}/{}", prefix, name)
+                        }
+                    });
+                    files.push(path.trim_start_matches("./").to_string());
+                }
+                pax_path = None;
+                pax_has_capabilities = false;
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Parse "<length> <key>=<value>\n" pax records, values can be binary
+fn parse_pax_records(data: &[u8]) -> Vec<(String, &[u8])> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let space = match data[pos..].iter().position(|b| *b == b' ') {
+            Some(s) => pos + s,
+            None => break,
+        };
+        let len: usize = match String::from_utf8_lossy(&data[pos..space]).parse() {
+            Ok(l) if (l > 0) && (pos + l <= data.len()) => l,
+            _ => break,
+        };
+        let record = &data[space + 1..pos + len - 1];
+        if let Some(eq) = record.iter().position(|b| *b == b'=') {
+            records.push((
+                String::from_utf8_lossy(&record[..eq]).to_string(),
+                &record[eq + 1..],
+            ));
+        }
+        pos += len;
+    }
+    records
+}
+
+fn parse_octal(field: &[u8]) -> usize {
+    let s = nul_terminated(field);
+    usize::from_str_radix(s.trim(), 8).unwrap_or(0)
+}
+
+fn nul_terminated(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Return true if a file has a capabilities extended attribute
+fn has_capabilities(path: &Path) -> bool {
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return true,
+    };
+    let name = CString::new(CAPABILITY_XATTR).unwrap();
+    // Safe: both strings are nul terminated, and a null buffer of size 0 only queries the size
+    let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    size > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, size: usize, typeflag: u8) -> Vec<u8> {
+        let mut header = vec![0; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        header[124..135].copy_from_slice(size.as_bytes());
+        header[156] = typeflag;
+        header
+    }
+
+    fn padded(data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        data.resize(data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn test_parse_pax_capability_files() {
+        let pax_data = b"45 SCHILY.xattr.security.capability=\x01\x00\x00\x02\x00\x20\x00\x00\n";
+        let mut tar = Vec::new();
+        tar.extend(header("usr/bin/foo", 3, b'0'));
+        tar.extend(padded(b"foo"));
+        tar.extend(header("PaxHeader/ping", pax_data.len(), b'x'));
+        tar.extend(padded(pax_data));
+        tar.extend(header("usr/bin/ping", 4, b'0'));
+        tar.extend(padded(b"ping"));
+        tar.extend(vec![0; 2 * BLOCK_SIZE]);
+
+        assert_eq!(
+            parse_pax_capability_files(tar.as_slice()).unwrap(),
+            ["usr/bin/ping"]
+        );
+        assert!(!has_capabilities(Path::new("/doesnotexist")));
+    }
+}
@@ -0,0 +1,111 @@
+//! D-Bus activated services and polkit actions, a broken one fails invisibly when an application
+//! needs it
+
+use std::error;
+use std::fs;
+
+use super::{check_command, ini_values, Check, Context};
+use crate::report::{Finding, ReferenceKind};
+
+/// Directories of D-Bus service files, for the session and system buses
+const DBUS_SERVICE_DIRS: [&str; 2] = [
+    "/usr/share/dbus-1/services/",
+    "/usr/share/dbus-1/system-services/",
+];
+
+/// Directory of polkit action policies
+const POLKIT_ACTIONS_DIR: &str = "/usr/share/polkit-1/actions/";
+
+/// Polkit annotation of the program an action runs (with pkexec)
+const POLKIT_EXEC_PATH_ANNOTATION: &str = "org.freedesktop.policykit.exec.path";
+
+pub struct DBusPolkitCheck;
+
+impl Check for DBusPolkitCheck {
+    fn name(&self) -> &'static str {
+        "dbus-polkit"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken D-Bus services and polkit actions"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, file) in ctx.foreign_package_files() {
+            let (kind, commands) = if DBUS_SERVICE_DIRS.iter().any(|d| file.starts_with(d))
+                && file.ends_with(".service")
+            {
+                let content = fs::read_to_string(file).unwrap_or_default();
+                let commands: Vec<String> = ini_values(&content, "Exec")
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                (ReferenceKind::DBusService, commands)
+            } else if file.starts_with(POLKIT_ACTIONS_DIR) && file.ends_with(".policy") {
+                let content = fs::read_to_string(file).unwrap_or_default();
+                (ReferenceKind::PolkitAction, polkit_exec_paths(&content))
+            } else {
+                continue;
+            };
+
+            for command in commands {
+                for (target, problem) in check_command(ctx.system, &command) {
+                    report(Finding::BrokenReference {
+                        kind,
+                        package: package.to_string(),
+                        source: file.to_string(),
+                        target,
+                        problem,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get programs run by actions of a polkit policy, without duplicates
+fn polkit_exec_paths(content: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for (i, _) in content.match_indices(POLKIT_EXEC_PATH_ANNOTATION) {
+        // <annotate key="org.freedesktop.policykit.exec.path">/usr/bin/foo</annotate>
+        let path = content[i..]
+            .split_once('>')
+            .and_then(|(_, rest)| rest.split_once('<'))
+            .map(|(path, _)| path.trim().to_string());
+        if let Some(path) = path {
+            if !path.is_empty() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polkit_exec_paths() {
+        assert_eq!(
+            polkit_exec_paths(
+                "<policyconfig>
+  <action id=\"org.foo.run\">
+    <defaults><allow_active>auth_admin</allow_active></defaults>
+    <annotate key=\"org.freedesktop.policykit.exec.path\">/usr/bin/foo</annotate>
+    <annotate key=\"org.freedesktop.policykit.exec.allow_gui\">true</annotate>
+  </action>
+  <action id=\"org.foo.run2\">
+    <annotate key=\"org.freedesktop.policykit.exec.path\"> /usr/bin/foo </annotate>
+  </action>
+</policyconfig>"
+            ),
+            ["/usr/bin/foo"]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! ELF files of foreign packages without debug information, neither installed from a debug
+//! package nor left in the file, for users who want symbolized backtraces of their crashes.
+//! Debuginfod servers only serve the official repositories packages.
+
+use std::error;
+use std::path::{Path, PathBuf};
+
+use super::{Check, Context};
+use crate::report::Finding;
+
+/// Directory of the debug files installed by debug packages, by path of the file
+const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// Directory of the debug files installed by debug packages, by build-id
+const BUILD_ID_DIR: &str = "/usr/lib/debug/.build-id";
+
+pub struct DebugInfoCheck;
+
+impl Check for DebugInfoCheck {
+    fn name(&self) -> &'static str {
+        "debug-info"
+    }
+
+    fn description(&self) -> &'static str {
+        "ELF files of foreign packages without available debug information"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, file) in ctx.foreign_package_files() {
+            let path = Path::new(file);
+            if !ctx.system.metadata(path).is_ok_and(|m| m.is_file) {
+                continue;
+            }
+            let is_elf = ctx
+                .system
+                .read_head(path, 4)
+                .is_ok_and(|h| h.starts_with(b"\x7fELF"));
+            if !is_elf {
+                continue;
+            }
+            let debug_info = match ctx.system.debug_info(path) {
+                Ok(Some(d)) => d,
+                _ => continue,
+            };
+            if debug_info.has_dwarf
+                || debug_info
+                    .build_id
+                    .as_ref()
+                    .is_some_and(|id| ctx.system.metadata(&debug_file(id)).is_ok())
+                || debug_info
+                    .debug_link
+                    .as_ref()
+                    .is_some_and(|l| ctx.system.metadata(&linked_debug_file(path, l)).is_ok())
+            {
+                continue;
+            }
+            report(Finding::MissingDebugInfo {
+                package: package.to_string(),
+                file: file.to_string(),
+                build_id: debug_info.build_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Get path of the debug file of a build-id, like
+/// /usr/lib/debug/.build-id/ab/cdef.debug for "abcdef"
+fn debug_file(build_id: &str) -> PathBuf {
+    let (dir, name) = build_id.split_at(build_id.len().min(2));
+    Path::new(BUILD_ID_DIR)
+        .join(dir)
+        .join(format!("{}.debug", name))
+}
+
+/// Get path of the debug file of a file with a debug link, like
+/// /usr/lib/debug/usr/bin/foo.debug for /usr/bin/foo linked to "foo.debug"
+fn linked_debug_file(path: &Path, debug_link: &str) -> PathBuf {
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    Path::new(DEBUG_DIR)
+        .join(dir.strip_prefix("/").unwrap_or(dir))
+        .join(debug_link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    /// Build little endian 64-bit ELF shared library, with a dynamic segment, a build-id note
+    /// segment if any, and section names and debug link sections if any
+    fn elf(build_id: Option<&[u8]>, debug_link: Option<&str>) -> Vec<u8> {
+        let segment_count: u16 = if build_id.is_some() { 2 } else { 1 };
+        let note_offset = 64 + 56 * u64::from(segment_count);
+        let mut note = Vec::new();
+        if let Some(id) = build_id {
+            // Name size, description size, NT_GNU_BUILD_ID
+            for v in [4_u32, id.len() as u32, 3] {
+                note.extend(v.to_le_bytes());
+            }
+            note.extend(b"GNU\0");
+            note.extend(id);
+        }
+        let names = b"\0.shstrtab\0.gnu_debuglink\0";
+        let names_offset = note_offset + note.len() as u64;
+        let mut link = Vec::new();
+        if let Some(filename) = debug_link {
+            // Filename, padding, and checksum of the debug file
+            link.extend(filename.as_bytes());
+            link.resize((filename.len() / 4 + 1) * 4, 0);
+            link.extend(0_u32.to_le_bytes());
+        }
+        let link_offset = names_offset + names.len() as u64;
+        let section_headers_offset = link_offset + link.len() as u64;
+        let section_count: u16 = if debug_link.is_some() { 3 } else { 0 };
+
+        let mut data = b"\x7fELF\x02\x01\x01".to_vec();
+        data.resize(16, 0);
+        // ET_DYN, x86-64, version
+        data.extend([3, 0, 62, 0, 1, 0, 0, 0]);
+        // Entry point, program headers offset, section headers offset, flags
+        data.extend(0_u64.to_le_bytes());
+        data.extend(64_u64.to_le_bytes());
+        data.extend(section_headers_offset.to_le_bytes());
+        data.extend(0_u32.to_le_bytes());
+        // Header size, program header size and count, section header size and count, index
+        for v in [64_u16, 56, segment_count, 64, section_count, 1] {
+            data.extend(v.to_le_bytes());
+        }
+        let mut segment = |p_type: u32, offset: u64, size: u64| {
+            data.extend(p_type.to_le_bytes());
+            data.extend(4_u32.to_le_bytes());
+            // Offset, virtual and physical addresses, file and memory sizes, alignment
+            for v in [offset, offset, offset, size, size, 4] {
+                data.extend(v.to_le_bytes());
+            }
+        };
+        // PT_DYNAMIC, empty
+        segment(2, note_offset, 0);
+        if build_id.is_some() {
+            // PT_NOTE
+            segment(4, note_offset, note.len() as u64);
+        }
+        data.extend(note);
+        if debug_link.is_some() {
+            data.extend(names);
+            data.extend(&link);
+            let mut section = |name: u32, sh_type: u32, offset: u64, size: u64| {
+                data.extend(name.to_le_bytes());
+                data.extend(sh_type.to_le_bytes());
+                // Flags, address, offset, size, link, info, alignment, entry size
+                for v in [0, 0, offset, size] {
+                    data.extend(v.to_le_bytes());
+                }
+                data.extend([0; 8]);
+                data.extend(1_u64.to_le_bytes());
+                data.extend(0_u64.to_le_bytes());
+            };
+            // SHT_NULL, SHT_STRTAB, SHT_PROGBITS
+            section(0, 0, 0, 0);
+            section(1, 3, names_offset, names.len() as u64);
+            section(11, 1, link_offset, link.len() as u64);
+        }
+        data
+    }
+
+    #[test]
+    fn test_debug_info_check() {
+        let system = MockSystem::default()
+            .package(
+                "foo",
+                true,
+                &[
+                    "/usr/lib/libfoo.so",
+                    "/usr/lib/libbar.so",
+                    "/usr/lib/libbaz.so",
+                    "/usr/lib/libqux.so",
+                ],
+            )
+            .file(
+                "/usr/lib/libfoo.so",
+                0o755,
+                &elf(Some(&[0xab, 0xcd, 0xef]), None),
+                &[],
+            )
+            .file(
+                "/usr/lib/libbar.so",
+                0o755,
+                &elf(Some(&[0x12, 0x34]), None),
+                &[],
+            )
+            .file("/usr/lib/libbaz.so", 0o755, &elf(None, None), &[])
+            .file(
+                "/usr/lib/libqux.so",
+                0o755,
+                &elf(None, Some("libqux.so.debug")),
+                &[],
+            )
+            .file("/usr/lib/debug/.build-id/ab/cdef.debug", 0o644, b"", &[])
+            .file("/usr/lib/debug/usr/lib/libqux.so.debug", 0o644, b"", &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        DebugInfoCheck.run(&ctx, &mut |f| findings.push(f)).unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding::MissingDebugInfo {
+                    package: "foo".to_string(),
+                    file: "/usr/lib/libbar.so".to_string(),
+                    build_id: Some("1234".to_string()),
+                },
+                Finding::MissingDebugInfo {
+                    package: "foo".to_string(),
+                    file: "/usr/lib/libbaz.so".to_string(),
+                    build_id: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Byte-identical executables installed by several foreign packages, like Electron applications
+//! each shipping the same helper binaries, which waste disk space
+
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::path::Path;
+
+use crossbeam::thread as cb_thread;
+
+use super::{Check, Context};
+use crate::format;
+use crate::report::Finding;
+
+/// Files smaller than that are not worth reporting
+const MIN_SIZE: u64 = 64 * 1024;
+
+/// Foreign package file candidate to deduplication
+struct Candidate<'a> {
+    package: &'a str,
+    file: &'a str,
+    size: u64,
+}
+
+pub struct DuplicateCheck;
+
+impl Check for DuplicateCheck {
+    fn name(&self) -> &'static str {
+        "duplicates"
+    }
+
+    fn description(&self) -> &'static str {
+        "identical executables installed by several foreign packages"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        // Only files of the same size can be identical, so only files sharing their size with a
+        // file of another package are read
+        let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for (package, file) in ctx.foreign_package_files() {
+            match ctx.system.metadata(Path::new(file)) {
+                Ok(m) if m.is_file && (m.mode & 0o111 != 0) && (m.len >= MIN_SIZE) => {
+                    by_size.entry(m.len).or_default().push(Candidate {
+                        package,
+                        file,
+                        size: m.len,
+                    })
+                }
+                _ => {}
+            }
+        }
+        let candidates: Vec<Candidate> = by_size
+            .into_values()
+            .filter(|same_size| same_size.iter().any(|c| c.package != same_size[0].package))
+            .flatten()
+            .collect();
+
+        // Files are hashed in parallel, then files with the same size and checksum are compared
+        // to rule out collisions
+        let jobs = ctx.config.jobs();
+        let chunk_size = candidates.len().div_ceil(jobs).max(1);
+        let checksums: Vec<Option<u32>> = cb_thread::scope(|scope| {
+            let handles: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move |_| {
+                        chunk
+                            .iter()
+                            .map(|c| read(ctx, c).map(|d| format::checksum(&d)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        })
+        .unwrap();
+
+        let mut groups: BTreeMap<(u64, u32), Vec<&Candidate>> = BTreeMap::new();
+        for (candidate, checksum) in candidates.iter().zip(checksums) {
+            if let Some(checksum) = checksum {
+                groups
+                    .entry((candidate.size, checksum))
+                    .or_default()
+                    .push(candidate);
+            }
+        }
+        for group in groups.into_values() {
+            let content = match read(ctx, group[0]) {
+                Some(d) => d,
+                None => continue,
+            };
+            let mut copies: Vec<&Candidate> = vec![group[0]];
+            copies.extend(
+                group[1..]
+                    .iter()
+                    .filter(|c| read(ctx, c).as_ref() == Some(&content)),
+            );
+            if copies.iter().all(|c| c.package == copies[0].package) {
+                continue;
+            }
+            copies.sort_unstable_by_key(|c| c.file);
+            report(Finding::DuplicateExecutable {
+                size: group[0].size,
+                copies: copies
+                    .iter()
+                    .map(|c| (c.package.to_string(), c.file.to_string()))
+                    .collect(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Read whole candidate file
+fn read(ctx: &Context<'_>, candidate: &Candidate) -> Option<Vec<u8>> {
+    ctx.system
+        .read_head(Path::new(candidate.file), candidate.size as usize)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_duplicate_check() {
+        let content = vec![1; MIN_SIZE as usize];
+        let mut other_content = content.clone();
+        other_content[0] = 2;
+        let system = MockSystem::default()
+            .package("foo", true, &["/opt/foo/driver", "/opt/foo/driver-copy"])
+            .package("bar", true, &["/opt/bar/driver", "/opt/bar/data"])
+            .package("baz", true, &["/opt/baz/driver"])
+            .file("/opt/foo/driver", 0o755, &content, &[])
+            .file("/opt/foo/driver-copy", 0o755, &other_content, &[])
+            .file("/opt/bar/driver", 0o755, &content, &[])
+            .file("/opt/bar/data", 0o644, &content, &[])
+            .file("/opt/baz/driver", 0o755, &other_content[..100], &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        DuplicateCheck.run(&ctx, &mut |f| findings.push(f)).unwrap();
+        assert_eq!(
+            findings,
+            [Finding::DuplicateExecutable {
+                size: MIN_SIZE,
+                copies: vec![
+                    ("bar".to_string(), "/opt/bar/driver".to_string()),
+                    ("foo".to_string(), "/opt/foo/driver".to_string()),
+                ],
+            }]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Login shell profiles (/etc/profile.d) and systemd environment generators configuration
+//! (environment.d) adding directories of removed packages to PATH or LD_LIBRARY_PATH
+
+use std::error;
+use std::fs;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Glob patterns of shell profiles, and of environment.d files
+const PROFILE_PATTERN: &str = "/etc/profile.d/*.sh";
+const ENVIRONMENT_D_PATTERNS: [&str; 2] =
+    ["/etc/environment.d/*.conf", "/usr/lib/environment.d/*.conf"];
+
+/// Variables of directory lists
+const PATH_VARS: [&str; 2] = ["PATH", "LD_LIBRARY_PATH"];
+
+/// Functions of /etc/profile adding a directory to PATH
+const PATH_FUNCTIONS: [&str; 2] = ["append_path", "prepend_path"];
+
+pub struct EnvironmentCheck;
+
+impl Check for EnvironmentCheck {
+    fn name(&self) -> &'static str {
+        "environment"
+    }
+
+    fn description(&self) -> &'static str {
+        "PATH and LD_LIBRARY_PATH directories of shell profiles and environment.d that do not exist"
+    }
+
+    /// Directories may legitimately be created later, like those of user installed tools
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let patterns = std::iter::once(PROFILE_PATTERN).chain(ENVIRONMENT_D_PATTERNS);
+        for pattern in patterns {
+            for filepath in glob::glob(pattern)?.flatten() {
+                if ctx.config.ignore_path.iter().any(|p| p.matches(&filepath)) {
+                    continue;
+                }
+                let content = match fs::read_to_string(&filepath) {
+                    Ok(c) => c,
+                    Err(_) => continue,
+                };
+                for dir in path_dirs(&content) {
+                    if ctx.system.metadata(Path::new(dir)).is_ok() {
+                        continue;
+                    }
+                    report(Finding::BrokenReference {
+                        kind: ReferenceKind::EnvironmentPath,
+                        package: ctx.owner(&filepath),
+                        source: filepath.to_string_lossy().to_string(),
+                        target: dir.to_string(),
+                        problem: TargetProblem::Missing,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get absolute directories added to PATH or LD_LIBRARY_PATH by a shell profile or an
+/// environment.d file, without duplicates. Directories depending on other variables are skipped,
+/// and so are those the file tests the existence of itself.
+fn path_dirs(content: &str) -> Vec<&str> {
+    let mut dirs = Vec::new();
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .collect();
+    for line in &lines {
+        let line = line.strip_prefix("export ").unwrap_or(line).trim();
+        let value = match line.split_once('=') {
+            Some((var, value)) if PATH_VARS.contains(&var.trim()) => value,
+            _ => match line.split_once(char::is_whitespace) {
+                Some((function, value)) if PATH_FUNCTIONS.contains(&function) => value,
+                _ => continue,
+            },
+        };
+        let value = value
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+        for dir in value.split(':') {
+            let tested = lines.iter().any(|l| l.contains("-d ") && l.contains(dir));
+            if dir.starts_with('/') && !dir.contains('$') && !tested && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_dirs() {
+        assert_eq!(
+            path_dirs(
+                "append_path '/opt/foo/bin'
+export PATH=\"$PATH:/opt/bar/bin:/opt/foo/bin\"
+export LD_LIBRARY_PATH=/opt/bar/lib:${LD_LIBRARY_PATH} # libraries
+if [ -d /opt/baz/bin ]; then
+    PATH=/opt/baz/bin:$PATH
+fi
+export GOPATH=/opt/go
+"
+            ),
+            ["/opt/foo/bin", "/opt/bar/bin", "/opt/bar/lib"]
+        );
+        assert_eq!(path_dirs("PATH=/opt/qux/bin:${PATH}\n"), ["/opt/qux/bin"]);
+    }
+}
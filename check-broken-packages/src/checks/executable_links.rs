@@ -0,0 +1,116 @@
+//! Symbolic links in executable directories, like alternatives managed by some packages, that
+//! point into directories of removed packages
+
+use std::error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::{Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Directories of executables, other usual ones are links to these on Arch Linux
+const EXECUTABLE_DIRS: [&str; 2] = ["/usr/bin", "/usr/local/bin"];
+
+/// Directory of Java environments, whose default one is selected with archlinux-java
+const JVM_DIR: &str = "/usr/lib/jvm";
+
+pub struct ExecutableLinkCheck;
+
+impl Check for ExecutableLinkCheck {
+    fn name(&self) -> &'static str {
+        "executable-links"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken symbolic links in executable directories"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for dir in &EXECUTABLE_DIRS {
+            ctx.check_deadline()?;
+            for (link, target) in broken_links(Path::new(dir)) {
+                let kind = if target.starts_with(JVM_DIR) {
+                    ReferenceKind::JavaExecutableLink
+                } else {
+                    ReferenceKind::ExecutableLink
+                };
+                report(Finding::BrokenReference {
+                    kind,
+                    package: ctx.owner(&link),
+                    source: link.to_string_lossy().to_string(),
+                    target: target.to_string_lossy().to_string(),
+                    problem: TargetProblem::Missing,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get broken symbolic links of a directory sorted by name, with their direct target
+fn broken_links(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut links: Vec<(PathBuf, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_symlink()))
+        .map(|e| e.path())
+        .filter(|p| {
+            p.to_str()
+                .is_some_and(|p| matches!(crate::is_valid_link(p), Ok(false)))
+        })
+        .filter_map(|p| fs::read_link(&p).ok().map(|t| (p, normalize(&dir.join(t)))))
+        .collect();
+    links.sort_unstable();
+    links
+}
+
+/// Remove "." and ".." components of a path, without resolving links since they are broken
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_broken_links() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let dir = tmp_dir.path();
+        fs::create_dir(dir.join("bin")).unwrap();
+        fs::create_dir_all(dir.join("lib/jvm/java-17/bin")).unwrap();
+        fs::write(dir.join("lib/jvm/java-17/bin/java"), "").unwrap();
+        symlink("../lib/jvm/java-17/bin/java", dir.join("bin/java")).unwrap();
+        symlink("../lib/jvm/java-8/bin/javac", dir.join("bin/javac")).unwrap();
+        symlink("/doesnotexist/foo", dir.join("bin/foo")).unwrap();
+
+        assert_eq!(
+            broken_links(&dir.join("bin")),
+            [
+                (dir.join("bin/foo"), PathBuf::from("/doesnotexist/foo")),
+                (dir.join("bin/javac"), dir.join("lib/jvm/java-8/bin/javac")),
+            ]
+        );
+    }
+}
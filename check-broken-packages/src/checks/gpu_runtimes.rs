@@ -0,0 +1,288 @@
+//! GPU compute runtimes (CUDA and ROCm) linked by foreign packages. They break differently from
+//! ordinary libraries: the runtime is upgraded to a new major version while prebuilt packages
+//! need the previous one, or a CUDA runtime needs a newer NVIDIA driver than the installed one.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::pacman::LocalPackage;
+use crate::report::Finding;
+
+/// Libraries of the CUDA toolkit, and of the libraries NVIDIA distributes with it, by name
+/// without the soname version
+const CUDA_LIBRARIES: [&str; 13] = [
+    "libcudart",
+    "libcublas",
+    "libcublasLt",
+    "libcufft",
+    "libcurand",
+    "libcusparse",
+    "libcusolver",
+    "libnvrtc",
+    "libnvJitLink",
+    "libnvToolsExt",
+    "libcupti",
+    "libcudnn",
+    "libnccl",
+];
+
+/// Libraries of ROCm, by name without the soname version
+const ROCM_LIBRARIES: [&str; 16] = [
+    "libamdhip64",
+    "libhiprtc",
+    "libhsa-runtime64",
+    "librocblas",
+    "libhipblas",
+    "libhipblaslt",
+    "librocfft",
+    "libhipfft",
+    "librocrand",
+    "libhiprand",
+    "librocsparse",
+    "libhipsparse",
+    "librocsolver",
+    "libhipsolver",
+    "libMIOpen",
+    "librccl",
+];
+
+/// Libraries whose soname version is the CUDA major version
+const CUDA_RUNTIME_LIBRARIES: [&str; 2] = ["libcudart", "libnvrtc"];
+
+/// Minimum NVIDIA driver version of each CUDA major version, from the CUDA release notes
+const CUDA_DRIVER_VERSIONS: [(u32, &str); 4] = [
+    (10, "410.48"),
+    (11, "450.80.02"),
+    (12, "525.60.13"),
+    (13, "580.65.06"),
+];
+
+/// Library of the NVIDIA driver, its package version is the driver version
+const CUDA_DRIVER_LIBRARY: &str = "libcuda.so.1";
+
+/// Split a soname into the library name and version, like ("libcudart", "12") for
+/// "libcudart.so.12"
+fn split_soname(soname: &str) -> Option<(&str, &str)> {
+    let (name, version) = soname.split_once(".so")?;
+    Some((name, version.strip_prefix('.').unwrap_or(version)))
+}
+
+/// Get GPU compute runtime a library belongs to
+fn runtime(name: &str) -> Option<&'static str> {
+    if CUDA_LIBRARIES.contains(&name) {
+        Some("CUDA")
+    } else if ROCM_LIBRARIES.contains(&name) {
+        Some("ROCm")
+    } else {
+        None
+    }
+}
+
+/// Parse a dotted version into its numbers, like [525, 60, 13] for "525.60.13"
+fn parse_version(version: &str) -> Vec<u32> {
+    version.split('.').map_while(|n| n.parse().ok()).collect()
+}
+
+/// Get upstream version of a package, without the epoch and the package release
+fn upstream_version(version: &str) -> &str {
+    let version = version.split_once(':').map_or(version, |(_e, v)| v);
+    version.rsplit_once('-').map_or(version, |(v, _r)| v)
+}
+
+/// Get installed sonames of the GPU compute libraries, by library name, from the installed file
+/// names. Unversioned development symbolic links are skipped.
+fn installed_libraries(packages: &[LocalPackage]) -> BTreeMap<&str, BTreeSet<&str>> {
+    let mut libraries: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for file_name in packages
+        .iter()
+        .flat_map(|p| &p.files)
+        .filter_map(|f| Path::new(f).file_name()?.to_str())
+    {
+        if let Some((name, version)) = split_soname(file_name) {
+            if runtime(name).is_some() && !version.is_empty() {
+                // Only the major version, like the soname
+                let major_len = version.find('.').unwrap_or(version.len());
+                let soname_len = file_name.len() - version.len() + major_len;
+                libraries
+                    .entry(name)
+                    .or_default()
+                    .insert(&file_name[..soname_len]);
+            }
+        }
+    }
+    libraries
+}
+
+pub struct GpuRuntimeCheck;
+
+impl Check for GpuRuntimeCheck {
+    fn name(&self) -> &'static str {
+        "gpu-runtimes"
+    }
+
+    fn description(&self) -> &'static str {
+        "CUDA and ROCm libraries not matching the installed runtime or driver"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let local_packages = ctx.system.local_packages()?;
+        let installed = installed_libraries(&local_packages);
+        let driver_version = local_packages
+            .iter()
+            .find(|p| {
+                p.files.iter().any(|f| {
+                    Path::new(f)
+                        .file_name()
+                        .is_some_and(|n| n == CUDA_DRIVER_LIBRARY)
+                })
+            })
+            .map(|p| upstream_version(&p.version));
+
+        // Only report each library of a package once
+        let mut reported: HashSet<(&str, String)> = HashSet::new();
+        for (package, file) in ctx.foreign_package_files() {
+            let path = Path::new(file);
+            let is_elf = ctx
+                .system
+                .read_head(path, 4)
+                .is_ok_and(|h| h.starts_with(b"\x7fELF"));
+            if !is_elf {
+                continue;
+            }
+            for (soname, library_path) in ctx.system.linked_libraries(path).unwrap_or_default() {
+                let (name, version) = match split_soname(&soname) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                let runtime = match runtime(name) {
+                    Some(r) => r,
+                    None => continue,
+                };
+                let finding = match library_path {
+                    // Without any version installed, it is only reported as a missing dependency
+                    None => match installed.get(name) {
+                        Some(installed) => Finding::GpuRuntimeMismatch {
+                            package: package.to_string(),
+                            file: file.to_string(),
+                            runtime: runtime.to_string(),
+                            dependency: soname.clone(),
+                            installed: installed.iter().map(|s| s.to_string()).collect(),
+                        },
+                        None => continue,
+                    },
+                    Some(_) => {
+                        // Like "11.0" for CUDA 11
+                        let required_version = version
+                            .split('.')
+                            .next()
+                            .and_then(|m| m.parse::<u32>().ok())
+                            .filter(|_| CUDA_RUNTIME_LIBRARIES.contains(&name))
+                            .and_then(|major| {
+                                CUDA_DRIVER_VERSIONS
+                                    .iter()
+                                    .find(|(m, _v)| *m == major)
+                                    .map(|(_m, v)| *v)
+                            });
+                        match (driver_version, required_version) {
+                            (Some(driver_version), Some(required_version))
+                                if parse_version(driver_version)
+                                    < parse_version(required_version) =>
+                            {
+                                Finding::GpuDriverTooOld {
+                                    package: package.to_string(),
+                                    file: file.to_string(),
+                                    dependency: soname.clone(),
+                                    driver_version: driver_version.to_string(),
+                                    required_version: required_version.to_string(),
+                                }
+                            }
+                            _ => continue,
+                        }
+                    }
+                };
+                if reported.insert((package, soname)) {
+                    report(finding);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_gpu_runtime_check() {
+        let system = MockSystem::default()
+            .package(
+                "cuda",
+                false,
+                &[
+                    "/opt/cuda/lib64/libcudart.so",
+                    "/opt/cuda/lib64/libcudart.so.12",
+                    "/opt/cuda/lib64/libcudart.so.12.4.127",
+                ],
+            )
+            .package("nvidia-470xx-utils", true, &["/usr/lib/libcuda.so.1"])
+            .version("nvidia-470xx-utils", "470.256.02-3")
+            .package("foo", true, &["/usr/bin/foo", "/usr/bin/foo-old"])
+            .file("/usr/bin/foo", 0o755, b"\x7fELF", &[])
+            .linked(
+                "/usr/bin/foo",
+                &[
+                    ("libcudart.so.12", "/opt/cuda/lib64/libcudart.so.12"),
+                    ("libc.so.6", "/usr/lib/libc.so.6"),
+                ],
+            )
+            .file(
+                "/usr/bin/foo-old",
+                0o755,
+                b"\x7fELF",
+                &["libcudart.so.11.0"],
+            )
+            .linked(
+                "/usr/bin/foo-old",
+                &[("libcudart.so.11.0", ""), ("libamdhip64.so.6", "")],
+            );
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        GpuRuntimeCheck
+            .run(&ctx, &mut |f| findings.push(f))
+            .unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding::GpuDriverTooOld {
+                    package: "foo".to_string(),
+                    file: "/usr/bin/foo".to_string(),
+                    dependency: "libcudart.so.12".to_string(),
+                    driver_version: "470.256.02".to_string(),
+                    required_version: "525.60.13".to_string(),
+                },
+                Finding::GpuRuntimeMismatch {
+                    package: "foo".to_string(),
+                    file: "/usr/bin/foo-old".to_string(),
+                    runtime: "CUDA".to_string(),
+                    dependency: "libcudart.so.11.0".to_string(),
+                    installed: vec!["libcudart.so.12".to_string()],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Graphics driver configurations, Vulkan ICDs and layers, EGL vendor libraries, and VA-API or
+//! VDPAU drivers selected in the environment. A broken one usually makes games and video players
+//! fall back to software rendering, or fail to start, after a driver package change.
+
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::{check_library, Check, Context};
+use crate::elf::Resolver;
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Directories of Vulkan ICD and layer manifests, and glvnd EGL vendor manifests
+const MANIFEST_DIRS: [&str; 6] = [
+    "/usr/share/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+    "/usr/share/vulkan/implicit_layer.d",
+    "/usr/share/vulkan/explicit_layer.d",
+    "/etc/vulkan/implicit_layer.d",
+    "/usr/share/glvnd/egl_vendor.d",
+];
+
+/// Files setting environment variables of all sessions
+const ENVIRONMENT_FILEPATH: &str = "/etc/environment";
+const ENVIRONMENT_DIR: &str = "/etc/environment.d";
+
+/// Library of a manifest, only with the fields we need
+#[derive(Deserialize)]
+struct ManifestLibrary {
+    /// Absent for Vulkan meta layers
+    library_path: Option<String>,
+}
+
+/// Vulkan ICD or layer, or EGL vendor manifest
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(rename = "ICD")]
+    icd: Option<ManifestLibrary>,
+    layer: Option<ManifestLibrary>,
+}
+
+pub struct GraphicsDriverCheck;
+
+impl Check for GraphicsDriverCheck {
+    fn name(&self) -> &'static str {
+        "graphics-drivers"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken Vulkan, EGL, VA-API and VDPAU driver configurations"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let resolver = Resolver::new();
+        for dir in &MANIFEST_DIRS {
+            check_manifest_dir(Path::new(dir), &resolver, ctx, report);
+        }
+
+        let mut environment_filepaths = vec![PathBuf::from(ENVIRONMENT_FILEPATH)];
+        if let Ok(entries) = fs::read_dir(ENVIRONMENT_DIR) {
+            let mut filepaths: Vec<_> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "conf"))
+                .collect();
+            filepaths.sort_unstable();
+            environment_filepaths.extend(filepaths);
+        }
+        for filepath in environment_filepaths {
+            if let Ok(content) = fs::read_to_string(&filepath) {
+                check_environment(&filepath, &content, ctx, report);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check libraries of all manifests in a directory
+fn check_manifest_dir(
+    dir: &Path,
+    resolver: &Resolver,
+    ctx: &Context<'_>,
+    report: &mut dyn FnMut(Finding),
+) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    let mut manifest_filepaths: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    manifest_filepaths.sort_unstable();
+
+    for manifest_filepath in manifest_filepaths {
+        if ctx
+            .config
+            .ignore_path
+            .iter()
+            .any(|p| p.matches(&manifest_filepath))
+        {
+            continue;
+        }
+        let manifest: Manifest = match fs::read_to_string(&manifest_filepath)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_str(&c).map_err(|e| e.to_string()))
+        {
+            Ok(m) => m,
+            Err(err) => {
+                eprintln!(
+                    "Failed to parse graphics driver manifest {:?}: {}",
+                    manifest_filepath, err
+                );
+                continue;
+            }
+        };
+        let library_path = match manifest.icd.or(manifest.layer).and_then(|l| l.library_path) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        // Like the Vulkan loader and glvnd: paths are relative to the manifest, and plain file
+        // names are looked up in the library search path
+        let (target, problems) = if library_path.contains('/') {
+            let library_filepath = dir.join(&library_path);
+            (
+                library_filepath.to_string_lossy().to_string(),
+                check_library(ctx.system, &library_filepath),
+            )
+        } else {
+            match resolver.find_system_library(&library_path) {
+                Some(library_filepath) => (
+                    library_filepath.to_string_lossy().to_string(),
+                    check_library(ctx.system, &library_filepath),
+                ),
+                None => (library_path, vec![TargetProblem::Missing]),
+            }
+        };
+        for problem in problems {
+            report(Finding::BrokenReference {
+                kind: ReferenceKind::GraphicsDriver,
+                package: ctx.owner(&manifest_filepath),
+                source: manifest_filepath.to_string_lossy().to_string(),
+                target: target.clone(),
+                problem,
+            });
+        }
+    }
+}
+
+/// Get driver library selected by an environment variable assignment, if it selects a VA-API or
+/// VDPAU driver
+fn driver_library(line: &str) -> Option<PathBuf> {
+    let line = line.trim();
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (name, value) = line.split_once('=')?;
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    if value.is_empty() || value.contains(['$', '/']) {
+        return None;
+    }
+    match name.trim() {
+        "LIBVA_DRIVER_NAME" => Some(PathBuf::from(format!(
+            "/usr/lib/dri/{}_drv_video.so",
+            value
+        ))),
+        "VDPAU_DRIVER" => Some(PathBuf::from(format!(
+            "/usr/lib/vdpau/libvdpau_{}.so.1",
+            value
+        ))),
+        _ => None,
+    }
+}
+
+/// Check VA-API and VDPAU drivers selected by an environment file
+fn check_environment(
+    filepath: &Path,
+    content: &str,
+    ctx: &Context<'_>,
+    report: &mut dyn FnMut(Finding),
+) {
+    for library_filepath in content
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .filter_map(driver_library)
+    {
+        for problem in check_library(ctx.system, &library_filepath) {
+            report(Finding::BrokenReference {
+                kind: ReferenceKind::GraphicsDriver,
+                package: ctx.owner(filepath),
+                source: filepath.to_string_lossy().to_string(),
+                target: library_filepath.to_string_lossy().to_string(),
+                problem,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_check_manifest_dir() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let dir = tmp_dir.path();
+        let nvidia_filepath = dir.join("nvidia_icd.json");
+        fs::write(
+            &nvidia_filepath,
+            "{\"file_format_version\": \"1.0.0\", \"ICD\": {\"library_path\": \"/doesnotexist/libGLX_nvidia.so.0\", \"api_version\": \"1.3.277\"}}",
+        )
+        .unwrap();
+        let layer_filepath = dir.join("layer.json");
+        fs::write(
+            &layer_filepath,
+            "{\"layer\": {\"name\": \"VK_LAYER_foo\", \"library_path\": \"lib/libfoo_layer.so\"}}",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("meta.json"),
+            "{\"layer\": {\"name\": \"VK_LAYER_meta\"}}",
+        )
+        .unwrap();
+        let layer_library_filepath = dir.join("lib/libfoo_layer.so");
+
+        let system = MockSystem::default()
+            .package("nvidia-utils", false, &[nvidia_filepath.to_str().unwrap()])
+            .package("foo-layer", true, &[layer_filepath.to_str().unwrap()])
+            .file(
+                layer_library_filepath.to_str().unwrap(),
+                0o755,
+                b"\x7fELF",
+                &["libbar.so.1"],
+            );
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        check_manifest_dir(dir, &Resolver::new(), &ctx, &mut |f| findings.push(f));
+        assert_eq!(
+            findings,
+            [
+                Finding::BrokenReference {
+                    kind: ReferenceKind::GraphicsDriver,
+                    package: "foo-layer".to_string(),
+                    source: layer_filepath.to_str().unwrap().to_string(),
+                    target: layer_library_filepath.to_str().unwrap().to_string(),
+                    problem: TargetProblem::MissingDependency("libbar.so.1".to_string()),
+                },
+                Finding::BrokenReference {
+                    kind: ReferenceKind::GraphicsDriver,
+                    package: "nvidia-utils".to_string(),
+                    source: nvidia_filepath.to_str().unwrap().to_string(),
+                    target: "/doesnotexist/libGLX_nvidia.so.0".to_string(),
+                    problem: TargetProblem::Missing,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_driver_library() {
+        assert_eq!(
+            driver_library("LIBVA_DRIVER_NAME=iHD"),
+            Some(PathBuf::from("/usr/lib/dri/iHD_drv_video.so"))
+        );
+        assert_eq!(
+            driver_library("export VDPAU_DRIVER=\"va_gl\""),
+            Some(PathBuf::from("/usr/lib/vdpau/libvdpau_va_gl.so.1"))
+        );
+        assert_eq!(driver_library("LIBVA_DRIVER_NAME=$DRIVER"), None);
+        assert_eq!(driver_library("EDITOR=vim"), None);
+    }
+}
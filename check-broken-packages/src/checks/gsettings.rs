@@ -0,0 +1,115 @@
+//! GSettings schemas, GTK applications crash at startup if theirs are not compiled
+
+use std::error;
+use std::path::Path;
+use std::process::Command;
+
+use super::caches::{mtime, newer_file_counts};
+use super::{Check, Context};
+use crate::report::{FileKind, Finding};
+use crate::tools;
+
+/// Schemas directory
+const SCHEMAS_DIR: &str = "/usr/share/glib-2.0/schemas";
+
+/// Compiled schemas filename, in the schemas directory
+const COMPILED_SCHEMAS_FILENAME: &str = "gschemas.compiled";
+
+pub struct GSettingsSchemaCheck;
+
+impl Check for GSettingsSchemaCheck {
+    fn name(&self) -> &'static str {
+        "gsettings"
+    }
+
+    fn description(&self) -> &'static str {
+        "stale or invalid GSettings schemas"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let compiled_schemas = format!("{}/{}", SCHEMAS_DIR, COMPILED_SCHEMAS_FILENAME);
+        let compiled_schemas_mtime = match mtime(Path::new(&compiled_schemas)) {
+            Some(m) => m,
+            // GLib is not installed
+            None => return Ok(()),
+        };
+
+        let files = ctx.foreign_package_files();
+        for (package, file_count) in newer_file_counts(&files, SCHEMAS_DIR, compiled_schemas_mtime)
+        {
+            report(Finding::StaleCache {
+                package,
+                cache: compiled_schemas.clone(),
+                command: format!("glib-compile-schemas {}", SCHEMAS_DIR),
+                file_count,
+            });
+        }
+
+        // Compile without writing anything, to get errors of each schema file. Staleness does not
+        // need the compiler, so only this part is skipped without it.
+        if !tools::GLIB_COMPILE_SCHEMAS.is_available() {
+            return Ok(());
+        }
+        let output = ctx.command_output(
+            Command::new("glib-compile-schemas").args(["--dry-run", SCHEMAS_DIR]),
+        )?;
+        for (file, error) in parse_errors(&String::from_utf8_lossy(&output.stderr)) {
+            report(Finding::InvalidFile {
+                kind: FileKind::GSettingsSchema,
+                package: ctx.owner(Path::new(&file)),
+                file,
+                error,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Get schema files with their first error from glib-compile-schemas output
+fn parse_errors(output: &str) -> Vec<(String, String)> {
+    let prefix = format!("{}/", SCHEMAS_DIR);
+    let mut errors: Vec<(String, String)> = Vec::new();
+    for line in output.lines() {
+        if !line.starts_with(&prefix) {
+            continue;
+        }
+        let (file, error) = match line.split_once(':') {
+            Some(t) => t,
+            None => continue,
+        };
+        // Strip line and column numbers
+        let error = error
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == ':')
+            .trim();
+        if !errors.iter().any(|(f, _e)| f == file) {
+            errors.push((file.to_string(), error.to_string()));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_errors(
+                "/usr/share/glib-2.0/schemas/org.foo.gschema.xml:12:5  Error on line 12 char 5: Element “key” was closed, but the currently open element is “schema”.  This entire file has been ignored.
+/usr/share/glib-2.0/schemas/org.foo.gschema.xml:14:1  Error on line 14 char 1: Unexpected end.
+No schema files found: doing nothing.
+"
+            ),
+            [(
+                "/usr/share/glib-2.0/schemas/org.foo.gschema.xml".to_string(),
+                "Error on line 12 char 5: Element “key” was closed, but the currently open element is “schema”.  This entire file has been ignored.".to_string()
+            )]
+        );
+    }
+}
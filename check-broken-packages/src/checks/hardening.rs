@@ -0,0 +1,124 @@
+//! ELF files of foreign packages built without the usual hardening features (non executable
+//! stack, RELRO, PIE), that distribution packages get from the makepkg compiler flags, for
+//! security conscious users
+
+use std::error;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::report::Finding;
+
+pub struct HardeningCheck;
+
+impl Check for HardeningCheck {
+    fn name(&self) -> &'static str {
+        "hardening"
+    }
+
+    fn description(&self) -> &'static str {
+        "ELF files of foreign packages with an executable stack, or without RELRO or PIE"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, file) in ctx.foreign_package_files() {
+            let path = Path::new(file);
+            if !ctx.system.metadata(path).is_ok_and(|m| m.is_file) {
+                continue;
+            }
+            let is_elf = ctx
+                .system
+                .read_head(path, 4)
+                .is_ok_and(|h| h.starts_with(b"\x7fELF"));
+            if !is_elf {
+                continue;
+            }
+            let issues = match ctx.system.hardening_issues(path) {
+                Ok(Some(i)) if !i.is_empty() => i,
+                _ => continue,
+            };
+            report(Finding::WeakHardening {
+                package: package.to_string(),
+                file: file.to_string(),
+                issues,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::report::HardeningIssue;
+    use crate::system::mock::MockSystem;
+
+    /// Build little endian 64-bit ELF header and program headers
+    fn elf(e_type: u16, segments: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = b"\x7fELF\x02\x01\x01".to_vec();
+        data.resize(16, 0);
+        data.extend(e_type.to_le_bytes());
+        // x86-64, version
+        data.extend([62, 0, 1, 0, 0, 0]);
+        // Entry point, program headers offset, section headers offset, flags
+        data.extend(0_u64.to_le_bytes());
+        data.extend(64_u64.to_le_bytes());
+        data.extend(0_u64.to_le_bytes());
+        data.extend(0_u32.to_le_bytes());
+        // Header size, program header size and count, section header size and count, index
+        for v in [64_u16, 56, segments.len() as u16, 64, 0, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        for (p_type, p_flags) in segments {
+            data.extend(p_type.to_le_bytes());
+            data.extend(p_flags.to_le_bytes());
+            data.resize(data.len() + 48, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn test_hardening_check() {
+        const PT_DYNAMIC: u32 = 2;
+        const PT_GNU_STACK: u32 = 0x6474_e551;
+        const PT_GNU_RELRO: u32 = 0x6474_e552;
+        // ET_EXEC, ET_DYN, ET_REL
+        let hardened = elf(3, &[(PT_DYNAMIC, 6), (PT_GNU_STACK, 6), (PT_GNU_RELRO, 4)]);
+        let weak = elf(2, &[(PT_DYNAMIC, 6), (PT_GNU_STACK, 7)]);
+        let object = elf(1, &[]);
+        let system = MockSystem::default()
+            .package(
+                "foo",
+                true,
+                &["/usr/bin/foo", "/usr/lib/libfoo.so", "/usr/lib/foo.o"],
+            )
+            .file("/usr/bin/foo", 0o755, &weak, &[])
+            .file("/usr/lib/libfoo.so", 0o755, &hardened, &[])
+            .file("/usr/lib/foo.o", 0o644, &object, &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        HardeningCheck.run(&ctx, &mut |f| findings.push(f)).unwrap();
+        assert_eq!(
+            findings,
+            [Finding::WeakHardening {
+                package: "foo".to_string(),
+                file: "/usr/bin/foo".to_string(),
+                issues: vec![
+                    HardeningIssue::ExecutableStack,
+                    HardeningIssue::NoRelro,
+                    HardeningIssue::NoPie
+                ],
+            }]
+        );
+    }
+}
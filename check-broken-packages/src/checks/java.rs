@@ -0,0 +1,126 @@
+//! Java launchers (wrapper scripts and desktop entries) requiring a specific Java environment,
+//! AUR Java applications frequently hardcode one
+
+use std::error;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Directory of Java environments
+const JVM_DIR: &str = "/usr/lib/jvm/";
+
+/// Java environment links managed by archlinux-java, always valid if any environment is installed
+const JVM_DEFAULT_LINKS: [&str; 2] = ["default", "default-runtime"];
+
+/// Maximum size of analyzed launchers, larger files are not wrapper scripts
+const MAX_LAUNCHER_SIZE: u64 = 1 << 20;
+
+pub struct JavaRuntimeCheck;
+
+impl Check for JavaRuntimeCheck {
+    fn name(&self) -> &'static str {
+        "java"
+    }
+
+    fn description(&self) -> &'static str {
+        "missing Java environments required by launchers"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, file) in ctx.foreign_package_files() {
+            ctx.check_deadline()?;
+            let content = match read_launcher(Path::new(file)) {
+                Some(c) => c,
+                None => continue,
+            };
+            // Launchers often try several environments, only report if none is installed
+            let java_dirs = required_java_dirs(&content);
+            if java_dirs.iter().any(|d| Path::new(d).is_dir()) {
+                continue;
+            }
+            for java_dir in java_dirs {
+                report(Finding::BrokenReference {
+                    kind: ReferenceKind::JavaRuntime,
+                    package: package.to_string(),
+                    source: file.to_string(),
+                    target: java_dir,
+                    problem: TargetProblem::Missing,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read content of a file if it is a desktop entry or an executable script
+fn read_launcher(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || (metadata.len() > MAX_LAUNCHER_SIZE) {
+        return None;
+    }
+    let is_desktop_entry = path.extension().is_some_and(|e| e == "desktop");
+    if !is_desktop_entry && (metadata.permissions().mode() & 0o111 == 0) {
+        return None;
+    }
+    let mut content = String::new();
+    fs::File::open(path)
+        .ok()?
+        .read_to_string(&mut content)
+        .ok()?;
+    if is_desktop_entry || content.starts_with("#!") {
+        Some(content)
+    } else {
+        None
+    }
+}
+
+/// Get Java environment directories referenced in a launcher, without duplicates
+fn required_java_dirs(content: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    for (i, _) in content.match_indices(JVM_DIR) {
+        let name: String = content[i + JVM_DIR.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || ['.', '-', '_'].contains(c))
+            .collect();
+        if name.is_empty() || JVM_DEFAULT_LINKS.contains(&name.as_str()) {
+            continue;
+        }
+        let dir = format!("{}{}", JVM_DIR, name);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_java_dirs() {
+        assert_eq!(
+            required_java_dirs(
+                "#!/bin/sh
+export JAVA_HOME=\"/usr/lib/jvm/java-11-openjdk\"
+exec /usr/lib/jvm/java-11-openjdk/bin/java -jar /usr/share/foo/foo.jar \"$@\"
+/usr/lib/jvm/default/bin/java -version
+[ -d /usr/lib/jvm/java-8-openjdk/jre ] && echo
+"
+            ),
+            [
+                "/usr/lib/jvm/java-11-openjdk",
+                "/usr/lib/jvm/java-8-openjdk"
+            ]
+        );
+        assert!(required_java_dirs("[Desktop Entry]\nExec=java -jar foo.jar\n").is_empty());
+    }
+}
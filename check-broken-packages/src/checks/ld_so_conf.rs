@@ -0,0 +1,199 @@
+//! Dynamic linker configuration: directories of /etc/ld.so.conf (and its includes) that do not
+//! exist, directories added by the user that shadow system libraries, and a dynamic linker cache
+//! older than installed libraries. Misconfigured linker paths cause breakage that the packages
+//! themselves do not explain.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::caches::mtime;
+use super::library_damage::is_library;
+use super::{Check, Context};
+use crate::elf::{self, LD_SO_CONF_FILEPATH};
+use crate::plugins::UNOWNED_PACKAGE;
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Directory of the system libraries
+const SYSTEM_LIB_DIR: &str = "/usr/lib";
+
+/// Directories of libraries indexed by the dynamic linker cache
+const CACHED_LIB_DIRS: [&str; 2] = ["/usr/lib/", "/usr/lib32/"];
+
+/// Dynamic linker cache, and the command updating it
+const LD_SO_CACHE_FILEPATH: &str = "/etc/ld.so.cache";
+const LD_SO_CACHE_COMMAND: &str = "ldconfig";
+
+/// Libraries modified more recently are ignored: the cache update of their transaction may still
+/// be pending when run from a hook
+const CACHE_UPDATE_DELAY: Duration = Duration::from_secs(10 * 60);
+
+pub struct LdSoConfCheck;
+
+impl Check for LdSoConfCheck {
+    fn name(&self) -> &'static str {
+        "ld-so-conf"
+    }
+
+    fn description(&self) -> &'static str {
+        "missing or shadowing dynamic linker directories, and stale dynamic linker cache"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let root = ctx.config.root.as_deref().unwrap_or_else(|| Path::new("/"));
+        let system_libraries = library_filenames(&elf::rooted(root, Path::new(SYSTEM_LIB_DIR)));
+        let system_lib_dir = fs::canonicalize(elf::rooted(root, Path::new(SYSTEM_LIB_DIR))).ok();
+
+        let mut seen = HashSet::new();
+        for (file, dir) in elf::ld_so_conf_entries(root) {
+            ctx.check_deadline()?;
+            if !seen.insert(dir.clone()) || ctx.config.ignore_path.iter().any(|p| p.matches(&dir)) {
+                continue;
+            }
+            let package = ctx.owner(&file);
+            let rooted_dir = elf::rooted(root, &dir);
+            if !rooted_dir.is_dir() {
+                report(Finding::BrokenReference {
+                    kind: ReferenceKind::LinkerConfig,
+                    package,
+                    source: file.to_string_lossy().to_string(),
+                    target: dir.to_string_lossy().to_string(),
+                    problem: TargetProblem::Missing,
+                });
+                continue;
+            }
+            // Package directories are expected to have their own libraries, like CUDA ones
+            let user_added = (file == Path::new(LD_SO_CONF_FILEPATH)) || package == UNOWNED_PACKAGE;
+            if !user_added || fs::canonicalize(&rooted_dir).ok() == system_lib_dir {
+                continue;
+            }
+            let shadowing: BTreeSet<String> = library_filenames(&rooted_dir)
+                .intersection(&system_libraries)
+                .cloned()
+                .collect();
+            if let Some(library) = shadowing.iter().next() {
+                report(Finding::ShadowingLibraryDir {
+                    source: file.to_string_lossy().to_string(),
+                    dir: dir.to_string_lossy().to_string(),
+                    library: library.to_string(),
+                    library_count: shadowing.len(),
+                });
+            }
+        }
+
+        if let Some(cache_mtime) = mtime(&elf::rooted(root, Path::new(LD_SO_CACHE_FILEPATH))) {
+            let file_index = ctx.file_index().ok_or("No index of package files")?;
+            let recent = SystemTime::now() - CACHE_UPDATE_DELAY;
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for dir in CACHED_LIB_DIRS {
+                ctx.check_deadline()?;
+                let libraries = file_index
+                    .files
+                    .range(dir.to_string()..)
+                    .take_while(|(f, _p)| f.starts_with(dir))
+                    .filter(|(f, _p)| is_library(&f[dir.len()..]));
+                for (file, package) in libraries {
+                    if mtime(&elf::rooted(root, Path::new(file)))
+                        .is_some_and(|m| (m > cache_mtime) && (m < recent))
+                    {
+                        *counts.entry(package.as_str()).or_insert(0) += 1;
+                    }
+                }
+            }
+            for (package, file_count) in counts {
+                report(Finding::StaleCache {
+                    package: package.to_string(),
+                    cache: LD_SO_CACHE_FILEPATH.to_string(),
+                    command: LD_SO_CACHE_COMMAND.to_string(),
+                    file_count,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get filenames of the shared libraries of a directory
+fn library_filenames(dir: &Path) -> BTreeSet<String> {
+    fs::read_dir(dir)
+        .map(|e| {
+            e.flatten()
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter(|f| is_library(f))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_ld_so_conf_check() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let root = tmp_dir.path();
+        for dir in [
+            "etc/ld.so.conf.d",
+            "usr/lib",
+            "opt/foo/lib",
+            "usr/local/lib",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(
+            root.join("etc/ld.so.conf"),
+            "include /etc/ld.so.conf.d/*.conf\n/usr/local/lib\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("etc/ld.so.conf.d/foo.conf"),
+            "/opt/foo/lib\n/opt/gone/lib\n",
+        )
+        .unwrap();
+        for lib in [
+            "usr/lib/libz.so.1",
+            "usr/local/lib/libz.so.1",
+            "opt/foo/lib/libz.so.1",
+        ] {
+            fs::write(root.join(lib), "").unwrap();
+        }
+        let system = MockSystem::default().package("foo", true, &["/etc/ld.so.conf.d/foo.conf"]);
+        let config = Config {
+            root: Some(root.to_path_buf()),
+            ..Config::default()
+        };
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        LdSoConfCheck.run(&ctx, &mut |f| findings.push(f)).unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding::BrokenReference {
+                    kind: ReferenceKind::LinkerConfig,
+                    package: "foo".to_string(),
+                    source: "/etc/ld.so.conf.d/foo.conf".to_string(),
+                    target: "/opt/gone/lib".to_string(),
+                    problem: TargetProblem::Missing,
+                },
+                Finding::ShadowingLibraryDir {
+                    source: "/etc/ld.so.conf".to_string(),
+                    dir: "/usr/local/lib".to_string(),
+                    library: "libz.so.1".to_string(),
+                    library_count: 1,
+                },
+            ]
+        );
+    }
+}
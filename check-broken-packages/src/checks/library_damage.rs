@@ -0,0 +1,142 @@
+//! Damaged shared libraries of the system library directories, of any package: a single corrupted
+//! library next to libc can break most programs, and be hard to find from their errors
+
+use std::error;
+use std::fs;
+use std::path::Path;
+
+use goblin::elf::{header, Elf};
+
+use super::{Check, Context};
+use crate::report::{FileDamage, Finding};
+
+/// Directories of system shared libraries, not their subdirectories
+const LIBRARY_DIRS: [&str; 2] = ["/usr/lib/", "/usr/lib32/"];
+
+pub struct LibraryDamageCheck;
+
+impl Check for LibraryDamageCheck {
+    fn name(&self) -> &'static str {
+        "library-damage"
+    }
+
+    fn description(&self) -> &'static str {
+        "corrupted or truncated shared libraries in system library directories"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let file_index = ctx.file_index().ok_or("No index of package files")?;
+        for dir in LIBRARY_DIRS {
+            let libraries = file_index
+                .files
+                .range(dir.to_string()..)
+                .take_while(|(f, _p)| f.starts_with(dir))
+                .filter(|(f, _p)| is_library(&f[dir.len()..]));
+            for (file, package) in libraries {
+                let path = Path::new(file);
+                // Symbolic links to a damaged library would report it several times
+                if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+                    || ctx.config.ignore_path.iter().any(|p| p.matches(path))
+                {
+                    continue;
+                }
+                let damage = match ctx.system.damage(path) {
+                    Ok(Some(d)) => Some(d),
+                    Ok(None) => ctx
+                        .system
+                        .read_head(path, header::header64::SIZEOF_EHDR)
+                        .ok()
+                        .filter(|h| is_corrupted(h))
+                        .map(|_h| FileDamage::CorruptedElf),
+                    Err(_) => None,
+                };
+                if let Some(damage) = damage {
+                    report(Finding::DamagedFile {
+                        package: package.to_string(),
+                        file: file.to_string(),
+                        damage,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return true if a filename directly in a library directory is a shared library, like
+/// "libfoo.so" or "libfoo.so.1.2"
+pub fn is_library(filename: &str) -> bool {
+    !filename.contains('/') && (filename.ends_with(".so") || filename.contains(".so."))
+}
+
+/// Return true if the head of a shared library is neither a valid ELF header, nor the text of a
+/// linker script (like libc.so)
+fn is_corrupted(head: &[u8]) -> bool {
+    if head.starts_with(header::ELFMAG) {
+        return Elf::parse_header(head).is_err();
+    }
+    !head
+        .iter()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_library_damage_check() {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(header::header64::SIZEOF_EHDR, 0);
+        let system = MockSystem::default()
+            .package(
+                "glibc",
+                false,
+                &[
+                    "/usr/lib/libc.so",
+                    "/usr/lib/libm.so.6",
+                    "/usr/lib/libz.so.1",
+                    "/usr/lib/libbad.so",
+                    "/usr/lib/libempty.so.1",
+                    "/usr/lib/gconv/zeros.so",
+                ],
+            )
+            .file(
+                "/usr/lib/libc.so",
+                0o644,
+                b"/* GNU ld script */\nGROUP ( /usr/lib/libc.so.6 )\n",
+                &[],
+            )
+            .file("/usr/lib/libm.so.6", 0o755, &elf, &[])
+            .file("/usr/lib/libz.so.1", 0o755, &[0; 64], &[])
+            .file("/usr/lib/libbad.so", 0o755, b"\x7fELF\x07\x07", &[])
+            .file("/usr/lib/libempty.so.1", 0o755, b"", &[])
+            .file("/usr/lib/gconv/zeros.so", 0o755, &[0; 64], &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        LibraryDamageCheck
+            .run(&ctx, &mut |f| findings.push(f))
+            .unwrap();
+        let damaged = |file: &str, damage| Finding::DamagedFile {
+            package: "glibc".to_string(),
+            file: file.to_string(),
+            damage,
+        };
+        assert_eq!(
+            findings,
+            [
+                damaged("/usr/lib/libbad.so", FileDamage::CorruptedElf),
+                damaged("/usr/lib/libempty.so.1", FileDamage::Empty),
+                damaged("/usr/lib/libz.so.1", FileDamage::CorruptedElf),
+            ]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Mkinitcpio configuration, entries referring to removed files make initramfs generation fail
+
+use std::collections::HashSet;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_command, Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Main configuration file
+const MKINITCPIO_CONF_FILEPATH: &str = "/etc/mkinitcpio.conf";
+
+/// Directory of configuration drop-in files
+const MKINITCPIO_CONF_DIR: &str = "/etc/mkinitcpio.conf.d";
+
+/// Directories of hook install scripts, in lookup order
+const HOOK_DIRS: [&str; 2] = ["/etc/initcpio/install", "/usr/lib/initcpio/install"];
+
+/// Directory of kernel modules, with a subdirectory per kernel version
+const KERNEL_MODULES_DIR: &str = "/usr/lib/modules";
+
+/// Kernel modules directory, and names of its modules
+type KernelModules = (PathBuf, HashSet<String>);
+
+pub struct MkinitcpioCheck;
+
+impl Check for MkinitcpioCheck {
+    fn name(&self) -> &'static str {
+        "mkinitcpio"
+    }
+
+    fn description(&self) -> &'static str {
+        "missing mkinitcpio hooks, binaries or modules"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        if !Path::new(MKINITCPIO_CONF_FILEPATH).is_file() {
+            // Mkinitcpio is not installed
+            return Ok(());
+        }
+        let mut config_filepaths = vec![PathBuf::from(MKINITCPIO_CONF_FILEPATH)];
+        if let Ok(entries) = fs::read_dir(MKINITCPIO_CONF_DIR) {
+            let mut drop_in_filepaths: Vec<_> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "conf"))
+                .collect();
+            drop_in_filepaths.sort_unstable();
+            config_filepaths.extend(drop_in_filepaths);
+        }
+        let kernels = installed_kernel_modules()?;
+
+        for config_filepath in config_filepaths {
+            let content = match fs::read_to_string(&config_filepath) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let mut add_finding = |target: String, problem: TargetProblem| {
+                report(Finding::BrokenReference {
+                    kind: ReferenceKind::MkinitcpioEntry,
+                    package: ctx.owner(&config_filepath),
+                    source: config_filepath.to_string_lossy().to_string(),
+                    target,
+                    problem,
+                });
+            };
+
+            for hook in parse_array(&content, "HOOKS") {
+                if !HOOK_DIRS.iter().any(|d| Path::new(d).join(&hook).is_file()) {
+                    add_finding(hook, TargetProblem::NotFound(HOOK_DIRS.join(":")));
+                }
+            }
+
+            for binary in parse_array(&content, "BINARIES") {
+                for (target, problem) in check_command(ctx.system, &binary) {
+                    add_finding(target, problem);
+                }
+            }
+
+            for module in parse_array(&content, "MODULES") {
+                // A trailing '?' marks an optional module
+                if module.ends_with('?') {
+                    continue;
+                }
+                let name = module.replace('-', "_");
+                for (kernel_dir, modules) in &kernels {
+                    if !modules.contains(&name) {
+                        add_finding(
+                            module.clone(),
+                            TargetProblem::NotFound(kernel_dir.to_string_lossy().to_string()),
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get module names (with '_' instead of '-') of each installed kernel, including built-in ones
+fn installed_kernel_modules() -> Result<Vec<KernelModules>, Box<dyn error::Error>> {
+    let mut kernel_dirs: Vec<PathBuf> = fs::read_dir(KERNEL_MODULES_DIR)?
+        .flatten()
+        .map(|e| e.path())
+        // Also excludes leftover directories of removed kernels
+        .filter(|p| p.join("modules.dep").is_file())
+        .collect();
+    kernel_dirs.sort_unstable();
+
+    let mut kernels = Vec::new();
+    for kernel_dir in kernel_dirs {
+        let mut modules = HashSet::new();
+        for (index_filename, separator) in &[("modules.dep", ':'), ("modules.builtin", '\n')] {
+            let content = fs::read_to_string(kernel_dir.join(index_filename)).unwrap_or_default();
+            modules.extend(parse_module_index(&content, *separator));
+        }
+        kernels.push((kernel_dir, modules));
+    }
+    Ok(kernels)
+}
+
+/// Get module names from a "modules.dep" or "modules.builtin" content, whose lines start with a
+/// module path
+fn parse_module_index(content: &str, separator: char) -> impl Iterator<Item = String> + '_ {
+    content.lines().filter_map(move |l| {
+        let path = l.split(separator).next()?;
+        let filename = path.rsplit('/').next()?;
+        let name = filename.split(".ko").next()?;
+        Some(name.replace('-', "_"))
+    })
+}
+
+/// Get values of a shell array (or legacy space separated string) variable, from its last
+/// assignment
+fn parse_array(content: &str, name: &str) -> Vec<String> {
+    let prefix = format!("{}=", name);
+    let mut values = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let value = match line.trim_start().strip_prefix(&prefix) {
+            Some(v) => v,
+            None => continue,
+        };
+        let mut raw = String::new();
+        if let Some(value) = value.strip_prefix('(') {
+            // Array, possibly spanning several lines
+            let mut cur = value.to_string();
+            loop {
+                let cur_uncommented = cur.split('#').next().unwrap();
+                if let Some((inner, _)) = cur_uncommented.split_once(')') {
+                    raw.push_str(inner);
+                    break;
+                }
+                raw.push_str(cur_uncommented);
+                raw.push(' ');
+                cur = match lines.next() {
+                    Some(l) => l.to_string(),
+                    None => break,
+                };
+            }
+        } else {
+            raw.push_str(value.split('#').next().unwrap());
+        }
+        values = raw
+            .split_whitespace()
+            .map(|v| v.trim_matches(|c| c == '"' || c == '\'').to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_array() {
+        let content = "# MODULES=(foo)
+MODULES=(nvidia nvidia-drm? \"i915\")
+BINARIES=()
+HOOKS=(base udev
+       autodetect # comment
+       lvm2 filesystems)
+HOOKS=(base udev lvm2 filesystems)
+COMPRESSION=\"zstd\"
+";
+        assert_eq!(
+            parse_array(content, "MODULES"),
+            ["nvidia", "nvidia-drm?", "i915"]
+        );
+        assert!(parse_array(content, "BINARIES").is_empty());
+        assert_eq!(
+            parse_array(content, "HOOKS"),
+            ["base", "udev", "lvm2", "filesystems"]
+        );
+        assert_eq!(parse_array(content, "COMPRESSION"), ["zstd"]);
+        assert!(parse_array(content, "FILES").is_empty());
+
+        assert_eq!(
+            parse_module_index(
+                "kernel/drivers/md/dm-mod.ko.zst: kernel/lib/foo.ko.zst\nkernel/x/i915.ko:\n",
+                ':'
+            )
+            .collect::<Vec<_>>(),
+            ["dm_mod", "i915"]
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Checks of system files (manifests, configurations...) referencing files from packages
+
+use std::collections::HashSet;
+use std::error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::cross;
+use crate::pacman::FileIndex;
+use crate::plugins::UNOWNED_PACKAGE;
+use crate::report::{Finding, TargetProblem};
+use crate::shipped::ShippedSonames;
+use crate::system::SystemProvider;
+use crate::tools::Tool;
+
+mod architecture;
+mod autostart;
+mod caches;
+mod capabilities;
+mod dbus_polkit;
+mod debug_info;
+mod duplicates;
+mod environment;
+mod executable_links;
+mod gpu_runtimes;
+mod graphics_drivers;
+mod gsettings;
+mod hardening;
+mod java;
+mod ld_so_conf;
+mod library_damage;
+mod mkinitcpio;
+mod native_messaging;
+mod nss;
+mod package_age;
+mod pam;
+mod preload;
+mod scheduled_jobs;
+mod systemd_units;
+mod udev;
+mod undeclared_dependencies;
+mod watchdog;
+mod wine_multilib;
+
+pub use watchdog::run_check;
+
+/// A check run in parallel with the package analysis
+pub trait Check: Sync {
+    /// Name used to enable or disable the check
+    fn name(&self) -> &'static str;
+
+    /// Short description of what the check finds
+    fn description(&self) -> &'static str;
+
+    /// Return true if the check only runs when explicitly enabled
+    fn opt_in(&self) -> bool {
+        false
+    }
+
+    /// External programs the check runs, it is disabled if one is absent
+    fn required_tools(&self) -> &'static [Tool] {
+        &[]
+    }
+
+    /// Run check, calling report for each finding
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>>;
+}
+
+/// All checks
+pub static CHECKS: [&dyn Check; 27] = [
+    &native_messaging::NativeMessagingHostCheck,
+    &pam::PamModuleCheck,
+    &nss::NssModuleCheck,
+    &mkinitcpio::MkinitcpioCheck,
+    &scheduled_jobs::ScheduledJobCheck,
+    &autostart::AutostartCheck,
+    &executable_links::ExecutableLinkCheck,
+    &java::JavaRuntimeCheck,
+    &caches::CacheCheck,
+    &gsettings::GSettingsSchemaCheck,
+    &systemd_units::SystemdUnitCheck,
+    &dbus_polkit::DBusPolkitCheck,
+    &capabilities::CapabilityCheck,
+    &library_damage::LibraryDamageCheck,
+    &ld_so_conf::LdSoConfCheck,
+    &preload::PreloadCheck,
+    &environment::EnvironmentCheck,
+    &udev::UdevRuleCheck,
+    &architecture::ArchitectureCheck,
+    &undeclared_dependencies::UndeclaredDependencyCheck,
+    &hardening::HardeningCheck,
+    &debug_info::DebugInfoCheck,
+    &duplicates::DuplicateCheck,
+    &package_age::PackageAgeCheck,
+    &graphics_drivers::GraphicsDriverCheck,
+    &gpu_runtimes::GpuRuntimeCheck,
+    &wine_multilib::WineMultilibCheck,
+];
+
+/// Get checks to run, in registration order
+pub fn enabled_checks(config: &Config) -> Result<Vec<&'static dyn Check>, String> {
+    for name in config
+        .enable_checks
+        .iter()
+        .chain(config.disable_checks.iter())
+        .chain(config.check_timeouts.keys())
+    {
+        if !CHECKS.iter().any(|c| c.name() == name) {
+            return Err(format!(
+                "Unknown check {:?}, valid checks are: {}",
+                name,
+                CHECKS
+                    .iter()
+                    .map(|c| c.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    Ok(CHECKS
+        .iter()
+        .filter(|c| {
+            let name = c.name().to_string();
+            !config.disable_checks.contains(&name)
+                && (!c.opt_in() || config.enable_checks.contains(&name))
+        })
+        .copied()
+        .collect())
+}
+
+/// Get the first external program required by a check that is absent, if any
+pub fn missing_tool(check: &dyn Check) -> Option<&'static Tool> {
+    check.required_tools().iter().find(|t| !t.is_available())
+}
+
+/// State shared by all checks
+pub struct Context<'a> {
+    pub config: &'a Config,
+
+    pub system: &'a dyn SystemProvider,
+
+    /// Index of package files, built on first use, shared with contexts derived from this one
+    file_index: Arc<OnceLock<Option<FileIndex>>>,
+
+    /// Names of foreign packages, listed on first use, shared with contexts derived from this one
+    foreign_packages: Arc<OnceLock<HashSet<String>>>,
+
+    /// Sonames shipped by the repositories, read on first use, shared with contexts derived from
+    /// this one
+    shipped_sonames: Arc<OnceLock<Option<ShippedSonames>>>,
+
+    /// Time after which the check run with this context is cancelled, if it has a time budget
+    deadline: Option<Instant>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(config: &'a Config, system: &'a dyn SystemProvider) -> Context<'a> {
+        Context {
+            config,
+            system,
+            file_index: Arc::new(OnceLock::new()),
+            foreign_packages: Arc::new(OnceLock::new()),
+            shipped_sonames: Arc::new(OnceLock::new()),
+            deadline: None,
+        }
+    }
+
+    /// Get context accessing the system through another provider, sharing the indexes of this one
+    pub fn with_system<'b>(&'b self, system: &'b dyn SystemProvider) -> Context<'b> {
+        Context {
+            config: self.config,
+            system,
+            file_index: Arc::clone(&self.file_index),
+            foreign_packages: Arc::clone(&self.foreign_packages),
+            shipped_sonames: Arc::clone(&self.shipped_sonames),
+            deadline: self.deadline,
+        }
+    }
+
+    /// Fail if the deadline of the check has passed. Accesses through `system` fail by themselves,
+    /// checks reading files directly call it in their loops so that they can be cancelled.
+    pub fn check_deadline(&self) -> io::Result<()> {
+        match self.deadline {
+            Some(deadline) => watchdog::check_deadline(deadline),
+            None => Ok(()),
+        }
+    }
+
+    /// Run a command and get its output, killing it if the deadline of the check passes first
+    pub fn command_output(&self, command: &mut Command) -> io::Result<Output> {
+        match self.deadline {
+            Some(deadline) => watchdog::output_before(command, deadline),
+            None => command.output(),
+        }
+    }
+
+    fn file_index(&self) -> Option<&FileIndex> {
+        self.file_index
+            .get_or_init(|| match self.system.file_index() {
+                Ok(i) => Some(i),
+                Err(err) => {
+                    eprintln!("Failed to index package files: {}", err);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// Get sonames shipped by the repositories, None if they could not be read
+    pub fn shipped_sonames(&self) -> Option<&ShippedSonames> {
+        self.shipped_sonames
+            .get_or_init(|| match self.system.shipped_sonames() {
+                Ok(s) => Some(s),
+                Err(err) => {
+                    eprintln!("Failed to read sonames of repository packages: {}", err);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// Get name of package owning a file, or a placeholder if it is not owned
+    pub fn owner(&self, path: &Path) -> String {
+        let file_index = self.file_index();
+        let owner = |p: &Path| {
+            p.to_str()
+                .and_then(|p| file_index.and_then(|i| i.owner(p)))
+                .map(|o| o.to_string())
+        };
+        owner(path)
+            .or_else(|| fs::canonicalize(path).ok().and_then(|p| owner(&p)))
+            .unwrap_or_else(|| UNOWNED_PACKAGE.to_string())
+    }
+
+    /// Build the indexes built on first use now, for long running processes answering queries
+    pub fn warm_up(&self) {
+        self.file_index();
+        self.foreign_packages();
+    }
+
+    /// Get names of foreign packages (typically from the AUR)
+    pub fn foreign_packages(&self) -> &HashSet<String> {
+        self.foreign_packages
+            .get_or_init(|| match self.system.foreign_packages() {
+                Ok(p) => p.into_iter().collect(),
+                Err(err) => {
+                    eprintln!("Failed to list foreign packages: {}", err);
+                    HashSet::new()
+                }
+            })
+    }
+
+    /// Get files of foreign packages with their package name, sorted by path, excluding ignored
+    /// paths and, unless configured otherwise, files of cross compilation toolchains
+    pub fn foreign_package_files(&self) -> Vec<(&str, &str)> {
+        let foreign_packages = self.foreign_packages();
+        self.file_index()
+            .map(|i| {
+                i.files
+                    .iter()
+                    .filter(|(_f, p)| foreign_packages.contains(p.as_str()))
+                    .filter(|(f, _p)| {
+                        !self
+                            .config
+                            .ignore_path
+                            .iter()
+                            .any(|i| i.matches(Path::new(f)))
+                    })
+                    .filter(|(f, _p)| {
+                        self.config.analyze_cross_toolchains || !cross::is_cross_toolchain_file(f)
+                    })
+                    .map(|(f, p)| (p.as_str(), f.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Directories searched for executables referenced by name, like the default PATH
+pub const SYSTEM_PATH_DIRS: [&str; 3] = ["/usr/local/sbin", "/usr/local/bin", "/usr/bin"];
+
+/// Find an executable referenced by name or path, like a shell would with the default PATH
+pub fn find_executable(system: &dyn SystemProvider, name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name));
+    }
+    SYSTEM_PATH_DIRS
+        .iter()
+        .map(|d| Path::new(d).join(name))
+        .find(|p| system.metadata(p).is_ok())
+}
+
+/// Get all values of a key in an INI like file (systemd unit, desktop entry...), in any section
+pub fn ini_values<'a>(content: &'a str, key: &str) -> Vec<&'a str> {
+    content
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .filter(|(k, _v)| k.trim() == key)
+        .map(|(_k, v)| v.trim())
+        .collect()
+}
+
+/// Get program run by a command line, skipping leading environment variable assignments
+pub fn command_program(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|t| !t.contains('='))
+        .map(|t| t.trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Get problems preventing the program of a command line from being run, with the program path
+pub fn check_command(system: &dyn SystemProvider, command: &str) -> Vec<(String, TargetProblem)> {
+    let program = match command_program(command) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    match find_executable(system, program) {
+        Some(program_filepath) => {
+            let program_filepath_str = program_filepath.to_string_lossy().to_string();
+            check_executable(system, &program_filepath)
+                .into_iter()
+                .map(|p| (program_filepath_str.clone(), p))
+                .collect()
+        }
+        None => vec![(
+            program.to_string(),
+            TargetProblem::NotFound(SYSTEM_PATH_DIRS.join(":")),
+        )],
+    }
+}
+
+/// Get problems preventing a file from being executed
+pub fn check_executable(system: &dyn SystemProvider, path: &Path) -> Vec<TargetProblem> {
+    let metadata = match system.metadata(path) {
+        Ok(m) => m,
+        Err(_) => return vec![TargetProblem::Missing],
+    };
+    if !metadata.is_file || (metadata.mode & 0o111 == 0) {
+        return vec![TargetProblem::NotExecutable];
+    }
+
+    if let Some(interpreter) = script_interpreter(system, path) {
+        if !system
+            .metadata(Path::new(&interpreter))
+            .is_ok_and(|m| m.is_file)
+        {
+            return vec![TargetProblem::MissingInterpreter(interpreter)];
+        }
+        return Vec::new();
+    }
+
+    check_library(system, path)
+}
+
+/// Get problems preventing a shared library from being loaded
+pub fn check_library(system: &dyn SystemProvider, path: &Path) -> Vec<TargetProblem> {
+    if !system.metadata(path).is_ok_and(|m| m.is_file) {
+        return vec![TargetProblem::Missing];
+    }
+    match system.missing_dependencies(path) {
+        Ok(missing_deps) => missing_deps
+            .into_iter()
+            .map(TargetProblem::MissingDependency)
+            .collect(),
+        Err(err) => {
+            eprintln!(
+                "Failed to get missing dependencies for path {:?}: {}",
+                path, err
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Get interpreter path from the shebang line of a script
+fn script_interpreter(system: &dyn SystemProvider, path: &Path) -> Option<String> {
+    let head = system.read_head(path, 256).ok()?;
+    let first_line = head.split(|b| *b == b'\n').next()?;
+    String::from_utf8_lossy(first_line)
+        .strip_prefix("#!")
+        .and_then(|l| l.split_whitespace().next())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_check_executable() {
+        let system = MockSystem::default()
+            .package("foo", true, &["/usr/bin/script", "/usr/bin/elf"])
+            .file("/usr/bin/script", 0o755, b"#!/doesnotexist/sh\necho\n", &[])
+            .file("/usr/bin/elf", 0o755, b"\x7fELF", &["libfoo.so.1"])
+            .file("/usr/bin/data", 0o644, b"", &[]);
+        let path = Path::new;
+
+        assert_eq!(
+            check_executable(&system, path("/usr/bin/script")),
+            [TargetProblem::MissingInterpreter(
+                "/doesnotexist/sh".to_string()
+            )]
+        );
+        assert_eq!(
+            check_executable(&system, path("/usr/bin/data")),
+            [TargetProblem::NotExecutable]
+        );
+        assert_eq!(
+            check_executable(&system, path("/usr/bin/missing")),
+            [TargetProblem::Missing]
+        );
+        assert_eq!(
+            check_command(&system, "FOO=1 elf --bar"),
+            [(
+                "/usr/bin/elf".to_string(),
+                TargetProblem::MissingDependency("libfoo.so.1".to_string())
+            )]
+        );
+
+        assert_eq!(
+            ini_values(
+                "[Service]\nExecStart=/usr/bin/foo -a\nExecStart = bar\n",
+                "ExecStart"
+            ),
+            ["/usr/bin/foo -a", "bar"]
+        );
+        assert_eq!(command_program("FOO=1 'foo' --bar"), Some("foo"));
+
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+        assert_eq!(ctx.owner(path("/usr/bin/elf")), "foo");
+        assert_eq!(ctx.owner(path("/usr/bin/data")), UNOWNED_PACKAGE);
+        assert_eq!(ctx.foreign_package_files().len(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+//! Browser native messaging hosts, executables browser extensions talk to
+
+use std::error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::{check_executable, Check, Context};
+use crate::report::{Finding, ReferenceKind};
+
+/// Directories of system wide native messaging host manifests, for Firefox and Chromium based
+/// browsers
+const MANIFEST_DIRS: [&str; 4] = [
+    "/usr/lib/mozilla/native-messaging-hosts",
+    "/etc/chromium/native-messaging-hosts",
+    "/etc/opt/chrome/native-messaging-hosts",
+    "/etc/opt/edge/native-messaging-hosts",
+];
+
+/// Native messaging host manifest, only with the fields we need
+#[derive(Deserialize)]
+struct Manifest {
+    /// Host executable
+    path: String,
+}
+
+pub struct NativeMessagingHostCheck;
+
+impl Check for NativeMessagingHostCheck {
+    fn name(&self) -> &'static str {
+        "native-messaging"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken browser native messaging hosts"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for dir in &MANIFEST_DIRS {
+            check_manifest_dir(Path::new(dir), ctx, report);
+        }
+        Ok(())
+    }
+}
+
+/// Check host executables of all manifests in a directory
+fn check_manifest_dir(dir: &Path, ctx: &Context<'_>, report: &mut dyn FnMut(Finding)) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    let mut manifest_filepaths: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    manifest_filepaths.sort_unstable();
+
+    for manifest_filepath in manifest_filepaths {
+        if ctx
+            .config
+            .ignore_path
+            .iter()
+            .any(|p| p.matches(&manifest_filepath))
+        {
+            continue;
+        }
+        let manifest: Manifest = match fs::read_to_string(&manifest_filepath)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_str(&c).map_err(|e| e.to_string()))
+        {
+            Ok(m) => m,
+            Err(err) => {
+                eprintln!(
+                    "Failed to parse native messaging host manifest {:?}: {}",
+                    manifest_filepath, err
+                );
+                continue;
+            }
+        };
+        // Path must be absolute on Linux, but be tolerant
+        let host_filepath = dir.join(&manifest.path);
+
+        for problem in check_executable(ctx.system, &host_filepath) {
+            report(Finding::BrokenReference {
+                kind: ReferenceKind::NativeMessagingHost,
+                package: ctx.owner(&manifest_filepath),
+                source: manifest_filepath.to_string_lossy().to_string(),
+                target: host_filepath.to_string_lossy().to_string(),
+                problem,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::report::TargetProblem;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_check_manifest_dir() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let dir = tmp_dir.path();
+        let manifest_filepath = dir.join("org.foo.json");
+        fs::write(
+            &manifest_filepath,
+            "{\"name\": \"org.foo\", \"path\": \"/doesnotexist/foo-host\", \"type\": \"stdio\"}",
+        )
+        .unwrap();
+        let host_filepath = dir.join("bar-host");
+        fs::write(dir.join("org.bar.json"), "{\"path\": \"bar-host\"}").unwrap();
+        fs::write(dir.join("README"), "").unwrap();
+
+        let system = MockSystem::default()
+            .package("foo", true, &[manifest_filepath.to_str().unwrap()])
+            .file(host_filepath.to_str().unwrap(), 0o755, b"#!/bin/sh\n", &[])
+            .file("/bin/sh", 0o755, b"\x7fELF", &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        check_manifest_dir(dir, &ctx, &mut |f| findings.push(f));
+        assert_eq!(
+            findings,
+            [Finding::BrokenReference {
+                kind: ReferenceKind::NativeMessagingHost,
+                package: "foo".to_string(),
+                source: manifest_filepath.to_str().unwrap().to_string(),
+                target: "/doesnotexist/foo-host".to_string(),
+                problem: TargetProblem::Missing,
+            }]
+        );
+    }
+}
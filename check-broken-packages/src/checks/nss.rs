@@ -0,0 +1,108 @@
+//! NSS modules, a broken one can prevent resolving users or hosts
+
+use std::error;
+use std::fs;
+use std::path::Path;
+
+use super::{check_library, Check, Context};
+use crate::elf::Resolver;
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Name service switch configuration file
+const NSSWITCH_CONF_FILEPATH: &str = "/etc/nsswitch.conf";
+
+/// Services built into the C library
+const BUILTIN_SERVICES: [&str; 2] = ["files", "dns"];
+
+pub struct NssModuleCheck;
+
+impl Check for NssModuleCheck {
+    fn name(&self) -> &'static str {
+        "nss"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken NSS modules"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let config_filepath = Path::new(NSSWITCH_CONF_FILEPATH);
+        let content = fs::read_to_string(config_filepath)?;
+        let resolver = Resolver::new();
+
+        for service in parse_services(&content) {
+            let soname = format!("libnss_{}.so.2", service);
+            let (target, problems) = match resolver.find_system_library(&soname) {
+                Some(library_filepath) => (
+                    library_filepath.to_string_lossy().to_string(),
+                    check_library(ctx.system, &library_filepath),
+                ),
+                None => (soname, vec![TargetProblem::Missing]),
+            };
+            for problem in problems {
+                report(Finding::BrokenReference {
+                    kind: ReferenceKind::NssModule,
+                    package: ctx.owner(config_filepath),
+                    source: NSSWITCH_CONF_FILEPATH.to_string(),
+                    target: target.clone(),
+                    problem,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get services, each backed by a module, used by a name service switch configuration, without
+/// duplicates
+fn parse_services(content: &str) -> Vec<&str> {
+    let mut services = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap();
+        let sources = match line.split_once(':') {
+            Some((_database, sources)) => sources,
+            None => continue,
+        };
+        let mut in_action = false;
+        for token in sources.split_whitespace() {
+            // Skip "[!UNAVAIL=return]" like actions, that may contain spaces
+            if token.starts_with('[') {
+                in_action = true;
+            }
+            if in_action {
+                in_action = !token.ends_with(']');
+                continue;
+            }
+            if !BUILTIN_SERVICES.contains(&token) && !services.contains(&token) {
+                services.push(token);
+            }
+        }
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_services() {
+        assert_eq!(
+            parse_services(
+                "# Name Service Switch configuration file.
+passwd: files systemd
+group: files [SUCCESS=merge] systemd
+hosts: mymachines resolve [!UNAVAIL=return] files myhostname dns
+netgroup: files
+shadow: files [ NOTFOUND=return ] compat
+"
+            ),
+            ["systemd", "mymachines", "resolve", "myhostname", "compat"]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Foreign packages not installed again (rebuilt or upgraded) for a long time, since old builds
+//! are the most likely to be broken by upgrades of their dependencies
+
+use std::error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Check, Context};
+use crate::report::Finding;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct PackageAgeCheck;
+
+impl Check for PackageAgeCheck {
+    fn name(&self) -> &'static str {
+        "package-age"
+    }
+
+    fn description(&self) -> &'static str {
+        "foreign packages not rebuilt or upgraded for a long time"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let max_age = ctx.config.max_package_age();
+        let foreign_packages = ctx.foreign_packages();
+        let mut old_packages: Vec<(String, u64)> = ctx
+            .system
+            .local_packages()?
+            .into_iter()
+            // Packages without install date are not reported
+            .filter(|p| foreign_packages.contains(&p.name) && (p.install_date > 0))
+            .map(|p| (p.name, now.saturating_sub(p.install_date) / SECONDS_PER_DAY))
+            .filter(|(_p, age_days)| *age_days > max_age)
+            .collect();
+        // Oldest first
+        old_packages.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (package, age_days) in old_packages {
+            report(Finding::OldPackage { package, age_days });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_package_age_check() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let days_ago = |days: u64| now - days * SECONDS_PER_DAY - 60;
+        let system = MockSystem::default()
+            .package("foo", true, &[])
+            .package("bar", true, &[])
+            .package("baz", true, &[])
+            .package("glibc", false, &[])
+            .install_date("foo", days_ago(40))
+            .install_date("bar", days_ago(400))
+            .install_date("baz", days_ago(10))
+            .install_date("glibc", days_ago(1000));
+        let config = Config {
+            max_package_age: Some(30),
+            ..Config::default()
+        };
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        PackageAgeCheck
+            .run(&ctx, &mut |f| findings.push(f))
+            .unwrap();
+        assert_eq!(
+            findings,
+            [
+                Finding::OldPackage {
+                    package: "bar".to_string(),
+                    age_days: 400,
+                },
+                Finding::OldPackage {
+                    package: "foo".to_string(),
+                    age_days: 40,
+                },
+            ]
+        );
+    }
+}
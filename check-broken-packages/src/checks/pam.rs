@@ -0,0 +1,122 @@
+//! PAM modules, a broken one can prevent logging in
+
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_library, Check, Context};
+use crate::report::{Finding, ReferenceKind};
+
+/// PAM configuration directory
+const PAM_CONFIG_DIR: &str = "/etc/pam.d";
+
+/// Directory of modules referenced by a relative path
+const PAM_MODULE_DIR: &str = "/usr/lib/security";
+
+pub struct PamModuleCheck;
+
+impl Check for PamModuleCheck {
+    fn name(&self) -> &'static str {
+        "pam"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken PAM modules"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let mut config_filepaths: Vec<PathBuf> = fs::read_dir(PAM_CONFIG_DIR)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        config_filepaths.sort_unstable();
+
+        for config_filepath in config_filepaths {
+            let content = match fs::read_to_string(&config_filepath) {
+                Ok(c) => c,
+                Err(err) => {
+                    eprintln!("Failed to read {:?}: {}", config_filepath, err);
+                    continue;
+                }
+            };
+            for module in parse_modules(&content) {
+                let module_filepath = Path::new(PAM_MODULE_DIR).join(module);
+                for problem in check_library(ctx.system, &module_filepath) {
+                    report(Finding::BrokenReference {
+                        kind: ReferenceKind::PamModule,
+                        package: ctx.owner(&config_filepath),
+                        source: config_filepath.to_string_lossy().to_string(),
+                        target: module_filepath.to_string_lossy().to_string(),
+                        problem,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get modules referenced by a PAM configuration file, without duplicates, excluding those whose
+/// absence is explicitly tolerated ("-type" lines)
+fn parse_modules(content: &str) -> Vec<&str> {
+    let mut modules = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() || line.starts_with('@') || line.starts_with('-') {
+            continue;
+        }
+        let (_type, rest) = match line.split_once(char::is_whitespace) {
+            Some(t) => t,
+            None => continue,
+        };
+        let rest = rest.trim_start();
+        // Control is either a single keyword or a bracketed list of actions
+        let (control, rest) = if rest.starts_with('[') {
+            match rest.split_once(']') {
+                Some(t) => t,
+                None => continue,
+            }
+        } else {
+            rest.split_once(char::is_whitespace).unwrap_or((rest, ""))
+        };
+        if (control == "include") || (control == "substack") {
+            // Includes another configuration file, which is checked on its own
+            continue;
+        }
+        if let Some(module) = rest.split_whitespace().next() {
+            if !modules.contains(&module) {
+                modules.push(module);
+            }
+        }
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modules() {
+        assert_eq!(
+            parse_modules(
+                "#%PAM-1.0
+auth       required     pam_unix.so     try_first_pass nullok # comment
+auth       [success=1 default=ignore]  pam_succeed_if.so uid >= 1000
+-auth      optional     pam_gnome_keyring.so
+account    include      system-login
+session    required     /opt/foo/pam_foo.so
+@include common-auth
+password   required     pam_unix.so
+"
+            ),
+            ["pam_unix.so", "pam_succeed_if.so", "/opt/foo/pam_foo.so"]
+        );
+    }
+}
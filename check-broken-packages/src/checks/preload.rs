@@ -0,0 +1,109 @@
+//! Libraries preloaded in all programs by /etc/ld.so.preload or LD_PRELOAD, a broken one breaks
+//! every dynamically linked program, and they are easy to forget about
+
+use std::env;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_library, Check, Context};
+use crate::elf::Resolver;
+use crate::plugins::UNOWNED_PACKAGE;
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// Preload configuration file of the dynamic linker
+const LD_SO_PRELOAD_FILEPATH: &str = "/etc/ld.so.preload";
+
+/// Environment variable of preloaded libraries
+const LD_PRELOAD_VAR: &str = "LD_PRELOAD";
+
+pub struct PreloadCheck;
+
+impl Check for PreloadCheck {
+    fn name(&self) -> &'static str {
+        "preload"
+    }
+
+    fn description(&self) -> &'static str {
+        "missing or broken preloaded libraries"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let resolver = Resolver::new();
+        let config_filepath = Path::new(LD_SO_PRELOAD_FILEPATH);
+        let mut sources = Vec::new();
+        if let Ok(content) = fs::read_to_string(config_filepath) {
+            sources.push((
+                LD_SO_PRELOAD_FILEPATH.to_string(),
+                ctx.owner(config_filepath),
+                content,
+            ));
+        }
+        if let Some(value) = env::var_os(LD_PRELOAD_VAR) {
+            sources.push((
+                LD_PRELOAD_VAR.to_string(),
+                UNOWNED_PACKAGE.to_string(),
+                value.to_string_lossy().to_string(),
+            ));
+        }
+
+        for (source, package, content) in sources {
+            for entry in parse_entries(&content) {
+                let library_filepath = if entry.contains('/') {
+                    Some(PathBuf::from(entry))
+                } else {
+                    resolver.find_system_library(entry)
+                };
+                let (target, problems) = match library_filepath {
+                    Some(library_filepath) => (
+                        library_filepath.to_string_lossy().to_string(),
+                        check_library(ctx.system, &library_filepath),
+                    ),
+                    None => (entry.to_string(), vec![TargetProblem::Missing]),
+                };
+                for problem in problems {
+                    report(Finding::BrokenReference {
+                        kind: ReferenceKind::PreloadedLibrary,
+                        package: package.clone(),
+                        source: source.clone(),
+                        target: target.clone(),
+                        problem,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get preloaded libraries of /etc/ld.so.preload content or of LD_PRELOAD, separated by
+/// whitespace or colons, skipping comments and those depending on dynamic string tokens like
+/// $PLATFORM, which are only known to the dynamic linker
+fn parse_entries(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default())
+        .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ':'))
+        .filter(|e| !e.is_empty() && !e.contains('$'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries() {
+        assert_eq!(
+            parse_entries(
+                "# Preloaded libraries\n/usr/lib/libfoo.so libbar.so:/opt/$LIB/libbaz.so\n\n"
+            ),
+            ["/usr/lib/libfoo.so", "libbar.so"]
+        );
+    }
+}
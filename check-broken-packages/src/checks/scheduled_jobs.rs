@@ -0,0 +1,203 @@
+//! Scheduled jobs (cron and systemd timers), a broken one silently fails every time it runs
+
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_command, check_executable, ini_values, Check, Context};
+use crate::report::{Finding, ReferenceKind, TargetProblem};
+
+/// System crontabs, with a user field
+const SYSTEM_CRONTABS: [&str; 2] = ["/etc/crontab", "/etc/cron.d"];
+
+/// User crontabs, without a user field
+const USER_CRONTAB_DIR: &str = "/var/spool/cron";
+
+/// Directories of scripts run periodically by run-parts
+const RUN_PARTS_DIRS: [&str; 4] = [
+    "/etc/cron.hourly",
+    "/etc/cron.daily",
+    "/etc/cron.weekly",
+    "/etc/cron.monthly",
+];
+
+/// Directory of enabled timer links
+const ENABLED_TIMERS_DIR: &str = "/etc/systemd/system/timers.target.wants";
+
+/// Directories of system units, in lookup order
+const UNIT_DIRS: [&str; 2] = ["/etc/systemd/system", "/usr/lib/systemd/system"];
+
+pub struct ScheduledJobCheck;
+
+impl Check for ScheduledJobCheck {
+    fn name(&self) -> &'static str {
+        "scheduled-jobs"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken cron jobs and systemd timers"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let mut add_finding = |source: &Path, target: String, problem: TargetProblem| {
+            report(Finding::BrokenReference {
+                kind: ReferenceKind::ScheduledJob,
+                package: ctx.owner(source),
+                source: source.to_string_lossy().to_string(),
+                target,
+                problem,
+            });
+        };
+        // Crontabs
+        let mut crontabs: Vec<(PathBuf, bool)> = Vec::new();
+        for system_crontab in &SYSTEM_CRONTABS {
+            crontabs.extend(dir_files(Path::new(system_crontab)).map(|f| (f, true)));
+        }
+        crontabs.extend(dir_files(Path::new(USER_CRONTAB_DIR)).map(|f| (f, false)));
+        for (crontab, has_user) in crontabs {
+            let content = match fs::read_to_string(&crontab) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            for command in parse_crontab(&content, has_user) {
+                for (target, problem) in check_command(ctx.system, command) {
+                    add_finding(&crontab, target, problem);
+                }
+            }
+        }
+
+        // Run-parts scripts, those that are not executable are skipped by run-parts
+        for run_parts_dir in &RUN_PARTS_DIRS {
+            for script in dir_files(Path::new(run_parts_dir)) {
+                for problem in check_executable(ctx.system, &script) {
+                    if problem != TargetProblem::NotExecutable {
+                        add_finding(&script, script.to_string_lossy().to_string(), problem);
+                    }
+                }
+            }
+        }
+
+        // Enabled timers
+        for timer_link in dir_files(Path::new(ENABLED_TIMERS_DIR)) {
+            let timer_name = match timer_link.file_name().and_then(|n| n.to_str()) {
+                Some(n) => n.to_string(),
+                None => continue,
+            };
+            let timer_content = find_unit(&timer_name)
+                .and_then(|p| fs::read_to_string(p).ok())
+                .unwrap_or_default();
+            let service_name = ini_values(&timer_content, "Unit")
+                .first()
+                .map(|u| u.to_string())
+                .unwrap_or_else(|| timer_name.replace(".timer", ".service"));
+            let service_filepath = match find_unit(&service_name) {
+                Some(p) => p,
+                None => continue,
+            };
+            let service_content = match fs::read_to_string(&service_filepath) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            for exec_start in ini_values(&service_content, "ExecStart") {
+                // Strip special executable prefixes
+                let command = exec_start.trim_start_matches(['@', '-', ':', '+', '!']);
+                for (target, problem) in check_command(ctx.system, command) {
+                    add_finding(&service_filepath, target, problem);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get files of a directory sorted by name, or the path itself if it is a file
+fn dir_files(path: &Path) -> impl Iterator<Item = PathBuf> {
+    let mut files: Vec<PathBuf> = if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        fs::read_dir(path)
+            .map(|e| {
+                e.flatten()
+                    .map(|e| e.path())
+                    .filter(|p| !p.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    files.sort_unstable();
+    files.into_iter()
+}
+
+/// Find an unit file by name, falling back to the template for instances ("foo@bar.service")
+fn find_unit(name: &str) -> Option<PathBuf> {
+    let mut names = vec![name.to_string()];
+    if let (Some((prefix, _instance)), Some((_, suffix))) =
+        (name.split_once('@'), name.rsplit_once('.'))
+    {
+        names.push(format!("{}@.{}", prefix, suffix));
+    }
+    names
+        .iter()
+        .flat_map(|n| UNIT_DIRS.iter().map(move |d| Path::new(d).join(n)))
+        .find(|p| p.is_file())
+}
+
+/// Get commands of a crontab
+fn parse_crontab(content: &str, has_user: bool) -> Vec<&str> {
+    let mut commands = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap();
+        if !first.starts_with('@') && first.contains('=') {
+            // Environment variable
+            continue;
+        }
+        let time_field_count = if first.starts_with('@') { 1 } else { 5 };
+        let skip_count = time_field_count + if has_user { 1 } else { 0 };
+
+        // Find command start in the line, to keep its original spacing
+        let mut rest = line;
+        for _ in 0..skip_count {
+            rest = match rest.split_once(char::is_whitespace) {
+                Some((_field, rest)) => rest.trim_start(),
+                None => "",
+            };
+        }
+        if !rest.is_empty() {
+            commands.push(rest);
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_crontab() {
+        let content = "SHELL=/bin/bash
+# comment
+*/5 * * * * root /usr/bin/foo --bar >/dev/null
+@daily root  run-parts /etc/cron.daily
+";
+        assert_eq!(
+            parse_crontab(content, true),
+            ["/usr/bin/foo --bar >/dev/null", "run-parts /etc/cron.daily"]
+        );
+        assert_eq!(parse_crontab("0 3 * * 1 backup.sh\n", false), ["backup.sh"]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Systemd units of foreign packages, validated with systemd-analyze
+
+use std::error;
+use std::io;
+use std::process::Command;
+
+use super::{Check, Context};
+use crate::report::{FileKind, Finding};
+use crate::tools::{self, Tool};
+
+/// Directories of units installed by packages, with the systemd-analyze option to verify them
+const UNIT_DIRS: [(&str, Option<&str>); 2] = [
+    ("/usr/lib/systemd/system/", None),
+    ("/usr/lib/systemd/user/", Some("--user")),
+];
+
+/// Unit file extensions
+const UNIT_EXTENSIONS: [&str; 10] = [
+    "automount",
+    "mount",
+    "path",
+    "scope",
+    "service",
+    "slice",
+    "socket",
+    "swap",
+    "target",
+    "timer",
+];
+
+pub struct SystemdUnitCheck;
+
+impl Check for SystemdUnitCheck {
+    fn name(&self) -> &'static str {
+        "systemd-units"
+    }
+
+    fn description(&self) -> &'static str {
+        "invalid systemd units"
+    }
+
+    fn required_tools(&self) -> &'static [Tool] {
+        &[tools::SYSTEMD_ANALYZE]
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (package, file) in ctx.foreign_package_files() {
+            ctx.check_deadline()?;
+            let user_opt = match UNIT_DIRS.iter().find(|(d, _)| file.starts_with(d)) {
+                Some((_, user_opt)) => user_opt,
+                None => continue,
+            };
+            let is_unit = file
+                .rsplit_once('.')
+                .is_some_and(|(_, e)| UNIT_EXTENSIONS.contains(&e));
+            // Templates can not be verified without an instance
+            if !is_unit || file.contains("@.") {
+                continue;
+            }
+
+            let output = match ctx.command_output(
+                Command::new("systemd-analyze")
+                    .arg("verify")
+                    .args(user_opt)
+                    .args(["--man=no", "--recursive-errors=no", file]),
+            ) {
+                Ok(o) => o,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err.into()),
+            };
+            let errors = parse_errors(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() || !errors.is_empty() {
+                report(Finding::InvalidFile {
+                    kind: FileKind::SystemdUnit,
+                    package: package.to_string(),
+                    file: file.to_string(),
+                    error: if errors.is_empty() {
+                        "verification failed".to_string()
+                    } else {
+                        errors.join("; ")
+                    },
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get error messages from systemd-analyze verify output, without the file or unit prefix
+fn parse_errors(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            // "/usr/lib/systemd/system/foo.service:12: Unknown key name 'Foo'" or
+            // "foo.service: Command /usr/bin/foo is not executable: No such file or directory"
+            let mut parts = l.splitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(_path), Some(line), Some(msg)) if line.trim().parse::<u32>().is_ok() => {
+                    format!("line {}: {}", line.trim(), msg.trim())
+                }
+                (Some(unit), Some(msg), rest) if !unit.contains(' ') => match rest {
+                    Some(rest) => format!("{}:{}", msg.trim(), rest),
+                    None => msg.trim().to_string(),
+                },
+                _ => l.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_errors(
+                "/usr/lib/systemd/system/foo.service:12: Unknown key name 'Foo' in section 'Service', ignoring.
+foo.service: Command /usr/bin/foo is not executable: No such file or directory
+"
+            ),
+            [
+                "line 12: Unknown key name 'Foo' in section 'Service', ignoring.",
+                "Command /usr/bin/foo is not executable: No such file or directory"
+            ]
+        );
+    }
+}
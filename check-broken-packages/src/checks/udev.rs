@@ -0,0 +1,134 @@
+//! Udev rules, whose helper programs fail silently when a device is plugged in
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{check_executable, command_program, Check, Context};
+use crate::report::{Finding, ReferenceKind};
+
+/// Rules directories, a rule file overrides those with the same name in following directories
+const RULES_DIRS: [&str; 3] = [
+    "/etc/udev/rules.d",
+    "/run/udev/rules.d",
+    "/usr/lib/udev/rules.d",
+];
+
+/// Directory of programs referenced by a relative path
+const UDEV_DIR: &str = "/usr/lib/udev";
+
+pub struct UdevRuleCheck;
+
+impl Check for UdevRuleCheck {
+    fn name(&self) -> &'static str {
+        "udev"
+    }
+
+    fn description(&self) -> &'static str {
+        "broken udev rule programs"
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for rules_filepath in rules_files() {
+            let content = match fs::read_to_string(&rules_filepath) {
+                Ok(c) => c,
+                Err(err) => {
+                    eprintln!("Failed to read {:?}: {}", rules_filepath, err);
+                    continue;
+                }
+            };
+            for program in parse_programs(&content) {
+                let program_filepath = Path::new(UDEV_DIR).join(program);
+                for problem in check_executable(ctx.system, &program_filepath) {
+                    report(Finding::BrokenReference {
+                        kind: ReferenceKind::UdevRule,
+                        package: ctx.owner(&rules_filepath),
+                        source: rules_filepath.to_string_lossy().to_string(),
+                        target: program_filepath.to_string_lossy().to_string(),
+                        problem,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get rules files in effect, sorted by name, skipping masked ones
+fn rules_files() -> Vec<PathBuf> {
+    let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for dir in RULES_DIRS {
+        let entries = match fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if filename.ends_with(".rules") {
+                files.entry(filename).or_insert_with(|| entry.path());
+            }
+        }
+    }
+    files
+        .into_values()
+        .filter(|p| fs::read_link(p).map_or(true, |t| t != Path::new("/dev/null")))
+        .collect()
+}
+
+/// Get programs run by rules (with RUN, PROGRAM and IMPORT{program}) without duplicates,
+/// skipping those built from device properties that can only be known when the rule runs
+fn parse_programs(content: &str) -> Vec<&str> {
+    let mut programs = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut rest = line;
+        // KEY{attr}=="value", KEY+="value"...
+        while let Some((key, value_start)) = rest.split_once('"') {
+            let (value, next) = match value_start.split_once('"') {
+                Some(t) => t,
+                None => break,
+            };
+            rest = next.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+            let key = key
+                .trim_start_matches(|c: char| c == ',' || c.is_whitespace())
+                .trim_end_matches(|c| "=+-:!".contains(c));
+            let runs_program =
+                matches!(key, "RUN" | "RUN{program}" | "PROGRAM" | "IMPORT{program}");
+            if !runs_program {
+                continue;
+            }
+            if let Some(program) = command_program(value) {
+                if !program.contains(['$', '%']) && !programs.contains(&program) {
+                    programs.push(program);
+                }
+            }
+        }
+    }
+    programs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_programs() {
+        assert_eq!(
+            parse_programs(
+                "# RUN+=\"/usr/bin/commented\"
+ACTION==\"add\", SUBSYSTEM==\"usb\", ATTR{idVendor}==\"1234\", RUN+=\"/usr/bin/foo --add\"
+KERNEL==\"sd*\", IMPORT{program}=\"bar_id %k\", PROGRAM=\"baz qux\", RUN{builtin}+=\"kmod load\"
+SUBSYSTEM==\"block\", RUN+=\"$env{HELPER}\", RUN+=\"/usr/bin/foo --remove\"
+"
+            ),
+            ["/usr/bin/foo", "bar_id", "baz"]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Libraries linked by ELF files of foreign packages, from packages that are not declared
+//! dependencies, like namcap reports, which helps AUR maintainers audit their packages
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::pacman::LocalPackage;
+use crate::plugins::UNOWNED_PACKAGE;
+use crate::report::Finding;
+
+pub struct UndeclaredDependencyCheck;
+
+/// Get name of a dependency or provide without its version constraint, like "libfoo.so" from
+/// "libfoo.so=1-64"
+fn unversioned(name: &str) -> &str {
+    name.split(['<', '>', '=']).next().unwrap_or(name)
+}
+
+impl Check for UndeclaredDependencyCheck {
+    fn name(&self) -> &'static str {
+        "undeclared-dependencies"
+    }
+
+    fn description(&self) -> &'static str {
+        "libraries of foreign packages linked from packages that are not declared dependencies"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        let local_packages = ctx.system.local_packages()?;
+
+        // Packages satisfying each name a dependency can be declared with
+        let mut satisfiers: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for package in &local_packages {
+            for name in std::iter::once(package.name.as_str())
+                .chain(package.provides.iter().map(|p| unversioned(p)))
+            {
+                satisfiers.entry(name).or_default().insert(&package.name);
+            }
+        }
+        let declared = |package: &LocalPackage| -> HashSet<&str> {
+            package
+                .depends
+                .iter()
+                .filter_map(|d| satisfiers.get(unversioned(d)))
+                .flatten()
+                .copied()
+                .collect()
+        };
+        let local_packages: HashMap<&str, &LocalPackage> = local_packages
+            .iter()
+            .map(|p| (p.name.as_str(), p))
+            .collect();
+
+        // Only report each undeclared dependency of a package once
+        let mut reported: HashSet<(String, String)> = HashSet::new();
+        let mut declared_cache: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (package, file) in ctx.foreign_package_files() {
+            let path = Path::new(file);
+            let is_elf = ctx
+                .system
+                .read_head(path, 4)
+                .is_ok_and(|h| h.starts_with(b"\x7fELF"));
+            if !is_elf {
+                continue;
+            }
+            let local_package = match local_packages.get(package) {
+                Some(p) => p,
+                None => continue,
+            };
+            let declared = declared_cache
+                .entry(package)
+                .or_insert_with(|| declared(local_package));
+            for (soname, library_path) in ctx.system.linked_libraries(path).unwrap_or_default() {
+                let provider = match library_path {
+                    Some(p) => ctx.owner(&p),
+                    // Reported as a missing dependency
+                    None => continue,
+                };
+                if (provider == package)
+                    || (provider == UNOWNED_PACKAGE)
+                    || declared.contains(provider.as_str())
+                    || !reported.insert((package.to_string(), provider.clone()))
+                {
+                    continue;
+                }
+                report(Finding::UndeclaredDependency {
+                    package: package.to_string(),
+                    file: file.to_string(),
+                    dependency: soname,
+                    provider,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_undeclared_dependency_check() {
+        let system = MockSystem::default()
+            .package("foo", true, &["/usr/bin/foo", "/usr/bin/foo-helper"])
+            .package("bar", false, &["/usr/lib/libbar.so.1"])
+            .package("baz", false, &["/usr/lib/libbaz.so.2"])
+            .package("glibc", false, &["/usr/lib/libc.so.6"])
+            .depends("foo", &["glibc", "bar>=1"])
+            .file("/usr/bin/foo", 0o755, b"\x7fELF", &[])
+            .linked(
+                "/usr/bin/foo",
+                &[
+                    ("libc.so.6", "/usr/lib/libc.so.6"),
+                    ("libbar.so.1", "/usr/lib/libbar.so.1"),
+                    ("libbaz.so.2", "/usr/lib/libbaz.so.2"),
+                ],
+            )
+            .file("/usr/bin/foo-helper", 0o755, b"\x7fELF", &[])
+            .linked(
+                "/usr/bin/foo-helper",
+                &[("libbaz.so.2", "/usr/lib/libbaz.so.2")],
+            );
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+
+        let mut findings = Vec::new();
+        UndeclaredDependencyCheck
+            .run(&ctx, &mut |f| findings.push(f))
+            .unwrap();
+        assert_eq!(
+            findings,
+            [Finding::UndeclaredDependency {
+                package: "foo".to_string(),
+                file: "/usr/bin/foo".to_string(),
+                dependency: "libbaz.so.2".to_string(),
+                provider: "baz".to_string(),
+            }]
+        );
+    }
+}
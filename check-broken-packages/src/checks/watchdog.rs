@@ -0,0 +1,328 @@
+//! Time budget of checks, so that enabling slow checks does not make hook runs arbitrarily long.
+//! Threads can not be killed: a check exceeding its budget is cancelled by making all its
+//! accesses to the system fail, as well as its deadline queries, by killing the commands it runs,
+//! and the findings it reports after that are dropped.
+
+use std::error;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Check, Context};
+use crate::elf::{DebugInfo, ElfInfo, MissingLibrary};
+use crate::held::HeldPackages;
+use crate::pacman::{FileIndex, LocalPackage};
+use crate::report::{FileDamage, Finding, HardeningIssue};
+use crate::shipped::ShippedSonames;
+use crate::system::{FileMetadata, LinkedLibrary, SystemProvider};
+
+/// Interval between two checks of the exit of a command run with a deadline
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Fail if a deadline has passed
+pub fn check_deadline(deadline: Instant) -> io::Result<()> {
+    if Instant::now() >= deadline {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "Check timed out"));
+    }
+    Ok(())
+}
+
+/// Run a command and get its output, killing it with the processes it started if a deadline passes
+/// first
+pub fn output_before(command: &mut Command, deadline: Instant) -> io::Result<Output> {
+    check_deadline(deadline)?;
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Own process group, to kill its children too, which would keep the pipes open
+        .process_group(0)
+        .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    thread::scope(|scope| {
+        // Read outputs while waiting, so that a command filling a pipe does not block
+        let stdout_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            stdout.read_to_end(&mut buffer).map(|_| buffer)
+        });
+        let stderr_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            stderr.read_to_end(&mut buffer).map(|_| buffer)
+        });
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let Err(err) = check_deadline(deadline) {
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                child.wait()?;
+                return Err(err);
+            }
+            thread::sleep(COMMAND_POLL_INTERVAL);
+        };
+        Ok(Output {
+            status,
+            stdout: stdout_reader.join().unwrap()?,
+            stderr: stderr_reader.join().unwrap()?,
+        })
+    })
+}
+
+/// System provider failing once a deadline has passed
+struct DeadlineSystem<'a> {
+    inner: &'a dyn SystemProvider,
+    deadline: Instant,
+}
+
+impl DeadlineSystem<'_> {
+    fn check_deadline(&self) -> io::Result<()> {
+        check_deadline(self.deadline)
+    }
+}
+
+impl SystemProvider for DeadlineSystem<'_> {
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.foreign_packages()
+    }
+
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.packages()
+    }
+
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.local_packages()
+    }
+
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.package_files(package)
+    }
+
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.file_index()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.check_deadline()?;
+        self.inner.metadata(path)
+    }
+
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        self.check_deadline()?;
+        self.inner.read_head(path, len)
+    }
+
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.missing_dependencies(path)
+    }
+
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.missing_dependency_chains(path)
+    }
+
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.linked_libraries(path)
+    }
+
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+        self.check_deadline()?;
+        self.inner.damage(path)
+    }
+
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.elf_info(path)
+    }
+
+    fn hardening_issues(&self, path: &Path) -> io::Result<Option<Vec<HardeningIssue>>> {
+        self.check_deadline()?;
+        self.inner.hardening_issues(path)
+    }
+
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+        self.check_deadline()?;
+        self.inner.debug_info(path)
+    }
+
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.held_packages()
+    }
+
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        self.check_deadline()?;
+        self.inner.shipped_sonames()
+    }
+}
+
+/// Run a check within its time budget from the configuration, reporting a finding if it timed
+/// out. Its indexes are built before the budget starts, since they are shared by all checks.
+pub fn run_check(
+    check: &dyn Check,
+    ctx: &Context<'_>,
+    report: &mut dyn FnMut(Finding),
+) -> Result<(), Box<dyn error::Error>> {
+    let timeout = match ctx.config.check_timeout(check.name()) {
+        Some(t) => t,
+        None => return check.run(ctx, report),
+    };
+    ctx.warm_up();
+    let deadline = Instant::now() + timeout;
+    let system = DeadlineSystem {
+        inner: ctx.system,
+        deadline,
+    };
+    let ctx = Context {
+        deadline: Some(deadline),
+        ..ctx.with_system(&system)
+    };
+    let res = check.run(&ctx, &mut |finding| {
+        // Accesses failing because of the deadline may cause bogus findings
+        if Instant::now() < deadline {
+            report(finding);
+        }
+    });
+    if Instant::now() >= deadline {
+        report(Finding::CheckTimedOut {
+            check: check.name().to_string(),
+            timeout: timeout.as_secs(),
+        });
+        return Ok(());
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+
+    /// Check reporting a missing file each time it is accessed, until it fails
+    struct SlowCheck;
+
+    impl Check for SlowCheck {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn description(&self) -> &'static str {
+            "nothing"
+        }
+
+        fn run(
+            &self,
+            ctx: &Context<'_>,
+            report: &mut dyn FnMut(Finding),
+        ) -> Result<(), Box<dyn error::Error>> {
+            loop {
+                if ctx.system.metadata(Path::new("/usr/bin/foo")).is_err() {
+                    report(Finding::BrokenServiceLink {
+                        link: "/usr/bin/foo".to_string(),
+                    });
+                }
+                ctx.system.local_packages()?;
+                thread::sleep(Duration::from_millis(200));
+            }
+        }
+    }
+
+    /// Check never accessing the system, running a command or waiting in a loop
+    struct BusyCheck {
+        command: bool,
+    }
+
+    impl Check for BusyCheck {
+        fn name(&self) -> &'static str {
+            "busy"
+        }
+
+        fn description(&self) -> &'static str {
+            "nothing"
+        }
+
+        fn run(
+            &self,
+            ctx: &Context<'_>,
+            _report: &mut dyn FnMut(Finding),
+        ) -> Result<(), Box<dyn error::Error>> {
+            if self.command {
+                ctx.command_output(Command::new("sleep").arg("30"))?;
+            }
+            loop {
+                ctx.check_deadline()?;
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_check() {
+        let system = MockSystem::default();
+        let config = Config {
+            check_timeouts: vec![("slow".to_string(), 1)].into_iter().collect(),
+            ..Config::default()
+        };
+        let ctx = Context::new(&config, &system);
+        let mut findings = Vec::new();
+        run_check(&SlowCheck, &ctx, &mut |f| findings.push(f)).unwrap();
+        assert_eq!(
+            findings.last(),
+            Some(&Finding::CheckTimedOut {
+                check: "slow".to_string(),
+                timeout: 1,
+            })
+        );
+        assert!(findings.len() > 1);
+    }
+
+    #[test]
+    fn test_run_check_without_system_access() {
+        let system = MockSystem::default();
+        let config = Config {
+            check_timeouts: vec![("busy".to_string(), 1)].into_iter().collect(),
+            ..Config::default()
+        };
+        let ctx = Context::new(&config, &system);
+        for command in [false, true].iter().copied() {
+            let start = Instant::now();
+            let mut findings = Vec::new();
+            run_check(&BusyCheck { command }, &ctx, &mut |f| findings.push(f)).unwrap();
+            assert!(start.elapsed() < Duration::from_secs(5));
+            assert_eq!(
+                findings,
+                [Finding::CheckTimedOut {
+                    check: "busy".to_string(),
+                    timeout: 1,
+                }]
+            );
+        }
+
+        let output = output_before(
+            Command::new("echo").arg("foo"),
+            Instant::now() + Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"foo\n");
+    }
+}
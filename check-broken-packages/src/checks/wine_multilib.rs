@@ -0,0 +1,203 @@
+//! 32-bit libraries needed by Wine related foreign packages, which run 32-bit Windows programs
+//! with 32-bit counterparts of their libraries. Wine itself reports missing ones very opaquely.
+
+use std::collections::HashSet;
+use std::error;
+use std::path::Path;
+
+use super::{Check, Context};
+use crate::pacman::LocalPackage;
+use crate::plugins::UNOWNED_PACKAGE;
+use crate::report::Finding;
+
+/// Directory of 32-bit libraries, and of their 64-bit counterparts
+const LIB32_DIR: &str = "/usr/lib32";
+const LIB_DIR: &str = "/usr/lib";
+
+/// Prefix of the multilib packages of 32-bit libraries, like "lib32-gnutls" for "gnutls"
+const LIB32_PACKAGE_PREFIX: &str = "lib32-";
+
+/// Return true if a package is related to Wine, from its name or dependencies
+fn is_wine_package(package: &LocalPackage) -> bool {
+    let is_wine = |name: &str| name.contains("wine") || name.contains("proton");
+    is_wine(&package.name)
+        || package
+            .depends
+            .iter()
+            .any(|d| is_wine(d.split(['<', '>', '=']).next().unwrap_or(d)))
+}
+
+/// Get ELF class of a file, 1 for 32-bit and 2 for 64-bit, None if it is not an ELF file
+fn elf_class(ctx: &Context<'_>, path: &Path) -> Option<u8> {
+    if !ctx.system.metadata(path).is_ok_and(|m| m.is_file) {
+        return None;
+    }
+    let head = ctx.system.read_head(path, 5).ok()?;
+    if (head.len() < 5) || !head.starts_with(b"\x7fELF") {
+        return None;
+    }
+    Some(head[4])
+}
+
+pub struct WineMultilibCheck;
+
+impl Check for WineMultilibCheck {
+    fn name(&self) -> &'static str {
+        "wine-multilib"
+    }
+
+    fn description(&self) -> &'static str {
+        "missing 32-bit libraries of Wine related foreign packages"
+    }
+
+    fn opt_in(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        ctx: &Context<'_>,
+        report: &mut dyn FnMut(Finding),
+    ) -> Result<(), Box<dyn error::Error>> {
+        for package in ctx
+            .system
+            .local_packages()?
+            .iter()
+            .filter(|p| ctx.foreign_packages().contains(&p.name) && is_wine_package(p))
+        {
+            check_package(package, ctx, report);
+        }
+        Ok(())
+    }
+}
+
+/// Check that the libraries needed by the ELF files of a package have a 32-bit version. Those of
+/// 64-bit files only need one if the package has 32-bit files, since WoW64 builds of Wine do not.
+fn check_package(package: &LocalPackage, ctx: &Context<'_>, report: &mut dyn FnMut(Finding)) {
+    let own_libraries: HashSet<&str> = package
+        .files
+        .iter()
+        .filter_map(|f| Path::new(f).file_name()?.to_str())
+        .collect();
+    let elf_files: Vec<(&str, u8)> = package
+        .files
+        .iter()
+        .filter_map(|f| Some((f.as_str(), elf_class(ctx, Path::new(f))?)))
+        .collect();
+    let has_32bit_files = elf_files.iter().any(|(_f, class)| *class == 1);
+
+    // Only report each library once
+    let mut reported: HashSet<String> = HashSet::new();
+    for (file, class) in elf_files {
+        if (class != 1) && !has_32bit_files {
+            continue;
+        }
+        for (soname, library_path) in ctx
+            .system
+            .linked_libraries(Path::new(file))
+            .unwrap_or_default()
+        {
+            if own_libraries.contains(soname.as_str()) {
+                continue;
+            }
+            let missing = match (class, library_path) {
+                (1, None) => true,
+                // Libraries of the package or of other directories than the system one do not
+                // have a 32-bit counterpart in the system directory
+                (_, Some(p)) if class != 1 && p.parent() == Some(Path::new(LIB_DIR)) => !ctx
+                    .system
+                    .metadata(&Path::new(LIB32_DIR).join(&soname))
+                    .is_ok_and(|m| m.is_file),
+                _ => false,
+            };
+            if !missing || !reported.insert(soname.clone()) {
+                continue;
+            }
+            // Multilib packages are named after the package of the 64-bit library
+            let suggestion = Some(ctx.owner(&Path::new(LIB_DIR).join(&soname)))
+                .filter(|o| o != UNOWNED_PACKAGE)
+                .map(|o| format!("{}{}", LIB32_PACKAGE_PREFIX, o));
+            report(Finding::MissingMultilibLibrary {
+                package: package.name.clone(),
+                file: file.to_string(),
+                dependency: soname,
+                suggestion,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::system::mock::MockSystem;
+    use crate::system::SystemProvider;
+
+    #[test]
+    fn test_check_package() {
+        let elf32 = b"\x7fELF\x01";
+        let elf64 = b"\x7fELF\x02";
+        let system = MockSystem::default()
+            .package(
+                "wine-foo",
+                true,
+                &[
+                    "/usr/bin/wine-foo",
+                    "/usr/lib32/wine/foo.so",
+                    "/usr/lib32/wine/libbundled.so",
+                ],
+            )
+            .package("gnutls", false, &["/usr/lib/libgnutls.so.30"])
+            .package("freetype2", false, &["/usr/lib/libfreetype.so.6"])
+            .package("glibc", false, &["/usr/lib/libc.so.6"])
+            .package("lib32-glibc", false, &["/usr/lib32/libc.so.6"])
+            .file("/usr/bin/wine-foo", 0o755, elf64, &[])
+            .linked(
+                "/usr/bin/wine-foo",
+                &[
+                    ("libfreetype.so.6", "/usr/lib/libfreetype.so.6"),
+                    ("libc.so.6", "/usr/lib/libc.so.6"),
+                ],
+            )
+            .file("/usr/lib32/wine/foo.so", 0o755, elf32, &[])
+            .linked(
+                "/usr/lib32/wine/foo.so",
+                &[
+                    ("libgnutls.so.30", ""),
+                    ("libbundled.so", ""),
+                    ("libc.so.6", "/usr/lib32/libc.so.6"),
+                ],
+            )
+            .file("/usr/lib32/libc.so.6", 0o755, elf32, &[]);
+        let config = Config::default();
+        let ctx = Context::new(&config, &system);
+        let package = system
+            .local_packages()
+            .unwrap()
+            .into_iter()
+            .find(|p| p.name == "wine-foo")
+            .unwrap();
+        assert!(is_wine_package(&package));
+
+        let mut findings = Vec::new();
+        check_package(&package, &ctx, &mut |f| findings.push(f));
+        assert_eq!(
+            findings,
+            [
+                Finding::MissingMultilibLibrary {
+                    package: "wine-foo".to_string(),
+                    file: "/usr/bin/wine-foo".to_string(),
+                    dependency: "libfreetype.so.6".to_string(),
+                    suggestion: Some("lib32-freetype2".to_string()),
+                },
+                Finding::MissingMultilibLibrary {
+                    package: "wine-foo".to_string(),
+                    file: "/usr/lib32/wine/foo.so".to_string(),
+                    dependency: "libgnutls.so.30".to_string(),
+                    suggestion: Some("lib32-gnutls".to_string()),
+                },
+            ]
+        );
+    }
+}
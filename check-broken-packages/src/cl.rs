@@ -0,0 +1,396 @@
+//! Command line interface
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::defer::Defer;
+use crate::filter::Filter;
+use crate::ignore::{IgnorePath, IgnoreRule};
+use crate::logger::{LogFilter, LogFormat};
+use crate::rebuild::RebuildList;
+use crate::report::Severity;
+use crate::severity::SeverityOverride;
+use crate::sources::PackageSource;
+
+/// Check for packages with broken dependencies
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct CommandLineOpts {
+    /// Report file where all findings are written (default: state directory)
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Memory ceiling for findings kept for display, beyond which they are only written to the
+    /// report file (accepts K/M/G suffixes)
+    #[arg(long, value_name = "SIZE", default_value = "256M", value_parser = parse_size)]
+    pub max_memory: usize,
+
+    /// Format of log messages written to standard error: 'text', or 'json' for one JSON object
+    /// per line
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Log levels, a default one and overrides for modules, like
+    /// 'warn,check_broken_packages::daemon=debug'
+    #[arg(long, value_name = "FILTER", default_value = "warn")]
+    pub log_level: LogFilter,
+
+    /// Ignore findings with a code, optionally only for packages (or paths for findings not about
+    /// a package) matching a glob pattern, can be repeated
+    #[arg(long, value_name = "CODE[:PATTERN]")]
+    pub ignore: Vec<IgnoreRule>,
+
+    /// Only keep findings matching an expression over their fields, like
+    /// 'check==lib && package!=zoom && missing~"libcuda"'
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+
+    /// Override the severity of findings with a code, optionally only for packages matching a
+    /// glob pattern, like 'LIB001:zoom*=info', can be repeated
+    #[arg(long, value_name = "CODE[:PATTERN]=SEVERITY")]
+    pub severity: Vec<SeverityOverride>,
+
+    /// Only report findings of at least a severity: 'info', 'warning' or 'error'
+    #[arg(long, value_name = "SEVERITY")]
+    pub min_severity: Option<Severity>,
+
+    /// Exit with code 3 if a finding has at least a severity: 'info', 'warning' or 'error'
+    #[arg(long, value_name = "SEVERITY")]
+    pub fail_on: Option<Severity>,
+
+    /// Where to get the packages to analyze: 'foreign' (default, packages not in any sync
+    /// database), 'list:FILE' (one package per line), 'clone-dir:DIR' (PKGBUILD checkouts of an
+    /// AUR helper), or 'paru'/'yay' (clone directory of these AUR helpers for the current user)
+    #[arg(long, value_name = "SOURCE")]
+    pub package_source: Option<PackageSource>,
+
+    /// Directory of PKGBUILD checkouts, with one subdirectory per package base, to locate those of
+    /// packages to rebuild (default: the clone directory of the package source, if any)
+    #[arg(long, value_name = "DIR")]
+    pub pkgbuild_dir: Option<PathBuf>,
+
+    /// Write a shell script rebuilding packages with findings from their PKGBUILD checkouts, in
+    /// dependency order, to a file
+    #[arg(long, value_name = "FILE")]
+    pub rebuild_script: Option<PathBuf>,
+
+    /// After the findings, print the installed size of the packages with findings, largest first,
+    /// to decide which to remove rather than rebuild
+    #[arg(long)]
+    pub disk_usage: bool,
+
+    /// Exclude files matching a glob pattern (and their subtree for directories) from analysis,
+    /// can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    pub ignore_path: Vec<IgnorePath>,
+
+    /// Analyze all shared objects in a directory, regardless of the package owning them (useful
+    /// for plugins of applications), can be repeated
+    #[arg(long, value_name = "DIR")]
+    pub plugin_root: Vec<PathBuf>,
+
+    /// Skip files with executable permissions that are not executables, based on their extension
+    /// or content
+    #[arg(long)]
+    pub skip_data_files: bool,
+
+    /// Analyze files of cross compilation toolchains (sysroots like /usr/arm-none-eabi, and
+    /// /usr/lib/gcc directories of other targets), skipped by default
+    #[arg(long)]
+    pub analyze_cross_toolchains: bool,
+
+    /// When not running as root, analyze files the current user can not read in a single run of
+    /// a helper with 'sudo -n' at the end, instead of reporting them as unreadable
+    #[arg(long)]
+    pub elevate: bool,
+
+    /// When running as root, analyze package files in helpers running as this unprivileged user
+    /// (default: nobody), or as root with 'root'
+    #[arg(long, value_name = "USER")]
+    pub analysis_user: Option<String>,
+
+    /// Analyze all installed packages, not only foreign ones (slow, for the systemd timer), an
+    /// interrupted analysis resumes from a checkpoint on the next run
+    #[arg(long)]
+    pub all_packages: bool,
+
+    /// Run an opt-in check, can be repeated
+    #[arg(long, value_name = "CHECK")]
+    pub enable_check: Vec<String>,
+
+    /// Do not run a check, can be repeated
+    #[arg(long, value_name = "CHECK")]
+    pub disable_check: Vec<String>,
+
+    /// Seconds a check other than the package analysis may run before being cancelled, and
+    /// reported as timed out, 0 for no limit (default: 60)
+    #[arg(long, value_name = "SECONDS")]
+    pub check_timeout: Option<u64>,
+
+    /// Seconds to wait for the pacman database lock to be released, before reading the database
+    /// directly instead of with pacman (default: 0)
+    #[arg(long, value_name = "SECONDS")]
+    pub db_lock_timeout: Option<u64>,
+
+    /// Number of worker threads analyzing files and walking directory trees (default: number of
+    /// CPUs)
+    #[arg(short, long, value_name = "COUNT")]
+    pub jobs: Option<usize>,
+
+    /// Age in days beyond which the package-age check reports a foreign package that was not
+    /// installed again, rebuilt or upgraded (default: 180)
+    #[arg(long, value_name = "DAYS")]
+    pub max_package_age: Option<u64>,
+
+    /// Run as a pacman hook: only display the findings of a few packages, to keep the pacman
+    /// output readable, the others are only in the report file
+    #[arg(long)]
+    pub hook: bool,
+
+    /// Number of packages whose findings are displayed in hook mode (default: 10)
+    #[arg(long, value_name = "COUNT")]
+    pub hook_max_packages: Option<usize>,
+
+    /// In hook mode, record the transaction targets (read from standard input) and schedule the
+    /// analysis with systemd-run instead of running it: a number of minutes later, or 'idle' as
+    /// soon as the system is idle
+    #[arg(long, value_name = "MINUTES|idle")]
+    pub defer: Option<Defer>,
+
+    /// Wait for the system to be idle (load average below half the number of CPUs) before
+    /// analyzing
+    #[arg(long)]
+    pub wait_idle: bool,
+
+    /// Report the chain of libraries through which a missing library is needed, resolving the
+    /// whole dependency tree of every file like lddtree
+    #[arg(long)]
+    pub resolve_tree: bool,
+
+    /// Find missing libraries by running ldd instead of parsing ELF files in process, ldd runs
+    /// the dynamic loader of files, so only use it on trusted files
+    #[arg(long)]
+    pub ldd: bool,
+
+    /// Print notes about analysis decisions
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Do not display progress and notices on standard error, only errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Explain how every dynamic dependency of an executable or library is resolved, and exit
+    #[arg(long, value_name = "FILE")]
+    pub explain: Option<PathBuf>,
+
+    /// Explain which files of a package are in Python directories ignored by the current
+    /// interpreter, how to fix it, and exit
+    #[arg(long, value_name = "PACKAGE", conflicts_with = "explain")]
+    pub explain_package: Option<String>,
+
+    /// Predict files of foreign packages that the pending upgrade will break, from the
+    /// synchronized databases, and exit (for a PreTransaction hook)
+    #[arg(long, conflicts_with_all = ["explain", "explain_package"])]
+    pub predict_upgrade: bool,
+
+    /// Analyze ELF files of a build output directory (like the pkg directory of makepkg), and
+    /// again each time they change, until interrupted
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["hook", "predict_upgrade"])]
+    pub watch: Option<PathBuf>,
+
+    /// Read names of the packages a transaction removes from standard input, one per line, print
+    /// files of foreign packages kept that the removal would break, and exit (for a
+    /// PreTransaction hook)
+    #[arg(long, conflicts_with_all = ["explain", "explain_package", "predict_upgrade"])]
+    pub check_removal: bool,
+
+    /// Exit with an error when --check-removal finds files the removal would break, so that the
+    /// hook aborts the transaction
+    #[arg(long)]
+    pub block_removals: bool,
+
+    /// Configuration file (default: /etc/check-broken-packages.toml if it exists)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Prefix findings with a glyph identifying their type
+    #[arg(long)]
+    pub glyphs: bool,
+
+    /// Print findings as JSON objects, one per line, like in the report file
+    #[arg(long)]
+    pub json: bool,
+
+    /// Print findings as a single JSON object, with a section for each check (the code family in
+    /// lowercase, like 'lib' for LIB001) listing its findings
+    #[arg(long, conflicts_with_all = ["json", "stats_json"])]
+    pub json_by_check: bool,
+
+    /// Exit with a bitmask of the categories with findings, of at least the --fail-on severity if
+    /// set: 4 for libraries, 8 for Python and Perl, 16 for system references, 32 for package files
+    /// and 64 for others
+    #[arg(long)]
+    pub exit_bitmask: bool,
+
+    /// Print instead of findings the names of the foreign packages to rebuild, one per line in
+    /// rebuild order, for an AUR helper: 'python' for those with files in stale Python directories,
+    /// 'libs' for those with files missing shared libraries
+    #[arg(
+        long,
+        value_name = "KIND",
+        conflicts_with_all = ["json", "stats_json", "json_by_check"]
+    )]
+    pub list_rebuilds: Option<RebuildList>,
+
+    /// Print instead of findings a JSON object of anonymized statistics (counts of findings by
+    /// code, of packages, and of packages missing each soname, without names or paths), to share
+    /// when reporting a widespread breakage
+    #[arg(long, conflicts_with = "json")]
+    pub stats_json: bool,
+
+    /// Analyze packages of a system mounted at a directory (a chroot, container or backup)
+    /// instead of the running system, only the package dependency analysis is supported
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Index shared libraries of system library directories by soname, export the index to a
+    /// file, and exit
+    #[arg(long, value_name = "FILE")]
+    pub export_soname_index: Option<PathBuf>,
+
+    /// Resolve dependencies with a soname index exported by a previous run, instead of looking up
+    /// files in library directories, except those that changed since the export
+    #[arg(long, value_name = "FILE")]
+    pub import_soname_index: Option<PathBuf>,
+
+    /// Print the configuration file merged with command line options, and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Print the JSON Schema of the report file and of --json findings, and exit
+    #[arg(long)]
+    pub print_schema: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands run instead of the analysis
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print shell completion script to standard output
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print man page to standard output
+    GenMan,
+
+    /// Check that the environment the analysis depends on works, and suggest fixes
+    Doctor,
+
+    /// Print summaries of the previous runs, with the findings that appeared and were resolved
+    /// since the run before
+    History,
+
+    /// Print installed files that directly need a library soname, like "libssl.so.3", that is
+    /// what a soname bump of the library breaks
+    WhoNeeds { soname: String },
+
+    /// Keep indexes of the installed packages in memory, updated when the pacman database
+    /// changes, and answer queries of the query command over a unix socket
+    Daemon {
+        /// Socket path (default: in the runtime directory)
+        #[arg(long, value_name = "FILE")]
+        socket: Option<PathBuf>,
+
+        /// Also answer calls of D-Bus clients, like desktop widgets, on the system bus (or the
+        /// session bus when not run as root)
+        #[arg(long)]
+        dbus: bool,
+    },
+
+    /// Send a query to the daemon and print its answer as JSON objects, one per line: 'who-needs
+    /// SONAME' for the installed files directly needing a soname, 'scan PACKAGE' for the
+    /// findings of the files of a package, or 'last-report' for the findings of the last report
+    Query {
+        /// Socket path of the daemon (default: in the runtime directory)
+        #[arg(long, value_name = "FILE")]
+        socket: Option<PathBuf>,
+
+        #[arg(required = true, value_name = "QUERY")]
+        query: Vec<String>,
+    },
+
+    /// Print installed files that removing a package, or a soname bump of a library (like
+    /// "libssl.so.3"), would break
+    SimulateRemoval {
+        /// Package name, or library soname
+        target: String,
+    },
+
+    /// Print files of a built package archive, not installed yet, that would miss a shared
+    /// library of the system once installed, to validate a build before installing it
+    Analyze {
+        /// Package archive, like "foo-1.0-1-x86_64.pkg.tar.zst"
+        #[arg(long, value_name = "FILE")]
+        filelist: PathBuf,
+    },
+
+    /// Print sonames that package archives installed together need, and that neither the
+    /// system nor the archives provide, one per line (or as a JSON array with --json), for AUR
+    /// helpers to warn about broken installs before installing. Exits with code 3 if there are
+    /// some.
+    MissingSonames {
+        #[arg(required = true, value_name = "FILE")]
+        archives: Vec<PathBuf>,
+    },
+
+    /// Analyze files as root for --elevate, printing one JSON line per file
+    #[command(hide = true)]
+    ElevatedHelper {
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+
+    /// Analyze files read from standard input as an unprivileged user, printing one JSON line per
+    /// file
+    #[command(hide = true)]
+    UnprivilegedHelper,
+}
+
+/// Parse a byte size with an optional binary unit suffix, like "64M"
+fn parse_size(s: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let value = digits
+        .parse::<usize>()
+        .map_err(|e| format!("Invalid size {:?}: {}", s, e))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size {:?} is too large", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("123"), Ok(123));
+        assert_eq!(parse_size("2k"), Ok(2048));
+        assert_eq!(parse_size("64M"), Ok(64 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("12T").is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Configuration file
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cl::CommandLineOpts;
+use crate::defer::Defer;
+use crate::filter::Filter;
+use crate::ignore::{IgnorePath, IgnoreRule};
+use crate::report::Severity;
+use crate::severity::SeverityOverrides;
+use crate::sources::PackageSource;
+
+/// Default configuration file path
+pub const DEFAULT_CONFIG_FILEPATH: &str = concat!("/etc/", env!("CARGO_PKG_NAME"), ".toml");
+
+/// Default age in days beyond which a foreign package not installed again is reported
+const DEFAULT_MAX_PACKAGE_AGE: u64 = 180;
+
+/// Default number of packages whose findings are displayed in hook mode
+const DEFAULT_HOOK_MAX_PACKAGES: usize = 10;
+
+/// Default number of seconds a check may run before being cancelled
+const DEFAULT_CHECK_TIMEOUT: u64 = 60;
+
+/// Configuration, from the configuration file merged with command line options
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Rules of findings to ignore
+    pub ignore: Vec<IgnoreRule>,
+
+    /// Expression of the findings to keep, None to keep all
+    pub filter: Option<Filter>,
+
+    /// Severity of findings overriding their default one
+    pub severity: SeverityOverrides,
+
+    /// Minimum severity of findings to report, None to report all
+    pub min_severity: Option<Severity>,
+
+    /// Minimum severity of findings making the run fail, None to never fail because of findings
+    pub fail_on: Option<Severity>,
+
+    /// Where to get the packages to analyze
+    pub package_source: PackageSource,
+
+    /// Directory of PKGBUILD checkouts, None to use the clone directory of the package source
+    pub pkgbuild_dir: Option<PathBuf>,
+
+    /// Path of the generated rebuild script, None to not generate it
+    pub rebuild_script: Option<PathBuf>,
+
+    /// Print installed size of the packages with findings after them
+    pub disk_usage: bool,
+
+    /// Path patterns of files excluded from analysis
+    pub ignore_path: Vec<IgnorePath>,
+
+    /// Directories where all shared objects are analyzed, regardless of the package owning them
+    pub plugin_roots: Vec<PathBuf>,
+
+    /// Packages resolving some of their libraries at runtime, like with a wrapper script setting
+    /// LD_LIBRARY_PATH, with the directories of these libraries
+    pub bundled_runtime: BTreeMap<String, Vec<PathBuf>>,
+
+    /// Skip files with executable permissions that are not executables (images, text...)
+    pub skip_data_files: bool,
+
+    /// Analyze files of cross compilation toolchains, built for another target than the system
+    pub analyze_cross_toolchains: bool,
+
+    /// When not running as root, analyze files the current user can not read with sudo, if it
+    /// does not need a password
+    pub elevate: bool,
+
+    /// When running as root, unprivileged user analyzing package files, 'root' to analyze them as
+    /// root, None for the default
+    pub analysis_user: Option<String>,
+
+    /// Names of opt-in checks to run
+    pub enable_checks: Vec<String>,
+
+    /// Names of checks not to run
+    pub disable_checks: Vec<String>,
+
+    /// Seconds a check other than the package analysis may run before being cancelled, 0 for no
+    /// limit, None for the default
+    pub check_timeout: Option<u64>,
+
+    /// Seconds specific checks may run before being cancelled, overriding check_timeout
+    pub check_timeouts: BTreeMap<String, u64>,
+
+    /// Seconds to wait for the pacman database lock to be released, before reading the database
+    /// directly instead of with pacman
+    pub db_lock_timeout: u64,
+
+    /// Number of worker threads analyzing files and walking directory trees, None for the number
+    /// of CPUs
+    pub jobs: Option<usize>,
+
+    /// Age in days beyond which a foreign package not installed again (rebuilt or upgraded) is
+    /// reported, None for the default
+    pub max_package_age: Option<u64>,
+
+    /// Number of packages whose findings are displayed in hook mode, the others are only in the
+    /// report file, None for the default
+    pub hook_max_packages: Option<usize>,
+
+    /// When to run the analysis of hook runs, None to run it during the transaction
+    pub defer: Option<Defer>,
+
+    /// Resolve the whole dependency tree of files in process, to report the chain of libraries
+    /// through which missing ones are needed
+    pub resolve_tree: bool,
+
+    /// Find missing libraries with ldd instead of parsing ELF files in process
+    pub ldd: bool,
+
+    /// Exit with an error when a checked removal would break files of foreign packages, to abort
+    /// the transaction
+    pub block_removals: bool,
+
+    /// Soname index file exported by a previous run, to resolve dependencies without looking up
+    /// files in unchanged library directories
+    pub import_soname_index: Option<PathBuf>,
+
+    /// Analyze all installed packages, not only foreign ones
+    #[serde(skip)]
+    pub all_packages: bool,
+
+    /// Print notes about analysis decisions
+    #[serde(skip)]
+    pub verbose: bool,
+
+    /// Do not display progress and notices on standard error
+    pub quiet: bool,
+
+    /// Root directory of the analyzed system, None for the running system
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+impl Config {
+    /// Load configuration from file, use default if there is no file at the default location
+    pub fn load(filepath: Option<&Path>) -> Result<Config, Box<dyn error::Error>> {
+        let (filepath, required) = match filepath {
+            Some(p) => (p, true),
+            None => (Path::new(DEFAULT_CONFIG_FILEPATH), false),
+        };
+        let content = match fs::read_to_string(filepath) {
+            Ok(c) => c,
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(err) => return Err(format!("{:?}: {}", filepath, err).into()),
+        };
+        toml::from_str(&content).map_err(|e| format!("{:?}: {}", filepath, e).into())
+    }
+
+    /// Get number of worker threads
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(num_cpus::get).max(1)
+    }
+
+    /// Get age in days beyond which a foreign package not installed again is reported
+    pub fn max_package_age(&self) -> u64 {
+        self.max_package_age.unwrap_or(DEFAULT_MAX_PACKAGE_AGE)
+    }
+
+    /// Get number of packages whose findings are displayed in hook mode
+    pub fn hook_max_packages(&self) -> usize {
+        self.hook_max_packages.unwrap_or(DEFAULT_HOOK_MAX_PACKAGES)
+    }
+
+    /// Get time a check may run before being cancelled, None for no limit
+    pub fn check_timeout(&self, check: &str) -> Option<Duration> {
+        let seconds = self
+            .check_timeouts
+            .get(check)
+            .copied()
+            .or(self.check_timeout)
+            .unwrap_or(DEFAULT_CHECK_TIMEOUT);
+        Some(Duration::from_secs(seconds)).filter(|d| !d.is_zero())
+    }
+
+    /// Merge command line options into configuration, they add up to the configuration file values
+    pub fn merge(&mut self, cl_opts: &CommandLineOpts) {
+        self.ignore.extend(cl_opts.ignore.iter().cloned());
+        if cl_opts.filter.is_some() {
+            self.filter.clone_from(&cl_opts.filter);
+        }
+        self.severity.extend(cl_opts.severity.iter().cloned());
+        if cl_opts.min_severity.is_some() {
+            self.min_severity = cl_opts.min_severity;
+        }
+        if cl_opts.fail_on.is_some() {
+            self.fail_on = cl_opts.fail_on;
+        }
+        if let Some(package_source) = &cl_opts.package_source {
+            self.package_source.clone_from(package_source);
+        }
+        if cl_opts.pkgbuild_dir.is_some() {
+            self.pkgbuild_dir.clone_from(&cl_opts.pkgbuild_dir);
+        }
+        if cl_opts.rebuild_script.is_some() {
+            self.rebuild_script.clone_from(&cl_opts.rebuild_script);
+        }
+        self.disk_usage |= cl_opts.disk_usage;
+        self.ignore_path.extend(cl_opts.ignore_path.iter().cloned());
+        self.plugin_roots
+            .extend(cl_opts.plugin_root.iter().cloned());
+        self.skip_data_files |= cl_opts.skip_data_files;
+        self.analyze_cross_toolchains |= cl_opts.analyze_cross_toolchains;
+        self.elevate |= cl_opts.elevate;
+        if cl_opts.analysis_user.is_some() {
+            self.analysis_user.clone_from(&cl_opts.analysis_user);
+        }
+        self.enable_checks
+            .extend(cl_opts.enable_check.iter().cloned());
+        self.disable_checks
+            .extend(cl_opts.disable_check.iter().cloned());
+        if cl_opts.check_timeout.is_some() {
+            self.check_timeout = cl_opts.check_timeout;
+        }
+        if let Some(db_lock_timeout) = cl_opts.db_lock_timeout {
+            self.db_lock_timeout = db_lock_timeout;
+        }
+        if cl_opts.jobs.is_some() {
+            self.jobs = cl_opts.jobs;
+        }
+        if cl_opts.max_package_age.is_some() {
+            self.max_package_age = cl_opts.max_package_age;
+        }
+        if cl_opts.hook_max_packages.is_some() {
+            self.hook_max_packages = cl_opts.hook_max_packages;
+        }
+        if cl_opts.defer.is_some() {
+            self.defer = cl_opts.defer;
+        }
+        self.resolve_tree |= cl_opts.resolve_tree;
+        self.ldd |= cl_opts.ldd;
+        self.block_removals |= cl_opts.block_removals;
+        if cl_opts.import_soname_index.is_some() {
+            self.import_soname_index
+                .clone_from(&cl_opts.import_soname_index);
+        }
+        self.all_packages = cl_opts.all_packages;
+        self.verbose = cl_opts.verbose;
+        self.quiet |= cl_opts.quiet;
+        self.root.clone_from(&cl_opts.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config: Config = toml::from_str(
+            "ignore = [\"LIB001:zoom*\", \"SD001\"]\nignore_path = [\"/opt/*/bundled\"]\n\
+             min_severity = \"warning\"\ndefer = \"idle\"\ncheck_timeout = 0\n\
+             [check_timeouts]\ndebug-info = 120\n\
+             [bundled_runtime]\nzoom = [\"/opt/zoom\", \"/opt/zoom/cef\"]\n\
+             [severity]\nPY002 = \"error\"\n\"LIB001:zoom*\" = \"info\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.ignore.len(), 2);
+        assert_eq!(config.ignore_path.len(), 1);
+        assert_eq!(config.bundled_runtime["zoom"].len(), 2);
+        assert_eq!(config.min_severity, Some(Severity::Warning));
+        assert_eq!(config.defer, Some(Defer::Idle));
+        assert_eq!(
+            config.check_timeout("debug-info"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(config.check_timeout("pam"), None);
+        assert_eq!(
+            Config::default().check_timeout("pam"),
+            Some(Duration::from_secs(DEFAULT_CHECK_TIMEOUT))
+        );
+        let printed: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(printed.ignore.len(), 2);
+        assert_eq!(printed.bundled_runtime, config.bundled_runtime);
+        assert_eq!(BTreeMap::from(printed.severity).len(), 2);
+
+        assert!(toml::from_str::<Config>("ignore = [\"FOO001\"]").is_err());
+        assert!(toml::from_str::<Config>("foo = 1").is_err());
+        assert!(toml::from_str::<Config>("[severity]\nLIB001 = \"fatal\"").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Trees of cross compilation toolchains, like those of the arm-none-eabi-* or mingw-w64-*
+//! packages: their sysroots and compiler runtime directories hold libraries and programs built for
+//! another target than the system, which can not be analyzed like system files
+
+/// Directories of compilers files, with a subdirectory per target, named like avr or
+/// x86_64-pc-linux-gnu
+const COMPILER_DIRS: [&str; 2] = ["/usr/lib/gcc/", "/usr/libexec/gcc/"];
+
+/// Directory of toolchain sysroots, named after their target triple
+const SYSROOTS_DIR: &str = "/usr/";
+
+/// Last components of target triples: operating systems, ABIs and object formats
+const TRIPLE_SYSTEMS: [&str; 12] = [
+    "linux", "gnu", "musl", "android", "mingw32", "cygwin", "eabi", "eabihf", "elf", "none",
+    "wasi", "darwin",
+];
+
+/// Return true if a directory name is a target triple, like aarch64-linux-gnu, arm-none-eabi or
+/// x86_64-w64-mingw32
+fn is_target_triple(name: &str) -> bool {
+    let mut parts = name.split('-');
+    let arch = parts.next().unwrap_or_default();
+    let parts: Vec<&str> = parts.collect();
+    !arch.is_empty()
+        && arch
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || (c == '_'))
+        && parts.iter().any(|p| {
+            TRIPLE_SYSTEMS
+                .iter()
+                .any(|s| p.starts_with(s) && p[s.len()..].chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+/// Get target of the toolchain tree a file is in, None if it is not in one
+pub fn target_triple(path: &str) -> Option<&str> {
+    let subdir = |dir: &str| {
+        path.strip_prefix(dir)
+            .and_then(|p| p.split_once('/'))
+            .map(|(t, _p)| t)
+    };
+    COMPILER_DIRS
+        .iter()
+        .find_map(|d| subdir(d))
+        .or_else(|| subdir(SYSROOTS_DIR).filter(|t| is_target_triple(t)))
+}
+
+/// Return true if a file is in the tree of a toolchain for another target than the system, the
+/// native toolchain (like /usr/lib/gcc/x86_64-pc-linux-gnu) being analyzed as usual
+pub fn is_cross_toolchain_file(path: &str) -> bool {
+    target_triple(path).is_some_and(|t| {
+        !(t.starts_with(std::env::consts::ARCH) && t.contains("-linux") && !t.contains("-musl"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_triple() {
+        assert_eq!(
+            target_triple("/usr/arm-none-eabi/lib/libc.a"),
+            Some("arm-none-eabi")
+        );
+        assert_eq!(
+            target_triple("/usr/lib/gcc/x86_64-w64-mingw32/14.1.0/libgcc_s.a"),
+            Some("x86_64-w64-mingw32")
+        );
+        assert_eq!(
+            target_triple("/usr/libexec/gcc/aarch64-linux-gnu/14.1.0/cc1"),
+            Some("aarch64-linux-gnu")
+        );
+        assert_eq!(
+            target_triple("/usr/riscv64-elf/lib/crt0.o"),
+            Some("riscv64-elf")
+        );
+        assert_eq!(
+            target_triple("/usr/lib/gcc/avr/7.3.0/libgcc.a"),
+            Some("avr")
+        );
+        assert_eq!(target_triple("/usr/lib/libfoo.so.1"), None);
+        assert_eq!(target_triple("/usr/share-foo/bar"), None);
+
+        assert!(is_cross_toolchain_file("/usr/arm-none-eabi/bin/ld"));
+        assert!(is_cross_toolchain_file(
+            "/usr/x86_64-w64-mingw32/bin/libwinpthread-1.dll"
+        ));
+        assert!(!is_cross_toolchain_file(&format!(
+            "/usr/lib/gcc/{}-pc-linux-gnu/14.1.0/liblto_plugin.so",
+            std::env::consts::ARCH
+        )));
+    }
+}
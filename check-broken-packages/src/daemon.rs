@@ -0,0 +1,583 @@
+//! Daemon keeping the indexes of installed packages warm in memory, rebuilt when the pacman local
+//! database changes, and answering queries over a unix socket, so that clients like hooks do not
+//! pay for building them on each run, and optionally over D-Bus, for desktop integration
+
+use std::collections::HashMap;
+use std::error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use simple_error::SimpleError;
+
+use crate::checks;
+use crate::config::Config;
+use crate::dbus;
+use crate::held::HeldPackages;
+use crate::needs_index::NeedsIndex;
+use crate::pacman::{self, LocalPackage};
+use crate::paths;
+use crate::report;
+use crate::system::SystemProvider;
+
+/// Name of the socket in the runtime directory
+const SOCKET_NAME: &str = "daemon.sock";
+
+/// Interval between two checks of cancellation while waiting for clients or database changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time a client has to send its query, so that a client that never sends one does not keep a thread
+const CLIENT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum length of a query line, sonames and package names are much shorter
+const MAX_QUERY_LEN: u64 = 4096;
+
+/// Maximum number of clients answered at the same time, others are refused until one is done
+const MAX_CLIENT_COUNT: usize = 16;
+
+/// Time without database change after which a transaction is considered done, so that indexes
+/// are rebuilt once per transaction rather than once per package
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// D-Bus name, object and interface of the daemon
+pub const DBUS_NAME: &str = "io.github.desbma.CheckBrokenPackages";
+const DBUS_PATH: &str = "/io/github/desbma/CheckBrokenPackages";
+
+/// Introspection data of the D-Bus object, which documents its interface
+const DBUS_INTROSPECTION: &str = include_str!("../dbus/io.github.desbma.CheckBrokenPackages.xml");
+
+/// Get default socket path
+pub fn socket_filepath() -> Option<PathBuf> {
+    paths::runtime_dir().map(|d| d.join(SOCKET_NAME))
+}
+
+/// Query sent by a client, as a line like "who-needs libssl.so.3"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Installed files directly needing a soname
+    WhoNeeds(String),
+    /// Findings of the files of a package
+    Scan(String),
+    /// Findings of the report file of the last analysis
+    LastReport,
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("who-needs"), Some(soname), None) => Ok(Request::WhoNeeds(soname.to_string())),
+            (Some("scan"), Some(package), None) => Ok(Request::Scan(package.to_string())),
+            (Some("last-report"), None, None) => Ok(Request::LastReport),
+            _ => Err(format!(
+                "Expected 'who-needs SONAME', 'scan PACKAGE' or 'last-report', got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Request::WhoNeeds(soname) => write!(f, "who-needs {}", soname),
+            Request::Scan(package) => write!(f, "scan {}", package),
+            Request::LastReport => write!(f, "last-report"),
+        }
+    }
+}
+
+/// Indexes of the installed packages, rebuilt when the local database changes
+struct State<'a> {
+    /// Context of the analysis, with its file index and foreign packages built
+    ctx: checks::Context<'a>,
+
+    /// Installed packages by name
+    packages: HashMap<String, LocalPackage>,
+
+    needs_index: NeedsIndex,
+
+    held_packages: HeldPackages,
+
+    report_filepath: Option<&'a Path>,
+}
+
+impl<'a> State<'a> {
+    /// Build indexes, reusing the entries of a previous dependency index for packages not
+    /// reinstalled since
+    fn build(
+        system: &'a dyn SystemProvider,
+        config: &'a Config,
+        report_filepath: Option<&'a Path>,
+        previous: NeedsIndex,
+    ) -> Result<State<'a>, Box<dyn error::Error>> {
+        let needs_index = NeedsIndex::build(system, previous, config.jobs())?;
+        let ctx = checks::Context::new(config, system);
+        ctx.warm_up();
+        let held_packages = system.held_packages().unwrap_or_else(|err| {
+            eprintln!("Failed to get held back packages: {}", err);
+            HeldPackages::default()
+        });
+        Ok(State {
+            ctx,
+            packages: system
+                .local_packages()?
+                .into_iter()
+                .map(|p| (p.name.clone(), p))
+                .collect(),
+            needs_index,
+            held_packages,
+            report_filepath,
+        })
+    }
+
+    /// Answer a query with JSON objects, in the format of the equivalent command output
+    fn answer(&self, request: &Request) -> Result<Vec<serde_json::Value>, String> {
+        match request {
+            Request::WhoNeeds(soname) => Ok(self
+                .needs_index
+                .who_needs(soname)
+                .into_iter()
+                .map(|(package, file)| {
+                    serde_json::json!({"package": package, "file": file, "soname": soname})
+                })
+                .collect()),
+            Request::Scan(package) => {
+                let local_package = self
+                    .packages
+                    .get(package)
+                    .ok_or_else(|| format!("Package '{}' is not installed", package))?;
+                let (system, config) = (self.ctx.system, self.ctx.config);
+                let exec_files =
+                    crate::executable_files(system, package, local_package.files.clone(), config);
+                let runtime_dirs = Arc::new(crate::package_runtime_dirs(
+                    system,
+                    package,
+                    &exec_files,
+                    config,
+                ));
+                let package = Arc::new(package.to_string());
+                Ok(exec_files
+                    .into_iter()
+                    .flat_map(|exec_filepath| {
+                        let work = crate::ExecFileWork {
+                            package: Arc::clone(&package),
+                            exec_filepath: Arc::new(exec_filepath),
+                            package_last: false,
+                            package_pending: None,
+                            runtime_dirs: Arc::clone(&runtime_dirs),
+                        };
+                        crate::analyze_file(&self.ctx, &self.held_packages, &work)
+                    })
+                    .filter(|f| crate::is_reported(f, config))
+                    .map(|f| serde_json::to_value(f).unwrap())
+                    .collect())
+            }
+            Request::LastReport => {
+                let report_filepath = self.report_filepath.ok_or("No report file")?;
+                let findings = report::read_findings(report_filepath).map_err(|e| {
+                    format!("Failed to read report file {:?}: {}", report_filepath, e)
+                })?;
+                Ok(findings
+                    .iter()
+                    .map(|f| serde_json::to_value(f).unwrap())
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Wait for a file descriptor to be readable, for at most a duration, and return true if it is
+fn wait_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) } {
+        -1 => {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(err)
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Inotify watch of the package entries of the local database
+struct DbWatcher {
+    fd: OwnedFd,
+}
+
+impl DbWatcher {
+    fn new(db_dir: &Path) -> io::Result<DbWatcher> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let db_dir = CString::new(db_dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // Package entries are directories created and removed by transactions
+        let mask = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), db_dir.as_ptr(), mask) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(DbWatcher { fd })
+    }
+
+    /// Discard pending events
+    fn drain(&self) {
+        let mut buffer = [0_u8; 4096];
+        unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+    }
+
+    /// Wait for the database to change, and for the transaction changing it to end, and return
+    /// false if cancelled before
+    fn wait_change(&self, lock_filepath: &Path, cancelled: &AtomicBool) -> io::Result<bool> {
+        loop {
+            if cancelled.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            if wait_readable(self.fd.as_raw_fd(), POLL_INTERVAL)? {
+                self.drain();
+                break;
+            }
+        }
+        loop {
+            if cancelled.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            if wait_readable(self.fd.as_raw_fd(), SETTLE_DELAY)? {
+                self.drain();
+            } else if !lock_filepath.exists() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Answer the query of a client
+fn serve_client(stream: UnixStream, state: &RwLock<State<'_>>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_READ_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new((&stream).take(MAX_QUERY_LEN)).read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Query too long, or not terminated by a new line",
+        ));
+    }
+    let answer = line
+        .parse::<Request>()
+        .and_then(|r| state.read().unwrap().answer(&r));
+    let mut writer = io::BufWriter::new(&stream);
+    match answer {
+        Ok(values) => {
+            for value in values {
+                writeln!(writer, "{}", value)?;
+            }
+        }
+        Err(err) => writeln!(writer, "{}", serde_json::json!({ "error": err }))?,
+    }
+    writer.flush()
+}
+
+/// Answer a D-Bus method call, the methods of the daemon interface return the JSON objects of
+/// socket queries as strings
+fn dbus_reply(call: &dbus::Message, state: &RwLock<State<'_>>) -> dbus::Message {
+    if call.path.as_deref() != Some(DBUS_PATH) {
+        return dbus::Message::error(
+            call,
+            "org.freedesktop.DBus.Error.UnknownObject",
+            "Unknown object",
+        );
+    }
+    // The interface of a call is optional, except for scans: the bus policy only lets
+    // administrators call Scan, and matches calls by interface
+    let has_interface = call.interface.is_some();
+    let request = match (
+        call.interface.as_deref().unwrap_or(DBUS_NAME),
+        call.member.as_deref().unwrap_or_default(),
+        call.body.as_slice(),
+    ) {
+        (DBUS_NAME, "Scan", [dbus::Value::String(package)]) if has_interface => {
+            Request::Scan(package.clone())
+        }
+        (DBUS_NAME, "WhoNeeds", [dbus::Value::String(soname)]) => Request::WhoNeeds(soname.clone()),
+        (DBUS_NAME, "LastReport", []) => Request::LastReport,
+        ("org.freedesktop.DBus.Introspectable", "Introspect", []) => {
+            return dbus::Message::method_return(
+                call,
+                vec![dbus::Value::String(DBUS_INTROSPECTION.to_string())],
+            );
+        }
+        ("org.freedesktop.DBus.Peer", "Ping", []) => {
+            return dbus::Message::method_return(call, Vec::new());
+        }
+        _ => {
+            return dbus::Message::error(
+                call,
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                "Unknown method, or invalid arguments",
+            );
+        }
+    };
+    match state.read().unwrap().answer(&request) {
+        Ok(values) => dbus::Message::method_return(
+            call,
+            vec![dbus::Value::StringArray(
+                values.iter().map(|v| v.to_string()).collect(),
+            )],
+        ),
+        Err(err) => dbus::Message::error(call, &format!("{}.Error.Failed", DBUS_NAME), &err),
+    }
+}
+
+/// Answer D-Bus method calls, until cancelled
+fn serve_dbus(
+    mut connection: dbus::Connection,
+    state: &RwLock<State<'_>>,
+    cancelled: &AtomicBool,
+) -> io::Result<()> {
+    while !cancelled.load(Ordering::SeqCst) {
+        if !wait_readable(connection.as_raw_fd(), POLL_INTERVAL)? {
+            continue;
+        }
+        let call = match connection.receive() {
+            Ok(m) if m.kind == dbus::METHOD_CALL => m,
+            Ok(_) => continue,
+            // Message with unsupported content, already consumed
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => continue,
+            Err(err) => return Err(err),
+        };
+        let reply = dbus_reply(&call, state);
+        if (call.flags & dbus::NO_REPLY_EXPECTED) == 0 {
+            connection.send(reply)?;
+        }
+    }
+    Ok(())
+}
+
+/// Build indexes and answer queries on a socket, and on the system bus (or session bus when not
+/// root) if enabled, until cancelled
+pub fn run(
+    socket_filepath: &Path,
+    report_filepath: Option<&Path>,
+    dbus: bool,
+    system: &dyn SystemProvider,
+    config: &Config,
+    cancelled: &AtomicBool,
+) -> Result<(), Box<dyn error::Error>> {
+    // Watch before building indexes, so that no change is missed. The database of a system
+    // mounted at a root directory is read once.
+    let watcher = match config.root {
+        None => Some(DbWatcher::new(Path::new(pacman::LOCAL_DB_DIR))?),
+        Some(_) => None,
+    };
+    // The cache is for the running system only, and kept up to date for other runs
+    let save_index = |state: &State<'_>| {
+        if config.root.is_none() {
+            if let Err(err) = state.needs_index.save() {
+                eprintln!("Failed to cache dependency index: {}", err);
+            }
+        }
+    };
+    let previous = if config.root.is_none() {
+        NeedsIndex::load()
+    } else {
+        NeedsIndex::default()
+    };
+    let state = State::build(system, config, report_filepath, previous)?;
+    save_index(&state);
+    let state = RwLock::new(state);
+
+    let dbus_connection = if dbus {
+        let address = dbus::bus_address(paths::is_root())
+            .ok_or_else(|| SimpleError::new("No session bus address"))?;
+        let mut connection = dbus::Connection::open(&address)?;
+        connection.request_name(DBUS_NAME)?;
+        if !config.quiet {
+            eprintln!("Answering D-Bus calls as {}", DBUS_NAME);
+        }
+        Some(connection)
+    } else {
+        None
+    };
+
+    if let Some(dir) = socket_filepath.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Left by a daemon that did not exit cleanly
+    match fs::remove_file(socket_filepath) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(socket_filepath)?;
+    if !config.quiet {
+        eprintln!("Answering queries on {:?}", socket_filepath);
+    }
+
+    let client_count = AtomicUsize::new(0);
+    let res = thread::scope(|scope| {
+        let state = &state;
+        let client_count = &client_count;
+        let save_index = &save_index;
+        if let Some(watcher) = watcher {
+            scope.spawn(move || loop {
+                match watcher.wait_change(Path::new(pacman::DB_LOCK_FILEPATH), cancelled) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(err) => {
+                        eprintln!("Failed to watch the pacman database: {}", err);
+                        break;
+                    }
+                }
+                let previous = state.read().unwrap().needs_index.clone();
+                match State::build(system, config, report_filepath, previous) {
+                    Ok(s) => {
+                        save_index(&s);
+                        *state.write().unwrap() = s;
+                        if config.verbose {
+                            eprintln!("Pacman database changed, indexes updated");
+                        }
+                    }
+                    Err(err) => eprintln!("Failed to update indexes: {}", err),
+                }
+            });
+        }
+
+        if let Some(connection) = dbus_connection {
+            scope.spawn(move || {
+                if let Err(err) = serve_dbus(connection, state, cancelled) {
+                    eprintln!("Failed to answer D-Bus calls: {}", err);
+                }
+            });
+        }
+
+        while !cancelled.load(Ordering::SeqCst) {
+            if !wait_readable(listener.as_raw_fd(), POLL_INTERVAL)? {
+                continue;
+            }
+            let (stream, _addr) = listener.accept()?;
+            if client_count.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENT_COUNT {
+                client_count.fetch_sub(1, Ordering::SeqCst);
+                // Dropping the stream closes it, the client sees an empty answer
+                continue;
+            }
+            scope.spawn(move || {
+                if let Err(err) = serve_client(stream, state) {
+                    eprintln!("Failed to answer query: {}", err);
+                }
+                client_count.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        io::Result::Ok(())
+    });
+    // Stop the watcher if accepting clients failed
+    cancelled.store(true, Ordering::SeqCst);
+    fs::remove_file(socket_filepath)?;
+    Ok(res?)
+}
+
+/// Send a query to the daemon, and print its answer
+pub fn query(socket_filepath: &Path, request: &Request) -> Result<(), Box<dyn error::Error>> {
+    let mut stream = UnixStream::connect(socket_filepath).map_err(|e| {
+        SimpleError::new(format!(
+            "Failed to connect to daemon socket {:?}, is the daemon running? {}",
+            socket_filepath, e
+        ))
+    })?;
+    writeln!(stream, "{}", request)?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let value: serde_json::Value = serde_json::from_str(&line)?;
+        if let Some(err) = value.get("error").and_then(|e| e.as_str()) {
+            return Err(SimpleError::new(err).into());
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_state_answer() {
+        let system = MockSystem::default()
+            .package("openssl", false, &["/usr/lib/libssl.so.3"])
+            .package("app", true, &["/usr/bin/app", "/usr/share/app/data"])
+            .file("/usr/lib/libssl.so.3", 0o755, b"\x7fELF", &[])
+            .file("/usr/bin/app", 0o755, b"\x7fELF", &["libfoo.so.1"])
+            .linked(
+                "/usr/bin/app",
+                &[("libssl.so.3", "/usr/lib/libssl.so.3"), ("libfoo.so.1", "")],
+            )
+            .file("/usr/share/app/data", 0o644, b"data", &[]);
+        let config = Config::default();
+        let state = State::build(&system, &config, None, NeedsIndex::default()).unwrap();
+
+        assert_eq!(
+            state
+                .answer(&"who-needs libssl.so.3".parse().unwrap())
+                .unwrap(),
+            [
+                serde_json::json!({"package": "app", "file": "/usr/bin/app", "soname": "libssl.so.3"})
+            ]
+        );
+        assert_eq!(
+            state.answer(&"scan app".parse().unwrap()).unwrap(),
+            [serde_json::json!({
+                "type": "missing_dependency",
+                "package": "app",
+                "file": "/usr/bin/app",
+                "dependency": "libfoo.so.1",
+            })]
+        );
+        assert!(state.answer(&Request::Scan("foo".to_string())).is_err());
+
+        let state = RwLock::new(state);
+        let mut call = dbus::Message::method_call(DBUS_NAME, DBUS_PATH, DBUS_NAME, "Scan");
+        call.body = vec![dbus::Value::String("app".to_string())];
+        assert_eq!(dbus_reply(&call, &state).kind, dbus::METHOD_RETURN);
+        call.interface = None;
+        assert_eq!(dbus_reply(&call, &state).kind, dbus::ERROR);
+        let (client, server) = UnixStream::pair().unwrap();
+        (&client)
+            .write_all(&vec![b'a'; MAX_QUERY_LEN as usize + 1])
+            .unwrap();
+        assert!(serve_client(server, &state).is_err());
+        let state = state.into_inner().unwrap();
+        assert!(state.answer(&Request::LastReport).is_err());
+        assert!("who-needs".parse::<Request>().is_err());
+        assert_eq!(
+            Request::WhoNeeds("libssl.so.3".to_string()).to_string(),
+            "who-needs libssl.so.3"
+        );
+    }
+}
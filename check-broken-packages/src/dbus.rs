@@ -0,0 +1,501 @@
+//! Minimal implementation of the D-Bus protocol, enough to expose the daemon on a bus:
+//! authentication, and little endian messages with string, string array and integer arguments
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+/// Address of the system bus, if not overridden by the environment
+const SYSTEM_BUS_ADDRESS: &str = "unix:path=/run/dbus/system_bus_socket";
+
+/// Message types
+pub const METHOD_CALL: u8 = 1;
+pub const METHOD_RETURN: u8 = 2;
+pub const ERROR: u8 = 3;
+
+/// Message flag of calls the caller does not wait a reply for
+pub const NO_REPLY_EXPECTED: u8 = 0x1;
+
+/// Header field codes
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+
+/// Length of the fixed part of the header, up to the length of the header fields array
+const FIXED_HEADER_LEN: usize = 16;
+
+/// Bus daemon name, object and interface
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// Flag of RequestName to fail if the name is owned, and its reply if the name is now owned
+const NAME_FLAG_DO_NOT_QUEUE: u32 = 0x4;
+const NAME_REPLY_PRIMARY_OWNER: u32 = 1;
+
+/// Argument of a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    StringArray(Vec<String>),
+    U32(u32),
+}
+
+impl Value {
+    fn signature(&self) -> &'static str {
+        match self {
+            Value::String(_) => "s",
+            Value::StringArray(_) => "as",
+            Value::U32(_) => "u",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Message {
+    pub kind: u8,
+    pub flags: u8,
+    /// Set when sent
+    pub serial: u32,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub reply_serial: Option<u32>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+}
+
+impl Message {
+    pub fn method_call(destination: &str, path: &str, interface: &str, member: &str) -> Message {
+        Message {
+            kind: METHOD_CALL,
+            path: Some(path.to_string()),
+            interface: Some(interface.to_string()),
+            member: Some(member.to_string()),
+            destination: Some(destination.to_string()),
+            ..Message::default()
+        }
+    }
+
+    /// Build reply to a method call
+    pub fn method_return(call: &Message, body: Vec<Value>) -> Message {
+        Message {
+            kind: METHOD_RETURN,
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            body,
+            ..Message::default()
+        }
+    }
+
+    /// Build error reply to a method call
+    pub fn error(call: &Message, name: &str, text: &str) -> Message {
+        Message {
+            kind: ERROR,
+            error_name: Some(name.to_string()),
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            body: vec![Value::String(text.to_string())],
+            ..Message::default()
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        // The body starts at an offset multiple of 8, which is the largest alignment
+        let mut body = Encoder::default();
+        for value in &self.body {
+            body.value(value);
+        }
+        let signature: String = self.body.iter().map(Value::signature).collect();
+
+        let mut encoder = Encoder::default();
+        encoder.buf.extend([b'l', self.kind, self.flags, 1]);
+        encoder.u32(body.buf.len() as u32);
+        encoder.u32(self.serial);
+        let strings = [
+            (FIELD_PATH, "o", &self.path),
+            (FIELD_INTERFACE, "s", &self.interface),
+            (FIELD_MEMBER, "s", &self.member),
+            (FIELD_ERROR_NAME, "s", &self.error_name),
+            (FIELD_DESTINATION, "s", &self.destination),
+            (FIELD_SENDER, "s", &self.sender),
+        ];
+        let fields_start = encoder.array_start();
+        for (code, field_signature, value) in strings {
+            if let Some(value) = value {
+                encoder.align(8);
+                encoder.buf.push(code);
+                encoder.signature(field_signature);
+                encoder.string(value);
+            }
+        }
+        if let Some(reply_serial) = self.reply_serial {
+            encoder.align(8);
+            encoder.buf.push(FIELD_REPLY_SERIAL);
+            encoder.signature("u");
+            encoder.u32(reply_serial);
+        }
+        if !signature.is_empty() {
+            encoder.align(8);
+            encoder.buf.push(FIELD_SIGNATURE);
+            encoder.signature("g");
+            encoder.signature(&signature);
+        }
+        encoder.array_end(fields_start);
+        encoder.align(8);
+        encoder.buf.extend(body.buf);
+        encoder.buf
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Message> {
+        let mut decoder = Decoder { buf, pos: 0 };
+        if decoder.u8()? != b'l' {
+            return Err(invalid_data("Unsupported big endian message"));
+        }
+        let mut message = Message {
+            kind: decoder.u8()?,
+            flags: decoder.u8()?,
+            ..Message::default()
+        };
+        let _version = decoder.u8()?;
+        let _body_len = decoder.u32()?;
+        message.serial = decoder.u32()?;
+        let fields_end = decoder.u32()? as usize + decoder.pos;
+        let mut signature = String::new();
+        while decoder.pos < fields_end {
+            decoder.align(8)?;
+            let code = decoder.u8()?;
+            let field_signature = decoder.signature()?;
+            let string = match field_signature.as_str() {
+                "s" | "o" => Some(decoder.string()?),
+                "g" => Some(decoder.signature()?),
+                "u" => {
+                    let value = decoder.u32()?;
+                    if code == FIELD_REPLY_SERIAL {
+                        message.reply_serial = Some(value);
+                    }
+                    None
+                }
+                _ => return Err(invalid_data("Unsupported header field type")),
+            };
+            match code {
+                FIELD_PATH => message.path = string,
+                FIELD_INTERFACE => message.interface = string,
+                FIELD_MEMBER => message.member = string,
+                FIELD_ERROR_NAME => message.error_name = string,
+                FIELD_DESTINATION => message.destination = string,
+                FIELD_SENDER => message.sender = string,
+                FIELD_SIGNATURE => signature = string.unwrap_or_default(),
+                _ => {}
+            }
+        }
+        decoder.align(8)?;
+        let mut types = signature.chars();
+        while let Some(c) = types.next() {
+            message
+                .body
+                .push(match (c, types.as_str().starts_with('s')) {
+                    ('s', _) => Value::String(decoder.string()?),
+                    ('u', _) => Value::U32(decoder.u32()?),
+                    ('a', true) => {
+                        types.next();
+                        let end = decoder.u32()? as usize + decoder.pos;
+                        let mut strings = Vec::new();
+                        while decoder.pos < end {
+                            strings.push(decoder.string()?);
+                        }
+                        Value::StringArray(strings)
+                    }
+                    _ => return Err(invalid_data("Unsupported argument type")),
+                });
+        }
+        Ok(message)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Marshaller of values, aligned relative to the start of the buffer
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn align(&mut self, alignment: usize) {
+        while !self.buf.len().is_multiple_of(alignment) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend(value.to_le_bytes());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.buf.extend(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn signature(&mut self, s: &str) {
+        self.buf.push(s.len() as u8);
+        self.buf.extend(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    /// Write placeholder of an array length, and get the array start, assuming its elements are
+    /// aligned like its length
+    fn array_start(&mut self) -> usize {
+        self.u32(0);
+        self.buf.len()
+    }
+
+    /// Write length of an array that started at an offset
+    fn array_end(&mut self, start: usize) {
+        let len = (self.buf.len() - start) as u32;
+        self.buf[start - 4..start].copy_from_slice(&len.to_le_bytes());
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::String(s) => self.string(s),
+            Value::StringArray(strings) => {
+                let start = self.array_start();
+                for s in strings {
+                    self.string(s);
+                }
+                self.array_end(start);
+            }
+            Value::U32(v) => self.u32(*v),
+        }
+    }
+}
+
+/// Unmarshaller of values
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid_data("Truncated message"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, alignment: usize) -> io::Result<()> {
+        let padding = (alignment - self.pos % alignment) % alignment;
+        self.bytes(padding).map(|_| ())
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4)?;
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read string of a length, followed by a nul byte
+    fn string_of_len(&mut self, len: usize) -> io::Result<String> {
+        let s = String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| invalid_data("Invalid string"))?;
+        self.bytes(1)?;
+        Ok(s)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        self.string_of_len(len)
+    }
+
+    fn signature(&mut self) -> io::Result<String> {
+        let len = self.u8()? as usize;
+        self.string_of_len(len)
+    }
+}
+
+/// Get address of the system bus, or of the session bus
+pub fn bus_address(system: bool) -> Option<String> {
+    let (env_var, default) = if system {
+        ("DBUS_SYSTEM_BUS_ADDRESS", Some(SYSTEM_BUS_ADDRESS))
+    } else {
+        ("DBUS_SESSION_BUS_ADDRESS", None)
+    };
+    env::var(env_var)
+        .ok()
+        .filter(|a| !a.is_empty())
+        .or_else(|| default.map(str::to_string))
+}
+
+/// Connection to a bus
+pub struct Connection {
+    stream: UnixStream,
+    last_serial: u32,
+}
+
+impl Connection {
+    /// Connect and authenticate to a bus, from its address like "unix:path=/run/dbus/socket"
+    pub fn open(address: &str) -> io::Result<Connection> {
+        let socket_filepath = address
+            .split(';')
+            .filter_map(|a| a.strip_prefix("unix:"))
+            .flat_map(|a| a.split(','))
+            .find_map(|kv| kv.strip_prefix("path="))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("Unsupported bus address {:?}", address),
+                )
+            })?;
+        let mut stream = UnixStream::connect(socket_filepath)?;
+
+        // Authenticated by the uid of the socket peer
+        let uid = unsafe { libc::geteuid() }.to_string();
+        let uid_hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+        stream.write_all(format!("\0AUTH EXTERNAL {}\r\n", uid_hex).as_bytes())?;
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        if !line.starts_with(b"OK ") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Bus authentication failed: {}",
+                    String::from_utf8_lossy(&line).trim()
+                ),
+            ));
+        }
+        stream.write_all(b"BEGIN\r\n")?;
+
+        let mut connection = Connection {
+            stream,
+            last_serial: 0,
+        };
+        connection.call(Message::method_call(BUS_NAME, BUS_PATH, BUS_NAME, "Hello"))?;
+        Ok(connection)
+    }
+
+    /// Own a well-known name, failing if another connection owns it
+    pub fn request_name(&mut self, name: &str) -> io::Result<()> {
+        let mut call = Message::method_call(BUS_NAME, BUS_PATH, BUS_NAME, "RequestName");
+        call.body = vec![
+            Value::String(name.to_string()),
+            Value::U32(NAME_FLAG_DO_NOT_QUEUE),
+        ];
+        match self.call(call)?.body.as_slice() {
+            [Value::U32(NAME_REPLY_PRIMARY_OWNER)] => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("Bus name {} is already owned", name),
+            )),
+        }
+    }
+
+    /// Send message, and get its serial
+    pub fn send(&mut self, mut message: Message) -> io::Result<u32> {
+        self.last_serial += 1;
+        message.serial = self.last_serial;
+        self.stream.write_all(&message.encode())?;
+        Ok(message.serial)
+    }
+
+    /// Wait for the next message
+    pub fn receive(&mut self) -> io::Result<Message> {
+        let mut buf = vec![0; FIXED_HEADER_LEN];
+        self.stream.read_exact(&mut buf)?;
+        let uint = |offset: usize| {
+            u32::from_le_bytes([
+                buf[offset],
+                buf[offset + 1],
+                buf[offset + 2],
+                buf[offset + 3],
+            ]) as usize
+        };
+        let (body_len, fields_len) = (uint(4), uint(12));
+        let len = (FIXED_HEADER_LEN + fields_len).div_ceil(8) * 8 + body_len;
+        buf.resize(len, 0);
+        self.stream.read_exact(&mut buf[FIXED_HEADER_LEN..])?;
+        Message::decode(&buf)
+    }
+
+    /// Call a method, and wait for its reply
+    fn call(&mut self, message: Message) -> io::Result<Message> {
+        let serial = self.send(message)?;
+        loop {
+            let reply = self.receive()?;
+            if reply.reply_serial != Some(serial) {
+                continue;
+            }
+            if reply.kind == ERROR {
+                let text = match reply.body.first() {
+                    Some(Value::String(s)) => s.as_str(),
+                    _ => "",
+                };
+                return Err(io::Error::other(format!(
+                    "{}: {}",
+                    reply.error_name.as_deref().unwrap_or_default(),
+                    text
+                )));
+            }
+            return Ok(reply);
+        }
+    }
+}
+
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_encoding() {
+        let mut call = Message::method_call(BUS_NAME, BUS_PATH, BUS_NAME, "RequestName");
+        call.serial = 2;
+        call.body = vec![Value::String("a.b".to_string()), Value::U32(4)];
+        let buf = call.encode();
+        assert_eq!(&buf[..12], b"l\x01\x00\x01\x0c\x00\x00\x00\x02\x00\x00\x00");
+        assert_eq!(
+            &buf[buf.len() - 12..],
+            b"\x03\x00\x00\x00a.b\x00\x04\x00\x00\x00"
+        );
+        assert_eq!(Message::decode(&buf).unwrap(), call);
+
+        call.sender = Some(":1.42".to_string());
+        let reply = Message::method_return(
+            &call,
+            vec![Value::StringArray(vec!["{}".to_string(), "[]".to_string()])],
+        );
+        let decoded = Message::decode(&reply.encode()).unwrap();
+        assert_eq!(decoded, reply);
+        assert_eq!(decoded.reply_serial, Some(2));
+        assert_eq!(decoded.destination.as_deref(), Some(":1.42"));
+
+        assert!(Message::decode(&buf[..buf.len() - 1]).is_err());
+    }
+}
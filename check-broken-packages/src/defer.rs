@@ -0,0 +1,312 @@
+//! Deferral of the analysis of hook runs, so that big upgrades finish faster: the hook only
+//! records the transaction targets, and schedules the analysis with systemd-run a number of
+//! minutes later, or as soon as the system is idle
+
+use std::convert::TryFrom;
+use std::error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// Name of the transient systemd unit of deferred analyses
+pub const UNIT_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "-deferred");
+
+/// Name of the file of recorded targets in the state directory
+const TARGETS_FILENAME: &str = "deferred_targets";
+
+/// Name of the file of targets taken by a deferred analysis in the state directory, until it
+/// succeeds
+const TAKEN_TARGETS_FILENAME: &str = "deferred_targets.taken";
+
+/// Maximum number of deferred analysis units, a transaction happening while all of them run is
+/// only analyzed by the next one
+const MAX_UNIT_COUNT: usize = 3;
+
+/// Interval between two checks of the load average while waiting for the system to be idle
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When to run a deferred analysis
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Defer {
+    /// A number of minutes after the transaction
+    Minutes(u64),
+    /// As soon as the system is idle
+    Idle,
+}
+
+impl FromStr for Defer {
+    type Err = String;
+
+    /// Parse deferral from a number of minutes, or "idle"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idle" => Ok(Defer::Idle),
+            _ => s.parse().map(Defer::Minutes).map_err(|_| {
+                format!(
+                    "Invalid deferral {:?}, expected a number of minutes or 'idle'",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Defer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Defer::Minutes(minutes) => write!(f, "{}", minutes),
+            Defer::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+impl TryFrom<String> for Defer {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Defer> for String {
+    fn from(defer: Defer) -> String {
+        defer.to_string()
+    }
+}
+
+fn targets_filepath() -> Option<PathBuf> {
+    paths::state_dir().map(|d| d.join(TARGETS_FILENAME))
+}
+
+fn taken_targets_filepath() -> Option<PathBuf> {
+    paths::state_dir().map(|d| d.join(TAKEN_TARGETS_FILENAME))
+}
+
+/// Add targets of a transaction to those recorded since the last deferred analysis
+pub fn record_targets(targets: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let filepath = targets_filepath().ok_or("No state directory")?;
+    if let Some(dir) = filepath.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filepath)?;
+    for target in targets {
+        writeln!(file, "{}", target)?;
+    }
+    Ok(())
+}
+
+/// Get targets recorded since the last successful deferred analysis, without duplicates. They are
+/// moved aside, so that transactions recorded from now on are left to the next deferred analysis,
+/// and kept until `forget_taken_targets` is called.
+pub fn take_targets() -> Vec<String> {
+    let (filepath, taken_filepath) = match (targets_filepath(), taken_targets_filepath()) {
+        (Some(f), Some(t)) => (f, t),
+        _ => return Vec::new(),
+    };
+    if let Err(err) = move_targets(&filepath, &taken_filepath) {
+        if err.kind() != io::ErrorKind::NotFound {
+            eprintln!("Failed to take deferred targets: {}", err);
+        }
+    }
+    let mut targets: Vec<String> = match fs::read_to_string(&taken_filepath) {
+        Ok(c) => c.lines().map(str::to_string).collect(),
+        Err(_) => return Vec::new(),
+    };
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+}
+
+/// Move recorded targets to the taken targets, adding them to those of a previous analysis that
+/// did not succeed
+fn move_targets(filepath: &Path, taken_filepath: &Path) -> io::Result<()> {
+    if !taken_filepath.exists() {
+        return fs::rename(filepath, taken_filepath);
+    }
+    let targets = fs::read(filepath)?;
+    fs::OpenOptions::new()
+        .append(true)
+        .open(taken_filepath)?
+        .write_all(&targets)?;
+    fs::remove_file(filepath)
+}
+
+/// Forget the targets taken by an analysis that succeeded
+pub fn forget_taken_targets() {
+    if let Some(taken_filepath) = taken_targets_filepath() {
+        match fs::remove_file(taken_filepath) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                eprintln!("Failed to forget deferred targets: {}", err);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Get command line arguments of the deferred analysis, from those of the hook run: it is not a
+/// hook run itself, and waits for the system to be idle if needed
+fn deferred_args(mut args: impl Iterator<Item = OsString>, defer: Defer) -> Vec<OsString> {
+    let mut deferred = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--hook" || arg.to_string_lossy().starts_with("--defer=") {
+            continue;
+        }
+        if arg == "--defer" {
+            args.next();
+            continue;
+        }
+        deferred.push(arg);
+    }
+    if defer == Defer::Idle {
+        deferred.push(OsString::from("--wait-idle"));
+    }
+    deferred
+}
+
+/// Get name of a deferred analysis unit
+fn unit_name(index: usize) -> String {
+    match index {
+        0 => UNIT_NAME.to_string(),
+        _ => format!("{}-{}", UNIT_NAME, index + 1),
+    }
+}
+
+/// Return true if the service of a unit is running, it took its targets already
+fn is_running(unit: &str) -> bool {
+    Command::new("systemctl")
+        .args(["is-active", "--quiet", &format!("{}.service", unit)])
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Schedule the analysis in a transient systemd unit, with the arguments of the hook run. A
+/// deferred analysis already scheduled by a previous transaction, and not yet running, also covers
+/// this one, otherwise another unit is scheduled.
+pub fn schedule(defer: Defer) -> Result<(), Box<dyn error::Error>> {
+    for index in 0..MAX_UNIT_COUNT {
+        let unit = unit_name(index);
+        match run_unit(&unit, defer)? {
+            true => return Ok(()),
+            false if !is_running(&unit) => return Ok(()),
+            false => {}
+        }
+    }
+    Ok(())
+}
+
+/// Start a transient systemd unit running the deferred analysis, return false if it already exists
+fn run_unit(unit: &str, defer: Defer) -> Result<bool, Box<dyn error::Error>> {
+    let exe = std::env::current_exe()?;
+    let mut cmd = Command::new("systemd-run");
+    cmd.args([
+        format!("--unit={}", unit).as_str(),
+        "--description=Deferred check for packages with missing dependencies",
+        "--collect",
+        "--no-block",
+        "--property=Nice=19",
+        "--property=IOSchedulingClass=idle",
+    ]);
+    if let Defer::Minutes(minutes) = defer {
+        cmd.arg(format!("--on-active={}min", minutes.max(1)));
+    }
+    cmd.arg(exe)
+        .args(deferred_args(std::env::args_os().skip(1), defer));
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("already exists") {
+            return Err(format!("systemd-run failed: {}", stderr.trim()).into());
+        }
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Return true if the system is idle, from the content of /proc/loadavg: the load average of the
+/// last minute is below half the number of CPUs
+fn is_idle(loadavg: &str, cpu_count: usize) -> bool {
+    loadavg
+        .split_whitespace()
+        .next()
+        .and_then(|l| l.parse::<f64>().ok())
+        .is_none_or(|l| l < cpu_count as f64 / 2.0)
+}
+
+/// Wait for the system to be idle, and return false if cancelled before
+pub fn wait_idle(cancelled: &AtomicBool) -> io::Result<bool> {
+    let cpu_count = num_cpus::get();
+    loop {
+        if is_idle(&fs::read_to_string("/proc/loadavg")?, cpu_count) {
+            return Ok(true);
+        }
+        // Wake up regularly to check cancellation
+        for _ in 0..IDLE_POLL_INTERVAL.as_secs() {
+            if cancelled.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_deferred_args() {
+        let args = ["--hook", "--defer", "5", "--enable-check", "debug-info"].map(OsString::from);
+        assert_eq!(
+            deferred_args(args.iter().cloned(), Defer::Minutes(5)),
+            ["--enable-check", "debug-info"]
+        );
+        let args = ["--defer=idle", "--hook"].map(OsString::from);
+        assert_eq!(
+            deferred_args(args.iter().cloned(), Defer::Idle),
+            ["--wait-idle"]
+        );
+
+        assert_eq!("idle".parse(), Ok(Defer::Idle));
+        assert_eq!("10".parse(), Ok(Defer::Minutes(10)));
+        assert!("soon".parse::<Defer>().is_err());
+
+        assert_eq!(unit_name(0), UNIT_NAME);
+        assert_eq!(unit_name(1), format!("{}-2", UNIT_NAME));
+
+        assert!(is_idle("0.52 0.58 0.59 1/467 12345\n", 4));
+        assert!(!is_idle("3.10 2.58 1.59 5/467 12345\n", 4));
+    }
+
+    #[test]
+    fn test_move_targets() {
+        let dir = TempDir::new("").unwrap();
+        let filepath = dir.path().join(TARGETS_FILENAME);
+        let taken_filepath = dir.path().join(TAKEN_TARGETS_FILENAME);
+
+        assert!(move_targets(&filepath, &taken_filepath).is_err());
+        fs::write(&filepath, "foo\n").unwrap();
+        move_targets(&filepath, &taken_filepath).unwrap();
+        assert!(!filepath.exists());
+        fs::write(&filepath, "bar\n").unwrap();
+        move_targets(&filepath, &taken_filepath).unwrap();
+        assert!(!filepath.exists());
+        assert_eq!(fs::read_to_string(&taken_filepath).unwrap(), "foo\nbar\n");
+    }
+}
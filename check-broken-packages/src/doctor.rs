@@ -0,0 +1,198 @@
+//! Self diagnostics of the environment the analysis depends on
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{Config, DEFAULT_CONFIG_FILEPATH};
+use crate::elf::ElfInfo;
+use crate::pacman::{LOCAL_DB_DIR, SYNC_DB_DIR};
+
+/// Directories where pacman looks for hooks
+const HOOK_DIRS: [&str; 2] = ["/etc/pacman.d/hooks", "/usr/share/libalpm/hooks"];
+
+/// Hook filename
+const HOOK_FILENAME: &str = concat!(env!("CARGO_PKG_NAME"), ".hook");
+
+/// Something wrong with the environment, and how to fix it
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub error: String,
+    pub fix: String,
+}
+
+/// Result of a diagnostic, with details on success
+pub struct Diagnostic {
+    pub name: &'static str,
+    pub result: Result<String, Problem>,
+}
+
+/// Run all diagnostics
+pub fn diagnose(config_filepath: Option<&Path>) -> Vec<Diagnostic> {
+    vec![
+        Diagnostic {
+            name: "pacman database",
+            result: check_local_db(),
+        },
+        Diagnostic {
+            name: "pacman files database",
+            result: check_files_db(Path::new(SYNC_DB_DIR)),
+        },
+        Diagnostic {
+            name: "ldd",
+            result: check_ldd(),
+        },
+        Diagnostic {
+            name: "ELF parser",
+            result: check_elf_parser(),
+        },
+        Diagnostic {
+            name: "configuration",
+            result: check_config(config_filepath),
+        },
+        Diagnostic {
+            name: "pacman hook",
+            result: check_hook(&HOOK_DIRS.map(PathBuf::from)),
+        },
+    ]
+}
+
+fn check_local_db() -> Result<String, Problem> {
+    if let Err(err) = fs::read_dir(LOCAL_DB_DIR) {
+        return Err(Problem {
+            error: format!("Unable to read {}: {}", LOCAL_DB_DIR, err),
+            fix: "run as a user allowed to read the pacman database".to_string(),
+        });
+    }
+    match Command::new("pacman").arg("-Qq").output() {
+        Ok(o) if o.status.success() => Ok(format!(
+            "{} packages installed",
+            o.stdout
+                .split(|b| *b == b'\n')
+                .filter(|l| !l.is_empty())
+                .count()
+        )),
+        Ok(o) => Err(Problem {
+            error: format!(
+                "pacman failed to list packages: {}",
+                String::from_utf8_lossy(&o.stderr).trim()
+            ),
+            fix: "check the pacman database with 'pacman -Dk'".to_string(),
+        }),
+        Err(err) => Err(Problem {
+            error: format!("Unable to run pacman: {}", err),
+            fix: "install pacman, this tool only works on Arch Linux based systems".to_string(),
+        }),
+    }
+}
+
+fn check_files_db(sync_dir: &Path) -> Result<String, Problem> {
+    let files_dbs = fs::read_dir(sync_dir)
+        .map(|e| {
+            e.flatten()
+                .filter(|e| e.path().extension().is_some_and(|e| e == "files"))
+                .count()
+        })
+        .unwrap_or(0);
+    if files_dbs == 0 {
+        return Err(Problem {
+            error: format!("No files database in {:?}", sync_dir),
+            fix: "download files databases with 'pacman -Fy'".to_string(),
+        });
+    }
+    Ok(format!("{} repository files databases", files_dbs))
+}
+
+fn check_ldd() -> Result<String, Problem> {
+    let exe = env::current_exe().map_err(|e| Problem {
+        error: format!("Unable to locate own executable: {}", e),
+        fix: "run the tool from an existing file".to_string(),
+    })?;
+    match Command::new("ldd").arg(&exe).output() {
+        Ok(o) if o.status.success() => Ok(format!("resolved dependencies of {:?}", exe)),
+        Ok(o) => Err(Problem {
+            error: format!(
+                "ldd failed on {:?}: {}",
+                exe,
+                String::from_utf8_lossy(&o.stderr).trim()
+            ),
+            fix: "reinstall the glibc package".to_string(),
+        }),
+        Err(err) => Err(Problem {
+            error: format!("Unable to run ldd: {}", err),
+            fix: "install the glibc package, which provides ldd".to_string(),
+        }),
+    }
+}
+
+fn check_elf_parser() -> Result<String, Problem> {
+    let parsed = env::current_exe()
+        .map_err(|e| e.to_string())
+        .and_then(|p| ElfInfo::parse(&p).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(Some(elf)) => Ok(format!(
+            "{} direct dependencies of own executable",
+            elf.needed.len()
+        )),
+        Ok(None) => Err(Problem {
+            error: "Own executable is not recognized as an ELF file".to_string(),
+            fix: "report a bug".to_string(),
+        }),
+        Err(err) => Err(Problem {
+            error: format!("Failed to parse own executable: {}", err),
+            fix: "report a bug".to_string(),
+        }),
+    }
+}
+
+fn check_config(filepath: Option<&Path>) -> Result<String, Problem> {
+    let displayed_filepath = filepath.unwrap_or_else(|| Path::new(DEFAULT_CONFIG_FILEPATH));
+    match Config::load(filepath) {
+        Ok(_) if filepath.is_none() && !displayed_filepath.exists() => Ok(format!(
+            "{:?} does not exist, using defaults",
+            displayed_filepath
+        )),
+        Ok(_) => Ok(format!("{:?} is valid", displayed_filepath)),
+        Err(err) => Err(Problem {
+            error: format!("Invalid configuration: {}", err),
+            fix: format!("fix or remove {:?}", displayed_filepath),
+        }),
+    }
+}
+
+fn check_hook(hook_dirs: &[PathBuf]) -> Result<String, Problem> {
+    match hook_dirs
+        .iter()
+        .map(|d| d.join(HOOK_FILENAME))
+        .find(|p| p.is_file())
+    {
+        Some(hook_filepath) => Ok(format!("installed in {:?}", hook_filepath)),
+        None => Err(Problem {
+            error: format!("{} not found in {:?}", HOOK_FILENAME, hook_dirs),
+            fix: format!(
+                "copy {} to {}, or the analysis will not run after upgrades",
+                HOOK_FILENAME, HOOK_DIRS[0]
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_check_hook() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let dirs = [tmp_dir.path().join("etc"), tmp_dir.path().join("usr")];
+        assert!(check_hook(&dirs).is_err());
+        assert!(check_files_db(tmp_dir.path()).is_err());
+
+        fs::create_dir(&dirs[1]).unwrap();
+        fs::write(dirs[1].join(HOOK_FILENAME), "").unwrap();
+        assert!(check_hook(&dirs).is_ok());
+    }
+}
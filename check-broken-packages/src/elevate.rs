@@ -0,0 +1,247 @@
+//! Analysis of the files the current user can not read (like 0700 root programs), batched in a
+//! single run of a helper elevated with `sudo -n` at the end of the analysis, so that it never
+//! prompts for a password, and never runs once per worker thread
+
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::elf::{DebugInfo, ElfInfo, MissingLibrary};
+use crate::held::HeldPackages;
+use crate::pacman::{FileIndex, LocalPackage};
+use crate::report::{FileDamage, HardeningIssue};
+use crate::shipped::ShippedSonames;
+use crate::system::{FileMetadata, LinkedLibrary, LoaderError, RealSystem, SystemProvider};
+
+/// Analysis of a file by the elevated helper
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Analyzed {
+        damage: Option<FileDamage>,
+        missing_dependencies: Vec<String>,
+        chains: Vec<MissingLibrary>,
+    },
+    LoaderError(String),
+    Failed(String),
+}
+
+/// Line of the helper output
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct FileAnalysis {
+    file: PathBuf,
+    outcome: Outcome,
+}
+
+fn analyze_file(system: &dyn SystemProvider, path: &Path) -> Outcome {
+    let damage = match system.damage(path) {
+        Ok(d) => d,
+        Err(err) => return Outcome::Failed(err.to_string()),
+    };
+    if damage.is_some() {
+        return Outcome::Analyzed {
+            damage,
+            missing_dependencies: Vec::new(),
+            chains: Vec::new(),
+        };
+    }
+    let missing_dependencies = match system.missing_dependencies(path) {
+        Ok(d) => d,
+        Err(err) => {
+            return match err.downcast::<LoaderError>() {
+                Ok(err) => Outcome::LoaderError(err.0),
+                Err(err) => Outcome::Failed(err.to_string()),
+            }
+        }
+    };
+    Outcome::Analyzed {
+        damage,
+        missing_dependencies,
+        chains: system.missing_dependency_chains(path).unwrap_or_default(),
+    }
+}
+
+/// Analyze files as the elevated helper, printing one JSON line per file
+pub fn run_helper(files: &[PathBuf]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for file in files {
+        let analysis = FileAnalysis {
+            file: file.clone(),
+            outcome: analyze_file(&RealSystem::default(), file),
+        };
+        writeln!(stdout, "{}", serde_json::to_string(&analysis)?)?;
+    }
+    Ok(())
+}
+
+/// Analyze files with the helper run by `sudo -n`, failing if sudo would need a password
+pub fn analyze_elevated(files: &[String]) -> Result<ElevatedAnalyses, Box<dyn error::Error>> {
+    let output = Command::new("sudo")
+        .arg("-n")
+        .arg(env::current_exe()?)
+        .arg("elevated-helper")
+        .arg("--")
+        .args(files)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to run elevated helper: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    parse_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_output(output: &str) -> Result<ElevatedAnalyses, Box<dyn error::Error>> {
+    let mut analyses = HashMap::new();
+    for line in output.lines().filter(|l| !l.is_empty()) {
+        let analysis: FileAnalysis = serde_json::from_str(line)?;
+        analyses.insert(analysis.file, analysis.outcome);
+    }
+    Ok(ElevatedAnalyses(analyses))
+}
+
+/// Analyses of files by the elevated helper
+#[derive(Debug, Default)]
+pub struct ElevatedAnalyses(HashMap<PathBuf, Outcome>);
+
+/// System provider answering with the analyses of the elevated helper for the files it analyzed
+pub struct ElevatedSystem<'a> {
+    pub inner: &'a dyn SystemProvider,
+    pub analyses: ElevatedAnalyses,
+}
+
+impl SystemProvider for ElevatedSystem<'_> {
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.foreign_packages()
+    }
+
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.packages()
+    }
+
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+        self.inner.local_packages()
+    }
+
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.package_files(package)
+    }
+
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+        self.inner.file_index()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.inner.metadata(path)
+    }
+
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        self.inner.read_head(path, len)
+    }
+
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+        match self.analyses.0.get(path) {
+            Some(Outcome::Analyzed {
+                missing_dependencies,
+                ..
+            }) => Ok(missing_dependencies.clone()),
+            Some(Outcome::LoaderError(error)) => Err(LoaderError(error.clone()).into()),
+            Some(Outcome::Failed(error)) => Err(error.clone().into()),
+            None => self.inner.missing_dependencies(path),
+        }
+    }
+
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        match self.analyses.0.get(path) {
+            Some(Outcome::Analyzed { chains, .. }) => Ok(chains.clone()),
+            Some(_) => self.missing_dependencies(path).map(|_d| Vec::new()),
+            None => self.inner.missing_dependency_chains(path),
+        }
+    }
+
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+        self.inner.linked_libraries(path)
+    }
+
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+        match self.analyses.0.get(path) {
+            Some(Outcome::Analyzed { damage, .. }) => Ok(*damage),
+            Some(_) => Ok(None),
+            None => self.inner.damage(path),
+        }
+    }
+
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        self.inner.elf_info(path)
+    }
+
+    fn hardening_issues(&self, path: &Path) -> io::Result<Option<Vec<HardeningIssue>>> {
+        self.inner.hardening_issues(path)
+    }
+
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+        self.inner.debug_info(path)
+    }
+
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+        self.inner.held_packages()
+    }
+
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        self.inner.shipped_sonames()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_elevated_system() {
+        let output = [
+            FileAnalysis {
+                file: PathBuf::from("/usr/bin/foo"),
+                outcome: Outcome::Analyzed {
+                    damage: None,
+                    missing_dependencies: vec!["libbar.so.1".to_string()],
+                    chains: Vec::new(),
+                },
+            },
+            FileAnalysis {
+                file: PathBuf::from("/usr/bin/baz"),
+                outcome: Outcome::LoaderError("bad ELF interpreter".to_string()),
+            },
+        ]
+        .iter()
+        .map(|a| serde_json::to_string(a).unwrap() + "\n")
+        .collect::<String>();
+        let inner = MockSystem::default().unreadable_file("/usr/bin/foo");
+        let system = ElevatedSystem {
+            inner: &inner,
+            analyses: parse_output(&output).unwrap(),
+        };
+
+        assert_eq!(system.damage(Path::new("/usr/bin/foo")).unwrap(), None);
+        assert_eq!(
+            system
+                .missing_dependencies(Path::new("/usr/bin/foo"))
+                .unwrap(),
+            ["libbar.so.1"]
+        );
+        assert!(system
+            .missing_dependencies(Path::new("/usr/bin/baz"))
+            .unwrap_err()
+            .is::<LoaderError>());
+    }
+}
@@ -0,0 +1,966 @@
+//! ELF dynamic dependency parsing and resolution, following the dynamic linker search order
+
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use goblin::container::{Container, Ctx, Endian};
+use goblin::elf::dynamic;
+use goblin::elf::header;
+use goblin::elf::note;
+use goblin::elf::program_header::{self, ProgramHeader};
+use goblin::elf::section_header::SectionHeader;
+use goblin::elf::Elf;
+use serde::{Deserialize, Serialize};
+
+use crate::parse::{self, LdSoConfLine};
+use crate::report::{FileDamage, HardeningIssue};
+use crate::soname_index::SonameIndex;
+
+/// Dynamic linker configuration file
+pub const LD_SO_CONF_FILEPATH: &str = "/etc/ld.so.conf";
+
+/// Directories always searched last by the dynamic linker
+const DEFAULT_LIB_DIRS: [&str; 2] = ["/lib", "/usr/lib"];
+
+/// Dynamic linking information of an ELF file
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ElfInfo {
+    /// ELF class (32 or 64 bits) and machine, libraries must match them to be loadable
+    pub class: u8,
+    pub machine: u16,
+
+    /// DT_NEEDED entries
+    pub needed: Vec<String>,
+
+    /// DT_RPATH directories
+    pub rpath: Vec<String>,
+
+    /// DT_RUNPATH directories
+    pub runpath: Vec<String>,
+}
+
+impl ElfInfo {
+    /// Parse dynamic linking information, returns None if file is not an ELF file
+    ///
+    /// Only the ELF header, program headers, dynamic section and its string table are read,
+    /// not the whole file.
+    pub fn parse(path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        let mut file = fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut buf = [0; header::header64::SIZEOF_EHDR];
+        let n = file.read(&mut buf)?;
+        if !buf[..n].starts_with(header::ELFMAG) {
+            return Ok(None);
+        }
+        let h = Elf::parse_header(&buf[..n])?;
+        let is_64 = h.e_ident[header::EI_CLASS] != header::ELFCLASS32;
+        let is_le = h.e_ident[header::EI_DATA] != header::ELFDATA2MSB;
+        let ctx = Ctx::new(
+            if is_64 {
+                Container::Big
+            } else {
+                Container::Little
+            },
+            if is_le { Endian::Little } else { Endian::Big },
+        );
+        let mut info = ElfInfo {
+            class: h.e_ident[header::EI_CLASS],
+            machine: h.e_machine,
+            needed: Vec::new(),
+            rpath: Vec::new(),
+            runpath: Vec::new(),
+        };
+
+        let program_headers_data = read_range(
+            &mut file,
+            len,
+            h.e_phoff,
+            u64::from(h.e_phnum) * u64::from(h.e_phentsize),
+        )?;
+        let program_headers =
+            ProgramHeader::parse(&program_headers_data, 0, usize::from(h.e_phnum), ctx)?;
+        let dynamic = match program_headers
+            .iter()
+            .find(|p| p.p_type == program_header::PT_DYNAMIC)
+        {
+            Some(d) => d,
+            // Static executable
+            None => return Ok(Some(info)),
+        };
+        let dynamic_data = read_range(&mut file, len, dynamic.p_offset, dynamic.p_filesz)?;
+
+        // Entries are a tag and a value, both of the word size
+        let word_size = if is_64 { 8 } else { 4 };
+        let mut string_offsets = Vec::new();
+        let mut strtab_addr = None;
+        let mut strtab_size = 0;
+        for entry in dynamic_data.chunks_exact(2 * word_size) {
+            let (tag, value) = (
+                read_word(&entry[..word_size], is_le),
+                read_word(&entry[word_size..], is_le),
+            );
+            match tag {
+                dynamic::DT_NULL => break,
+                dynamic::DT_STRTAB => strtab_addr = Some(value),
+                dynamic::DT_STRSZ => strtab_size = value,
+                dynamic::DT_NEEDED | dynamic::DT_RPATH | dynamic::DT_RUNPATH => {
+                    string_offsets.push((tag, value))
+                }
+                _ => {}
+            }
+        }
+        if string_offsets.is_empty() {
+            return Ok(Some(info));
+        }
+
+        // The string table is located by its address once loaded
+        let strtab_addr = strtab_addr.ok_or_else(|| malformed("no dynamic string table"))?;
+        let strtab_offset = program_headers
+            .iter()
+            .find(|p| {
+                (p.p_type == program_header::PT_LOAD)
+                    && (p.p_vaddr <= strtab_addr)
+                    && (strtab_addr < p.p_vaddr + p.p_filesz)
+            })
+            .map(|p| strtab_addr - p.p_vaddr + p.p_offset)
+            .ok_or_else(|| malformed("dynamic string table is not loaded"))?;
+        let strtab = read_range(&mut file, len, strtab_offset, strtab_size)?;
+        for (tag, offset) in string_offsets {
+            let string = usize::try_from(offset)
+                .ok()
+                .and_then(|o| strtab.get(o..))
+                .and_then(|s| s.split(|b| *b == 0).next())
+                .ok_or_else(|| malformed("invalid dynamic string offset"))?;
+            let string = std::str::from_utf8(string)
+                .map_err(|_| malformed("invalid dynamic string"))?
+                .to_string();
+            match tag {
+                dynamic::DT_NEEDED => info.needed.push(string),
+                dynamic::DT_RPATH => info.rpath.extend(split_paths(&string)),
+                _ => info.runpath.extend(split_paths(&string)),
+            }
+        }
+        Ok(Some(info))
+    }
+
+    /// Return true if the file at path is an ELF object loadable along this one
+    fn is_compatible(&self, path: &Path) -> bool {
+        class_machine(path) == Some((self.class, self.machine))
+    }
+}
+
+/// Get error of a malformed ELF file, that the dynamic loader may still load
+fn malformed(reason: &str) -> goblin::error::Error {
+    goblin::error::Error::Malformed(reason.to_string())
+}
+
+/// Read a range of an ELF file, that must lie within it
+fn read_range<R: Read + Seek>(
+    file: &mut R,
+    file_len: u64,
+    offset: u64,
+    len: u64,
+) -> Result<Vec<u8>, goblin::error::Error> {
+    if offset.checked_add(len).is_none_or(|end| end > file_len) {
+        return Err(malformed("range past the end of the file"));
+    }
+    let mut data = vec![0; len as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Read a word of the dynamic section
+fn read_word(data: &[u8], is_le: bool) -> u64 {
+    let mut bytes = [0; 8];
+    if is_le {
+        bytes[..data.len()].copy_from_slice(data);
+        u64::from_le_bytes(bytes)
+    } else {
+        bytes[8 - data.len()..].copy_from_slice(data);
+        u64::from_be_bytes(bytes)
+    }
+}
+
+/// Split a DT_RPATH or DT_RUNPATH entry into directories
+fn split_paths(entry: &str) -> Vec<String> {
+    entry
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Get ELF class and machine of a file, None if it is not an ELF file
+pub fn class_machine(path: &Path) -> Option<(u8, u16)> {
+    let mut buf = [0; header::header64::SIZEOF_EHDR];
+    let n = fs::File::open(path)
+        .and_then(|mut f| f.read(&mut buf))
+        .ok()?;
+    if n < header::SIZEOF_IDENT {
+        return None;
+    }
+    let h = Elf::parse_header(&buf[..n]).ok()?;
+    Some((h.e_ident[header::EI_CLASS], h.e_machine))
+}
+
+/// Return true if ELF files of a machine can be loaded on the running system, like ldd, files of
+/// other machines are not analyzed
+pub fn is_native_machine(machine: u16) -> bool {
+    match env::consts::ARCH {
+        "x86_64" => [header::EM_X86_64, header::EM_386].contains(&machine),
+        "x86" => machine == header::EM_386,
+        "aarch64" => machine == header::EM_AARCH64,
+        "arm" => machine == header::EM_ARM,
+        "riscv64" => machine == header::EM_RISCV,
+        _ => true,
+    }
+}
+
+/// Detect empty files and truncated ELF files, only reading the ELF header
+///
+/// Truncation is detected when the program or section headers, that are usually at the end of
+/// the file, lie past its end.
+pub fn damage(path: &Path) -> io::Result<Option<FileDamage>> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(Some(FileDamage::Empty));
+    }
+
+    let mut buf = [0; header::header64::SIZEOF_EHDR];
+    let n = file.read(&mut buf)?;
+    if !buf[..n].starts_with(header::ELFMAG) {
+        return Ok(None);
+    }
+    let header_size = match buf.get(header::EI_CLASS) {
+        Some(&header::ELFCLASS32) => header::header32::SIZEOF_EHDR,
+        _ => header::header64::SIZEOF_EHDR,
+    };
+    if n < header_size {
+        return Ok(Some(FileDamage::TruncatedElf));
+    }
+    let h = match Elf::parse_header(&buf[..n]) {
+        Ok(h) => h,
+        // Not for us to judge, the dependency analysis will fail on it
+        Err(_) => return Ok(None),
+    };
+    let headers_end = cmp::max(
+        h.e_phoff + u64::from(h.e_phnum) * u64::from(h.e_phentsize),
+        h.e_shoff + u64::from(h.e_shnum) * u64::from(h.e_shentsize),
+    );
+    Ok((headers_end > len).then_some(FileDamage::TruncatedElf))
+}
+
+/// Length read at the start of files to get their hardening features, where the program headers
+/// usually are
+pub const HARDENING_HEAD_LEN: usize = 4096;
+
+/// Get hardening features missing from an ELF executable or shared library loaded by the dynamic
+/// linker, from the start of the file which must contain its program headers. None if it is not
+/// such a file, static executables and objects not run by the system processor are skipped.
+pub fn hardening_issues(head: &[u8]) -> Option<Vec<HardeningIssue>> {
+    if !head.starts_with(header::ELFMAG) {
+        return None;
+    }
+    let h = Elf::parse_header(head).ok()?;
+    if (h.e_type != header::ET_EXEC) && (h.e_type != header::ET_DYN) {
+        return None;
+    }
+    // Little and big containers are 32 and 64-bit
+    let container = match h.e_ident[header::EI_CLASS] {
+        header::ELFCLASS32 => Container::Little,
+        _ => Container::Big,
+    };
+    let endian = match h.e_ident[header::EI_DATA] {
+        header::ELFDATA2MSB => Endian::Big,
+        _ => Endian::Little,
+    };
+    let program_headers = ProgramHeader::parse(
+        head,
+        h.e_phoff as usize,
+        usize::from(h.e_phnum),
+        Ctx::new(container, endian),
+    )
+    .ok()?;
+    let segment = |p_type| program_headers.iter().find(|p| p.p_type == p_type);
+    segment(program_header::PT_DYNAMIC)?;
+
+    let mut issues = Vec::new();
+    // Without a PT_GNU_STACK segment, the kernel maps the stack executable
+    if segment(program_header::PT_GNU_STACK).is_none_or(|p| p.p_flags & program_header::PF_X != 0) {
+        issues.push(HardeningIssue::ExecutableStack);
+    }
+    if segment(program_header::PT_GNU_RELRO).is_none() {
+        issues.push(HardeningIssue::NoRelro);
+    }
+    // Shared libraries are always position independent
+    if h.e_type == header::ET_EXEC {
+        issues.push(HardeningIssue::NoPie);
+    }
+    Some(issues)
+}
+
+/// Debugging information of an ELF file
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DebugInfo {
+    /// GNU build-id in hexadecimal, that debug files are looked up with
+    pub build_id: Option<String>,
+
+    /// True if the file has DWARF sections, when it was not stripped
+    pub has_dwarf: bool,
+
+    /// Filename of the separate debug file, from the .gnu_debuglink section
+    pub debug_link: Option<String>,
+}
+
+/// Maximum size of the notes, section names and debug link read to get debugging information,
+/// larger ones are ignored
+const DEBUG_SECTION_MAX_LEN: u64 = 64 * 1024;
+
+/// Get debugging information of an ELF executable or shared library loaded by the dynamic
+/// linker. None if it is not such a file.
+///
+/// Only the ELF header, program and section headers, and the notes, section names and debug link
+/// sections are read, not the whole file.
+pub fn debug_info<R: Read + Seek>(file: &mut R) -> io::Result<Option<DebugInfo>> {
+    match parse_debug_info(file) {
+        Ok(i) => Ok(i),
+        Err(goblin::error::Error::IO(err)) => Err(err),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parse_debug_info<R: Read + Seek>(
+    file: &mut R,
+) -> Result<Option<DebugInfo>, goblin::error::Error> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let head = read_range(file, len, 0, len.min(header::header64::SIZEOF_EHDR as u64))?;
+    if !head.starts_with(header::ELFMAG) {
+        return Ok(None);
+    }
+    let h = Elf::parse_header(&head)?;
+    if (h.e_type != header::ET_EXEC) && (h.e_type != header::ET_DYN) {
+        return Ok(None);
+    }
+    let ctx = Ctx::new(
+        if h.e_ident[header::EI_CLASS] == header::ELFCLASS32 {
+            Container::Little
+        } else {
+            Container::Big
+        },
+        if h.e_ident[header::EI_DATA] == header::ELFDATA2MSB {
+            Endian::Big
+        } else {
+            Endian::Little
+        },
+    );
+    let program_headers_data = read_range(
+        file,
+        len,
+        h.e_phoff,
+        u64::from(h.e_phnum) * u64::from(h.e_phentsize),
+    )?;
+    let program_headers =
+        ProgramHeader::parse(&program_headers_data, 0, usize::from(h.e_phnum), ctx)?;
+    if !program_headers
+        .iter()
+        .any(|p| p.p_type == program_header::PT_DYNAMIC)
+    {
+        return Ok(None);
+    }
+
+    let mut info = DebugInfo {
+        build_id: None,
+        has_dwarf: false,
+        debug_link: None,
+    };
+    // Offset, size and alignment of notes, in segments, and in sections unless they were removed
+    let mut notes: Vec<(u64, u64, u64)> = program_headers
+        .iter()
+        .filter(|p| p.p_type == program_header::PT_NOTE)
+        .map(|p| (p.p_offset, p.p_filesz, p.p_align))
+        .collect();
+    if h.e_shstrndx < h.e_shnum {
+        let section_headers_data = read_range(
+            file,
+            len,
+            h.e_shoff,
+            u64::from(h.e_shnum) * u64::from(h.e_shentsize),
+        )?;
+        let section_headers =
+            SectionHeader::parse_from(&section_headers_data, 0, usize::from(h.e_shnum), ctx)?;
+        let names = &section_headers[usize::from(h.e_shstrndx)];
+        if names.sh_size <= DEBUG_SECTION_MAX_LEN {
+            let names = read_range(file, len, names.sh_offset, names.sh_size)?;
+            for section in &section_headers {
+                let name = names
+                    .get(section.sh_name..)
+                    .and_then(|n| n.split(|b| *b == 0).next());
+                match name {
+                    Some(b".debug_info") => info.has_dwarf = true,
+                    Some(b".note.gnu.build-id") => {
+                        notes.push((section.sh_offset, section.sh_size, section.sh_addralign))
+                    }
+                    Some(b".gnu_debuglink") if section.sh_size <= DEBUG_SECTION_MAX_LEN => {
+                        let link = read_range(file, len, section.sh_offset, section.sh_size)?;
+                        // Filename, then padding and a checksum of the debug file
+                        info.debug_link = link
+                            .split(|b| *b == 0)
+                            .next()
+                            .and_then(|n| std::str::from_utf8(n).ok())
+                            .filter(|n| !n.is_empty())
+                            .map(str::to_string);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    for (offset, size, alignment) in notes {
+        if size > DEBUG_SECTION_MAX_LEN {
+            continue;
+        }
+        let data = read_range(file, len, offset, size)?;
+        let notes = note::NoteDataIterator {
+            data: &data,
+            size: data.len(),
+            offset: 0,
+            ctx: (alignment as usize, ctx),
+        };
+        if let Some(build_id) = notes
+            .flatten()
+            .find(|n| (n.n_type == note::NT_GNU_BUILD_ID) && (n.name == "GNU"))
+        {
+            info.build_id = Some(build_id.desc.iter().map(|b| format!("{:02x}", b)).collect());
+            break;
+        }
+    }
+    Ok(Some(info))
+}
+
+/// Where a library search directory comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchSource {
+    Rpath,
+    LdLibraryPath,
+    Runpath,
+    LdSoConf,
+    Default,
+}
+
+impl fmt::Display for SearchSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            SearchSource::Rpath => "RPATH",
+            SearchSource::LdLibraryPath => "LD_LIBRARY_PATH",
+            SearchSource::Runpath => "RUNPATH",
+            SearchSource::LdSoConf => "ld.so.conf",
+            SearchSource::Default => "system default",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Resolution of a DT_NEEDED entry
+#[derive(Debug)]
+pub struct Resolution {
+    pub soname: String,
+
+    /// Directories searched, in order
+    pub searched: Vec<(SearchSource, PathBuf)>,
+
+    /// Resolved library path and where its directory came from, None if not found
+    pub found: Option<(PathBuf, SearchSource)>,
+}
+
+/// Library missing from the dependency tree of an ELF file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MissingLibrary {
+    pub soname: String,
+
+    /// Sonames of the libraries through which it is needed, empty if the file itself needs it
+    pub chain: Vec<String>,
+
+    /// Path of the library of the tree that needs it, None if the file itself needs it
+    pub needed_by: Option<PathBuf>,
+}
+
+/// Resolves sonames to library paths like the dynamic linker does
+pub struct Resolver {
+    /// Root directory of the analyzed system, library paths are relative to it
+    root: PathBuf,
+
+    ld_library_path: Vec<PathBuf>,
+    ld_so_conf_dirs: Vec<PathBuf>,
+
+    /// Imported index of system library directories, to avoid looking up files in them
+    soname_index: Option<Arc<SonameIndex>>,
+
+    /// Libraries parsed when resolving dependency trees, with the identity of their file then,
+    /// so that libraries shared by many files are only parsed once
+    libraries: Mutex<HashMap<PathBuf, ParsedLibrary>>,
+}
+
+/// Device, inode, size and modification time of a file, which change when a package replaces it
+type FileIdentity = (u64, u64, u64, i64, i64);
+
+/// Library parsed by a resolver, None if it is not an ELF file or can not be parsed
+type ParsedLibrary = (FileIdentity, Option<Arc<ElfInfo>>);
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        let ld_library_path = env::var_os("LD_LIBRARY_PATH")
+            .map(|v| {
+                env::split_paths(&v)
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Resolver {
+            ld_library_path,
+            ..Resolver::with_root(Path::new("/"))
+        }
+    }
+
+    /// Create resolver for a system mounted at a root directory, ignoring our own environment
+    pub fn with_root(root: &Path) -> Resolver {
+        let mut ld_so_conf_dirs = Vec::new();
+        for (_file, dir) in ld_so_conf_entries(root) {
+            if !ld_so_conf_dirs.contains(&dir) {
+                ld_so_conf_dirs.push(dir);
+            }
+        }
+        Resolver {
+            root: root.to_path_buf(),
+            ld_library_path: Vec::new(),
+            ld_so_conf_dirs,
+            soname_index: None,
+            libraries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use an index of system library directories, for those that did not change since it was
+    /// built
+    pub fn with_soname_index(self, soname_index: Arc<SonameIndex>) -> Resolver {
+        Resolver {
+            soname_index: Some(soname_index),
+            ..self
+        }
+    }
+
+    /// Get system library directories, from the dynamic linker configuration and defaults
+    pub fn system_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.ld_so_conf_dirs.clone();
+        for dir in DEFAULT_LIB_DIRS.iter().map(PathBuf::from) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    /// Return true if a directory has a library, compatible with an ELF file if any
+    fn has_library(&self, dir: &Path, soname: &str, elf: Option<&ElfInfo>) -> bool {
+        let class_machine = elf.map(|e| (e.class, e.machine));
+        if let Some(found) = self
+            .soname_index
+            .as_ref()
+            .and_then(|i| i.lookup(dir, soname, class_machine))
+        {
+            return found;
+        }
+        let path = rooted(&self.root, &dir.join(soname));
+        match elf {
+            Some(elf) => elf.is_compatible(&path),
+            None => path.is_file(),
+        }
+    }
+
+    /// Find a library loaded by name at runtime (with dlopen) in system library directories
+    pub fn find_system_library(&self, soname: &str) -> Option<PathBuf> {
+        self.ld_library_path
+            .iter()
+            .chain(self.ld_so_conf_dirs.iter())
+            .map(PathBuf::as_path)
+            .chain(DEFAULT_LIB_DIRS.iter().map(Path::new))
+            .find(|d| self.has_library(d, soname, None))
+            .map(|d| d.join(soname))
+    }
+
+    /// Build ordered list of directories to search for libraries needed by an ELF file
+    fn search_dirs(&self, elf: &ElfInfo, elf_path: &Path) -> Vec<(SearchSource, PathBuf)> {
+        let origin = fs::canonicalize(rooted(&self.root, elf_path))
+            .ok()
+            .and_then(|p| {
+                p.strip_prefix(&self.root)
+                    .ok()
+                    .map(|p| Path::new("/").join(p))
+            })
+            .as_deref()
+            .unwrap_or(elf_path)
+            .parent()
+            .map_or_else(|| PathBuf::from("/"), Path::to_path_buf);
+        let lib = if elf.class == header::ELFCLASS32 {
+            "lib32"
+        } else {
+            "lib"
+        };
+        let expand = |d: &str| -> PathBuf {
+            let origin = origin.to_string_lossy();
+            PathBuf::from(
+                d.replace("${ORIGIN}", &origin)
+                    .replace("$ORIGIN", &origin)
+                    .replace("${LIB}", lib)
+                    .replace("$LIB", lib),
+            )
+        };
+
+        let mut dirs = Vec::new();
+        // DT_RPATH is ignored if DT_RUNPATH is present
+        if elf.runpath.is_empty() {
+            dirs.extend(elf.rpath.iter().map(|d| (SearchSource::Rpath, expand(d))));
+        }
+        dirs.extend(
+            self.ld_library_path
+                .iter()
+                .map(|d| (SearchSource::LdLibraryPath, d.clone())),
+        );
+        dirs.extend(
+            elf.runpath
+                .iter()
+                .map(|d| (SearchSource::Runpath, expand(d))),
+        );
+        dirs.extend(
+            self.ld_so_conf_dirs
+                .iter()
+                .map(|d| (SearchSource::LdSoConf, d.clone())),
+        );
+        dirs.extend(
+            DEFAULT_LIB_DIRS
+                .iter()
+                .map(|d| (SearchSource::Default, PathBuf::from(d))),
+        );
+        dirs
+    }
+
+    /// Resolve all DT_NEEDED entries of an ELF file
+    pub fn resolve(&self, elf: &ElfInfo, elf_path: &Path) -> Vec<Resolution> {
+        let search_dirs = self.search_dirs(elf, elf_path);
+        elf.needed
+            .iter()
+            .map(|soname| {
+                if soname.contains('/') {
+                    // Path, not searched
+                    let path = PathBuf::from(soname);
+                    let found = if elf.is_compatible(&rooted(&self.root, &path)) {
+                        Some((path, SearchSource::Default))
+                    } else {
+                        None
+                    };
+                    return Resolution {
+                        soname: soname.to_string(),
+                        searched: Vec::new(),
+                        found,
+                    };
+                }
+                let mut searched = Vec::new();
+                let mut found = None;
+                for (source, dir) in &search_dirs {
+                    searched.push((*source, dir.clone()));
+                    if self.has_library(dir, soname, Some(elf)) {
+                        found = Some((dir.join(soname), *source));
+                        break;
+                    }
+                }
+                Resolution {
+                    soname: soname.to_string(),
+                    searched,
+                    found,
+                }
+            })
+            .collect()
+    }
+
+    /// Parse a library of a dependency tree, or get it from the libraries already parsed
+    fn parse_library(&self, path: &Path) -> Option<Arc<ElfInfo>> {
+        let path = rooted(&self.root, path);
+        let metadata = fs::metadata(&path).ok()?;
+        let identity = (
+            metadata.dev(),
+            metadata.ino(),
+            metadata.len(),
+            metadata.mtime(),
+            metadata.mtime_nsec(),
+        );
+        if let Some((cached_identity, info)) = self.libraries.lock().unwrap().get(&path) {
+            if *cached_identity == identity {
+                return info.clone();
+            }
+        }
+        let info = ElfInfo::parse(&path).ok().flatten().map(Arc::new);
+        self.libraries
+            .lock()
+            .unwrap()
+            .insert(path, (identity, info.clone()));
+        info
+    }
+
+    /// Resolve the whole dependency tree of an ELF file, like lddtree, and get its missing
+    /// libraries, each with the shortest chain of libraries through which it is needed
+    pub fn resolve_tree(&self, elf: ElfInfo, elf_path: &Path) -> Vec<MissingLibrary> {
+        let mut missing: Vec<MissingLibrary> = Vec::new();
+        let mut visited: HashSet<PathBuf> = HashSet::new();
+        let mut queue: VecDeque<(Option<Arc<ElfInfo>>, PathBuf, Vec<String>)> = VecDeque::new();
+        queue.push_back((Some(Arc::new(elf)), elf_path.to_path_buf(), Vec::new()));
+        while let Some((info, path, chain)) = queue.pop_front() {
+            let info = match info.or_else(|| self.parse_library(&path)) {
+                Some(i) => i,
+                None => continue,
+            };
+            for resolution in self.resolve(&info, &path) {
+                match resolution.found {
+                    Some((library_path, _source)) => {
+                        if visited.insert(library_path.clone()) {
+                            let mut library_chain = chain.clone();
+                            library_chain.push(resolution.soname);
+                            queue.push_back((None, library_path, library_chain));
+                        }
+                    }
+                    None => {
+                        // Breadth first, so the first chain found is the shortest
+                        if !missing.iter().any(|m| m.soname == resolution.soname) {
+                            missing.push(MissingLibrary {
+                                soname: resolution.soname,
+                                chain: chain.clone(),
+                                needed_by: Some(path.clone()).filter(|_| !chain.is_empty()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// Get path of a file of a system mounted at a root directory
+pub fn rooted(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Get directories of the dynamic linker configuration of a system mounted at a root directory,
+/// in search order and with the configuration file declaring them, duplicates included
+pub fn ld_so_conf_entries(root: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut entries = Vec::new();
+    parse_ld_so_conf(root, Path::new(LD_SO_CONF_FILEPATH), &mut entries, 0);
+    entries
+}
+
+/// Parse dynamic linker configuration file of a system mounted at a root directory, following
+/// includes
+fn parse_ld_so_conf(
+    root: &Path,
+    filepath: &Path,
+    entries: &mut Vec<(PathBuf, PathBuf)>,
+    depth: usize,
+) {
+    if depth > 8 {
+        // Include loop
+        return;
+    }
+    let content = match fs::read_to_string(rooted(root, filepath)) {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    for line in parse::ld_so_conf(&content) {
+        match line {
+            LdSoConfLine::Include(pattern) => {
+                let pattern = if pattern.starts_with('/') {
+                    pattern.to_string()
+                } else {
+                    // Relative includes are relative to the including file directory
+                    format!(
+                        "{}/{}",
+                        filepath
+                            .parent()
+                            .unwrap_or_else(|| Path::new("/"))
+                            .display(),
+                        pattern
+                    )
+                };
+                let pattern = rooted(root, Path::new(&pattern));
+                if let Ok(paths) = glob::glob(&pattern.to_string_lossy()) {
+                    for include_filepath in paths.flatten() {
+                        if let Ok(include_filepath) = include_filepath.strip_prefix(root) {
+                            let include_filepath = Path::new("/").join(include_filepath);
+                            parse_ld_so_conf(root, &include_filepath, entries, depth + 1);
+                        }
+                    }
+                }
+            }
+            LdSoConfLine::Dir(dir) => {
+                entries.push((filepath.to_path_buf(), PathBuf::from(dir)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_ld_so_conf() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let conf_dir = tmp_dir.path().join("etc/ld.so.conf.d");
+        fs::create_dir_all(&conf_dir).unwrap();
+        fs::write(
+            tmp_dir.path().join("etc/ld.so.conf"),
+            "# comment\n/opt/a/lib\ninclude ld.so.conf.d/*.conf\n",
+        )
+        .unwrap();
+        fs::write(
+            conf_dir.join("b.conf"),
+            "/opt/b/lib # comment\n\n/opt/a/lib\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            ld_so_conf_entries(tmp_dir.path())[2],
+            (
+                PathBuf::from("/etc/ld.so.conf.d/b.conf"),
+                PathBuf::from("/opt/a/lib")
+            )
+        );
+        assert_eq!(
+            Resolver::with_root(tmp_dir.path()).ld_so_conf_dirs,
+            [PathBuf::from("/opt/a/lib"), PathBuf::from("/opt/b/lib")]
+        );
+    }
+
+    #[test]
+    fn test_is_native_machine() {
+        if cfg!(target_arch = "x86_64") {
+            assert!(is_native_machine(header::EM_X86_64));
+            assert!(is_native_machine(header::EM_386));
+            assert!(!is_native_machine(header::EM_AARCH64));
+        }
+    }
+
+    #[test]
+    fn test_damage() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let exe_data = fs::read(env::current_exe().unwrap()).unwrap();
+        for (filename, data, expected) in &[
+            ("empty", &b""[..], Some(FileDamage::Empty)),
+            ("script", &b"#!/bin/sh\n"[..], None),
+            ("header", &exe_data[..20], Some(FileDamage::TruncatedElf)),
+            (
+                "half",
+                &exe_data[..exe_data.len() / 2],
+                Some(FileDamage::TruncatedElf),
+            ),
+            ("exe", &exe_data[..], None),
+        ] {
+            let filepath = tmp_dir.path().join(filename);
+            fs::write(&filepath, data).unwrap();
+            assert_eq!(damage(&filepath).unwrap(), *expected, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let elf_path = env::current_exe().unwrap();
+        let elf = ElfInfo::parse(&elf_path).unwrap().unwrap();
+        assert!(!elf.needed.is_empty());
+
+        let resolutions = Resolver::new().resolve(&elf, &elf_path);
+        assert_eq!(resolutions.len(), elf.needed.len());
+        assert!(resolutions.iter().all(|r| r.found.is_some()));
+
+        let elf = ElfInfo {
+            needed: vec!["libdoesnotexist.so.1".to_string()],
+            rpath: vec!["$ORIGIN/../lib".to_string()],
+            ..elf
+        };
+        let resolutions = Resolver::new().resolve(&elf, &elf_path);
+        assert!(resolutions[0].found.is_none());
+        assert_eq!(resolutions[0].searched[0].0, SearchSource::Rpath);
+        assert!(ElfInfo::parse(Path::new("/etc/ld.so.conf"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        let elf_path = env::current_exe().unwrap();
+        let data = fs::read(&elf_path).unwrap();
+        let elf = Elf::parse(&data).unwrap();
+        let info = ElfInfo::parse(&elf_path).unwrap().unwrap();
+        assert_eq!(info.needed, elf.libraries);
+        assert_eq!(info.machine, elf.header.e_machine);
+
+        // Files truncated before their dynamic section are left to the dynamic loader
+        let tmp_dir = TempDir::new("").unwrap();
+        let filepath = tmp_dir.path().join("truncated");
+        fs::write(&filepath, &data[..header::header64::SIZEOF_EHDR + 1]).unwrap();
+        assert!(ElfInfo::parse(&filepath)
+            .unwrap_err()
+            .is::<goblin::error::Error>());
+
+        // Libraries of dependency trees are parsed once
+        let resolver = Resolver::new();
+        assert!(resolver.resolve_tree(info, &elf_path).is_empty());
+        let library_count = resolver.libraries.lock().unwrap().len();
+        assert!(library_count > 0);
+        let info = ElfInfo::parse(&elf_path).unwrap().unwrap();
+        resolver.resolve_tree(info, &elf_path);
+        assert_eq!(resolver.libraries.lock().unwrap().len(), library_count);
+    }
+
+    #[test]
+    fn test_debug_info() {
+        // Same as parsing the whole file
+        let elf_path = env::current_exe().unwrap();
+        let data = fs::read(&elf_path).unwrap();
+        let elf = Elf::parse(&data).unwrap();
+        let build_id: Option<String> = elf
+            .iter_note_headers(&data)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .find(|n| n.n_type == note::NT_GNU_BUILD_ID)
+            .map(|n| n.desc.iter().map(|b| format!("{:02x}", b)).collect());
+        let has_dwarf = elf
+            .section_headers
+            .iter()
+            .any(|h| elf.shdr_strtab.get_at(h.sh_name) == Some(".debug_info"));
+        let info = debug_info(&mut fs::File::open(&elf_path).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.build_id, build_id);
+        assert_eq!(info.has_dwarf, has_dwarf);
+
+        assert!(debug_info(&mut io::Cursor::new(b"#!/bin/sh\n"))
+            .unwrap()
+            .is_none());
+        // Truncated
+        assert!(debug_info(&mut io::Cursor::new(
+            &data[..header::header64::SIZEOF_EHDR + 1]
+        ))
+        .unwrap()
+        .is_none());
+    }
+}
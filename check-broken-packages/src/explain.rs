@@ -0,0 +1,126 @@
+//! Detailed explanations of findings
+
+use std::error;
+use std::path::Path;
+
+use ansi_term::Colour::*;
+use ansi_term::Style;
+use simple_error::SimpleError;
+
+use crate::elf::Resolver;
+use crate::pacman;
+use crate::python;
+use crate::render;
+use crate::system::SystemProvider;
+
+/// Print resolution of every dynamic dependency of an ELF file, with where it was found, or the
+/// directories that were searched if it was not. The file is parsed by a system provider.
+pub fn explain_file(path: &Path, system: &dyn SystemProvider) -> Result<(), Box<dyn error::Error>> {
+    let elf = system
+        .elf_info(path)?
+        .ok_or_else(|| SimpleError::new(format!("'{}' is not an ELF file", path.display())))?;
+
+    println!("{}", Style::new().bold().paint(path.display().to_string()));
+    if !elf.rpath.is_empty() {
+        println!("  RPATH: {}", elf.rpath.join(":"));
+    }
+    if !elf.runpath.is_empty() {
+        println!("  RUNPATH: {}", elf.runpath.join(":"));
+    }
+    if elf.needed.is_empty() {
+        println!("  No dynamic dependencies");
+        return Ok(());
+    }
+
+    for resolution in Resolver::new().resolve(&elf, path) {
+        match resolution.found {
+            Some((lib_path, source)) => println!(
+                "  {} => {} (from {})",
+                resolution.soname,
+                Green.paint(lib_path.display().to_string()),
+                source
+            ),
+            None => {
+                println!("  {} => {}", resolution.soname, Red.paint("not found"));
+                if resolution.searched.is_empty() {
+                    println!("    Dependency is a path, and is not a compatible ELF library");
+                }
+                for (source, dir) in resolution.searched {
+                    println!("    searched {} ({})", dir.display(), source);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print files of a package that are in Python directories ignored by the current interpreter,
+/// and how to fix it
+pub fn explain_package(package: &str) -> Result<(), Box<dyn error::Error>> {
+    let python_version = python::get_python_version()?;
+    let current_python_dir = python_version.dir();
+    let package_files = pacman::get_package_files(package)?;
+
+    println!(
+        "{}",
+        Style::new().bold().paint(format!("Package '{}'", package))
+    );
+    println!(
+        "  Current Python interpreter directory: {}",
+        current_python_dir
+    );
+
+    let mut stale_file_count = 0;
+    for python_dir in python::get_python_dirs(&python_version)? {
+        if python_dir == current_python_dir {
+            continue;
+        }
+        let prefix = format!("{}/", python_dir);
+        let stale_files: Vec<&String> = package_files
+            .iter()
+            .filter(|f| f.starts_with(&prefix))
+            .collect();
+        if stale_files.is_empty() {
+            continue;
+        }
+        println!(
+            "  {} in stale directory {}:",
+            render::plural(stale_files.len(), "file", "files"),
+            Yellow.paint(&python_dir)
+        );
+        for stale_file in &stale_files {
+            println!("    {}", stale_file);
+        }
+        stale_file_count += stale_files.len();
+    }
+
+    if stale_file_count == 0 {
+        println!("  No files in stale Python directories");
+        return Ok(());
+    }
+
+    let is_foreign = pacman::get_aur_packages()?.iter().any(|p| p == package);
+    let has_current_files = package_files
+        .iter()
+        .any(|f| f.starts_with(&format!("{}/", current_python_dir)));
+    let suggestion = if is_foreign {
+        format!(
+            "Rebuild the package for Python {}.{} (for example with your AUR helper), or remove it with 'pacman -Rs {}' if it is no longer needed",
+            python_version.major, python_version.minor, package
+        )
+    } else if has_current_files {
+        "The package also has files for the current interpreter, it is probably shipping leftovers, update it with 'pacman -Syu', or report the issue to its packager".to_string()
+    } else {
+        format!(
+            "The package comes from a repository but has not been rebuilt for Python {}.{}, it was probably dropped from the repositories, remove it with 'pacman -Rs {}'",
+            python_version.major, python_version.minor, package
+        )
+    };
+    println!(
+        "  {}",
+        Green.paint(format!("Suggested fix: {}", suggestion))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Expressions selecting findings from their fields, like
+//! `check==lib && package!=zoom && missing~"libcuda"`
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::report::{Finding, Severity};
+
+/// Comparison of a field value
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Contains,
+    NotContains,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Compare(String, Operator, String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// Filter keeping the findings its expression matches
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Filter {
+    expr: Expr,
+    source: String,
+}
+
+/// Get value of a field of a finding, None if it does not have the field. Besides the fields of
+/// the JSON report, "code", "check" (the code family in lowercase, like "lib" for LIB001),
+/// "severity" (possibly overridden by the configuration) and "subject" are available, and
+/// "missing" is an alias of "dependency".
+fn field_value(finding: &Finding, severity: Severity, field: &str) -> Option<String> {
+    fn value_string(value: &serde_json::Value) -> Option<String> {
+        match value {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Array(a) => Some(
+                a.iter()
+                    .filter_map(value_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            serde_json::Value::Null => None,
+            v => Some(v.to_string()),
+        }
+    }
+    match field {
+        "code" => Some(finding.code().to_string()),
+        "check" => Some(finding.check()),
+        "severity" => Some(severity.to_string()),
+        "subject" => Some(finding.subject().to_string()),
+        _ => {
+            let field = if field == "missing" {
+                "dependency"
+            } else {
+                field
+            };
+            match serde_json::to_value(finding).ok()? {
+                serde_json::Value::Object(fields) => fields.get(field).and_then(value_string),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, finding: &Finding, severity: Severity) -> bool {
+        match self {
+            Expr::Compare(field, operator, value) => {
+                let field_value = field_value(finding, severity, field);
+                match operator {
+                    Operator::Equal => field_value.as_ref() == Some(value),
+                    Operator::NotEqual => field_value.as_ref() != Some(value),
+                    Operator::Contains => field_value.is_some_and(|v| v.contains(value.as_str())),
+                    Operator::NotContains => {
+                        !field_value.is_some_and(|v| v.contains(value.as_str()))
+                    }
+                }
+            }
+            Expr::Not(e) => !e.matches(finding, severity),
+            Expr::And(a, b) => a.matches(finding, severity) && b.matches(finding, severity),
+            Expr::Or(a, b) => a.matches(finding, severity) || b.matches(finding, severity),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Operator(Operator),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+/// Characters of words not quoted
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-./*+@:".contains(c)
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Operator(Operator::Contains),
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Equal),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::NotEqual),
+            '!' if chars.next_if_eq(&'~').is_some() => Token::Operator(Operator::NotContains),
+            '!' => Token::Not,
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                Token::Word(word)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => return Err(format!("Unexpected character {:?}", c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, "!" binds tighter than "&&", which binds tighter than "||"
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("Missing closing parenthesis".to_string());
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(field)) => {
+                let operator = match self.next() {
+                    Some(Token::Operator(o)) => o,
+                    _ => return Err(format!("Expected ==, !=, ~ or !~ after field {:?}", field)),
+                };
+                match self.next() {
+                    Some(Token::Word(value)) => Ok(Expr::Compare(field, operator, value)),
+                    _ => Err(format!("Expected value to compare field {:?} with", field)),
+                }
+            }
+            _ => Err("Expected comparison, \"!\" or \"(\"".to_string()),
+        }
+    }
+}
+
+impl Filter {
+    /// Return true if the filter keeps a finding, with its effective severity
+    pub fn matches(&self, finding: &Finding, severity: Severity) -> bool {
+        self.expr.matches(finding, severity)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s).map_err(|e| format!("Invalid filter {:?}: {}", s, e))?,
+            pos: 0,
+        };
+        let expr = parser
+            .or()
+            .and_then(|e| match parser.next() {
+                None => Ok(e),
+                Some(t) => Err(format!("Unexpected {:?}", t)),
+            })
+            .map_err(|e| format!("Invalid filter {:?}: {}", s, e))?;
+        Ok(Filter {
+            expr,
+            source: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Filter> for String {
+    fn from(filter: Filter) -> String {
+        filter.source
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let missing = |package: &str, dependency: &str| Finding::MissingDependency {
+            package: package.to_string(),
+            file: format!("/usr/bin/{}", package),
+            dependency: dependency.to_string(),
+            held_back: None,
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+        let cuda = missing("blender", "libcuda.so.1");
+        let zoom = missing("zoom", "libcuda.so.1");
+        let other = missing("foo", "libfoo.so.1");
+
+        let filter: Filter = "check==lib && package!=zoom && missing~\"libcuda\""
+            .parse()
+            .unwrap();
+        assert!(filter.matches(&cuda, cuda.severity()));
+        assert!(!filter.matches(&zoom, zoom.severity()));
+        assert!(!filter.matches(&other, other.severity()));
+
+        let filter: Filter = "!(code==LIB001) || (severity==warning && held_back!~x)"
+            .parse()
+            .unwrap();
+        assert!(filter.matches(&other, other.severity()));
+        assert!("code==".parse::<Filter>().is_err());
+        assert!("(code==LIB001".parse::<Filter>().is_err());
+        assert!("code LIB001".parse::<Filter>().is_err());
+        assert!("code==\"LIB001".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! Versioned and checksummed format of the files written by a run and read back by later runs
+//! (cache, checkpoint, soname index), so that a file written by another version of the tool, or
+//! damaged, is migrated or discarded instead of being misread, and atomic writing of files
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// First line of a file
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format_version: u32,
+
+    /// CRC32 of the content after the header line
+    checksum: u32,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// Content does not match the header checksum
+    Corrupted,
+    /// Ends before its footer, partially written by an interrupted run
+    Truncated,
+    /// Written by a newer version of the tool, or an older one that can not be migrated
+    UnsupportedVersion(u32),
+    Invalid(serde_json::Error),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io(err) => write!(f, "{}", err),
+            FormatError::Corrupted => write!(f, "checksum mismatch, the file is corrupted"),
+            FormatError::Truncated => write!(f, "the file is truncated"),
+            FormatError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            FormatError::Invalid(err) => write!(f, "invalid content: {}", err),
+        }
+    }
+}
+
+impl error::Error for FormatError {}
+
+pub fn checksum(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// File written to a temporary file next to its destination, which replaces the destination once
+/// complete and synced to disk, so that a crash or power loss leaves either the previous file or
+/// the new one, never a partially written one
+pub struct AtomicFile {
+    filepath: PathBuf,
+    tmp_filepath: PathBuf,
+    /// None once committed
+    writer: Option<BufWriter<fs::File>>,
+}
+
+impl AtomicFile {
+    /// Create temporary file, and parent directories of the destination
+    pub fn create(filepath: &Path) -> io::Result<AtomicFile> {
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp_filename = filepath.file_name().unwrap_or_default().to_os_string();
+        tmp_filename.push(".tmp");
+        let tmp_filepath = filepath.with_file_name(tmp_filename);
+        // Truncates a temporary file left by an interrupted run
+        let file = fs::File::create(&tmp_filepath)?;
+        Ok(AtomicFile {
+            filepath: filepath.to_path_buf(),
+            tmp_filepath,
+            writer: Some(BufWriter::new(file)),
+        })
+    }
+
+    /// Get temporary file, to set its metadata before commit
+    pub fn file(&self) -> &fs::File {
+        self.writer.as_ref().unwrap().get_ref()
+    }
+
+    /// Get path of the temporary file, that is left in place if the commit fails
+    pub fn tmp_filepath(&self) -> &Path {
+        &self.tmp_filepath
+    }
+
+    /// Stop writing without replacing the destination, keeping the temporary file, and get its
+    /// path
+    pub fn keep(mut self) -> PathBuf {
+        self.writer.take();
+        self.tmp_filepath.clone()
+    }
+
+    /// Sync temporary file to disk, and rename it to the destination
+    pub fn commit(mut self) -> io::Result<()> {
+        let file = self
+            .writer
+            .take()
+            .unwrap()
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.tmp_filepath, &self.filepath)?;
+        // Persist the rename itself
+        let parent = match self.filepath.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        fs::File::open(parent)?.sync_all()
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            // Never committed, the destination is left untouched
+            let _ = fs::remove_file(&self.tmp_filepath);
+        }
+    }
+}
+
+/// Write value to a file with a header line of its format version and checksum, atomically
+pub fn write<T: Serialize>(
+    filepath: &Path,
+    format_version: u32,
+    value: &T,
+) -> Result<(), Box<dyn error::Error>> {
+    let content = serde_json::to_vec(value)?;
+    let header = Header {
+        format_version,
+        checksum: checksum(&content),
+    };
+    let mut file = AtomicFile::create(filepath)?;
+    serde_json::to_writer(&mut file, &header)?;
+    writeln!(file)?;
+    file.write_all(&content)?;
+    file.commit()?;
+    Ok(())
+}
+
+/// Read value written by `write` in a format version. Values of older versions are passed to a
+/// migration function with their version, which returns them converted to the current format,
+/// or None if they can not be. Files written before formats were versioned have version 0, and
+/// no checksum.
+pub fn read<T: DeserializeOwned, F: Fn(u32, serde_json::Value) -> Option<serde_json::Value>>(
+    filepath: &Path,
+    format_version: u32,
+    migrate: F,
+) -> Result<T, FormatError> {
+    let content = fs::read(filepath).map_err(FormatError::Io)?;
+    let header = content
+        .iter()
+        .position(|b| *b == b'\n')
+        .and_then(|i| Some((serde_json::from_slice::<Header>(&content[..i]).ok()?, i)));
+    let (version, data) = match header {
+        Some((header, i)) => {
+            let data = &content[i + 1..];
+            if checksum(data) != header.checksum {
+                return Err(FormatError::Corrupted);
+            }
+            (header.format_version, data)
+        }
+        None => (0, &content[..]),
+    };
+    if version == format_version {
+        serde_json::from_slice(data).map_err(FormatError::Invalid)
+    } else if version < format_version {
+        let value = serde_json::from_slice(data).map_err(FormatError::Invalid)?;
+        let value = migrate(version, value).ok_or(FormatError::UnsupportedVersion(version))?;
+        serde_json::from_value(value).map_err(FormatError::Invalid)
+    } else {
+        Err(FormatError::UnsupportedVersion(version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_atomic_file() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let filepath = tmp_dir.path().join("dir/report.jsonl");
+
+        let mut file = AtomicFile::create(&filepath).unwrap();
+        file.write_all(b"foo\n").unwrap();
+        assert!(!filepath.exists());
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&filepath).unwrap(), "foo\n");
+
+        // Dropped before commit, previous content is kept
+        let mut file = AtomicFile::create(&filepath).unwrap();
+        file.write_all(b"bar\n").unwrap();
+        drop(file);
+        assert_eq!(fs::read_to_string(&filepath).unwrap(), "foo\n");
+        assert_eq!(fs::read_dir(filepath.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let filepath = tmp_dir.path().join("data.json");
+        let value: HashMap<String, u32> = vec![("foo".to_string(), 1)].into_iter().collect();
+        let no_migration = |_, _| None;
+
+        write(&filepath, 2, &value).unwrap();
+        let read_value: HashMap<String, u32> = read(&filepath, 2, no_migration).unwrap();
+        assert_eq!(read_value, value);
+        assert!(matches!(
+            read::<HashMap<String, u32>, _>(&filepath, 1, no_migration),
+            Err(FormatError::UnsupportedVersion(2))
+        ));
+
+        // Older version, migrated
+        let migrate = |version, value: serde_json::Value| {
+            assert_eq!(version, 2);
+            Some(serde_json::json!({ "bar": value["foo"] }))
+        };
+        let read_value: HashMap<String, u32> = read(&filepath, 3, migrate).unwrap();
+        assert_eq!(read_value["bar"], 1);
+        assert!(matches!(
+            read::<HashMap<String, u32>, _>(&filepath, 3, no_migration),
+            Err(FormatError::UnsupportedVersion(2))
+        ));
+
+        // Damaged content
+        let mut content = fs::read(&filepath).unwrap();
+        *content.last_mut().unwrap() = b' ';
+        fs::write(&filepath, &content).unwrap();
+        assert!(matches!(
+            read::<HashMap<String, u32>, _>(&filepath, 2, no_migration),
+            Err(FormatError::Corrupted)
+        ));
+
+        // Written before formats were versioned
+        fs::write(&filepath, r#"{"foo":1}"#).unwrap();
+        let read_value: HashMap<String, u32> = read(&filepath, 1, |version, value| {
+            (version == 0).then_some(value)
+        })
+        .unwrap();
+        assert_eq!(read_value, value);
+    }
+}
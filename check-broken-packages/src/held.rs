@@ -0,0 +1,170 @@
+//! Packages held back from upgrades by IgnorePkg and IgnoreGroup of pacman.conf, which leave the
+//! system partially upgraded
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::elf;
+use crate::pacman::{self, provided_sonames, LocalPackage, SyncPackage};
+
+/// Pacman configuration file
+const PACMAN_CONF_FILEPATH: &str = "/etc/pacman.conf";
+
+/// Installed packages held back, with an upgrade available in sync databases
+#[derive(Default)]
+pub struct HeldPackages {
+    packages: HashSet<String>,
+    /// Soname provided by the sync version of a held back package, to package name
+    sonames: HashMap<String, String>,
+}
+
+impl HeldPackages {
+    /// Load held packages of a system at a root directory
+    pub fn load(root: &Path) -> Result<HeldPackages, Box<dyn error::Error>> {
+        let conf_filepath = elf::rooted(root, Path::new(PACMAN_CONF_FILEPATH));
+        let content = match fs::read_to_string(&conf_filepath) {
+            Ok(c) => c,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HeldPackages::default()),
+            Err(err) => return Err(format!("{:?}: {}", conf_filepath, err).into()),
+        };
+        let (ignore_pkgs, ignore_groups) = parse_pacman_conf(&content);
+        if ignore_pkgs.is_empty() && ignore_groups.is_empty() {
+            // Avoid reading databases
+            return Ok(HeldPackages::default());
+        }
+        let local_packages =
+            pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR)))?;
+        let sync_packages =
+            pacman::read_sync_db(&elf::rooted(root, Path::new(pacman::SYNC_DB_DIR)))?;
+        Ok(HeldPackages::new(
+            &local_packages,
+            &sync_packages,
+            &ignore_pkgs,
+            &ignore_groups,
+        ))
+    }
+
+    fn new(
+        local_packages: &[LocalPackage],
+        sync_packages: &HashMap<String, SyncPackage>,
+        ignore_pkgs: &[String],
+        ignore_groups: &[String],
+    ) -> HeldPackages {
+        // Like pacman, IgnorePkg values are glob patterns
+        let ignore_pkgs: Vec<glob::Pattern> = ignore_pkgs
+            .iter()
+            .filter_map(|p| glob::Pattern::new(p).ok())
+            .collect();
+        let mut held = HeldPackages::default();
+        for package in local_packages {
+            let is_held = ignore_pkgs.iter().any(|p| p.matches(&package.name))
+                || package.groups.iter().any(|g| ignore_groups.contains(g));
+            if !is_held {
+                continue;
+            }
+            let sync_package = match sync_packages.get(&package.name) {
+                Some(p) if p.version != package.version => p,
+                _ => continue,
+            };
+            for soname in provided_sonames(&sync_package.provides) {
+                held.sonames.insert(soname, package.name.clone());
+            }
+            held.packages.insert(package.name.clone());
+        }
+        held
+    }
+
+    /// Get the held back package that likely causes a package to miss a dependency: the package
+    /// itself, or the one whose upgrade would provide the dependency
+    pub fn cause(&self, package: &str, dependency: &str) -> Option<&str> {
+        self.packages
+            .get(package)
+            .or_else(|| self.sonames.get(dependency))
+            .map(String::as_str)
+    }
+}
+
+/// Get IgnorePkg and IgnoreGroup values of the options section of pacman.conf
+fn parse_pacman_conf(content: &str) -> (Vec<String>, Vec<String>) {
+    let mut ignore_pkgs = Vec::new();
+    let mut ignore_groups = Vec::new();
+    let mut in_options = false;
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('[') {
+            in_options = line == "[options]";
+            continue;
+        }
+        if !in_options {
+            continue;
+        }
+        if let Some((key, values)) = line.split_once('=') {
+            let values = values.split_whitespace().map(str::to_string);
+            match key.trim() {
+                "IgnorePkg" => ignore_pkgs.extend(values),
+                "IgnoreGroup" => ignore_groups.extend(values),
+                _ => {}
+            }
+        }
+    }
+    (ignore_pkgs, ignore_groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_packages() {
+        let (ignore_pkgs, ignore_groups) = parse_pacman_conf(
+            "[options]\nIgnorePkg = foo python-*\nIgnorePkg=bar # comment\nIgnoreGroup = gnome\n\
+             [core]\nIgnorePkg = baz\n",
+        );
+        assert_eq!(ignore_pkgs, ["foo", "python-*", "bar"]);
+        assert_eq!(ignore_groups, ["gnome"]);
+
+        let local_package = |name: &str, groups: &[&str]| LocalPackage {
+            name: name.to_string(),
+            version: "1-1".to_string(),
+            arch: String::new(),
+            provides: vec![format!("lib{}.so=1-64", name)],
+            depends: Vec::new(),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            installed_size: 0,
+            install_date: 0,
+            files: Vec::new(),
+        };
+        let local_packages = [
+            local_package("foo", &[]),
+            local_package("python-qux", &[]),
+            local_package("nautilus", &["gnome"]),
+            local_package("vim", &[]),
+        ];
+        let sync_packages: HashMap<String, SyncPackage> = ["foo", "nautilus", "vim"]
+            .iter()
+            .map(|n| {
+                let p = SyncPackage {
+                    name: n.to_string(),
+                    version: "2-1".to_string(),
+                    provides: vec![format!("lib{}.so=2-64", n)],
+                };
+                (p.name.clone(), p)
+            })
+            .collect();
+        let held = HeldPackages::new(
+            &local_packages,
+            &sync_packages,
+            &ignore_pkgs,
+            &ignore_groups,
+        );
+
+        assert_eq!(held.cause("foo", "libbar.so.1"), Some("foo"));
+        assert_eq!(held.cause("zoom", "libnautilus.so.2"), Some("nautilus"));
+        assert_eq!(held.cause("zoom", "libvim.so.2"), None);
+        // Not held back, since there is no upgrade for it
+        assert_eq!(held.cause("python-qux", "libbar.so.1"), None);
+    }
+}
@@ -0,0 +1,326 @@
+//! Rolling history of run summaries, to follow the health of the system over time
+
+use std::collections::BTreeSet;
+use std::error;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::{self, FormatError};
+use crate::report::Finding;
+
+/// Format version of history files
+const FORMAT_VERSION: u32 = 1;
+
+/// Number of runs kept, older ones are dropped
+const MAX_RUN_COUNT: usize = 200;
+
+/// Number of transaction targets displayed for a run
+const TARGET_DISPLAY_COUNT: usize = 3;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Number of consecutive runs a missing library of a bundled runtime has to be reported by before
+/// ignoring its directory is suggested
+const IGNORE_SUGGESTION_RUN_COUNT: u32 = 5;
+
+/// Names of directories where applications usually ship their own runtime, whose libraries are
+/// resolved by the application itself rather than by the dynamic loader
+const BUNDLED_DIR_NAMES: [&str; 6] = ["bundled", "cef", "jbr", "jre", "resources", "runtime"];
+
+/// Summary of a complete run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    /// End of the run, in seconds since the epoch
+    pub time: u64,
+
+    pub finding_count: usize,
+
+    /// Packages with findings
+    pub package_count: usize,
+
+    /// Findings that the previous run did not have, and previous run findings this one does not
+    /// have
+    pub new_count: usize,
+    pub resolved_count: usize,
+
+    /// Packages of the transaction analyzed by a hook run, empty for other runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+}
+
+/// Library missing for a file of a package
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MissingSoname {
+    pub package: String,
+    pub file: String,
+    pub soname: String,
+}
+
+/// Missing library, with the number of consecutive runs that reported it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct MissingSonameStreak {
+    #[serde(flatten)]
+    missing: MissingSoname,
+    run_count: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct History {
+    /// Oldest first
+    pub runs: Vec<RunSummary>,
+
+    /// Keys of the findings of the last run, to compare the next one with
+    last_findings: BTreeSet<String>,
+
+    /// Missing libraries of the last run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    missing_sonames: Vec<MissingSonameStreak>,
+}
+
+/// Get key identifying a finding between runs
+pub fn finding_key(finding: &Finding) -> String {
+    format!("{}:{}", finding.code(), finding.subject())
+}
+
+impl History {
+    /// Load history from a file, empty if there is none
+    pub fn load(filepath: &Path) -> Result<History, FormatError> {
+        match format::read(filepath, FORMAT_VERSION, |_, _| None) {
+            Err(FormatError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(History::default())
+            }
+            res => res,
+        }
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Box<dyn error::Error>> {
+        format::write(filepath, FORMAT_VERSION, self)
+    }
+
+    /// Add summary of a run, from the keys of its findings, its missing libraries, and the targets
+    /// of the transaction it analyzed. Subjects that are not paths are counted as packages.
+    /// Missing libraries are None for runs whose findings were filtered, they leave streaks
+    /// unchanged.
+    pub fn record(
+        &mut self,
+        time: u64,
+        findings: BTreeSet<String>,
+        missing_sonames: Option<BTreeSet<MissingSoname>>,
+        targets: Vec<String>,
+    ) {
+        let packages: BTreeSet<&str> = findings
+            .iter()
+            .filter_map(|k| k.split_once(':').map(|(_c, s)| s))
+            .filter(|s| !s.starts_with('/'))
+            .collect();
+        self.runs.push(RunSummary {
+            time,
+            finding_count: findings.len(),
+            package_count: packages.len(),
+            new_count: findings.difference(&self.last_findings).count(),
+            resolved_count: self.last_findings.difference(&findings).count(),
+            targets,
+        });
+        if self.runs.len() > MAX_RUN_COUNT {
+            self.runs.drain(..self.runs.len() - MAX_RUN_COUNT);
+        }
+        self.last_findings = findings;
+        let missing_sonames = match missing_sonames {
+            Some(m) => m,
+            None => return,
+        };
+        // Streaks are broken by runs not reporting the library
+        let previous = std::mem::take(&mut self.missing_sonames);
+        self.missing_sonames = missing_sonames
+            .into_iter()
+            .map(|missing| {
+                let run_count = previous
+                    .iter()
+                    .find(|s| s.missing == missing)
+                    .map_or(0, |s| s.run_count);
+                MissingSonameStreak {
+                    missing,
+                    run_count: run_count + 1,
+                }
+            })
+            .collect();
+    }
+
+    /// Get directories to ignore, of the bundled runtimes whose missing libraries were reported
+    /// unchanged by the last runs, and are likely resolved by the application itself
+    pub fn ignore_path_suggestions(&self) -> BTreeSet<PathBuf> {
+        self.missing_sonames
+            .iter()
+            .filter(|s| s.run_count >= IGNORE_SUGGESTION_RUN_COUNT)
+            .filter_map(|s| bundled_runtime_dir(Path::new(&s.missing.file)))
+            .collect()
+    }
+}
+
+/// Get directory of the bundled runtime a file looks to be part of: the first directory with a
+/// name of bundled runtime directories in its path, or its parent directory if it is in /opt
+fn bundled_runtime_dir(file: &Path) -> Option<PathBuf> {
+    let mut dir = PathBuf::new();
+    for component in file.parent()?.components() {
+        dir.push(component);
+        if let Component::Normal(name) = component {
+            if BUNDLED_DIR_NAMES.iter().any(|n| name == *n) {
+                return Some(dir);
+            }
+        }
+    }
+    file.starts_with("/opt")
+        .then_some(dir)
+        .filter(|d| d != Path::new("/opt"))
+}
+
+/// Format transaction targets of a run, only the first ones with the count of the others
+pub fn format_targets(targets: &[String]) -> String {
+    let mut s = targets
+        .iter()
+        .take(TARGET_DISPLAY_COUNT)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if targets.len() > TARGET_DISPLAY_COUNT {
+        s.push_str(&format!(" +{}", targets.len() - TARGET_DISPLAY_COUNT));
+    }
+    s
+}
+
+/// Format time in seconds since the epoch as an UTC date and time, like "2024-03-01 12:30"
+pub fn format_time(time: u64) -> String {
+    // Civil from days algorithm, with eras of 400 years starting on March 1st
+    let days = time / SECONDS_PER_DAY;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    let seconds = time % SECONDS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let keys = |k: &[&str]| k.iter().map(|k| k.to_string()).collect();
+        let mut history = History::default();
+        history.record(
+            1000,
+            keys(&["LIB001:foo", "LIB002:foo", "SD001:/etc/foo"]),
+            Some(BTreeSet::new()),
+            Vec::new(),
+        );
+        let targets: Vec<String> = ["bar", "baz", "foo", "qux"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        history.record(
+            2000,
+            keys(&["LIB001:foo", "PY001:bar"]),
+            Some(BTreeSet::new()),
+            targets.clone(),
+        );
+        assert_eq!(
+            history.runs[1],
+            RunSummary {
+                time: 2000,
+                finding_count: 2,
+                package_count: 2,
+                new_count: 1,
+                resolved_count: 2,
+                targets,
+            }
+        );
+        assert_eq!(format_targets(&history.runs[1].targets), "bar, baz, foo +1");
+
+        let tmp_dir = TempDir::new("").unwrap();
+        let filepath = tmp_dir.path().join("history.json");
+        assert!(History::load(&filepath).unwrap().runs.is_empty());
+        for i in 0..(MAX_RUN_COUNT - 1) {
+            history.record(
+                3000 + i as u64,
+                BTreeSet::new(),
+                Some(BTreeSet::new()),
+                Vec::new(),
+            );
+        }
+        history.save(&filepath).unwrap();
+        let history = History::load(&filepath).unwrap();
+        assert_eq!(history.runs.len(), MAX_RUN_COUNT);
+        assert_eq!(history.runs[0].time, 2000);
+
+        assert_eq!(format_time(0), "1970-01-01 00:00");
+        assert_eq!(format_time(1_709_296_200), "2024-03-01 12:30");
+        assert_eq!(format_time(1_709_164_800), "2024-02-29 00:00");
+    }
+
+    #[test]
+    fn test_ignore_path_suggestions() {
+        let missing = |file: &str, soname: &str| MissingSoname {
+            package: "foo".to_string(),
+            file: file.to_string(),
+            soname: soname.to_string(),
+        };
+        let mut history = History::default();
+        for i in 0..IGNORE_SUGGESTION_RUN_COUNT {
+            let mut missing_sonames: BTreeSet<MissingSoname> = [
+                missing("/opt/foo/resources/app/foo.node", "libfoo.so.1"),
+                missing("/usr/bin/foo", "libbar.so.2"),
+            ]
+            .iter()
+            .cloned()
+            .collect();
+            // Reported by all runs but one
+            if i != 1 {
+                missing_sonames.insert(missing("/opt/bar/bin/bar", "libbar.so.2"));
+            }
+            assert!(history.ignore_path_suggestions().is_empty());
+            history.record(
+                u64::from(i),
+                BTreeSet::new(),
+                Some(missing_sonames),
+                Vec::new(),
+            );
+            // Filtered runs do not break streaks
+            history.record(u64::from(i), BTreeSet::new(), None, Vec::new());
+        }
+        assert_eq!(
+            history.ignore_path_suggestions(),
+            [PathBuf::from("/opt/foo/resources")]
+                .iter()
+                .cloned()
+                .collect()
+        );
+
+        assert_eq!(
+            bundled_runtime_dir(Path::new("/usr/lib/foo/runtime/lib/libfoo.so")),
+            Some(PathBuf::from("/usr/lib/foo/runtime"))
+        );
+        assert_eq!(
+            bundled_runtime_dir(Path::new("/opt/foo/foo")),
+            Some(PathBuf::from("/opt/foo"))
+        );
+        assert_eq!(bundled_runtime_dir(Path::new("/opt/foo")), None);
+        assert_eq!(bundled_runtime_dir(Path::new("/usr/lib/libfoo.so")), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! Lightweight translation of user facing messages, from a catalog of message templates for the
+//! language of the user. Messages without a translation are displayed in English.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// Directory of the catalogs, one TOML file per language, like "fr.toml" or "pt_BR.toml"
+pub const CATALOG_DIR: &str = concat!("/usr/share/", env!("CARGO_PKG_NAME"), "/locale");
+
+/// Message templates of a language, with placeholders like "{file}"
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Catalog {
+    /// Finding message templates by finding code, with the finding fields as placeholders
+    findings: HashMap<String, String>,
+
+    /// Other message templates by identifier
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Load catalog of the first language that has one in a directory
+    pub fn load(dir: &Path, languages: &[String]) -> Option<Catalog> {
+        for language in languages {
+            let filepath = dir.join(format!("{}.toml", language));
+            match fs::read_to_string(&filepath) {
+                Ok(content) => match toml::from_str(&content) {
+                    Ok(catalog) => return Some(catalog),
+                    Err(err) => eprintln!("Ignoring invalid catalog {:?}: {}", filepath, err),
+                },
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => eprintln!("Ignoring catalog {:?}: {}", filepath, err),
+            }
+        }
+        None
+    }
+
+    /// Get message template of a finding code
+    pub fn finding(&self, code: &str) -> Option<&str> {
+        self.findings.get(code).map(String::as_str)
+    }
+}
+
+/// Get languages of a locale, most specific first, like ["pt_BR", "pt"] for "pt_BR.UTF-8"
+fn locale_languages(locale: &str) -> Vec<String> {
+    let language = locale.split(['.', '@']).next().unwrap_or_default();
+    if language.is_empty() || (language == "C") || (language == "POSIX") {
+        return Vec::new();
+    }
+    let mut languages = vec![language.to_string()];
+    if let Some((base, _territory)) = language.split_once('_') {
+        languages.push(base.to_string());
+    }
+    languages
+}
+
+/// Get languages of the user locale, from the same variables as gettext
+fn user_languages() -> Vec<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|l| !l.is_empty())
+        .map(|l| locale_languages(&l))
+        .unwrap_or_default()
+}
+
+/// Get catalog of the user language, loaded on first use
+pub fn catalog() -> Option<&'static Catalog> {
+    static CATALOG: OnceLock<Option<Catalog>> = OnceLock::new();
+    CATALOG
+        .get_or_init(|| Catalog::load(Path::new(CATALOG_DIR), &user_languages()))
+        .as_ref()
+}
+
+/// Replace placeholders of a template with their value, unknown placeholders are kept
+pub fn fill<N: AsRef<str>>(template: &str, args: &[(N, String)]) -> String {
+    let mut s = template.to_string();
+    for (name, value) in args {
+        s = s.replace(&format!("{{{}}}", name.as_ref()), value);
+    }
+    s
+}
+
+/// Translate message with an identifier, from its English template
+pub fn tr(id: &str, english: &str, args: &[(&str, String)]) -> String {
+    let template = catalog()
+        .and_then(|c| c.messages.get(id))
+        .map_or(english, String::as_str);
+    fill(template, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        assert_eq!(locale_languages("pt_BR.UTF-8"), ["pt_BR", "pt"]);
+        assert_eq!(locale_languages("fr"), ["fr"]);
+        assert_eq!(locale_languages("de_DE@euro"), ["de_DE", "de"]);
+        assert!(locale_languages("C.UTF-8").is_empty());
+
+        let tmp_dir = TempDir::new("").unwrap();
+        fs::write(
+            tmp_dir.path().join("fr.toml"),
+            "[findings]\nLIB001 = \"Le fichier {file} du paquet {package} n'a pas {dependency}\"\n",
+        )
+        .unwrap();
+        let catalog = Catalog::load(tmp_dir.path(), &locale_languages("fr_FR.UTF-8")).unwrap();
+        let args = [
+            ("file", "'/usr/bin/foo'".to_string()),
+            ("package", "'foo'".to_string()),
+            ("dependency", "'libfoo.so'".to_string()),
+        ];
+        assert_eq!(
+            fill(catalog.finding("LIB001").unwrap(), &args),
+            "Le fichier '/usr/bin/foo' du paquet 'foo' n'a pas 'libfoo.so'"
+        );
+        assert!(catalog.finding("PY001").is_none());
+        assert!(Catalog::load(tmp_dir.path(), &locale_languages("de_DE")).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! Rules to ignore findings
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::report::Finding;
+
+/// Rule matching findings to ignore, by code and optionally subject (package name or path) glob
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IgnoreRule {
+    code: String,
+    pattern: Option<glob::Pattern>,
+}
+
+impl IgnoreRule {
+    /// Return true if the rule only matches some subjects of its code
+    pub fn has_pattern(&self) -> bool {
+        self.pattern.is_some()
+    }
+
+    pub fn matches(&self, finding: &Finding) -> bool {
+        (finding.code() == self.code)
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|p| p.matches(finding.subject()))
+    }
+}
+
+impl FromStr for IgnoreRule {
+    type Err = String;
+
+    /// Parse rule from "CODE" or "CODE:PATTERN" string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.splitn(2, ':');
+        let code = tokens.next().unwrap().to_ascii_uppercase();
+        if !Finding::CODES.iter().any(|(c, _)| *c == code) {
+            return Err(format!(
+                "Unknown finding code {:?}, valid codes are: {}",
+                code,
+                Finding::CODES
+                    .iter()
+                    .map(|(c, _)| *c)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        let pattern = tokens
+            .next()
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| format!("Invalid pattern: {}", e))?;
+        Ok(IgnoreRule { code, pattern })
+    }
+}
+
+impl TryFrom<String> for IgnoreRule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IgnoreRule> for String {
+    fn from(rule: IgnoreRule) -> String {
+        rule.to_string()
+    }
+}
+
+impl fmt::Display for IgnoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pattern {
+            Some(pattern) => write!(f, "{}:{}", self.code, pattern),
+            None => write!(f, "{}", self.code),
+        }
+    }
+}
+
+/// Path glob pattern of files to exclude from analysis, with their subtree if they are directories
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IgnorePath(glob::Pattern);
+
+impl IgnorePath {
+    pub fn matches(&self, path: &Path) -> bool {
+        let match_opts = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+        path.ancestors()
+            .any(|p| self.0.matches_path_with(p, match_opts))
+    }
+}
+
+impl FromStr for IgnorePath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_end_matches('/');
+        glob::Pattern::new(s)
+            .map(IgnorePath)
+            .map_err(|e| format!("Invalid pattern: {}", e))
+    }
+}
+
+impl TryFrom<String> for IgnorePath {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IgnorePath> for String {
+    fn from(path: IgnorePath) -> String {
+        path.0.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_rule() {
+        let finding = Finding::MissingDependency {
+            package: "zoom-bin".to_string(),
+            file: "/opt/zoom/zoom".to_string(),
+            dependency: "libfoo.so.1".to_string(),
+            held_back: None,
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+
+        assert!("LIB001".parse::<IgnoreRule>().unwrap().matches(&finding));
+        assert!("lib001:zoom*"
+            .parse::<IgnoreRule>()
+            .unwrap()
+            .matches(&finding));
+        assert!(!"LIB001:foo"
+            .parse::<IgnoreRule>()
+            .unwrap()
+            .matches(&finding));
+        assert!(!"PY001".parse::<IgnoreRule>().unwrap().matches(&finding));
+        assert!("FOO001".parse::<IgnoreRule>().is_err());
+        assert!("LIB001:[".parse::<IgnoreRule>().is_err());
+    }
+
+    #[test]
+    fn test_ignore_path() {
+        let ignore_path: IgnorePath = "/opt/*/bundled/".parse().unwrap();
+        assert!(ignore_path.matches(Path::new("/opt/foo/bundled")));
+        assert!(ignore_path.matches(Path::new("/opt/foo/bundled/lib/libbar.so")));
+        assert!(!ignore_path.matches(Path::new("/opt/foo/bar/bundled")));
+        assert!(!ignore_path.matches(Path::new("/opt/foo/bin/foo")));
+
+        let ignore_path: IgnorePath = "/opt/**/*.so".parse().unwrap();
+        assert!(ignore_path.matches(Path::new("/opt/foo/bar/libbar.so")));
+        assert!(!ignore_path.matches(Path::new("/usr/lib/libbar.so")));
+    }
+}
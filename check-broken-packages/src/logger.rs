@@ -0,0 +1,162 @@
+//! Logger writing to standard error, so that log messages never mix with findings on standard
+//! output, as text or as JSON objects (one per line) for log ingestion pipelines
+
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Format of log messages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format {:?}, expected text or json", s)),
+        }
+    }
+}
+
+/// Log levels, a default one and overrides for modules (and their submodules), like
+/// 'warn,check_broken_packages::daemon=debug'
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        LogFilter {
+            default: LevelFilter::Warn,
+            modules: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = LogFilter::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |l: &str| {
+                LevelFilter::from_str(l).map_err(|_e| format!("Invalid log level {:?}", l))
+            };
+            match directive.split_once('=') {
+                Some((module, level)) => filter
+                    .modules
+                    .push((module.trim().to_string(), parse_level(level.trim())?)),
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+        // Most specific modules first
+        filter
+            .modules
+            .sort_by_key(|(m, _l)| std::cmp::Reverse(m.len()));
+        Ok(filter)
+    }
+}
+
+impl LogFilter {
+    /// Get level of a module
+    fn level(&self, module: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(m, _l)| {
+                module
+                    .strip_prefix(m.as_str())
+                    .is_some_and(|r| r.is_empty() || r.starts_with("::"))
+            })
+            .map_or(self.default, |(_m, l)| *l)
+    }
+
+    /// Get most verbose level of all modules
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_m, l)| *l)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+struct StderrLogger {
+    format: LogFormat,
+    filter: LogFilter,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let module = record.module_path().unwrap_or_else(|| record.target());
+        let line = match self.format {
+            LogFormat::Text => format!("{:<5} [{}] {}", record.level(), module, record.args()),
+            LogFormat::Json => serde_json::json!({
+                "timestamp": SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0.0, |d| d.as_secs_f64()),
+                "level": record.level().to_string(),
+                "module": module,
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        };
+        // Write whole lines at once, so that lines of worker threads never interleave
+        let _ = writeln!(io::stderr().lock(), "{}", line);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install logger
+pub fn init(format: LogFormat, filter: LogFilter) -> Result<(), SetLoggerError> {
+    static LOGGER: OnceLock<StderrLogger> = OnceLock::new();
+    log::set_max_level(filter.max_level());
+    log::set_logger(LOGGER.get_or_init(|| StderrLogger { format, filter }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        let filter: LogFilter =
+            "info, check_broken_packages::checks=debug,check_broken_packages::checks::fonts=off"
+                .parse()
+                .unwrap();
+        assert_eq!(filter.level("check_broken_packages"), LevelFilter::Info);
+        assert_eq!(
+            filter.level("check_broken_packages::checks::services"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            filter.level("check_broken_packages::checks::fonts"),
+            LevelFilter::Off
+        );
+        assert_eq!(
+            filter.level("check_broken_packages::checksum"),
+            LevelFilter::Info
+        );
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+        assert_eq!(LogFilter::from_str("").unwrap(), LogFilter::default());
+        assert!(LogFilter::from_str("loud").is_err());
+    }
+}
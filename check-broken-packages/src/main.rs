@@ -1,30 +1,98 @@
 use std::cmp;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::error;
-use std::fmt;
 use std::fs;
-use std::io::BufRead;
-use std::iter::FromIterator;
-use std::os::unix::fs::PermissionsExt;
-use std::process::Command;
-use std::str::FromStr;
-use std::sync::Arc;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, SystemTime};
 
 use ansi_term::Colour::*;
+use clap::{CommandFactory, Parser};
 use crossbeam::thread as cb_thread;
 use glob::glob;
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use log::debug;
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
 use simple_error::SimpleError;
 
+mod archive;
+mod audio;
+mod cache;
+mod category;
+mod checkpoint;
+mod checks;
+mod cl;
+mod config;
+mod cross;
+mod daemon;
+mod dbus;
+mod defer;
+mod doctor;
+mod elevate;
+mod elf;
+mod explain;
+mod filter;
+mod format;
+mod held;
+mod history;
+mod i18n;
+mod ignore;
+mod logger;
+mod man;
+mod needs_index;
+mod pacman;
+mod parse;
+mod paths;
+mod perl;
+mod plugins;
+mod providers;
+mod python;
+mod rebuild;
+mod render;
+mod report;
+mod sandbox;
+mod severity;
+mod shipped;
+mod soname_index;
+mod soname_summary;
+mod sources;
+mod stats;
+mod status;
+mod system;
+mod tools;
+mod unprivileged;
+mod upgrade;
+mod walk;
+mod watch;
+mod wrapper;
+
 type CrossbeamChannel<T> = (
     crossbeam::channel::Sender<T>,
     crossbeam::channel::Receiver<T>,
 );
 
+/// Maximum number of findings waiting to be aggregated
+const FINDING_CHANNEL_CAPACITY: usize = 1024;
+
+/// Exit code when a fatal error occured
+const EXIT_CODE_ERROR: i32 = 1;
+
+/// Exit code when a checked removal would break files of foreign packages, and removals are
+/// blocked
+const EXIT_CODE_REMOVAL_BLOCKED: i32 = 2;
+
+/// Exit code when a finding has at least the severity configured to fail on
+const EXIT_CODE_FINDINGS: i32 = 3;
+
+/// Exit code when interrupted by a signal, like shells do for SIGINT
+const EXIT_CODE_INTERRUPTED: i32 = 130;
+
 /// Executable file work unit for a worker thread to process
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ExecFileWork {
     /// AUR package name
     #[allow(clippy::rc_buffer)]
@@ -36,184 +104,185 @@ struct ExecFileWork {
 
     /// True if this is the last executable filepath for the package (used to report progress)
     package_last: bool,
-}
 
-struct PythonPackageVersion {
-    major: u8,
-    minor: u8,
-    release: u8,
-    package: u8,
-}
+    /// Number of files of the package not analyzed yet, shared by its work units (used to
+    /// checkpoint packages), None for plugin files
+    package_pending: Option<Arc<AtomicUsize>>,
 
-impl fmt::Display for PythonPackageVersion {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}.{}.{}-{}",
-            self.major, self.minor, self.release, self.package
-        )
-    }
+    /// Directories where the package resolves libraries at runtime, declared or added to
+    /// LD_LIBRARY_PATH by its wrapper scripts
+    runtime_dirs: Arc<Vec<PathBuf>>,
 }
 
-fn get_python_version() -> Result<PythonPackageVersion, Box<dyn error::Error>> {
-    let output = Command::new("pacman")
-        .args(&["-Qi", "python"])
-        .env("LANG", "C")
-        .output()?;
-
-    if !output.status.success() {
-        return Err(Box::new(SimpleError::new(
-            "Failed to query Python version with pacman",
-        )));
-    }
-
-    let version_line = output
-        .stdout
-        .lines()
-        .filter_map(Result::ok)
-        .find(|l| l.starts_with("Version"))
-        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to find version line"))?;
-    let version_str = version_line
-        .split(':')
-        .nth(1)
-        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to parse version line"))?
-        .trim_start();
-
-    let mut dot_iter = version_str.split('.');
-    let major = u8::from_str(dot_iter.next().ok_or_else(|| {
-        SimpleError::new("Unexpected pacman output: unable to parse Python version major part")
-    })?)?;
-    let minor = u8::from_str(dot_iter.next().ok_or_else(|| {
-        SimpleError::new("Unexpected pacman output: unable to parse Python version minor part")
-    })?)?;
-    let mut dash_iter = dot_iter
-        .next()
-        .ok_or_else(|| {
-            SimpleError::new(
-                "Unexpected pacman output: unable to parse Python version release/package part",
-            )
-        })?
-        .split('-');
-    let release = u8::from_str(dash_iter.next().ok_or_else(|| {
-        SimpleError::new("Unexpected pacman output: unable to parse Python version release part")
-    })?)?;
-    let package = u8::from_str(dash_iter.next().ok_or_else(|| {
-        SimpleError::new("Unexpected pacman output: unable to parse Python version package part")
-    })?)?;
-
-    Ok(PythonPackageVersion {
-        major,
-        minor,
-        release,
-        package,
-    })
-}
+/// Extensions of files that are never executables, even if they have executable permissions
+const DATA_FILE_EXTENSIONS: [&str; 20] = [
+    "bmp", "css", "csv", "gif", "htm", "html", "ico", "ini", "jpeg", "jpg", "js", "json", "md",
+    "png", "svg", "toml", "txt", "xml", "yaml", "yml",
+];
 
-fn get_package_owning_path(path: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
-    let output = Command::new("pacman").args(&["-Qoq", path]).output()?;
+/// Magic bytes at the start of ELF files
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
 
-    Ok(output
-        .stdout
-        .lines()
-        .map(std::result::Result::unwrap)
-        .collect())
-}
+/// Maximum number of packages displayed in the disk usage summary
+const DISK_USAGE_DISPLAY_COUNT: usize = 10;
 
-fn get_broken_python_packages(
-    current_python_version: &PythonPackageVersion,
-) -> Result<Vec<(String, String)>, Box<dyn error::Error>> {
-    let mut packages = Vec::new();
+/// Size of the start of scripts searched for LD_LIBRARY_PATH assignments, wrapper scripts are short
+const WRAPPER_MAX_LEN: usize = 64 * 1024;
 
-    let current_python_dir = format!(
-        "/usr/lib/python{}.{}",
-        current_python_version.major, current_python_version.minor
-    );
+/// Maximum number of sonames displayed in the missing libraries summary
+const SONAME_DISPLAY_COUNT: usize = 10;
 
-    for python_dir_entry in glob(&format!("/usr/lib/python{}*", current_python_version.major))? {
-        let python_dir = python_dir_entry?
-            .into_os_string()
-            .into_string()
-            .map_err(|_| SimpleError::new("Failed to convert OS string to native string"))?;
-
-        if python_dir != current_python_dir {
-            let dir_packages = get_package_owning_path(&python_dir)?;
-            for package in dir_packages {
-                let couple = (package, python_dir.clone());
-                if !packages.contains(&couple) {
-                    packages.push(couple);
-                }
-            }
+/// Number of packages displayed in the verbose summary of the slowest packages to analyze
+const SLOWEST_PACKAGE_DISPLAY_COUNT: usize = 10;
+
+/// Return true if file is a data file with executable permissions, based on its extension or
+/// content, scripts and ELF files are not considered data files
+fn is_data_file(system: &dyn system::SystemProvider, path: &Path) -> bool {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        if DATA_FILE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) {
+            return true;
         }
     }
 
-    Ok(packages)
-}
-
-fn get_aur_packages() -> Result<Vec<String>, Box<dyn error::Error>> {
-    let output = Command::new("pacman").args(&["-Qqm"]).output()?;
-
-    if !output.status.success() {
-        return Err(Box::new(SimpleError::new(
-            "Failed to list packages with pacman",
-        )));
+    match system.read_head(path, ELF_MAGIC.len()) {
+        // Too short to be an executable
+        Ok(magic) if magic.len() < ELF_MAGIC.len() => true,
+        Ok(magic) => (magic != ELF_MAGIC) && !magic.starts_with(b"#!"),
+        // Let the analysis report the problem
+        Err(_) => false,
     }
+}
 
-    Ok(Vec::from_iter(
-        output.stdout.lines().map(std::result::Result::unwrap),
+fn get_package_executable_files(
+    system: &dyn system::SystemProvider,
+    package: &str,
+    config: &config::Config,
+) -> Result<Vec<String>, Box<dyn error::Error>> {
+    Ok(executable_files(
+        system,
+        package,
+        system.package_files(package)?,
+        config,
     ))
 }
 
-fn get_package_executable_files(package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+/// Get files of a package to analyze, among some of its files
+fn executable_files(
+    system: &dyn system::SystemProvider,
+    package: &str,
+    paths: Vec<String>,
+    config: &config::Config,
+) -> Vec<String> {
     let mut files = Vec::new();
 
-    let output = Command::new("pacman").args(&["-Ql", package]).output()?;
-
-    if !output.status.success() {
-        return Err(Box::new(SimpleError::new(format!(
-            "Failed to list files for package '{}' with pacman",
-            package
-        ))));
-    }
-
-    for line in output.stdout.lines() {
-        let line = line?;
-        let path = line
-            .split(' ')
-            .nth(1)
-            .ok_or_else(|| {
-                SimpleError::new("Unexpected pacman output: unable to parse package file list")
-            })?
-            .to_string();
-        let metadata = match fs::metadata(&path) {
+    for path in paths {
+        if let Some(ignore_path) = config
+            .ignore_path
+            .iter()
+            .find(|p| p.matches(Path::new(&path)))
+        {
+            debug!("Ignoring {:?} due to path pattern {:?}", path, ignore_path);
+            continue;
+        }
+        if !config.analyze_cross_toolchains && cross::is_cross_toolchain_file(&path) {
+            if config.verbose {
+                eprintln!(
+                    "Skipping file '{}' from package '{}' of a cross compilation toolchain",
+                    path, package
+                );
+            }
+            continue;
+        }
+        let metadata = match system.metadata(Path::new(&path)) {
             Ok(m) => m,
+            // Let the analysis report the file as unreadable
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                files.push(path);
+                continue;
+            }
             Err(_e) => continue,
         };
-        if metadata.file_type().is_file() && ((metadata.permissions().mode() & 0o111) != 0) {
+        // Audio plugins are loaded by their host, and often lack executable permissions
+        let is_audio_plugin = audio::plugin_format(Path::new(&path)).is_some()
+            && Path::new(&path)
+                .file_name()
+                .is_some_and(|f| plugins::is_shared_object_name(&f.to_string_lossy()));
+        if metadata.is_file && (((metadata.mode & 0o111) != 0) || is_audio_plugin) {
+            // Empty executables are damaged rather than data files
+            if config.skip_data_files
+                && (metadata.len > 0)
+                && is_data_file(system, Path::new(&path))
+            {
+                if config.verbose {
+                    eprintln!(
+                        "Skipping data file '{}' from package '{}' with executable permissions",
+                        path, package
+                    );
+                }
+                continue;
+            }
             files.push(path);
         }
     }
 
-    Ok(files)
+    files
 }
 
-fn get_missing_dependencies(exec_file: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
-    let mut missing_deps = Vec::new();
-
-    let output = Command::new("ldd").args(&[exec_file]).output()?;
-
-    if output.status.success() {
-        for missing_dep in output
-            .stdout
-            .lines()
-            .map(std::result::Result::unwrap)
-            .filter(|l| l.ends_with("=> not found"))
-            .map(|l| l.split(' ').next().unwrap().trim_start().to_string())
-        {
-            missing_deps.push(missing_dep);
+/// Get missing dependencies of a file with ldd, run in a sandbox since it can execute code of the
+/// file
+fn get_missing_dependencies(
+    exec_file: &str,
+    sandbox: sandbox::Sandbox,
+) -> Result<Vec<String>, Box<dyn error::Error>> {
+    let output = sandbox.command("ldd", &[exec_file]).output()?;
+    let result = parse::ldd_result(output.status.success(), &output.stdout, &output.stderr)
+        .map_err(|e| format!("Failed to analyze {:?}: {}", exec_file, e))?;
+    match result {
+        parse::LddResult::Dynamic(missing_deps) => Ok(missing_deps),
+        // Files of a foreign architecture are reported by their own check
+        parse::LddResult::NotDynamic
+        | parse::LddResult::WrongArchitecture
+        | parse::LddResult::NotFound => Ok(Vec::new()),
+        parse::LddResult::PermissionDenied => {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied).into())
         }
+        parse::LddResult::LoaderError(error) => Err(system::LoaderError(error).into()),
     }
+}
 
-    Ok(missing_deps)
+/// Get directories where a package resolves libraries at runtime: those declared in the
+/// configuration, and those its wrapper scripts add to LD_LIBRARY_PATH
+fn package_runtime_dirs(
+    system: &dyn system::SystemProvider,
+    package: &str,
+    exec_files: &[String],
+    config: &config::Config,
+) -> Vec<PathBuf> {
+    let mut dirs = config
+        .bundled_runtime
+        .get(package)
+        .cloned()
+        .unwrap_or_default();
+    for exec_file in exec_files {
+        let path = Path::new(exec_file);
+        let content = match system.read_head(path, WRAPPER_MAX_LEN) {
+            Ok(c) if c.starts_with(b"#!") => c,
+            _ => continue,
+        };
+        for dir in wrapper::library_path_dirs(&String::from_utf8_lossy(&content), path) {
+            if !dirs.contains(&dir) {
+                if config.verbose {
+                    eprintln!(
+                        "Resolving libraries of package '{}' in {:?}, added to LD_LIBRARY_PATH \
+                         by {:?}",
+                        package, dir, path
+                    );
+                }
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
 }
 
 fn get_sd_enabled_service_links() -> Result<VecDeque<String>, Box<dyn error::Error>> {
@@ -223,18 +292,18 @@ fn get_sd_enabled_service_links() -> Result<VecDeque<String>, Box<dyn error::Err
         glob("/etc/systemd/system/*.target.*"),
         glob("/etc/systemd/user/*.target.*"),
     ];
-    for dir_content in &mut dirs_content {
-        if let Ok(dir_content) = dir_content {
-            for base_dir in dir_content {
-                if let Ok(base_dir) = base_dir {
-                    for file in std::fs::read_dir(base_dir.as_path())
-                        .unwrap()
-                        .map(Result::unwrap)
-                    {
-                        if file.file_type()?.is_symlink() {
-                            service_links
-                                .push_back(file.path().into_os_string().into_string().unwrap());
-                        }
+    for dir_content in dirs_content.iter_mut().flatten() {
+        for base_dir in dir_content.flatten() {
+            // Links are in directories like multi-user.target.wants, skip anything else
+            if !base_dir.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(base_dir.as_path())? {
+                let file = file?;
+                if file.file_type()?.is_symlink() {
+                    // Unit names are ASCII, other links are not units
+                    if let Ok(link) = file.path().into_os_string().into_string() {
+                        service_links.push_back(link);
                     }
                 }
             }
@@ -247,7 +316,13 @@ fn get_sd_enabled_service_links() -> Result<VecDeque<String>, Box<dyn error::Err
 fn is_valid_link(link: &str) -> Result<bool, Box<dyn error::Error>> {
     let mut target = link.to_string();
     loop {
-        target = fs::read_link(target)?
+        // Relative targets are relative to the link directory
+        let link_dir = Path::new(&target)
+            .parent()
+            .unwrap_or_else(|| Path::new("/"))
+            .to_path_buf();
+        target = link_dir
+            .join(fs::read_link(target)?)
             .into_os_string()
             .into_string()
             .unwrap();
@@ -272,133 +347,475 @@ fn is_valid_link(link: &str) -> Result<bool, Box<dyn error::Error>> {
     }
 }
 
-fn main() {
-    // Init logger
-    simple_logger::init().unwrap();
+/// Sets the cancellation flag if the thread owning it panics, so that other workers stop early
+struct CancelOnPanic<'a>(&'a AtomicBool);
 
-    // Python broken packages channel
-    let (python_broken_packages_tx, python_broken_packages_rx) = crossbeam::unbounded();
-    thread::Builder::new()
-        .spawn(move || {
-            let to_send = match get_python_version() {
-                Ok(current_python_version) => {
-                    debug!("Python version: {}", current_python_version);
-                    let broken_python_packages =
-                        get_broken_python_packages(&current_python_version);
-                    match broken_python_packages {
-                        Ok(broken_python_packages) => broken_python_packages,
-                        Err(err) => {
-                            eprintln!("Failed to list Python packages: {}", err);
-                            Vec::<(String, String)>::new()
-                        }
-                    }
-                }
-                Err(err) => {
-                    eprintln!("Failed to get Python version: {}", err);
-                    Vec::<(String, String)>::new()
-                }
+impl Drop for CancelOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Analyze an executable file of a package, and get its findings
+fn analyze_file(
+    ctx: &checks::Context,
+    held_packages: &held::HeldPackages,
+    work: &ExecFileWork,
+) -> Vec<report::Finding> {
+    let system = ctx.system;
+    let path = Path::new(work.exec_filepath.as_str());
+    // Cheap pre-check, damaged files can not be analyzed anyway
+    let damage = match system.damage(path) {
+        Ok(d) => d,
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            return vec![report::Finding::UnreadableFile {
+                package: work.package.to_string(),
+                file: work.exec_filepath.to_string(),
+            }];
+        }
+        Err(_e) => None,
+    };
+    if let Some(damage) = damage {
+        return vec![report::Finding::DamagedFile {
+            package: work.package.to_string(),
+            file: work.exec_filepath.to_string(),
+            damage,
+        }];
+    }
+    let missing_deps = if ctx.config.resolve_tree {
+        system.missing_dependency_chains(path)
+    } else {
+        system.missing_dependencies(path).map(|d| {
+            // Libraries through which they are needed, only resolved for broken files
+            let mut trees = if d.is_empty() {
+                Vec::new()
+            } else {
+                system.missing_dependency_chains(path).unwrap_or_default()
             };
-            python_broken_packages_tx.send(to_send).unwrap();
+            d.into_iter()
+                .map(|soname| {
+                    let needed_by = trees
+                        .iter()
+                        .position(|m| m.soname == soname)
+                        .and_then(|i| trees.swap_remove(i).needed_by);
+                    elf::MissingLibrary {
+                        soname,
+                        chain: Vec::new(),
+                        needed_by,
+                    }
+                })
+                .collect()
         })
-        .unwrap();
+    };
+    let mut missing_deps = match missing_deps {
+        Ok(d) => d,
+        Err(err) if err.downcast_ref::<system::LoaderError>().is_some() => {
+            let error = err.downcast::<system::LoaderError>().unwrap().0;
+            return vec![report::Finding::LoaderError {
+                package: work.package.to_string(),
+                file: work.exec_filepath.to_string(),
+                error,
+            }];
+        }
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied) =>
+        {
+            return vec![report::Finding::UnreadableFile {
+                package: work.package.to_string(),
+                file: work.exec_filepath.to_string(),
+            }];
+        }
+        Err(err) => {
+            eprintln!(
+                "Failed to get missing dependencies for path '{}': {}",
+                &work.exec_filepath, err
+            );
+            return Vec::new();
+        }
+    };
+    // Libraries the package resolves itself at runtime
+    missing_deps.retain(|m| {
+        !work
+            .runtime_dirs
+            .iter()
+            .any(|d| system.metadata(&d.join(&m.soname)).is_ok_and(|m| m.is_file))
+    });
+    missing_deps
+        .into_iter()
+        .map(|missing_dep| match audio::plugin_format(path) {
+            Some(format) => report::Finding::AudioPluginMissingDependency {
+                package: work.package.to_string(),
+                file: work.exec_filepath.to_string(),
+                format: format.to_string(),
+                dependency: missing_dep.soname,
+            },
+            None => report::Finding::MissingDependency {
+                package: work.package.to_string(),
+                file: work.exec_filepath.to_string(),
+                held_back: held_packages
+                    .cause(&work.package, &missing_dep.soname)
+                    .map(str::to_string),
+                // Only the package itself can repackage a binary needing the library
+                unshipped: shipped::is_prebuilt(&work.package)
+                    && missing_dep.needed_by.is_none()
+                    && ctx
+                        .shipped_sonames()
+                        .is_some_and(|s| s.is_unshipped(&missing_dep.soname)),
+                dependency: missing_dep.soname,
+                chain: missing_dep.chain,
+                // Only another package can be the culprit
+                transitive: missing_dep
+                    .needed_by
+                    .map(|p| report::BrokenLibrary {
+                        package: ctx.owner(&p),
+                        file: p.to_string_lossy().to_string(),
+                    })
+                    .filter(|l| l.package != *work.package),
+            },
+        })
+        .collect()
+}
+
+/// Get name of the user analyzing package files when running as root
+fn analysis_user_name(config: &config::Config) -> &str {
+    config
+        .analysis_user
+        .as_deref()
+        .unwrap_or(unprivileged::DEFAULT_USER)
+}
+
+/// Get unprivileged user analyzing package files, None to analyze them in process
+fn analysis_user(
+    config: &config::Config,
+) -> Result<Option<unprivileged::User>, Box<dyn error::Error>> {
+    let name = analysis_user_name(config);
+    if !paths::is_root() || (name == "root") {
+        return Ok(None);
+    }
+    unprivileged::User::lookup(name).map(Some)
+}
+
+/// Wrap a system provider so that files are parsed by helpers running as an unprivileged user
+/// when running as root, never as root, the helpers building the same provider. Exits if the
+/// helpers can not be started.
+fn unprivileged_system(
+    system: Box<dyn system::SystemProvider>,
+    helper_system: unprivileged::HelperSystem,
+    config: &config::Config,
+) -> Box<dyn system::SystemProvider> {
+    let res = analysis_user(config).and_then(|user| match user {
+        Some(user) => Ok(Box::new(unprivileged::UnprivilegedSystem::start(
+            system,
+            user,
+            helper_system,
+        )?) as Box<dyn system::SystemProvider>),
+        None => Ok(system),
+    });
+    match res {
+        Ok(s) => s,
+        Err(err) => {
+            eprintln!(
+                "{}",
+                Red.paint(format!(
+                    "Unable to analyze package files as an unprivileged user: {}. Set another \
+                     user with --analysis-user, or 'root' to analyze them as root.",
+                    err
+                ))
+            );
+            process::exit(EXIT_CODE_ERROR);
+        }
+    }
+}
+
+/// Get system provider parsing the files of package archives and directories, outside of the
+/// analysis of installed packages
+fn file_parser(config: &config::Config) -> Box<dyn system::SystemProvider> {
+    unprivileged_system(
+        Box::new(system::RealSystem::default()),
+        unprivileged::HelperSystem::default(),
+        config,
+    )
+}
 
-    // Get usable core count
-    let cpu_count = num_cpus::get();
+/// Return true if a finding produced outside of the analysis is not ignored, and passes the
+/// severity and expression filters of the configuration, like those of the aggregator
+fn is_reported(finding: &report::Finding, config: &config::Config) -> bool {
+    let severity = config.severity.severity(finding);
+    !config.ignore.iter().any(|r| r.matches(finding))
+        && config.min_severity.is_none_or(|s| severity >= s)
+        && config
+            .filter
+            .as_ref()
+            .is_none_or(|e| e.matches(finding, severity))
+}
+
+/// Run all checks, sending findings to the aggregator
+///
+/// Stops early if `cancelled` is set, in which case findings are partial.
+#[allow(clippy::too_many_arguments)]
+fn analyze(
+    config: &config::Config,
+    aggregator: &mut report::Aggregator,
+    python_broken_packages_rx: crossbeam::channel::Receiver<Vec<python::StaleDir>>,
+    perl_broken_packages_rx: crossbeam::channel::Receiver<Vec<python::StaleDir>>,
+    checks: &[&dyn checks::Check],
+    system: &dyn system::SystemProvider,
+    status: &status::Status,
+    checkpoint: Option<&checkpoint::Checkpoint>,
+    cancelled: &AtomicBool,
+) -> Result<(), Box<dyn error::Error>> {
+    // Get worker thread count
+    let job_count = config.jobs();
 
     // Get package names
-    let aur_packages = get_aur_packages().unwrap();
+    status.set_phase("listing packages");
+    let mut aur_packages = if config.all_packages {
+        system.packages()?
+    } else {
+        config.package_source.packages(system)?
+    };
+    if let Some(checkpoint) = checkpoint {
+        let done_packages = checkpoint.done_packages();
+        if !done_packages.is_empty() {
+            if !config.quiet {
+                eprintln!(
+                    "Resuming analysis from checkpoint, {} already analyzed",
+                    render::plural(done_packages.len(), "package", "packages")
+                );
+            }
+            aur_packages.retain(|p| !done_packages.contains(p));
+            for finding in checkpoint.findings() {
+                aggregator.add(finding);
+            }
+        }
+    }
+
+    // Get plugin files, not already analyzed as part of foreign packages
+    let mut plugin_files = Vec::new();
+    if !config.plugin_roots.is_empty() && config.root.is_none() {
+        status.set_phase("listing plugins");
+        let file_index = system.file_index()?;
+        let exclude_packages: HashSet<&str> = aur_packages.iter().map(String::as_str).collect();
+        for plugin_root in &config.plugin_roots {
+            let root_plugin_files = plugins::get_plugin_files(
+                plugin_root,
+                &file_index,
+                &exclude_packages,
+                &config.ignore_path,
+                job_count,
+                status.walk_progress(),
+            );
+            debug!(
+                "{} plugin files in {:?}",
+                root_plugin_files.len(),
+                plugin_root
+            );
+            if !root_plugin_files.is_empty() {
+                plugin_files.push(root_plugin_files);
+            }
+        }
+    }
+    // Each plugin root counts as a package
+    status.set_package_count(aur_packages.len() + plugin_files.len());
 
     // Get systemd enabled services
-    let enabled_sd_service_links = get_sd_enabled_service_links().unwrap();
+    let enabled_sd_service_links = if config.root.is_none() {
+        get_sd_enabled_service_links()?
+    } else {
+        VecDeque::new()
+    };
     let mut broken_sd_service_links: VecDeque<String> = VecDeque::new();
 
     // Init progressbar
     let progress = ProgressBar::with_draw_target(
-        (aur_packages.len() + enabled_sd_service_links.len()) as u64,
-        ProgressDrawTarget::stderr(),
+        (aur_packages.len() + plugin_files.len() + enabled_sd_service_links.len() + checks.len())
+            as u64,
+        if config.quiet {
+            ProgressDrawTarget::hidden()
+        } else {
+            ProgressDrawTarget::stderr()
+        },
     );
     progress.set_style(ProgressStyle::default_bar().template("Analyzing {wide_bar} {pos}/{len}"));
+    status.set_phase("analyzing packages");
+
+    // Findings channel, bounded so that findings are aggregated as they are produced
+    let (findings_tx, findings_rx) = crossbeam::bounded(FINDING_CHANNEL_CAPACITY);
+
+    let check_ctx = checks::Context::new(config, system);
+
+    // Held back packages, to explain missing dependencies caused by a partial upgrade
+    let held_packages = system.held_packages().unwrap_or_else(|err| {
+        eprintln!("Failed to get held back packages: {}", err);
+        held::HeldPackages::default()
+    });
+    let held_packages = &held_packages;
+
+    // Files the current user can not read, analyzed by the elevated helper once all others are
+    let elevate = config.elevate && config.root.is_none() && !paths::is_root();
+    let unreadable_works: Mutex<Vec<ExecFileWork>> = Mutex::new(Vec::new());
+    let unreadable_works_ref = &unreadable_works;
+
+    let scope_res = cb_thread::scope(|scope| {
+        // Findings aggregation worker
+        let aggregator = &mut *aggregator;
+        scope.spawn(move |_| {
+            let _guard = CancelOnPanic(cancelled);
+            for finding in findings_rx.iter() {
+                aggregator.add(finding);
+            }
+        });
 
-    // Missing deps channel
-    let (missing_deps_tx, missing_deps_rx) = crossbeam::unbounded();
-
-    cb_thread::scope(|scope| {
         // Executable file channel
         let (exec_files_tx, exec_files_rx): CrossbeamChannel<ExecFileWork> = crossbeam::unbounded();
 
         // Executable files to missing deps workers
-        for _ in 0..cpu_count {
+        for _ in 0..job_count {
             let exec_files_rx = exec_files_rx.clone();
-            let missing_deps_tx = missing_deps_tx.clone();
+            let findings_tx = findings_tx.clone();
             let progress = progress.clone();
+            let check_ctx = &check_ctx;
             scope.spawn(move |_| {
+                let _guard = CancelOnPanic(cancelled);
                 while let Ok(exec_file_work) = exec_files_rx.recv() {
+                    if cancelled.load(Ordering::SeqCst) {
+                        break;
+                    }
                     debug!("exec_files_rx => {:?}", &exec_file_work);
-                    let missing_deps = get_missing_dependencies(&exec_file_work.exec_filepath);
-                    match missing_deps {
-                        Ok(missing_deps) => {
-                            for missing_dep in missing_deps {
-                                let to_send = (
-                                    Arc::clone(&exec_file_work.package),
-                                    Arc::clone(&exec_file_work.exec_filepath),
-                                    missing_dep,
-                                );
-                                debug!("{:?} => missing_deps_tx", &to_send);
-                                if missing_deps_tx.send(to_send).is_err() {
-                                    break;
-                                }
-                            }
+                    let in_flight_guard = status
+                        .file_in_flight(&exec_file_work.package, &exec_file_work.exec_filepath);
+                    let findings = analyze_file(check_ctx, held_packages, &exec_file_work);
+                    drop(in_flight_guard);
+                    let deferred = elevate
+                        && matches!(
+                            findings.as_slice(),
+                            [report::Finding::UnreadableFile { .. }]
+                        );
+                    if deferred {
+                        unreadable_works_ref
+                            .lock()
+                            .unwrap()
+                            .push(exec_file_work.clone());
+                    }
+                    for finding in findings {
+                        if let Some(checkpoint) = checkpoint {
+                            checkpoint.add_finding(&exec_file_work.package, &finding);
+                        }
+                        if deferred {
+                            continue;
+                        }
+                        debug!("{:?} => findings_tx", &finding);
+                        if findings_tx.send(finding).is_err() {
+                            break;
                         }
-                        Err(err) => {
-                            eprintln!(
-                                "Failed to get missing dependencies for path '{}': {}",
-                                &exec_file_work.exec_filepath, err
-                            );
+                    }
+                    if let (Some(checkpoint), Some(pending)) =
+                        (checkpoint, &exec_file_work.package_pending)
+                    {
+                        if pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                            checkpoint.package_done(&exec_file_work.package);
                         }
                     }
                     if exec_file_work.package_last {
                         progress.inc(1);
+                        status.package_done();
                     }
                 }
             });
         }
 
+        // Other checks workers
+        for check in checks {
+            let findings_tx = findings_tx.clone();
+            let progress = progress.clone();
+            let check_ctx = &check_ctx;
+            scope.spawn(move |_| {
+                let _guard = CancelOnPanic(cancelled);
+                let res = checks::run_check(*check, check_ctx, &mut |finding| {
+                    if !cancelled.load(Ordering::SeqCst) {
+                        let _ = findings_tx.send(finding);
+                    }
+                });
+                if let Err(err) = res {
+                    eprintln!("Check '{}' failed: {}", check.name(), err);
+                }
+                progress.inc(1);
+            });
+        }
+
         // Drop this end of the channel, workers have their own clone
-        drop(missing_deps_tx);
+        drop(findings_tx);
+
+        // Send plugin files
+        for root_plugin_files in plugin_files {
+            let count = root_plugin_files.len();
+            for (i, (package, plugin_file)) in root_plugin_files.into_iter().enumerate() {
+                let to_send = ExecFileWork {
+                    package,
+                    exec_filepath: Arc::new(plugin_file),
+                    package_last: i == count - 1,
+                    package_pending: None,
+                    runtime_dirs: Arc::new(Vec::new()),
+                };
+                debug!("{:?} => exec_files_tx", &to_send);
+                if exec_files_tx.send(to_send).is_err() {
+                    break;
+                }
+            }
+        }
 
         cb_thread::scope(|scope| {
             // Package name channel
             let (package_tx, package_rx): CrossbeamChannel<Arc<String>> = crossbeam::unbounded();
 
             // Package name to executable files workers
-            let worker_count = cmp::min(cpu_count, aur_packages.len());
+            let worker_count = cmp::min(job_count, aur_packages.len());
             for _ in 0..worker_count {
                 let package_rx = package_rx.clone();
                 let exec_files_tx = exec_files_tx.clone();
                 let progress = progress.clone();
                 scope.spawn(move |_| {
+                    let _guard = CancelOnPanic(cancelled);
                     while let Ok(package) = package_rx.recv() {
+                        if cancelled.load(Ordering::SeqCst) {
+                            break;
+                        }
                         debug!("package_rx => {:?}", package);
-                        let exec_files = match get_package_executable_files(&package) {
-                            Ok(exec_files) => exec_files,
-                            Err(err) => {
-                                eprintln!(
-                                    "Failed to get executable files of package '{}': {}",
-                                    &package, err
-                                );
-                                progress.inc(1);
-                                continue;
-                            }
-                        };
+                        let exec_files =
+                            match get_package_executable_files(system, &package, config) {
+                                Ok(exec_files) => exec_files,
+                                Err(err) => {
+                                    eprintln!(
+                                        "Failed to get executable files of package '{}': {}",
+                                        &package, err
+                                    );
+                                    progress.inc(1);
+                                    status.package_done();
+                                    continue;
+                                }
+                            };
                         if exec_files.is_empty() {
+                            if let Some(checkpoint) = checkpoint {
+                                checkpoint.package_done(&package);
+                            }
                             progress.inc(1);
+                            status.package_done();
                             continue;
                         }
+                        let package_pending = Arc::new(AtomicUsize::new(exec_files.len()));
+                        let runtime_dirs =
+                            Arc::new(package_runtime_dirs(system, &package, &exec_files, config));
                         for (i, exec_file) in exec_files.iter().enumerate() {
                             let to_send = ExecFileWork {
                                 package: Arc::clone(&package),
                                 exec_filepath: Arc::new(exec_file.to_string()),
                                 package_last: i == exec_files.len() - 1,
+                                package_pending: Some(Arc::clone(&package_pending)),
+                                runtime_dirs: Arc::clone(&runtime_dirs),
                             };
                             debug!("{:?} => exec_files_tx", &to_send);
                             if exec_files_tx.send(to_send).is_err() {
@@ -414,55 +831,1527 @@ fn main() {
 
             // Send package names
             for aur_package in aur_packages {
+                if cancelled.load(Ordering::SeqCst) {
+                    break;
+                }
                 debug!("{:?} => package_tx", aur_package);
-                package_tx.send(Arc::new(aur_package)).unwrap();
+                if package_tx.send(Arc::new(aur_package)).is_err() {
+                    // All workers are gone
+                    break;
+                }
             }
         })
-        .unwrap();
+        .map_err(|_| ())?;
 
         // We don't bother to use a worker thread for this, the overhead is not worth it
+        status.set_phase("checking systemd service links");
         for enabled_sd_service_link in enabled_sd_service_links {
-            if !is_valid_link(&enabled_sd_service_link).unwrap() {
-                broken_sd_service_links.push_back(enabled_sd_service_link);
+            if cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+            match is_valid_link(&enabled_sd_service_link) {
+                Ok(true) => {}
+                Ok(false) => broken_sd_service_links.push_back(enabled_sd_service_link),
+                Err(err) => eprintln!(
+                    "Failed to check link '{}': {}",
+                    &enabled_sd_service_link, err
+                ),
             }
             progress.inc(1);
         }
-    })
-    .unwrap();
+
+        Ok(())
+    });
 
     progress.finish_and_clear();
 
-    for (package, file, missing_dep) in missing_deps_rx.iter() {
+    scope_res
+        .unwrap_or(Err(()))
+        .map_err(|_| SimpleError::new("Worker thread panicked"))?;
+
+    let unreadable_works = unreadable_works.into_inner().unwrap();
+    if !unreadable_works.is_empty() && !cancelled.load(Ordering::SeqCst) {
+        status.set_phase("analyzing unreadable files");
+        let files: Vec<String> = unreadable_works
+            .iter()
+            .map(|w| w.exec_filepath.to_string())
+            .collect();
+        match elevate::analyze_elevated(&files) {
+            Ok(analyses) => {
+                let elevated_system = elevate::ElevatedSystem {
+                    inner: system,
+                    analyses,
+                };
+                let elevated_ctx = check_ctx.with_system(&elevated_system);
+                for work in &unreadable_works {
+                    for finding in analyze_file(&elevated_ctx, held_packages, work) {
+                        aggregator.add(finding);
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                for work in unreadable_works {
+                    aggregator.add(report::Finding::UnreadableFile {
+                        package: work.package.to_string(),
+                        file: work.exec_filepath.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Don't wait for the Python check if we are cancelled
+    status.set_phase("waiting for Python check");
+    let broken_python_packages = if cancelled.load(Ordering::SeqCst) {
+        python_broken_packages_rx.try_recv().ok()
+    } else {
+        python_broken_packages_rx.recv().ok()
+    };
+    for stale_dir in broken_python_packages.into_iter().flatten() {
+        for (package, file_count) in stale_dir.owners {
+            aggregator.add(report::Finding::StalePythonDir {
+                package,
+                dir: stale_dir.dir.clone(),
+                file_count,
+            });
+        }
+        if stale_dir.unowned_file_count > 0 {
+            aggregator.add(report::Finding::StalePythonLeftovers {
+                dir: stale_dir.dir,
+                file_count: stale_dir.unowned_file_count,
+                removable: stale_dir.removable,
+            });
+        }
+    }
+
+    // Same for the Perl check
+    status.set_phase("waiting for Perl check");
+    let broken_perl_packages = if cancelled.load(Ordering::SeqCst) {
+        perl_broken_packages_rx.try_recv().ok()
+    } else {
+        perl_broken_packages_rx.recv().ok()
+    };
+    for stale_dir in broken_perl_packages.into_iter().flatten() {
+        for (package, file_count) in stale_dir.owners {
+            aggregator.add(report::Finding::StalePerlDir {
+                package,
+                dir: stale_dir.dir.clone(),
+                file_count,
+            });
+        }
+        if stale_dir.unowned_file_count > 0 {
+            aggregator.add(report::Finding::StalePerlLeftovers {
+                dir: stale_dir.dir,
+                file_count: stale_dir.unowned_file_count,
+                removable: stale_dir.removable,
+            });
+        }
+    }
+
+    for broken_sd_service_link in broken_sd_service_links {
+        aggregator.add(report::Finding::BrokenServiceLink {
+            link: broken_sd_service_link,
+        });
+    }
+
+    Ok(())
+}
+
+/// Get path of the run history file
+fn history_filepath() -> Option<PathBuf> {
+    paths::state_dir().map(|d| d.join("history.json"))
+}
+
+fn default_report_filepath() -> Option<PathBuf> {
+    paths::state_dir().map(|d| d.join("report.jsonl"))
+}
+
+/// Get index of the dependencies of installed files, from the cached one updated for the
+/// packages that changed
+fn needs_index(
+    system: &dyn system::SystemProvider,
+    config: &config::Config,
+) -> Result<needs_index::NeedsIndex, Box<dyn error::Error>> {
+    // The cache is for the running system only
+    let previous = if config.root.is_none() {
+        needs_index::NeedsIndex::load()
+    } else {
+        needs_index::NeedsIndex::default()
+    };
+    let index = needs_index::NeedsIndex::build(system, previous, config.jobs())?;
+    if config.root.is_none() {
+        if let Err(err) = index.save() {
+            eprintln!("Failed to cache dependency index: {}", err);
+        }
+    }
+    Ok(index)
+}
+
+/// Update the cached index of the dependencies of installed files for the packages of a
+/// transaction, if there is one, so that queries of the index stay fresh
+fn update_needs_index(system: &dyn system::SystemProvider, targets: &[String], jobs: usize) {
+    let mut index = needs_index::NeedsIndex::load();
+    if index.is_empty() {
+        // Building it from scratch would slow down the transaction
+        return;
+    }
+    let res = index
+        .update(system, targets, jobs)
+        .and_then(|_| index.save());
+    if let Err(err) = res {
+        eprintln!("Failed to update dependency index: {}", err);
+    }
+}
+
+/// Print installed files of the packages to include directly needing any of some sonames, with a
+/// summary ending with a description of what they have in common, and get their count
+fn print_dependents(
+    index: &needs_index::NeedsIndex,
+    sonames: &[String],
+    include_package: &dyn Fn(&str) -> bool,
+    summary_end: &str,
+    json: bool,
+) -> usize {
+    let mut files: Vec<(&str, &str, &str)> = sonames
+        .iter()
+        .flat_map(|soname| {
+            index
+                .who_needs(soname)
+                .into_iter()
+                .map(move |(package, file)| (package, file, soname.as_str()))
+        })
+        .filter(|(package, _file, _soname)| include_package(package))
+        .collect();
+    files.sort_unstable();
+    for (package, file, soname) in &files {
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({"package": package, "file": file, "soname": soname})
+            );
+        } else if sonames.len() > 1 {
+            println!("{} {} ({})", package, file, soname);
+        } else {
+            println!("{} {}", package, file);
+        }
+    }
+    if !json {
+        let packages: HashSet<&str> = files.iter().map(|(p, _f, _s)| *p).collect();
+        eprintln!(
+            "{} from {} {}",
+            render::plural(files.len(), "file", "files"),
+            render::plural(packages.len(), "package", "packages"),
+            summary_end
+        );
+    }
+    files.len()
+}
+
+/// Print installed files that a removal or soname bump would break, of a package (its
+/// libraries not also installed by another package) or of a soname
+fn simulate_removal(
+    system: &dyn system::SystemProvider,
+    target: &str,
+    config: &config::Config,
+    json: bool,
+) -> Result<(), Box<dyn error::Error>> {
+    let index = needs_index(system, config)?;
+    if target.contains(".so") {
+        print_dependents(
+            &index,
+            &[target.to_string()],
+            &|_| true,
+            &format!("would break if {} is removed", target),
+            json,
+        );
+        return Ok(());
+    }
+    let local_packages = system.local_packages()?;
+    let package = local_packages
+        .iter()
+        .find(|p| p.name == target)
+        .ok_or_else(|| format!("Package '{}' is not installed", target))?;
+    let mut sonames = needs_index::library_sonames(package);
+    for other in local_packages.iter().filter(|p| p.name != target) {
+        for soname in needs_index::library_sonames(other) {
+            sonames.remove(&soname);
+        }
+    }
+    print_dependents(
+        &index,
+        &sonames.into_iter().collect::<Vec<_>>(),
+        &|p| p != target,
+        &format!("would break if package '{}' is removed", target),
+        json,
+    );
+    Ok(())
+}
+
+/// Get foreign packages kept by a removal that may need libraries of the removed packages: all of
+/// them, or only those depending on a removed package, directly or through a provide
+fn removal_candidates(
+    local_packages: &[pacman::LocalPackage],
+    foreign_packages: &HashSet<String>,
+    removed: &HashSet<String>,
+    all: bool,
+) -> Vec<pacman::LocalPackage> {
+    // Names the removed packages can be depended on with
+    let removed_names: HashSet<&str> = local_packages
+        .iter()
+        .filter(|p| removed.contains(&p.name))
+        .flat_map(|p| {
+            std::iter::once(p.name.as_str())
+                .chain(p.provides.iter().map(|p| rebuild::unversioned(p)))
+        })
+        .collect();
+    local_packages
+        .iter()
+        .filter(|p| foreign_packages.contains(&p.name) && !removed.contains(&p.name))
+        .filter(|p| {
+            all || p
+                .depends
+                .iter()
+                .any(|d| removed_names.contains(rebuild::unversioned(d)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Print files of foreign packages kept that removing packages, read from standard input, would
+/// break, and get their count.
+///
+/// Running before the transaction, only foreign packages kept are indexed, reusing the cached
+/// index (kept up to date by the daemon and analyses) for those not reinstalled since. Without a
+/// cached index, only those depending on a removed package are.
+fn check_removal(
+    system: &dyn system::SystemProvider,
+    config: &config::Config,
+    json: bool,
+) -> Result<usize, Box<dyn error::Error>> {
+    let mut removed = HashSet::new();
+    for line in io::stdin().lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            removed.insert(line.trim().to_string());
+        }
+    }
+    let local_packages = system.local_packages()?;
+    let mut sonames = BTreeSet::new();
+    for package in local_packages.iter().filter(|p| removed.contains(&p.name)) {
+        sonames.extend(needs_index::library_sonames(package));
+    }
+    for package in local_packages.iter().filter(|p| !removed.contains(&p.name)) {
+        for soname in needs_index::library_sonames(package) {
+            sonames.remove(&soname);
+        }
+    }
+    if sonames.is_empty() {
+        return Ok(0);
+    }
+    let foreign_packages: HashSet<String> = system.foreign_packages()?.into_iter().collect();
+    let previous = if config.root.is_none() {
+        needs_index::NeedsIndex::load()
+    } else {
+        needs_index::NeedsIndex::default()
+    };
+    let candidates = removal_candidates(
+        &local_packages,
+        &foreign_packages,
+        &removed,
+        !previous.is_empty(),
+    );
+    let index =
+        needs_index::NeedsIndex::build_packages(system, candidates, previous, config.jobs());
+    Ok(print_dependents(
+        &index,
+        &sonames.into_iter().collect::<Vec<_>>(),
+        &|p| foreign_packages.contains(p) && !removed.contains(p),
+        "would break with the removal",
+        json,
+    ))
+}
+
+/// Print summaries of the recorded runs, oldest first
+fn print_history() -> Result<(), Box<dyn error::Error>> {
+    let filepath = history_filepath().ok_or("No state directory")?;
+    let history = history::History::load(&filepath)?;
+    if history.runs.is_empty() {
+        println!("No run recorded yet");
+        return Ok(());
+    }
+    println!(
+        "{:<16}  {:>8}  {:>8}  {:>5}  {:>8}  Transaction",
+        "Date (UTC)", "Findings", "Packages", "New", "Resolved"
+    );
+    for run in &history.runs {
         println!(
+            "{:<16}  {:>8}  {:>8}  {:>5}  {:>8}  {}",
+            history::format_time(run.time),
+            run.finding_count,
+            run.package_count,
+            format!("+{}", run.new_count),
+            format!("-{}", run.resolved_count),
+            history::format_targets(&run.targets)
+        );
+    }
+    Ok(())
+}
+
+/// Get time to wait for the pacman database lock to be released before reading the database with
+/// pacman, None to not wait for it
+fn db_lock_timeout(cl_opts: &cl::CommandLineOpts, config: &config::Config) -> Option<Duration> {
+    match cl_opts.command {
+        // The daemon outlives transactions, it waits for the current one to end to read the live
+        // database, and then follows its changes
+        Some(cl::Command::Daemon { .. }) => Some(Duration::MAX),
+        // Hooks are run by the pacman process holding the lock, for the whole transaction, and
+        // reading the database with pacman does not need it
+        _ if cl_opts.hook || cl_opts.check_removal => None,
+        _ => Some(Duration::from_secs(config.db_lock_timeout)),
+    }
+}
+
+/// Return true if the pacman database can be read with pacman, waiting for its lock if needed
+fn is_db_unlocked(lock_filepath: &Path, timeout: Option<Duration>) -> bool {
+    timeout.is_none_or(|t| pacman::wait_db_unlocked(lock_filepath, t))
+}
+
+/// Add summary of a complete run of the running system to the history, and return it
+fn record_history(
+    findings: BTreeSet<String>,
+    missing_sonames: Option<BTreeSet<history::MissingSoname>>,
+    targets: Vec<String>,
+) -> Option<history::History> {
+    let filepath = history_filepath()?;
+    let mut history = history::History::load(&filepath).unwrap_or_else(|err| {
+        eprintln!("Discarding invalid history file {:?}: {}", filepath, err);
+        history::History::default()
+    });
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    history.record(now, findings, missing_sonames, targets);
+    if let Err(err) = history.save(&filepath) {
+        eprintln!("Failed to write history file {:?}: {}", filepath, err);
+    }
+    Some(history)
+}
+
+/// Print configuration line ignoring the bundled runtimes whose missing libraries the last runs
+/// reported unchanged
+fn print_ignore_suggestions(history: &history::History) {
+    let dirs = history.ignore_path_suggestions();
+    if dirs.is_empty() {
+        return;
+    }
+    println!(
+        "{}",
+        i18n::tr(
+            "ignore_suggestions",
+            "Libraries of bundled runtimes reported missing by the last runs, likely resolved by \
+             their application, can be ignored by adding to the configuration file:",
+            &[]
+        )
+    );
+    println!(
+        "  ignore_path = [{}]",
+        dirs.iter()
+            .map(|d| serde_json::to_string(d).unwrap())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Print missing sonames with the number of packages and files missing them, most packages first
+fn print_soname_summary(summary: &soname_summary::SonameSummary) {
+    let groups = summary.groups();
+    println!("{}", i18n::tr("missing_sonames", "Missing libraries:", &[]));
+    for (soname, package_count, file_count) in groups.iter().take(SONAME_DISPLAY_COUNT) {
+        println!(
+            "  {} missing — affects {} / {}",
+            soname,
+            render::plural(*package_count, "package", "packages"),
+            render::plural(*file_count, "file", "files")
+        );
+    }
+    if groups.len() > SONAME_DISPLAY_COUNT {
+        println!(
+            "  and {} more",
+            render::plural(groups.len() - SONAME_DISPLAY_COUNT, "soname", "sonames")
+        );
+    }
+}
+
+/// Print packages of the sync repositories providing missing libraries, from the files databases
+fn print_providers(summary: &soname_summary::SonameSummary, quiet: bool) {
+    let sonames: Vec<&str> = summary
+        .groups()
+        .iter()
+        .take(SONAME_DISPLAY_COUNT)
+        .map(|(s, _, _)| *s)
+        .collect();
+    if sonames.is_empty() {
+        return;
+    }
+    let providers = match providers::FilesDb::get() {
+        Some(files_db) => match files_db.providers(&sonames) {
+            Ok(p) => p,
+            Err(err) => {
+                eprintln!(
+                    "Failed to look up packages providing missing libraries: {}",
+                    err
+                );
+                return;
+            }
+        },
+        None => {
+            if !quiet {
+                eprintln!(
+                    "Download the files databases with 'pacman -Fy', or install pkgfile, to look \
+                     up packages providing missing libraries"
+                );
+            }
+            return;
+        }
+    };
+    println!(
+        "{}",
+        i18n::tr(
+            "providers",
+            "Repository packages providing missing libraries:",
+            &[]
+        )
+    );
+    for soname in sonames {
+        match providers.get(soname) {
+            Some(packages) if !packages.is_empty() => println!(
+                "  {}: {}",
+                soname,
+                packages.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            _ => println!(
+                "  {}: {}",
+                soname,
+                i18n::tr(
+                    "no_provider",
+                    "no repository package provides it, packages missing it have to be rebuilt",
+                    &[]
+                )
+            ),
+        }
+    }
+}
+
+/// Print packages whose files took the longest to analyze, to find those worth ignoring
+fn print_slowest_packages(status: &status::Status) {
+    let slowest = status.slowest_packages(SLOWEST_PACKAGE_DISPLAY_COUNT);
+    if slowest.is_empty() {
+        return;
+    }
+    eprintln!("Slowest packages to analyze (time summed over worker threads):");
+    for (package, duration) in slowest {
+        eprintln!("  {:.1}s {}", duration.as_secs_f32(), package);
+    }
+}
+
+/// Print installed size of packages, largest first
+fn print_disk_usage(packages: &BTreeSet<String>, root: &Path) {
+    let local_packages =
+        match pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR))) {
+            Ok(p) => p,
+            Err(err) => {
+                eprintln!("Failed to read package sizes: {}", err);
+                return;
+            }
+        };
+    let mut sizes: Vec<(&str, u64)> = local_packages
+        .iter()
+        .filter(|p| packages.contains(&p.name))
+        .map(|p| (p.name.as_str(), p.installed_size))
+        .collect();
+    if sizes.is_empty() {
+        return;
+    }
+    sizes.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let total: u64 = sizes.iter().map(|(_p, s)| s).sum();
+    println!(
+        "{}",
+        i18n::tr(
+            "disk_usage",
+            "Installed size of packages with findings ({total} in total):",
+            &[("total", HumanBytes(total).to_string())]
+        )
+    );
+    for (package, size) in sizes.iter().take(DISK_USAGE_DISPLAY_COUNT) {
+        println!("  {}: {}", package, HumanBytes(*size));
+    }
+    if sizes.len() > DISK_USAGE_DISPLAY_COUNT {
+        println!(
+            "  and {} more",
+            render::plural(
+                sizes.len() - DISK_USAGE_DISPLAY_COUNT,
+                "package",
+                "packages"
+            )
+        );
+    }
+}
+
+/// Print foreign packages to rebuild in rebuild order, one per line
+fn print_rebuild_list(
+    packages: &BTreeSet<String>,
+    system: &dyn system::SystemProvider,
+    root: &Path,
+) {
+    let foreign_packages: HashSet<String> = match system.foreign_packages() {
+        Ok(p) => p.into_iter().collect(),
+        Err(err) => {
+            eprintln!("Failed to list foreign packages: {}", err);
+            return;
+        }
+    };
+    let packages: BTreeSet<String> = packages
+        .iter()
+        .filter(|p| foreign_packages.contains(*p))
+        .cloned()
+        .collect();
+    let local_packages = pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR)))
+        .unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to read package dependencies, rebuild order may be wrong: {}",
+                err
+            );
+            Vec::new()
+        });
+    for package in rebuild::rebuild_order(&packages, &local_packages) {
+        println!("{}", package);
+    }
+}
+
+/// Write script rebuilding packages in dependency order, from their PKGBUILD checkouts
+fn write_rebuild_script(
+    filepath: &Path,
+    packages: &BTreeSet<String>,
+    checkouts: &BTreeMap<String, PathBuf>,
+    root: &Path,
+) {
+    let local_packages = pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR)))
+        .unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to read package dependencies, rebuild order may be wrong: {}",
+                err
+            );
+            Vec::new()
+        });
+    let order = rebuild::rebuild_order(packages, &local_packages);
+    match rebuild::write_script(filepath, &rebuild::script(&order, checkouts)) {
+        Ok(()) => eprintln!(
+            "{}",
+            i18n::tr(
+                "rebuild_script_written",
+                "Rebuild script for {packages} written to {file}",
+                &[
+                    (
+                        "packages",
+                        render::plural(order.len(), "package", "packages")
+                    ),
+                    ("file", format!("{:?}", filepath)),
+                ]
+            )
+        ),
+        Err(err) => eprintln!(
             "{}",
-            Yellow.paint(format!(
-                "File '{}' from package '{}' is missing dependency '{}'",
-                file, package, missing_dep
+            Red.paint(format!(
+                "Failed to write rebuild script {:?}: {}",
+                filepath, err
             ))
-        );
+        ),
     }
+}
 
-    if let Ok(broken_python_packages) = python_broken_packages_rx.recv() {
-        for (broken_python_package, dir) in broken_python_packages {
-            println!(
+fn main() {
+    // Parse command line
+    let cl_opts = cl::CommandLineOpts::parse();
+
+    // Init logger
+    logger::init(cl_opts.log_format, cl_opts.log_level.clone()).unwrap();
+
+    match cl_opts.command {
+        Some(cl::Command::Completions { shell }) => {
+            let mut cmd = cl::CommandLineOpts::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+            return;
+        }
+        Some(cl::Command::ElevatedHelper { ref files }) => {
+            if let Err(err) = elevate::run_helper(files) {
+                eprintln!("{}", Red.paint(format!("Failed to analyze files: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::UnprivilegedHelper) => {
+            let helper_system = unprivileged::HelperSystem {
+                root: cl_opts.root.clone(),
+                soname_index: cl_opts.import_soname_index.clone(),
+                ldd: cl_opts.ldd,
+            };
+            if let Err(err) = unprivileged::run_helper(&helper_system) {
+                eprintln!("{}", Red.paint(format!("Failed to analyze files: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::GenMan) => {
+            if let Err(err) = man::write_man_page(&mut io::stdout()) {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to write man page: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::History) => {
+            if let Err(err) = print_history() {
+                eprintln!("{}", Red.paint(format!("Failed to read history: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::Doctor) => {
+            let mut ok = true;
+            for diagnostic in doctor::diagnose(cl_opts.config.as_deref()) {
+                match diagnostic.result {
+                    Ok(details) => println!(
+                        "{}",
+                        Green.paint(format!("✓ {}: {}", diagnostic.name, details))
+                    ),
+                    Err(problem) => {
+                        ok = false;
+                        println!(
+                            "{}\n  fix: {}",
+                            Red.paint(format!("✗ {}: {}", diagnostic.name, problem.error)),
+                            problem.fix
+                        );
+                    }
+                }
+            }
+            if !ok {
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::Query { socket, query }) => {
+            let res = query
+                .join(" ")
+                .parse::<daemon::Request>()
+                .map_err(|e| e.into())
+                .and_then(|request| {
+                    let socket = socket
+                        .clone()
+                        .or_else(daemon::socket_filepath)
+                        .ok_or_else(|| SimpleError::new("No runtime directory"))?;
+                    daemon::query(&socket, &request)
+                });
+            if let Err(err) = res {
+                eprintln!("{}", Red.paint(format!("Query failed: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::WhoNeeds { .. })
+        | Some(cl::Command::SimulateRemoval { .. })
+        | Some(cl::Command::Analyze { .. })
+        | Some(cl::Command::MissingSonames { .. })
+        | Some(cl::Command::Daemon { .. })
+        | None => {}
+    }
+
+    if cl_opts.print_schema {
+        print!("{}", report::REPORT_SCHEMA);
+        return;
+    }
+
+    // Load config
+    let mut config = match config::Config::load(cl_opts.config.as_deref()) {
+        Ok(c) => c,
+        Err(err) => {
+            eprintln!(
+                "{}",
+                Red.paint(format!("Failed to load configuration: {}", err))
+            );
+            process::exit(EXIT_CODE_ERROR);
+        }
+    };
+    config.merge(&cl_opts);
+    debug!("{:?}", config);
+
+    if cl_opts.print_config {
+        match toml::to_string_pretty(&config) {
+            Ok(s) => print!("{}", s),
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to serialize configuration: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+        return;
+    }
+
+    if let Some(explain_filepath) = &cl_opts.explain {
+        if let Err(err) = explain::explain_file(explain_filepath, file_parser(&config).as_ref()) {
+            eprintln!("{}", Red.paint(format!("Failed to explain file: {}", err)));
+            process::exit(EXIT_CODE_ERROR);
+        }
+        return;
+    }
+
+    if let Some(package) = &cl_opts.explain_package {
+        if let Err(err) = explain::explain_package(package) {
+            eprintln!(
+                "{}",
+                Red.paint(format!("Failed to explain package: {}", err))
+            );
+            process::exit(EXIT_CODE_ERROR);
+        }
+        return;
+    }
+
+    if let Some(filepath) = &cl_opts.export_soname_index {
+        let root = config.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+        let dirs = elf::Resolver::with_root(&root).system_dirs();
+        let res = soname_index::SonameIndex::build(&root, &dirs).save(filepath);
+        if let Err(err) = res {
+            eprintln!(
+                "{}",
+                Red.paint(format!("Failed to export soname index: {}", err))
+            );
+            process::exit(EXIT_CODE_ERROR);
+        }
+        return;
+    }
+
+    if let Some(cl::Command::Analyze { filelist }) = &cl_opts.command {
+        let root = config.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+        match archive::analyze(
+            std::slice::from_ref(filelist),
+            &root,
+            file_parser(&config).as_ref(),
+        ) {
+            Ok(findings) => {
+                let renderer = render::Renderer::new(cl_opts.glyphs, config.severity.clone());
+                let findings: Vec<_> = findings
+                    .iter()
+                    .filter(|f| is_reported(f, &config))
+                    .collect();
+                for finding in &findings {
+                    if cl_opts.json {
+                        println!("{}", serde_json::to_string(finding).unwrap());
+                    } else {
+                        println!("{}", renderer.finding(finding));
+                    }
+                }
+                if !findings.is_empty() {
+                    process::exit(EXIT_CODE_FINDINGS);
+                }
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to analyze package archive: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+        return;
+    }
+
+    if let Some(cl::Command::MissingSonames { archives }) = &cl_opts.command {
+        let root = config.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+        match archive::missing_sonames(archives, &root, file_parser(&config).as_ref()) {
+            Ok(sonames) => {
+                if cl_opts.json {
+                    println!("{}", serde_json::to_string(&sonames).unwrap());
+                } else {
+                    for soname in &sonames {
+                        println!("{}", soname);
+                    }
+                }
+                if !sonames.is_empty() {
+                    process::exit(EXIT_CODE_FINDINGS);
+                }
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to analyze package archives: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+        return;
+    }
+
+    if cl_opts.predict_upgrade {
+        let root = config.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+        match upgrade::predict_breakage(&root, &config, file_parser(&config).as_ref()) {
+            Ok(findings) => {
+                let renderer = render::Renderer::new(cl_opts.glyphs, config.severity.clone());
+                for finding in findings.iter().filter(|f| is_reported(f, &config)) {
+                    if cl_opts.json {
+                        println!("{}", serde_json::to_string(finding).unwrap());
+                    } else {
+                        println!("{}", renderer.finding(finding));
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to predict upgrade breakage: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+        return;
+    }
+
+    // Targets of the transaction are on standard input if the hook has NeedsTargets
+    let hook_targets: Vec<String> =
+        if cl_opts.hook && config.root.is_none() && !io::stdin().is_terminal() {
+            io::stdin()
+                .lines()
+                .map_while(Result::ok)
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+    if cl_opts.hook && config.root.is_none() {
+        if let Some(defer) = config.defer {
+            match defer::record_targets(&hook_targets).and_then(|_| defer::schedule(defer)) {
+                Ok(()) => {
+                    if !config.quiet {
+                        eprintln!(
+                            "Analysis deferred ({}), see the journal of {}",
+                            match defer {
+                                defer::Defer::Minutes(m) => format!("in {} min", m),
+                                defer::Defer::Idle => "when the system is idle".to_string(),
+                            },
+                            defer::UNIT_NAME
+                        );
+                    }
+                    return;
+                }
+                Err(err) => eprintln!(
+                    "{}",
+                    Yellow.paint(format!("Failed to defer analysis, analyzing now: {}", err))
+                ),
+            }
+        }
+    }
+
+    // Other checks read files of the running system
+    let mut checks = match checks::enabled_checks(&config) {
+        Ok(_) if config.root.is_some() => Vec::new(),
+        Ok(c) => c,
+        Err(err) => {
+            eprintln!("{}", Red.paint(format!("Invalid configuration: {}", err)));
+            process::exit(EXIT_CODE_ERROR);
+        }
+    };
+    // Checks whose programs are absent can not run
+    checks.retain(|check| match checks::missing_tool(*check) {
+        Some(tool) => {
+            if !config.quiet {
+                eprintln!(
+                    "Check '{}' disabled: {} is not installed (from package {})",
+                    check.name(),
+                    tool.program,
+                    tool.package
+                );
+            }
+            false
+        }
+        None => true,
+    });
+
+    // Cancel on SIGINT/SIGTERM, and exit immediately if the signal is received a second time
+    let cancelled = Arc::new(AtomicBool::new(false));
+    for signal in &[SIGINT, SIGTERM] {
+        signal_hook::flag::register_conditional_shutdown(
+            *signal,
+            EXIT_CODE_INTERRUPTED,
+            Arc::clone(&cancelled),
+        )
+        .unwrap();
+        signal_hook::flag::register(*signal, Arc::clone(&cancelled)).unwrap();
+    }
+
+    if let Some(dir) = &cl_opts.watch {
+        let renderer = render::Renderer::new(cl_opts.glyphs, config.severity.clone());
+        if let Err(err) = watch::watch(
+            dir,
+            &renderer,
+            cl_opts.json,
+            &cancelled,
+            file_parser(&config).as_ref(),
+        ) {
+            eprintln!(
+                "{}",
+                Red.paint(format!("Failed to watch {:?}: {}", dir, err))
+            );
+            process::exit(EXIT_CODE_ERROR);
+        }
+        return;
+    }
+
+    // Transactions whose analysis was deferred, an analysis of the running system covers them.
+    // They are taken before waiting for the system to be idle, transactions happening meanwhile
+    // schedule another deferred analysis.
+    let deferred_targets =
+        if config.root.is_none() && cl_opts.command.is_none() && !cl_opts.check_removal {
+            defer::take_targets()
+        } else {
+            Vec::new()
+        };
+    if cl_opts.wait_idle {
+        match defer::wait_idle(&cancelled) {
+            Ok(true) => {}
+            Ok(false) => process::exit(EXIT_CODE_INTERRUPTED),
+            Err(err) => eprintln!("Failed to wait for the system to be idle: {}", err),
+        }
+    }
+    if !deferred_targets.is_empty() && !config.quiet {
+        eprintln!(
+            "Analyzing after the deferred transactions of {}",
+            render::plural(deferred_targets.len(), "package", "packages")
+        );
+    }
+    let deferred_count = deferred_targets.len();
+    let mut transaction_targets: Vec<String> =
+        deferred_targets.into_iter().chain(hook_targets).collect();
+    transaction_targets.sort_unstable();
+    transaction_targets.dedup();
+    let transaction = (!transaction_targets.is_empty()).then(|| report::Transaction {
+        time: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        targets: transaction_targets.clone(),
+    });
+
+    // Soname index exported by a previous run
+    let root = config.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+    let soname_index = config.import_soname_index.as_ref().and_then(|filepath| {
+        match soname_index::SonameIndex::load(filepath, &root) {
+            Ok(i) => Some(Arc::new(i)),
+            // Exported by another version of the tool or damaged, the analysis works without it
+            Err(err @ format::FormatError::Corrupted)
+            | Err(err @ format::FormatError::UnsupportedVersion(_)) => {
+                eprintln!(
+                    "{}",
+                    Yellow.paint(format!(
+                        "Ignoring soname index {:?}: {}, export it again",
+                        filepath, err
+                    ))
+                );
+                None
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!(
+                        "Failed to import soname index {:?}: {}",
+                        filepath, err
+                    ))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+    });
+
+    // System to analyze
+    let mut helper_system = unprivileged::HelperSystem {
+        ldd: config.ldd,
+        ..Default::default()
+    };
+    let system: Box<dyn system::SystemProvider> = if config.root.is_none()
+        && soname_index.is_none()
+        && is_db_unlocked(
+            Path::new(pacman::DB_LOCK_FILEPATH),
+            db_lock_timeout(&cl_opts, &config),
+        ) {
+        Box::new(system::RealSystem { ldd: config.ldd })
+    } else {
+        if config.root.is_none() && soname_index.is_none() && !config.quiet {
+            // Another program is using the database, read its current state directly
+            eprintln!(
                 "{}",
                 Yellow.paint(format!(
-                    "Package '{}' has files in directory '{}' that are ignored by the current Python interpreter",
-                    broken_python_package, dir
+                    "Pacman database is locked ({} exists), reading it directly",
+                    pacman::DB_LOCK_FILEPATH
                 ))
             );
         }
+        // Read databases directly, and resolve dependencies with the soname index if any
+        helper_system.root = Some(root.clone());
+        if soname_index.is_some() {
+            helper_system
+                .soname_index
+                .clone_from(&config.import_soname_index);
+        }
+        match system::RootSystem::new(&root, soname_index, config.ldd) {
+            Ok(s) => Box::new(s),
+            Err(err) => {
+                eprintln!("{}", Red.paint(format!("Failed to read system: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+    };
+    // When running as root, package files are parsed by helpers running as an unprivileged user,
+    // never as root
+    let system = unprivileged_system(system, helper_system, &config);
+
+    match &cl_opts.command {
+        Some(cl::Command::WhoNeeds { soname }) => {
+            match needs_index(system.as_ref(), &config) {
+                Ok(index) => {
+                    print_dependents(
+                        &index,
+                        &[soname.to_string()],
+                        &|_| true,
+                        &format!("need {}", soname),
+                        cl_opts.json,
+                    );
+                }
+                Err(err) => {
+                    eprintln!(
+                        "{}",
+                        Red.paint(format!("Failed to index dependencies: {}", err))
+                    );
+                    process::exit(EXIT_CODE_ERROR);
+                }
+            }
+            return;
+        }
+        Some(cl::Command::Daemon { socket, dbus }) => {
+            let report_filepath = cl_opts.report.clone().or_else(default_report_filepath);
+            let res = socket
+                .clone()
+                .or_else(daemon::socket_filepath)
+                .ok_or_else(|| SimpleError::new("No runtime directory").into())
+                .and_then(|socket| {
+                    daemon::run(
+                        &socket,
+                        report_filepath.as_deref(),
+                        *dbus,
+                        system.as_ref(),
+                        &config,
+                        &cancelled,
+                    )
+                });
+            if let Err(err) = res {
+                eprintln!("{}", Red.paint(format!("Daemon failed: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        Some(cl::Command::SimulateRemoval { target }) => {
+            if let Err(err) = simulate_removal(system.as_ref(), target, &config, cl_opts.json) {
+                eprintln!(
+                    "{}",
+                    Red.paint(format!("Failed to simulate removal: {}", err))
+                );
+                process::exit(EXIT_CODE_ERROR);
+            }
+            return;
+        }
+        _ => {}
     }
 
-    for broken_sd_service_link in broken_sd_service_links {
-        println!(
+    if cl_opts.check_removal {
+        match check_removal(system.as_ref(), &config, cl_opts.json) {
+            Ok(count) if (count > 0) && config.block_removals => {
+                eprintln!(
+                    "{}",
+                    Red.paint("Aborting transaction, rebuild or remove these packages first")
+                );
+                process::exit(EXIT_CODE_REMOVAL_BLOCKED);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}", Red.paint(format!("Failed to check removal: {}", err)));
+                process::exit(EXIT_CODE_ERROR);
+            }
+        }
+        return;
+    }
+
+    // Dump status on SIGUSR1
+    let status = Arc::new(status::Status::default());
+    if let Err(err) = status::Status::dump_on_signal(&status) {
+        eprintln!("Failed to setup SIGUSR1 handler: {}", err);
+    }
+
+    // Python broken packages channel, the Python check only runs on the running system
+    let (python_broken_packages_tx, python_broken_packages_rx) = crossbeam::unbounded();
+    if config.root.is_some() {
+        let _ = python_broken_packages_tx.send(Vec::new());
+    } else {
+        let status = Arc::clone(&status);
+        let job_count = config.jobs();
+        thread::Builder::new()
+            .spawn(move || {
+                let to_send = match python::get_python_version() {
+                    Ok(current_python_version) => {
+                        debug!("Python version: {}", current_python_version);
+                        let broken_python_packages = python::get_broken_python_packages(
+                            &current_python_version,
+                            job_count,
+                            status.walk_progress(),
+                        );
+                        match broken_python_packages {
+                            Ok(broken_python_packages) => broken_python_packages,
+                            Err(err) => {
+                                eprintln!("Failed to list Python packages: {}", err);
+                                Vec::<python::StaleDir>::new()
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to get Python version: {}", err);
+                        Vec::<python::StaleDir>::new()
+                    }
+                };
+                // Receiver may be gone if we were cancelled
+                let _ = python_broken_packages_tx.send(to_send);
+            })
+            .unwrap();
+    }
+
+    // Perl broken packages channel, likewise
+    let (perl_broken_packages_tx, perl_broken_packages_rx) = crossbeam::unbounded();
+    if config.root.is_some() {
+        let _ = perl_broken_packages_tx.send(Vec::new());
+    } else {
+        let status = Arc::clone(&status);
+        let job_count = config.jobs();
+        thread::Builder::new()
+            .spawn(move || {
+                let to_send = match perl::get_perl_version() {
+                    Ok(current_perl_version) => {
+                        debug!("Perl version: {}", current_perl_version);
+                        let broken_perl_packages = perl::get_broken_perl_packages(
+                            &current_perl_version,
+                            job_count,
+                            status.walk_progress(),
+                        );
+                        match broken_perl_packages {
+                            Ok(broken_perl_packages) => broken_perl_packages,
+                            Err(err) => {
+                                eprintln!("Failed to list Perl packages: {}", err);
+                                Vec::<python::StaleDir>::new()
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to get Perl version: {}", err);
+                        Vec::<python::StaleDir>::new()
+                    }
+                };
+                // Receiver may be gone if we were cancelled
+                let _ = perl_broken_packages_tx.send(to_send);
+            })
+            .unwrap();
+    }
+
+    // Findings aggregator
+    let report_filepath = cl_opts.report.clone().or_else(default_report_filepath);
+    let mut aggregator = report::Aggregator::new(
+        report_filepath.as_deref(),
+        cl_opts.max_memory,
+        config.ignore.clone(),
+        config.filter.clone(),
+        config.severity.clone(),
+        config.min_severity,
+        transaction,
+    );
+
+    if config.root.is_none() && !paths::is_root() && !config.elevate && !config.quiet {
+        eprintln!(
             "{}",
-            Yellow.paint(format!(
-                "Systemd enabled service has broken link in '{}'",
-                &broken_sd_service_link,
+            Yellow.paint(
+                "Not running as root, files the current user can not read will not be inspected"
+            )
+        );
+    }
+
+    // Checkpoint of long analyses of all packages, invalidated by any package change
+    let checkpoint = match paths::state_dir() {
+        Some(state_dir) if config.all_packages && config.root.is_none() => {
+            let db_change_time = fs::metadata(pacman::LOCAL_DB_DIR)
+                .and_then(|m| m.modified())
+                .unwrap_or_else(|_| SystemTime::now());
+            Some(checkpoint::Checkpoint::load(
+                &state_dir.join("checkpoint.json"),
+                db_change_time,
             ))
+        }
+        _ => None,
+    };
+
+    let res = analyze(
+        &config,
+        &mut aggregator,
+        python_broken_packages_rx,
+        perl_broken_packages_rx,
+        &checks,
+        system.as_ref(),
+        &status,
+        checkpoint.as_ref(),
+        &cancelled,
+    );
+
+    if let Some(checkpoint) = &checkpoint {
+        if res.is_ok() && !cancelled.load(Ordering::SeqCst) {
+            checkpoint.remove();
+        } else if let Err(err) = checkpoint.write() {
+            eprintln!("Failed to write checkpoint file: {}", err);
+        }
+    }
+
+    // PKGBUILD checkouts, to map findings back to them
+    let checkouts = match &config.pkgbuild_dir {
+        Some(pkgbuild_dir) => sources::checkouts(pkgbuild_dir),
+        None => config.package_source.checkouts(),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Failed to get PKGBUILD checkouts: {}", err);
+        BTreeMap::new()
+    });
+
+    // Flush and display findings, even partial ones
+    let report = aggregator.finish();
+    let renderer = render::Renderer::new(cl_opts.glyphs, config.severity.clone());
+    let mut unreadable_count = 0;
+    let mut rebuild_packages = BTreeSet::new();
+    // Subjects of findings, that are package names unless they are paths
+    let mut finding_subjects = BTreeSet::new();
+    let mut finding_keys = BTreeSet::new();
+    let mut missing_sonames = BTreeSet::new();
+    let mut stats = stats::Stats::default();
+    let mut soname_summary = soname_summary::SonameSummary::default();
+    // Machine readable output, without human readable summaries
+    let machine_output = cl_opts.json
+        || cl_opts.stats_json
+        || cl_opts.json_by_check
+        || cl_opts.list_rebuilds.is_some();
+    let mut listed_packages = BTreeSet::new();
+    let mut check_sections: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
+    // Categories with findings failing the run
+    let mut exit_bitmask = 0;
+    // Subjects whose findings are displayed, and the others, in hook mode
+    let mut displayed_subjects = HashSet::new();
+    let mut hidden_subjects = HashSet::new();
+    let res_display = report.for_each(|f| {
+        finding_keys.insert(history::finding_key(f));
+        if let report::Finding::MissingDependency {
+            package,
+            file,
+            dependency,
+            held_back: None,
+            transitive: None,
+            ..
+        } = f
+        {
+            missing_sonames.insert(history::MissingSoname {
+                package: package.clone(),
+                file: file.clone(),
+                soname: dependency.clone(),
+            });
+        }
+        soname_summary.add(f);
+        if config.disk_usage {
+            finding_subjects.insert(f.subject().to_string());
+        }
+        if let report::Finding::UnreadableFile { .. } = f {
+            unreadable_count += 1;
+        }
+        if config
+            .fail_on
+            .is_none_or(|s| config.severity.severity(f) >= s)
+        {
+            exit_bitmask |= category::category(f).bit;
+        }
+        if let Some(package) = f.rebuild_package() {
+            rebuild_packages.insert(package.to_string());
+        }
+        if let Some(list) = cl_opts.list_rebuilds {
+            if let Some(package) = list.package(f) {
+                listed_packages.insert(package.to_string());
+            }
+        } else if cl_opts.stats_json {
+            stats.add(f);
+        } else if cl_opts.json_by_check {
+            check_sections
+                .entry(f.check())
+                .or_default()
+                .push(serde_json::to_value(f).unwrap());
+        } else if cl_opts.json {
+            println!("{}", serde_json::to_string(f).unwrap());
+        } else if !cl_opts.hook
+            || displayed_subjects.contains(f.subject())
+            || (displayed_subjects.len() < config.hook_max_packages())
+        {
+            displayed_subjects.insert(f.subject().to_string());
+            println!("{}", renderer.finding(f));
+        } else {
+            hidden_subjects.insert(f.subject().to_string());
+        }
+    });
+    if !hidden_subjects.is_empty() {
+        let more = render::plural(hidden_subjects.len(), "more package", "more packages");
+        eprintln!(
+            "{}",
+            match &report_filepath {
+                Some(filepath) => i18n::tr(
+                    "more_packages",
+                    "… and {more}, see report file {report}",
+                    &[("more", more), ("report", format!("{:?}", filepath))]
+                ),
+                None => i18n::tr("more_packages_no_report", "… and {more}", &[("more", more)]),
+            }
         );
     }
+    if let Err(err) = &res_display {
+        eprintln!("Failed to read back findings from report file: {}", err);
+    }
+    if cl_opts.stats_json {
+        println!("{}", serde_json::to_string(&stats).unwrap());
+    }
+    if cl_opts.json_by_check {
+        println!("{}", serde_json::to_string(&check_sections).unwrap());
+    }
+    if cl_opts.list_rebuilds.is_some() {
+        print_rebuild_list(&listed_packages, system.as_ref(), &root);
+    }
+    if !machine_output && soname_summary.is_relevant() {
+        print_soname_summary(&soname_summary);
+    }
+    if !machine_output && config.root.is_none() {
+        print_providers(&soname_summary, config.quiet);
+    }
+    if !machine_output && !checkouts.is_empty() {
+        let mut broken_checkouts = BTreeMap::new();
+        let _ = report.for_each(|f| {
+            if let Some((package, dir)) = checkouts.get_key_value(f.subject()) {
+                broken_checkouts.insert(package.clone(), dir.clone());
+            }
+        });
+        if !broken_checkouts.is_empty() {
+            println!(
+                "{}",
+                i18n::tr(
+                    "checkouts",
+                    "PKGBUILD checkouts of packages with findings:",
+                    &[]
+                )
+            );
+            for (package, dir) in broken_checkouts {
+                println!("  {}: {}", package, dir.display());
+            }
+        }
+    }
+    if config.disk_usage && !machine_output {
+        print_disk_usage(&finding_subjects, &root);
+    }
+    if config.verbose {
+        print_slowest_packages(&status);
+    }
+    if let Some(rebuild_script) = &config.rebuild_script {
+        write_rebuild_script(rebuild_script, &rebuild_packages, &checkouts, &root);
+    }
+    if unreadable_count > 0 {
+        let files = render::plural(unreadable_count, "file", "files");
+        let message = if paths::is_root() {
+            // Files are read by the helpers, running as the analysis user
+            i18n::tr(
+                "unreadable_files_analysis_user",
+                "{files} could not be inspected by the analysis user {user} due to missing \
+                 permissions, set another user with --analysis-user for a complete report",
+                &[
+                    ("files", files),
+                    ("user", analysis_user_name(&config).to_string()),
+                ],
+            )
+        } else {
+            i18n::tr(
+                "unreadable_files",
+                "{files} could not be inspected due to missing permissions, run as root for a \
+                 complete report",
+                &[("files", files)],
+            )
+        };
+        eprintln!("{}", Yellow.paint(message));
+    }
+
+    if res.is_ok()
+        && res_display.is_ok()
+        && !cancelled.load(Ordering::SeqCst)
+        && config.root.is_none()
+    {
+        if !transaction_targets.is_empty() {
+            update_needs_index(system.as_ref(), &transaction_targets, config.jobs());
+        }
+        if deferred_count > 0 {
+            defer::forget_taken_targets();
+        }
+        // Missing libraries of runs whose findings were filtered are incomplete, they would break
+        // streaks
+        let is_filtered = config.filter.is_some()
+            || !config.disable_checks.is_empty()
+            || config.max_package_age.is_some()
+            || !config.ignore.is_empty()
+            || config.min_severity.is_some();
+        let history = record_history(
+            finding_keys,
+            Some(missing_sonames).filter(|_| !is_filtered),
+            transaction_targets,
+        );
+        if let Some(history) = history.filter(|_| !machine_output && !config.quiet) {
+            print_ignore_suggestions(&history);
+        }
+    }
+
+    if let Err(err) = res {
+        eprintln!("{}", Red.paint(format!("Analysis failed: {}", err)));
+        process::exit(EXIT_CODE_ERROR);
+    } else if cancelled.load(Ordering::SeqCst) {
+        eprintln!(
+            "{}",
+            Red.paint(i18n::tr(
+                "interrupted",
+                "Analysis interrupted, findings are incomplete",
+                &[]
+            ))
+        );
+        process::exit(EXIT_CODE_INTERRUPTED);
+    } else if cl_opts.exit_bitmask {
+        process::exit(exit_bitmask);
+    } else if config
+        .fail_on
+        .is_some_and(|s| report.max_severity().is_some_and(|m| m >= s))
+    {
+        process::exit(EXIT_CODE_FINDINGS);
+    }
 }
 
 #[cfg(test)]
@@ -470,12 +2359,80 @@ mod tests {
     use std::env;
     use std::fs::{File, Permissions};
     use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
     use std::path::PathBuf;
 
     use tempdir::TempDir;
 
     use super::*;
 
+    #[test]
+    fn test_db_lock_timeout() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let lock_filepath = tmp_dir.path().join("db.lck");
+        File::create(&lock_filepath).unwrap();
+        let config = config::Config::default();
+
+        // Hooks run while pacman holds the lock, and read the database with pacman anyway
+        for args in &[&["--hook"][..], &["--check-removal"][..]] {
+            let cl_opts = cl::CommandLineOpts::parse_from(
+                std::iter::once("check-broken-packages").chain(args.iter().copied()),
+            );
+            assert_eq!(db_lock_timeout(&cl_opts, &config), None);
+            assert!(is_db_unlocked(
+                &lock_filepath,
+                db_lock_timeout(&cl_opts, &config)
+            ));
+        }
+
+        let cl_opts = cl::CommandLineOpts::parse_from(["check-broken-packages"]);
+        assert_eq!(
+            db_lock_timeout(&cl_opts, &config),
+            Some(Duration::from_secs(0))
+        );
+        assert!(!is_db_unlocked(
+            &lock_filepath,
+            db_lock_timeout(&cl_opts, &config)
+        ));
+    }
+
+    #[test]
+    fn test_removal_candidates() {
+        let local_package =
+            |name: &str, provides: &[&str], depends: &[&str]| pacman::LocalPackage {
+                name: name.to_string(),
+                version: "1-1".to_string(),
+                arch: String::new(),
+                provides: provides.iter().map(|p| p.to_string()).collect(),
+                depends: depends.iter().map(|d| d.to_string()).collect(),
+                groups: Vec::new(),
+                installed_size: 0,
+                install_date: 0,
+                files: Vec::new(),
+            };
+        let local_packages = [
+            local_package("libfoo", &["libfoo.so=1-64"], &[]),
+            local_package("app", &[], &["libfoo.so>=1"]),
+            local_package("bar", &[], &["libfoo"]),
+            local_package("baz", &[], &["glibc"]),
+            local_package("repo-app", &[], &["libfoo"]),
+            local_package("gone", &[], &["libfoo"]),
+        ];
+        let foreign_packages: HashSet<String> = ["app", "bar", "baz", "gone"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let removed: HashSet<String> = ["libfoo", "gone"].iter().map(|p| p.to_string()).collect();
+        let names = |all| {
+            removal_candidates(&local_packages, &foreign_packages, &removed, all)
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(false), ["app", "bar"]);
+        assert_eq!(names(true), ["app", "bar", "baz"]);
+    }
+
     fn update_path(dir: &str) -> std::ffi::OsString {
         let path_orig = env::var_os("PATH").unwrap();
 
@@ -528,7 +2485,7 @@ mod tests {
 
         let path_orig = update_path(tmp_dir.path().to_str().unwrap());
 
-        let missing_deps = get_missing_dependencies("dummy");
+        let missing_deps = get_missing_dependencies("dummy", sandbox::Sandbox::None);
         assert!(missing_deps.is_ok());
         assert_eq!(
             missing_deps.unwrap(),
@@ -547,4 +2504,111 @@ mod tests {
 
         env::set_var("PATH", &path_orig);
     }
+
+    #[test]
+    fn test_get_package_executable_files() {
+        let system = system::mock::MockSystem::default()
+            .package(
+                "foo",
+                true,
+                &[
+                    "/usr/bin/foo",
+                    "/usr/share/foo/logo.png",
+                    "/usr/share/foo/README",
+                    "/usr/share/foo/private",
+                    "/usr/arm-none-eabi/bin/ld",
+                ],
+            )
+            .file("/usr/bin/foo", 0o755, b"\x7fELF\x02\x01", &["libbar.so.1"])
+            .file("/usr/share/foo/logo.png", 0o755, b"\x89PNG", &[])
+            .file("/usr/share/foo/README", 0o644, b"foo", &[])
+            .unreadable_file("/usr/share/foo/private")
+            .file("/usr/arm-none-eabi/bin/ld", 0o755, b"\x7fELF\x01\x01", &[]);
+        let mut config = config::Config::default();
+        assert_eq!(
+            get_package_executable_files(&system, "foo", &config).unwrap(),
+            [
+                "/usr/bin/foo",
+                "/usr/share/foo/logo.png",
+                "/usr/share/foo/private"
+            ]
+        );
+        config.skip_data_files = true;
+        assert_eq!(
+            get_package_executable_files(&system, "foo", &config).unwrap(),
+            ["/usr/bin/foo", "/usr/share/foo/private"]
+        );
+        config.analyze_cross_toolchains = true;
+        assert_eq!(
+            get_package_executable_files(&system, "foo", &config).unwrap(),
+            [
+                "/usr/bin/foo",
+                "/usr/share/foo/private",
+                "/usr/arm-none-eabi/bin/ld"
+            ]
+        );
+        assert!(get_package_executable_files(&system, "bar", &config).is_err());
+    }
+
+    #[test]
+    fn test_is_data_file() {
+        let tmp_dir = TempDir::new("").unwrap();
+
+        for (filename, content, is_data) in &[
+            ("image.png", &b"\x7fELF"[..], true),
+            ("elf", &b"\x7fELF\x02\x01"[..], false),
+            ("script", &b"#!/bin/sh\n"[..], false),
+            ("text", &b"hello world"[..], true),
+            ("tiny", &b"ab"[..], true),
+        ] {
+            let filepath = tmp_dir.path().join(filename);
+            fs::write(&filepath, content).unwrap();
+            assert_eq!(
+                is_data_file(&system::RealSystem::default(), &filepath),
+                *is_data,
+                "{}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn test_analyze_file() {
+        let system = system::mock::MockSystem::default()
+            .package("zoom-bin", true, &["/opt/zoom/zoom"])
+            .package("zoom", true, &["/usr/bin/zoom"])
+            .file(
+                "/opt/zoom/zoom",
+                0o755,
+                b"\x7fELF\x02\x01",
+                &["libicuuc.so.72", "libfoo.so.1"],
+            )
+            .file(
+                "/usr/bin/zoom",
+                0o755,
+                b"\x7fELF\x02\x01",
+                &["libicuuc.so.72"],
+            )
+            .shipped(&["libicuuc.so.75", "libc.so.6"]);
+        let config = config::Config::default();
+        let ctx = checks::Context::new(&config, &system);
+        let unshipped = |package: &str, file: &str| -> Vec<bool> {
+            let work = ExecFileWork {
+                package: Arc::new(package.to_string()),
+                exec_filepath: Arc::new(file.to_string()),
+                package_last: true,
+                package_pending: None,
+                runtime_dirs: Arc::new(Vec::new()),
+            };
+            analyze_file(&ctx, &held::HeldPackages::default(), &work)
+                .into_iter()
+                .map(|f| match f {
+                    report::Finding::MissingDependency { unshipped, .. } => unshipped,
+                    _ => panic!("{:?}", f),
+                })
+                .collect()
+        };
+        assert_eq!(unshipped("zoom-bin", "/opt/zoom/zoom"), [true, false]);
+        assert_eq!(unshipped("zoom", "/usr/bin/zoom"), [false]);
+    }
 }
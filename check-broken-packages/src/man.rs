@@ -0,0 +1,88 @@
+//! Man page generation, from the command line definition, checks and finding codes
+
+use std::io::{self, Write};
+
+use clap::CommandFactory;
+use clap_mangen::roff::{bold, italic, roman, Roff};
+use clap_mangen::Man;
+
+use crate::checks::CHECKS;
+use crate::cl::CommandLineOpts;
+use crate::config::DEFAULT_CONFIG_FILEPATH;
+use crate::report::Finding;
+
+/// Write man page in roff format
+pub fn write_man_page(w: &mut dyn Write) -> io::Result<()> {
+    let man = Man::new(CommandLineOpts::command());
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+    man.render_subcommands_section(w)?;
+
+    let mut roff = Roff::new();
+    roff.control("SH", ["CHECKS"]);
+    roff.text([roman(
+        "Checks run in parallel with the package dependency analysis. \
+         Opt-in checks only run when enabled with --enable-check.",
+    )]);
+    for check in CHECKS.iter() {
+        roff.control("TP", []);
+        let mut name = vec![bold(check.name())];
+        if check.opt_in() {
+            name.push(roman(" (opt-in)"));
+        }
+        roff.text(name);
+        roff.text([roman(check.description())]);
+        for tool in check.required_tools() {
+            roff.text([roman(format!(
+                "Requires {} (from package {}).",
+                tool.program, tool.package
+            ))]);
+        }
+    }
+
+    roff.control("SH", ["FINDING CODES"]);
+    roff.text([roman(
+        "Each finding type has a stable code, that can be used to ignore findings with --ignore.",
+    )]);
+    for (code, description) in Finding::CODES.iter() {
+        roff.control("TP", []);
+        roff.text([bold(*code)]);
+        roff.text([roman(*description)]);
+    }
+
+    roff.control("SH", ["FILES"]);
+    roff.control("TP", []);
+    roff.text([italic(DEFAULT_CONFIG_FILEPATH)]);
+    roff.text([roman(
+        "Configuration file, with the same settings as the command line options.",
+    )]);
+
+    roff.control("SH", ["EXIT STATUS"]);
+    roff.text([roman(
+        "0 if the analysis completed, 1 if it failed, 2 if a checked removal is blocked, 3 with \
+         --fail-on if a finding has the given severity, a bitmask of the categories with findings \
+         with --exit-bitmask, 130 if it was interrupted.",
+    )]);
+    roff.to_writer(w)?;
+
+    man.render_version_section(w)?;
+    man.render_authors_section(w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_man_page() {
+        let mut page = Vec::new();
+        write_man_page(&mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains("\\-\\-ignore\\-path"));
+        assert!(page.contains("capabilities"));
+        assert!(page.contains("LIB001"));
+    }
+}
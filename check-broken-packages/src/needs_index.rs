@@ -0,0 +1,272 @@
+//! Reverse index of the dynamic dependencies (DT_NEEDED entries) of all installed files, to find
+//! what needs a library. It is cached between runs, and entries of a package are reused as long
+//! as the package is not reinstalled. Hook runs update the cached index for the packages of the
+//! transaction only.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error;
+use std::path::Path;
+
+use crossbeam::thread as cb_thread;
+use serde::{Deserialize, Serialize};
+
+use crate::cache;
+use crate::pacman::LocalPackage;
+use crate::system::SystemProvider;
+
+/// Format version of cached indexes
+const FORMAT_VERSION: u32 = 1;
+
+/// Name of the cached index
+const CACHE_NAME: &str = "needs_index";
+
+/// ELF files of an installed package with their DT_NEEDED entries
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PackageEntry {
+    version: String,
+
+    /// In seconds since the epoch, changes if the package is reinstalled with the same version
+    install_date: u64,
+
+    files: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct NeedsIndex {
+    /// Package name to its entry
+    packages: BTreeMap<String, PackageEntry>,
+}
+
+/// Get DT_NEEDED entries of the ELF files of a package
+fn package_entry(system: &dyn SystemProvider, package: &LocalPackage) -> PackageEntry {
+    let mut files = BTreeMap::new();
+    for file in &package.files {
+        let path = Path::new(file);
+        // Avoid parsing whole files that are not ELF files
+        match system.metadata(path) {
+            Ok(m) if m.is_file => {}
+            _ => continue,
+        }
+        if !system
+            .read_head(path, 4)
+            .is_ok_and(|h| h.starts_with(b"\x7fELF"))
+        {
+            continue;
+        }
+        if let Ok(libraries) = system.linked_libraries(path) {
+            if !libraries.is_empty() {
+                files.insert(
+                    file.clone(),
+                    libraries
+                        .into_iter()
+                        .map(|(soname, _path)| soname)
+                        .collect(),
+                );
+            }
+        }
+    }
+    PackageEntry {
+        version: package.version.clone(),
+        install_date: package.install_date,
+        files,
+    }
+}
+
+/// Get entries of packages with a number of worker threads, in package order
+fn package_entries(
+    system: &dyn SystemProvider,
+    packages: &[LocalPackage],
+    jobs: usize,
+) -> Vec<PackageEntry> {
+    let chunk_size = packages.len().div_ceil(jobs.max(1)).max(1);
+    cb_thread::scope(|scope| {
+        let handles: Vec<_> = packages
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move |_| {
+                    chunk
+                        .iter()
+                        .map(|p| package_entry(system, p))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+    .unwrap()
+}
+
+/// Get sonames of the shared libraries installed by a package, from their file names
+pub fn library_sonames(package: &LocalPackage) -> BTreeSet<String> {
+    package
+        .files
+        .iter()
+        .filter_map(|f| Path::new(f).file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| n.starts_with("lib") && n.contains(".so"))
+        .collect()
+}
+
+impl NeedsIndex {
+    /// Index installed packages with a number of worker threads, reusing the entries of a
+    /// previous index for packages not reinstalled since
+    pub fn build(
+        system: &dyn SystemProvider,
+        previous: NeedsIndex,
+        jobs: usize,
+    ) -> Result<NeedsIndex, Box<dyn error::Error>> {
+        Ok(NeedsIndex::build_packages(
+            system,
+            system.local_packages()?,
+            previous,
+            jobs,
+        ))
+    }
+
+    /// Index some installed packages only, like build
+    pub fn build_packages(
+        system: &dyn SystemProvider,
+        local_packages: Vec<LocalPackage>,
+        previous: NeedsIndex,
+        jobs: usize,
+    ) -> NeedsIndex {
+        let mut previous = previous.packages;
+        let mut packages = BTreeMap::new();
+        let mut to_index = Vec::new();
+        for package in local_packages {
+            match previous.remove(&package.name) {
+                Some(e)
+                    if (e.version == package.version)
+                        && (e.install_date == package.install_date) =>
+                {
+                    packages.insert(package.name, e);
+                }
+                _ => to_index.push(package),
+            }
+        }
+
+        let entries = package_entries(system, &to_index, jobs);
+        packages.extend(to_index.into_iter().map(|p| p.name).zip(entries));
+        NeedsIndex { packages }
+    }
+
+    /// Update index for the packages of a transaction: reindex those installed, and drop those
+    /// removed
+    pub fn update(
+        &mut self,
+        system: &dyn SystemProvider,
+        targets: &[String],
+        jobs: usize,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let targets: HashSet<&str> = targets.iter().map(String::as_str).collect();
+        let to_index: Vec<LocalPackage> = system
+            .local_packages()?
+            .into_iter()
+            .filter(|p| targets.contains(p.name.as_str()))
+            .collect();
+        for target in &targets {
+            self.packages.remove(*target);
+        }
+        let entries = package_entries(system, &to_index, jobs);
+        self.packages
+            .extend(to_index.into_iter().map(|p| p.name).zip(entries));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Load cached index, empty if there is none
+    pub fn load() -> NeedsIndex {
+        cache::load(CACHE_NAME, FORMAT_VERSION).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        cache::store(CACHE_NAME, FORMAT_VERSION, self)
+    }
+
+    /// Get files needing a soname directly, with their package, sorted by package then path
+    pub fn who_needs(&self, soname: &str) -> Vec<(&str, &str)> {
+        self.packages
+            .iter()
+            .flat_map(|(package, entry)| {
+                entry
+                    .files
+                    .iter()
+                    .filter(move |(_f, needed)| needed.iter().any(|n| n == soname))
+                    .map(move |(file, _needed)| (package.as_str(), file.as_str()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_who_needs() {
+        let system = MockSystem::default()
+            .package("openssl", false, &["/usr/lib/libssl.so.3"])
+            .package("curl", false, &["/usr/bin/curl", "/usr/share/doc/curl"])
+            .package("app", true, &["/usr/bin/app", "/usr/lib/app/plugin.so"])
+            .file("/usr/lib/libssl.so.3", 0o755, b"\x7fELF", &[])
+            .file("/usr/bin/curl", 0o755, b"\x7fELF", &[])
+            .linked("/usr/bin/curl", &[("libssl.so.3", "/usr/lib/libssl.so.3")])
+            .file("/usr/share/doc/curl", 0o644, b"curl", &[])
+            .file("/usr/bin/app", 0o755, b"\x7fELF", &[])
+            .linked("/usr/bin/app", &[("libfoo.so.1", "/usr/lib/libfoo.so.1")])
+            .file("/usr/lib/app/plugin.so", 0o755, b"\x7fELF", &[])
+            .linked(
+                "/usr/lib/app/plugin.so",
+                &[("libssl.so.3", "/usr/lib/libssl.so.3")],
+            );
+
+        let index = NeedsIndex::build(&system, NeedsIndex::default(), 2).unwrap();
+        assert_eq!(
+            index.who_needs("libssl.so.3"),
+            [("app", "/usr/lib/app/plugin.so"), ("curl", "/usr/bin/curl")]
+        );
+        assert!(index.who_needs("libcrypto.so.3").is_empty());
+        let openssl = system
+            .local_packages()
+            .unwrap()
+            .into_iter()
+            .find(|p| p.name == "openssl")
+            .unwrap();
+        assert_eq!(
+            library_sonames(&openssl).into_iter().collect::<Vec<_>>(),
+            ["libssl.so.3"]
+        );
+
+        // Entries of packages not reinstalled are reused
+        let mut previous = index;
+        previous.packages.get_mut("curl").unwrap().files.insert(
+            "/usr/bin/curl-old".to_string(),
+            vec!["libssl.so.3".to_string()],
+        );
+        let mut index = NeedsIndex::build(&system, previous, 2).unwrap();
+        assert_eq!(index.who_needs("libssl.so.3").len(), 3);
+
+        // Packages of a transaction are reindexed, or dropped if they were removed
+        index
+            .update(&system, &["curl".to_string(), "gone".to_string()], 2)
+            .unwrap();
+        assert_eq!(index.who_needs("libssl.so.3").len(), 2);
+        index.packages.insert(
+            "gone".to_string(),
+            PackageEntry {
+                version: "1-1".to_string(),
+                install_date: 0,
+                files: BTreeMap::new(),
+            },
+        );
+        index.update(&system, &["gone".to_string()], 2).unwrap();
+        assert!(!index.packages.contains_key("gone"));
+    }
+}
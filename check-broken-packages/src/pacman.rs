@@ -0,0 +1,432 @@
+//! Pacman database queries
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use simple_error::SimpleError;
+
+use crate::parse;
+
+/// Local database directory
+pub const LOCAL_DB_DIR: &str = "/var/lib/pacman/local";
+
+/// Sync databases directory
+pub const SYNC_DB_DIR: &str = "/var/lib/pacman/sync";
+
+/// Lock file of the pacman database, that exists while another program uses it
+pub const DB_LOCK_FILEPATH: &str = "/var/lib/pacman/db.lck";
+
+/// Interval between two checks of the database lock
+const DB_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// In-memory index of files owned by installed packages
+pub struct FileIndex {
+    /// Filepath to owning package name
+    pub files: BTreeMap<String, Arc<String>>,
+}
+
+impl FileIndex {
+    /// Build index from the file lists of all installed packages
+    pub fn new() -> Result<FileIndex, Box<dyn error::Error>> {
+        let output = Command::new("pacman").arg("-Ql").output()?;
+
+        if !output.status.success() {
+            return Err(Box::new(SimpleError::new(
+                "Failed to list package files with pacman",
+            )));
+        }
+
+        let mut files = BTreeMap::new();
+        let mut cur_package: Option<Arc<String>> = None;
+        for (package, path) in parse::package_file_list(&output.stdout)? {
+            if path.ends_with('/') {
+                // Directories can be owned by several packages, only index files
+                continue;
+            }
+            let package = match cur_package {
+                Some(ref p) if p.as_str() == package => Arc::clone(p),
+                _ => {
+                    let p = Arc::new(package.to_string());
+                    cur_package = Some(Arc::clone(&p));
+                    p
+                }
+            };
+            files.insert(path.to_string(), package);
+        }
+
+        Ok(FileIndex { files })
+    }
+
+    /// Get package owning a file
+    pub fn owner(&self, path: &str) -> Option<&Arc<String>> {
+        self.files.get(path)
+    }
+
+    /// Iterate over files under a directory, with their owning package
+    fn entries_under<'a>(
+        &'a self,
+        dir: &str,
+    ) -> impl Iterator<Item = (&'a String, &'a Arc<String>)> {
+        let prefix = format!("{}/", dir.trim_end_matches('/'));
+        self.files
+            .range(prefix.clone()..)
+            .take_while(move |(p, _)| p.starts_with(&prefix))
+    }
+
+    /// Iterate over files owned by any package under a directory
+    pub fn files_under<'a>(&'a self, dir: &str) -> impl Iterator<Item = &'a str> {
+        self.entries_under(dir).map(|(p, _)| p.as_str())
+    }
+
+    /// Get packages owning files under a directory with their file count, most files first
+    pub fn owners_under(&self, dir: &str) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_path, package) in self.entries_under(dir) {
+            *counts.entry(package.as_str()).or_insert(0) += 1;
+        }
+
+        let mut owners: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(p, c)| (p.to_string(), c))
+            .collect();
+        owners.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        owners
+    }
+}
+
+/// Get names of foreign packages (not from a sync repository, typically from the AUR)
+#[allow(clippy::needless_borrows_for_generic_args)]
+pub fn get_aur_packages() -> Result<Vec<String>, Box<dyn error::Error>> {
+    let output = Command::new("pacman").args(&["-Qqm"]).output()?;
+
+    if !output.status.success() {
+        return Err(Box::new(SimpleError::new(
+            "Failed to list packages with pacman",
+        )));
+    }
+
+    Ok(parse::package_names(&output.stdout)?)
+}
+
+/// Get names and versions of all installed packages
+pub fn get_installed_packages() -> Result<Vec<(String, String)>, Box<dyn error::Error>> {
+    let output = Command::new("pacman").args(["-Q"]).output()?;
+
+    if !output.status.success() {
+        return Err(Box::new(SimpleError::new(
+            "Failed to list packages with pacman",
+        )));
+    }
+
+    Ok(parse::package_versions(&output.stdout)?)
+}
+
+/// Get list of files owned by a package, excluding directories
+pub fn get_package_files(package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+    let output = Command::new("pacman").args(["-Qlq", package]).output()?;
+
+    if !output.status.success() {
+        return Err(Box::new(SimpleError::new(format!(
+            "Failed to list files for package '{}' with pacman",
+            package
+        ))));
+    }
+
+    Ok(parse::file_list(&output.stdout)?)
+}
+
+/// Wait for a database lock file to be removed, return false if it still exists after timeout
+pub fn wait_db_unlocked(lock_filepath: &Path, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while lock_filepath.exists() {
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(DB_LOCK_POLL_INTERVAL);
+    }
+    true
+}
+
+/// Package of a local database, read directly instead of with pacman
+#[derive(Debug, Clone)]
+pub struct LocalPackage {
+    pub name: String,
+    pub version: String,
+    /// Architecture the package is built for, like "x86_64", or "any" if architecture independent,
+    /// empty if unknown
+    pub arch: String,
+    /// Virtual packages and sonames provided, like "libfoo.so=1-64"
+    pub provides: Vec<String>,
+    /// Dependencies, with their optional version constraint, like "libfoo>=1"
+    pub depends: Vec<String>,
+    pub groups: Vec<String>,
+    /// Installed size in bytes
+    pub installed_size: u64,
+    /// When the package was last installed (built or upgraded), in seconds since the epoch
+    pub install_date: u64,
+    /// Absolute paths of files, excluding directories
+    pub files: Vec<String>,
+}
+
+/// Get values of each "%SECTION%" of a database entry file
+fn db_sections(content: &str) -> HashMap<&str, Vec<&str>> {
+    let mut sections = HashMap::new();
+    let mut cur: Option<&mut Vec<&str>> = None;
+    for line in content.lines() {
+        if line.len() > 2 && line.starts_with('%') && line.ends_with('%') {
+            cur = Some(sections.entry(&line[1..line.len() - 1]).or_default());
+        } else if line.is_empty() {
+            cur = None;
+        } else if let Some(values) = cur.as_mut() {
+            values.push(line);
+        }
+    }
+    sections
+}
+
+/// Get package name and version from a parsed "desc" database entry file
+fn desc_name_version<'a>(desc: &HashMap<&str, Vec<&'a str>>) -> Option<(&'a str, &'a str)> {
+    let name = desc.get("NAME").and_then(|v| v.first())?;
+    let version = desc.get("VERSION").and_then(|v| v.first()).unwrap_or(&"");
+    Some((name, version))
+}
+
+/// Get provides of a parsed "desc" database entry file
+fn desc_provides(desc: &HashMap<&str, Vec<&str>>) -> Vec<String> {
+    desc.get("PROVIDES")
+        .map(|p| p.iter().map(|p| p.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Get soname from a soname provide, like "libfoo.so.1" from "libfoo.so=1-64"
+fn provided_soname(provide: &str) -> Option<String> {
+    let (name, version) = provide.split_once('=')?;
+    if !name.ends_with(".so") {
+        return None;
+    }
+    // Architecture bits suffix
+    let version = version
+        .strip_suffix("-64")
+        .or_else(|| version.strip_suffix("-32"))
+        .unwrap_or(version);
+    Some(format!("{}.{}", name, version))
+}
+
+/// Get sonames from provides, ignoring other provides
+pub fn provided_sonames(provides: &[String]) -> impl Iterator<Item = String> + '_ {
+    provides.iter().filter_map(|p| provided_soname(p))
+}
+
+/// Read packages of a local database directory (like /var/lib/pacman/local)
+pub fn read_local_db(db_dir: &Path) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(db_dir)? {
+        let entry_dir = entry?.path();
+        let desc_filepath = entry_dir.join("desc");
+        if !desc_filepath.is_file() {
+            // ALPM_DB_VERSION file
+            continue;
+        }
+        let desc = fs::read_to_string(&desc_filepath)?;
+        let desc = db_sections(&desc);
+        let (name, version) = desc_name_version(&desc)
+            .ok_or_else(|| SimpleError::new(format!("No package name in {:?}", desc_filepath)))?;
+        let files = match fs::read_to_string(entry_dir.join("files")) {
+            Ok(c) => db_sections(&c)
+                .get("FILES")
+                .map(|f| {
+                    f.iter()
+                        .filter(|f| !f.ends_with('/'))
+                        .map(|f| format!("/{}", f))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        packages.push(LocalPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: desc
+                .get("ARCH")
+                .and_then(|a| a.first())
+                .map(|a| a.to_string())
+                .unwrap_or_default(),
+            provides: desc_provides(&desc),
+            depends: desc
+                .get("DEPENDS")
+                .map(|d| d.iter().map(|d| d.to_string()).collect())
+                .unwrap_or_default(),
+            groups: desc
+                .get("GROUPS")
+                .map(|g| g.iter().map(|g| g.to_string()).collect())
+                .unwrap_or_default(),
+            installed_size: desc
+                .get("SIZE")
+                .and_then(|s| s.first())
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            install_date: desc
+                .get("INSTALLDATE")
+                .and_then(|d| d.first())
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(0),
+            files,
+        });
+    }
+    packages.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+/// Get names of packages in sync database archives of a directory (like /var/lib/pacman/sync)
+pub fn read_sync_db_packages(sync_dir: &Path) -> Result<HashSet<String>, Box<dyn error::Error>> {
+    let mut packages = HashSet::new();
+    let entries = match fs::read_dir(sync_dir) {
+        Ok(e) => e,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(packages),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let db_filepath = entry?.path();
+        if db_filepath.extension().is_none_or(|e| e != "db") {
+            continue;
+        }
+        let output = Command::new("bsdtar")
+            .arg("-tf")
+            .arg(&db_filepath)
+            .output()?;
+        if !output.status.success() {
+            return Err(format!("Failed to list sync database {:?}", db_filepath).into());
+        }
+        packages.extend(parse::sync_db_package_names(&output.stdout)?);
+    }
+    Ok(packages)
+}
+
+/// Package of a sync database
+#[derive(Debug)]
+pub struct SyncPackage {
+    pub name: String,
+    pub version: String,
+    /// Virtual packages and sonames provided, like "libfoo.so=1-64"
+    pub provides: Vec<String>,
+}
+
+/// Read packages of sync database archives of a directory (like /var/lib/pacman/sync), by name,
+/// packages in several databases are taken from the first one in name order
+pub fn read_sync_db(
+    sync_dir: &Path,
+) -> Result<HashMap<String, SyncPackage>, Box<dyn error::Error>> {
+    let mut packages = HashMap::new();
+    let mut db_filepaths = Vec::new();
+    for entry in fs::read_dir(sync_dir)? {
+        let db_filepath = entry?.path();
+        if db_filepath.extension().is_some_and(|e| e == "db") {
+            db_filepaths.push(db_filepath);
+        }
+    }
+    db_filepaths.sort_unstable();
+    for db_filepath in db_filepaths {
+        // Extract all "desc" files, concatenated
+        let output = Command::new("bsdtar")
+            .arg("-xOf")
+            .arg(&db_filepath)
+            .arg("*/desc")
+            .output()?;
+        if !output.status.success() {
+            return Err(format!("Failed to read sync database {:?}", db_filepath).into());
+        }
+        let content = String::from_utf8_lossy(&output.stdout);
+        for package in parse_concatenated_descs(&content) {
+            packages.entry(package.name.clone()).or_insert(package);
+        }
+    }
+    Ok(packages)
+}
+
+/// Parse "desc" database entry files concatenated, each starting with a "%FILENAME%" section
+fn parse_concatenated_descs(content: &str) -> Vec<SyncPackage> {
+    content
+        .split("%FILENAME%\n")
+        .map(db_sections)
+        .filter_map(|desc| {
+            let (name, version) = desc_name_version(&desc)?;
+            Some(SyncPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                provides: desc_provides(&desc),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_db_sections() {
+        let sections = db_sections("%NAME%\nfoo\n\n%VERSION%\n1.0-1\n\n%DEPENDS%\nbar\nbaz>=2\n");
+        assert_eq!(sections["NAME"], ["foo"]);
+        assert_eq!(sections["DEPENDS"], ["bar", "baz>=2"]);
+        assert!(!sections.contains_key("FILES"));
+
+        let packages = parse_concatenated_descs(
+            "%FILENAME%\nfoo-1.0-1-x86_64.pkg.tar.zst\n\n%NAME%\nfoo\n\n%VERSION%\n1.0-1\n\n\
+             %PROVIDES%\nlibfoo.so=1-64\n\n%FILENAME%\nbar-2-1-any.pkg.tar.zst\n\n%NAME%\nbar\n",
+        );
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].provides, ["libfoo.so=1-64"]);
+        assert_eq!(
+            (packages[1].name.as_str(), packages[1].version.as_str()),
+            ("bar", "")
+        );
+    }
+
+    #[test]
+    fn test_wait_db_unlocked() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let lock_filepath = tmp_dir.path().join("db.lck");
+        assert!(wait_db_unlocked(&lock_filepath, Duration::ZERO));
+
+        fs::write(&lock_filepath, "").unwrap();
+        assert!(!wait_db_unlocked(&lock_filepath, Duration::ZERO));
+        let remover = {
+            let lock_filepath = lock_filepath.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::remove_file(lock_filepath).unwrap();
+            })
+        };
+        assert!(wait_db_unlocked(&lock_filepath, Duration::from_secs(10)));
+        remover.join().unwrap();
+    }
+
+    #[test]
+    fn test_file_index_owners_under() {
+        let a = Arc::new("a".to_string());
+        let b = Arc::new("b".to_string());
+        let mut files = BTreeMap::new();
+        files.insert("/usr/lib/python3.8/a1.py".to_string(), Arc::clone(&a));
+        files.insert("/usr/lib/python3.8/sub/b1.py".to_string(), Arc::clone(&b));
+        files.insert("/usr/lib/python3.8/sub/b2.py".to_string(), Arc::clone(&b));
+        files.insert("/usr/lib/python3.80/a2.py".to_string(), Arc::clone(&a));
+        files.insert("/usr/lib/python3.9/a3.py".to_string(), Arc::clone(&a));
+        let file_index = FileIndex { files };
+
+        assert_eq!(
+            file_index.owners_under("/usr/lib/python3.8"),
+            [("b".to_string(), 2), ("a".to_string(), 1)]
+        );
+        assert!(file_index.owners_under("/usr/lib/python3.7").is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+//! Parsers of external command outputs and configuration files, that return errors instead of
+//! panicking on malformed input
+
+use std::error;
+use std::fmt;
+
+/// Malformed line in a command output
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// Command whose output is parsed
+    pub command: &'static str,
+    /// Line number, starting at 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unexpected {} output at line {}: {}",
+            self.command, self.line, self.reason
+        )
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Iterate over non empty lines of an output with their number, failing on invalid UTF-8
+fn lines<'a>(
+    command: &'static str,
+    output: &'a [u8],
+) -> impl Iterator<Item = Result<(usize, &'a str), ParseError>> {
+    output
+        .split(|b| *b == b'\n')
+        .enumerate()
+        .filter(|(_i, l)| !l.is_empty())
+        .map(move |(i, l)| {
+            std::str::from_utf8(l)
+                .map(|l| (i + 1, l))
+                .map_err(|e| ParseError {
+                    command,
+                    line: i + 1,
+                    reason: e.to_string(),
+                })
+        })
+}
+
+/// Parse package names, one per line (pacman -Qq, -Qqm)
+pub fn package_names(output: &[u8]) -> Result<Vec<String>, ParseError> {
+    lines("pacman", output)
+        .map(|l| l.map(|(_i, l)| l.to_string()))
+        .collect()
+}
+
+/// Parse package names and versions (pacman -Q)
+pub fn package_versions(output: &[u8]) -> Result<Vec<(String, String)>, ParseError> {
+    lines("pacman", output)
+        .map(|l| {
+            let (i, l) = l?;
+            l.split_once(' ')
+                .filter(|(n, v)| !n.is_empty() && !v.is_empty())
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .ok_or_else(|| ParseError {
+                    command: "pacman",
+                    line: i,
+                    reason: format!("expected package name and version, got {:?}", l),
+                })
+        })
+        .collect()
+}
+
+/// Parse package names and file paths, including directories (pacman -Ql)
+pub fn package_file_list(output: &[u8]) -> Result<Vec<(&str, &str)>, ParseError> {
+    lines("pacman", output)
+        .map(|l| {
+            let (i, l) = l?;
+            // Paths can contain spaces, package names can not
+            l.split_once(' ')
+                .filter(|(_p, f)| f.starts_with('/'))
+                .ok_or_else(|| ParseError {
+                    command: "pacman",
+                    line: i,
+                    reason: format!("expected package name and absolute path, got {:?}", l),
+                })
+        })
+        .collect()
+}
+
+/// Parse file paths, excluding directories (pacman -Qlq)
+pub fn file_list(output: &[u8]) -> Result<Vec<String>, ParseError> {
+    lines("pacman", output)
+        .filter(|l| !matches!(l, Ok((_i, l)) if l.ends_with('/')))
+        .map(|l| l.map(|(_i, l)| l.to_string()))
+        .collect()
+}
+
+/// Parse package names from entries of a sync database archive (bsdtar -tf)
+pub fn sync_db_package_names(output: &[u8]) -> Result<Vec<String>, ParseError> {
+    let mut names: Vec<String> = Vec::new();
+    for line in lines("bsdtar", output) {
+        let (_i, line) = line?;
+        // Entries are "name-version-release/" and "name-version-release/desc"
+        let package_dir = line.split('/').next().unwrap_or_default();
+        if let Some(name) = package_dir.rsplitn(3, '-').nth(2) {
+            if names.last().is_none_or(|n| n != name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Parse repository, package name and file path of files found in the files databases
+/// (pacman -F --machinereadable), paths are relative to the root
+pub fn files_db_matches(output: &[u8]) -> Result<Vec<(String, String)>, ParseError> {
+    lines("pacman", output)
+        .map(|l| {
+            let (i, l) = l?;
+            let fields: Vec<&str> = l.split('\0').collect();
+            match fields[..] {
+                [repo, package, _version, path] if !path.is_empty() => {
+                    Ok((format!("{}/{}", repo, package), path.to_string()))
+                }
+                _ => Err(ParseError {
+                    command: "pacman",
+                    line: i,
+                    reason: format!(
+                        "expected repository, package, version and path, got {:?}",
+                        l
+                    ),
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Parse sonames of missing libraries from ldd output
+pub fn ldd_missing_dependencies(output: &[u8]) -> Result<Vec<String>, ParseError> {
+    let mut missing_deps = Vec::new();
+    for line in lines("ldd", output) {
+        let (i, line) = line?;
+        // "	libfoo.so.1 => not found"
+        if let Some(soname) = line.strip_suffix("=> not found") {
+            let soname = soname.trim();
+            if soname.is_empty() {
+                return Err(ParseError {
+                    command: "ldd",
+                    line: i,
+                    reason: "missing library without name".to_string(),
+                });
+            }
+            missing_deps.push(soname.to_string());
+        }
+    }
+    Ok(missing_deps)
+}
+
+/// Result of ldd on a file, classified from its exit status and outputs
+#[derive(Debug, PartialEq)]
+pub enum LddResult {
+    /// Dynamic ELF file, with the sonames of its missing libraries
+    Dynamic(Vec<String>),
+    /// Static executable, or not an ELF file at all
+    NotDynamic,
+    /// ELF file that the dynamic loader of the system can not execute
+    WrongArchitecture,
+    PermissionDenied,
+    /// File removed since it was listed
+    NotFound,
+    /// Failure of the dynamic loader, like a missing symbol version or a crash, with its error
+    LoaderError(String),
+}
+
+/// Classify ldd result on a file from its exit status, standard output and error output
+pub fn ldd_result(success: bool, stdout: &[u8], stderr: &[u8]) -> Result<LddResult, ParseError> {
+    let errors: Vec<&str> = lines("ldd", stderr)
+        .filter_map(Result::ok)
+        .map(|(_i, l)| l.trim())
+        // "ldd: warning: you do not have execution permission for `/usr/lib/libfoo.so'"
+        .filter(|l| !l.starts_with("ldd: warning:"))
+        .collect();
+    let all_lines = || {
+        errors
+            .iter()
+            .copied()
+            .chain(lines("ldd", stdout).filter_map(Result::ok).map(|(_i, l)| l))
+    };
+    if all_lines()
+        .any(|l| l.contains("not a dynamic executable") || l.contains("statically linked"))
+    {
+        return Ok(LddResult::NotDynamic);
+    }
+    if all_lines().any(|l| {
+        l.contains("wrong ELF class")
+            || l.contains("Exec format error")
+            || l.contains("cannot execute binary file")
+    }) {
+        return Ok(LddResult::WrongArchitecture);
+    }
+    if errors.iter().any(|l| l.ends_with("Permission denied")) {
+        return Ok(LddResult::PermissionDenied);
+    }
+    // "ldd: /usr/bin/foo: No such file or directory", from ldd itself rather than the loader
+    if errors
+        .iter()
+        .any(|l| l.starts_with("ldd: ") && l.ends_with("No such file or directory"))
+    {
+        return Ok(LddResult::NotFound);
+    }
+    let missing_deps = ldd_missing_dependencies(stdout)?;
+    if !missing_deps.is_empty() {
+        return Ok(LddResult::Dynamic(missing_deps));
+    }
+    // "./foo: /usr/lib/libc.so.6: version `GLIBC_2.38' not found (required by ./foo)"
+    let loader_error = all_lines().find(|l| !errors.is_empty() || l.contains("(required by"));
+    match loader_error {
+        Some(error) => Ok(LddResult::LoaderError(error.trim().to_string())),
+        None if !success => Ok(LddResult::LoaderError(
+            "ldd failed without error message".to_string(),
+        )),
+        None => Ok(LddResult::Dynamic(missing_deps)),
+    }
+}
+
+/// Directive of a dynamic linker configuration file
+#[derive(Debug, PartialEq)]
+pub enum LdSoConfLine<'a> {
+    Dir(&'a str),
+    /// Glob pattern of files to include
+    Include(&'a str),
+}
+
+/// Parse dynamic linker configuration file content, ignoring comments and empty lines
+pub fn ld_so_conf(content: &str) -> Vec<LdSoConfLine<'_>> {
+    content
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty())
+        .filter_map(|l| match l.strip_prefix("include") {
+            Some(pattern) if pattern.starts_with(char::is_whitespace) => {
+                Some(LdSoConfLine::Include(pattern.trim()))
+            }
+            Some(_) if l == "include" => None,
+            _ => Some(LdSoConfLine::Dir(l)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsers() {
+        assert_eq!(
+            package_file_list(b"foo /usr/\nfoo /usr/bin/foo bar\n").unwrap(),
+            [("foo", "/usr/"), ("foo", "/usr/bin/foo bar")]
+        );
+        assert_eq!(
+            package_file_list(b"foo /usr/\nfoo\n").unwrap_err(),
+            ParseError {
+                command: "pacman",
+                line: 2,
+                reason: "expected package name and absolute path, got \"foo\"".to_string()
+            }
+        );
+        assert_eq!(package_names(b"foo\n\xff\n").unwrap_err().line, 2);
+        assert_eq!(
+            file_list(b"/usr/\n/usr/bin/foo\n").unwrap(),
+            ["/usr/bin/foo"]
+        );
+        assert_eq!(
+            sync_db_package_names(b"foo-bar-1.0-1/\nfoo-bar-1.0-1/desc\nbaz-2:1.0-3/\n").unwrap(),
+            ["foo-bar", "baz"]
+        );
+        assert_eq!(
+            files_db_matches(b"extra\0ffmpeg4.4\x002:4.4.4-5\0usr/lib/libavutil.so.56\n").unwrap(),
+            [(
+                "extra/ffmpeg4.4".to_string(),
+                "usr/lib/libavutil.so.56".to_string()
+            )]
+        );
+        assert!(files_db_matches(b"extra\0ffmpeg4.4\n").is_err());
+        assert!(ldd_missing_dependencies(b" => not found\n").is_err());
+        assert_eq!(
+            ldd_result(false, b"", b"\tnot a dynamic executable\n").unwrap(),
+            LddResult::NotDynamic
+        );
+        assert_eq!(
+            ldd_result(false, b"", b"ldd: /usr/bin/foo: Permission denied\n").unwrap(),
+            LddResult::PermissionDenied
+        );
+        assert_eq!(
+            ldd_result(
+                true,
+                b"\tlibfoo.so.1 => not found\n",
+                b"ldd: warning: you do not have execution permission for `/usr/lib/libbar.so'\n"
+            )
+            .unwrap(),
+            LddResult::Dynamic(vec!["libfoo.so.1".to_string()])
+        );
+        assert_eq!(
+            ldd_result(
+                false,
+                b"/usr/bin/foo: /usr/lib/libc.so.6: version `GLIBC_2.99' not found (required by /usr/bin/foo)\n\tlibc.so.6 => /usr/lib/libc.so.6 (0x00007f4bd9add000)\n",
+                b""
+            )
+            .unwrap(),
+            LddResult::LoaderError("/usr/bin/foo: /usr/lib/libc.so.6: version `GLIBC_2.99' not found (required by /usr/bin/foo)".to_string())
+        );
+        assert_eq!(
+            ldd_result(false, b"", b"").unwrap(),
+            LddResult::LoaderError("ldd failed without error message".to_string())
+        );
+        assert_eq!(
+            ld_so_conf("# comment\n/opt/a/lib # a\ninclude ld.so.conf.d/*.conf\ninclude\n"),
+            [
+                LdSoConfLine::Dir("/opt/a/lib"),
+                LdSoConfLine::Include("ld.so.conf.d/*.conf")
+            ]
+        );
+
+        // Random inputs made of characters significant to the parsers must not panic
+        let alphabet = b" \t\n/-#=>()abc.:\xff\xc3";
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..10000 {
+            let mut input = Vec::new();
+            for _ in 0..(state % 64) {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                input.push(alphabet[(state % alphabet.len() as u64) as usize]);
+            }
+            let _ = package_names(&input);
+            let _ = package_versions(&input);
+            let _ = package_file_list(&input);
+            let _ = file_list(&input);
+            let _ = sync_db_package_names(&input);
+            let _ = files_db_matches(&input);
+            let _ = ldd_missing_dependencies(&input);
+            let _ = ldd_result(false, &input, &input);
+            let _ = ld_so_conf(&String::from_utf8_lossy(&input));
+            state = state.wrapping_add(1);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Locations of persistent files
+
+use std::env;
+use std::path::PathBuf;
+
+/// Return true if running as root, which is the case when run as a pacman hook
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Get a per user XDG base directory
+fn xdg_dir(env_var: &str, home_subdir: &str) -> Option<PathBuf> {
+    let base_dir = match env::var_os(env_var) {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => PathBuf::from(env::var_os("HOME")?).join(home_subdir),
+    };
+    Some(base_dir.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Get cache directory, system wide when running as root, per user otherwise
+pub fn cache_dir() -> Option<PathBuf> {
+    if is_root() {
+        return Some(PathBuf::from("/var/cache").join(env!("CARGO_PKG_NAME")));
+    }
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Get state directory (for reports), system wide when running as root, per user otherwise
+pub fn state_dir() -> Option<PathBuf> {
+    if is_root() {
+        return Some(PathBuf::from("/var/lib").join(env!("CARGO_PKG_NAME")));
+    }
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Get runtime directory (for sockets), system wide when running as root, per user otherwise
+pub fn runtime_dir() -> Option<PathBuf> {
+    if is_root() {
+        return Some(PathBuf::from("/run").join(env!("CARGO_PKG_NAME")));
+    }
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(d) if !d.is_empty() => Some(PathBuf::from(d).join(env!("CARGO_PKG_NAME"))),
+        _ => None,
+    }
+}
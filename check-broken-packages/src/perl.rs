@@ -0,0 +1,178 @@
+//! Detection of packages with files ignored by the current Perl interpreter, in the module
+//! directories of previous Perl versions
+
+use std::error;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+
+use glob::glob;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+
+use crate::cache;
+use crate::python::{self, StaleDir};
+
+/// Directory of the version specific Perl module directories
+const PERL_LIB_DIR: &str = "/usr/lib/perl5";
+
+pub struct PerlPackageVersion {
+    pub major: u8,
+    pub minor: u8,
+    version: String,
+}
+
+impl fmt::Display for PerlPackageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.version)
+    }
+}
+
+impl PerlPackageVersion {
+    /// Parse package version, like "5.40.0-2"
+    fn parse(version: &str) -> Result<PerlPackageVersion, Box<dyn error::Error>> {
+        let mut dot_iter = version.split('.');
+        let major = u8::from_str(dot_iter.next().ok_or_else(|| {
+            SimpleError::new("Unexpected pacman output: unable to parse Perl version major part")
+        })?)?;
+        let minor = u8::from_str(dot_iter.next().ok_or_else(|| {
+            SimpleError::new("Unexpected pacman output: unable to parse Perl version minor part")
+        })?)?;
+        Ok(PerlPackageVersion {
+            major,
+            minor,
+            version: version.to_string(),
+        })
+    }
+
+    /// Get version specific directory of the interpreter modules
+    pub fn dir(&self) -> String {
+        format!("{}/{}.{}", PERL_LIB_DIR, self.major, self.minor)
+    }
+}
+
+pub fn get_perl_version() -> Result<PerlPackageVersion, Box<dyn error::Error>> {
+    let output = Command::new("pacman")
+        .args(["-Qi", "perl"])
+        .env("LANG", "C")
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Box::new(SimpleError::new(
+            "Failed to query Perl version with pacman",
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version_line = stdout
+        .lines()
+        .find(|l| l.starts_with("Version"))
+        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to find version line"))?;
+    let version_str = version_line
+        .split(':')
+        .nth(1)
+        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to parse version line"))?
+        .trim();
+    PerlPackageVersion::parse(version_str)
+}
+
+const PERL_CHECK_CACHE_NAME: &str = "perl";
+
+/// Format version of the Perl check cache, to increase when `PerlCheckCache` changes
+const PERL_CHECK_CACHE_VERSION: u32 = 1;
+
+/// What the Perl check result depends on, if it is unchanged the previous result is still valid
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PerlCheckCacheKey {
+    perl_version: String,
+
+    /// Checksums of the modification times of Perl directories and all their subdirectories
+    dir_checksums: Vec<(String, u32)>,
+}
+
+impl PerlCheckCacheKey {
+    fn new(perl_version: &PerlPackageVersion, perl_dirs: &[String]) -> PerlCheckCacheKey {
+        PerlCheckCacheKey {
+            perl_version: perl_version.to_string(),
+            dir_checksums: perl_dirs
+                .iter()
+                .map(|d| (d.to_string(), python::tree_mtimes_checksum(Path::new(d))))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PerlCheckCache {
+    key: PerlCheckCacheKey,
+    stale_dirs: Vec<StaleDir>,
+}
+
+/// Get all version specific Perl directories
+fn get_perl_dirs() -> Result<Vec<String>, Box<dyn error::Error>> {
+    let mut perl_dirs = Vec::new();
+    for perl_dir_entry in glob(&format!("{}/[0-9]*", PERL_LIB_DIR))? {
+        let perl_dir = perl_dir_entry?
+            .into_os_string()
+            .into_string()
+            .map_err(|_| SimpleError::new("Failed to convert OS string to native string"))?;
+        perl_dirs.push(perl_dir);
+    }
+    Ok(perl_dirs)
+}
+
+/// Get Perl directories of previous versions, ignored by the current interpreter, with the
+/// packages owning files there, and the files no package owns, walking directories with a number
+/// of threads
+pub fn get_broken_perl_packages(
+    current_perl_version: &PerlPackageVersion,
+    jobs: usize,
+    progress: &AtomicUsize,
+) -> Result<Vec<StaleDir>, Box<dyn error::Error>> {
+    let current_perl_dir = current_perl_version.dir();
+    let perl_dirs = get_perl_dirs()?;
+
+    let cache_key = PerlCheckCacheKey::new(current_perl_version, &perl_dirs);
+    if let Some(cache) =
+        cache::load::<PerlCheckCache>(PERL_CHECK_CACHE_NAME, PERL_CHECK_CACHE_VERSION)
+    {
+        if cache.key == cache_key {
+            debug!("Using cached Perl check result");
+            return Ok(cache.stale_dirs);
+        }
+    }
+
+    let stale_dirs: Vec<String> = perl_dirs
+        .into_iter()
+        .filter(|d| d != &current_perl_dir)
+        .collect();
+    let stale_dirs = python::inspect_stale_dirs(stale_dirs, jobs, progress)?;
+
+    let cache = PerlCheckCache {
+        key: cache_key,
+        stale_dirs,
+    };
+    if let Err(err) = cache::store(PERL_CHECK_CACHE_NAME, PERL_CHECK_CACHE_VERSION, &cache) {
+        debug!("Failed to store Perl check result in cache: {}", err);
+    }
+
+    Ok(cache.stale_dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perl_version() {
+        let version = PerlPackageVersion::parse("5.40.0-2").unwrap();
+        assert_eq!((version.major, version.minor), (5, 40));
+        assert_eq!(version.to_string(), "5.40.0-2");
+        assert_eq!(version.dir(), "/usr/lib/perl5/5.40");
+        assert!(PerlPackageVersion::parse("x.40").is_err());
+        assert!(PerlPackageVersion::parse("5").is_err());
+    }
+}
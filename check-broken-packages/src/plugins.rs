@@ -0,0 +1,102 @@
+//! Shared object plugins loaded by applications
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+use crate::ignore::IgnorePath;
+use crate::pacman::FileIndex;
+use crate::walk;
+
+/// Package name used for files not owned by any package
+pub const UNOWNED_PACKAGE: &str = "(unowned)";
+
+/// Return true if filename looks like a shared object ("foo.so" or "foo.so.1.2")
+pub fn is_shared_object_name(filename: &str) -> bool {
+    filename.ends_with(".so") || filename.contains(".so.")
+}
+
+/// Get shared objects under a plugin root directory, with their owning package, excluding those
+/// of some packages (because they are already analyzed), walking it with a number of threads
+pub fn get_plugin_files(
+    root: &Path,
+    file_index: &FileIndex,
+    exclude_packages: &HashSet<&str>,
+    ignore_paths: &[IgnorePath],
+    jobs: usize,
+    progress: &AtomicUsize,
+) -> Vec<(Arc<String>, String)> {
+    let unowned = Arc::new(UNOWNED_PACKAGE.to_string());
+
+    let files = Mutex::new(Vec::new());
+    walk::walk(&[root], jobs, ignore_paths, progress, |chunk| {
+        let shared_objects = chunk.iter().filter_map(|p| {
+            p.file_name()
+                .filter(|f| is_shared_object_name(&f.to_string_lossy()))
+                .and_then(|_| p.to_str())
+                .map(str::to_string)
+        });
+        files.lock().unwrap().extend(shared_objects);
+    });
+    let mut files = files.into_inner().unwrap();
+    files.sort_unstable();
+
+    files
+        .into_iter()
+        .map(|f| {
+            let package = file_index
+                .owner(&f)
+                .map_or_else(|| Arc::clone(&unowned), Arc::clone);
+            (package, f)
+        })
+        .filter(|(p, _f)| !exclude_packages.contains(p.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_get_plugin_files() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let root = tmp_dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for filename in &["a.so", "sub/b.so.1", "sub/c.so", "d.txt", "e.so"] {
+            fs::write(root.join(filename), "").unwrap();
+        }
+        let path = |f: &str| root.join(f).to_str().unwrap().to_string();
+
+        let repo = Arc::new("repo".to_string());
+        let aur = Arc::new("aur".to_string());
+        let mut files = BTreeMap::new();
+        files.insert(path("a.so"), Arc::clone(&repo));
+        files.insert(path("sub/b.so.1"), Arc::clone(&repo));
+        files.insert(path("e.so"), Arc::clone(&aur));
+        let file_index = FileIndex { files };
+
+        let exclude_packages: HashSet<&str> = ["aur"].iter().cloned().collect();
+        let ignore_paths = [path("sub/c.so").parse().unwrap()];
+        assert_eq!(
+            get_plugin_files(
+                root,
+                &file_index,
+                &exclude_packages,
+                &ignore_paths,
+                2,
+                &AtomicUsize::new(0)
+            ),
+            [
+                (Arc::clone(&repo), path("a.so")),
+                (repo, path("sub/b.so.1"))
+            ]
+        );
+    }
+}
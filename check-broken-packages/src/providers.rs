@@ -0,0 +1,122 @@
+//! Packages of the sync repositories providing missing libraries, looked up in the files
+//! databases, to tell whether installing or upgrading a package fixes a missing library, or
+//! whether only rebuilding the packages missing it does
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use simple_error::SimpleError;
+
+use crate::pacman;
+use crate::parse;
+use crate::tools;
+
+/// Directories where the dynamic loader finds libraries of repository packages, relative to the
+/// root like paths of the files databases
+const LIBRARY_DIRS: [&str; 2] = ["usr/lib", "usr/lib32"];
+
+/// Files databases to look up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilesDb {
+    /// Those of pacman, downloaded with pacman -Fy
+    Pacman,
+    /// Those of pkgfile, downloaded with pkgfile -u
+    Pkgfile,
+}
+
+/// Packages providing each missing library, as "repository/package", empty for libraries that no
+/// repository package provides
+pub type Providers = BTreeMap<String, BTreeSet<String>>;
+
+impl FilesDb {
+    /// Get files databases of the running system, those of pacman if downloaded, or those of
+    /// pkgfile if it is installed
+    pub fn get() -> Option<FilesDb> {
+        if has_pacman_files_dbs(Path::new(pacman::SYNC_DB_DIR)) {
+            Some(FilesDb::Pacman)
+        } else if tools::PKGFILE.is_available() {
+            Some(FilesDb::Pkgfile)
+        } else {
+            None
+        }
+    }
+
+    /// Get packages providing libraries in library directories, by soname
+    pub fn providers(self, sonames: &[&str]) -> Result<Providers, Box<dyn error::Error>> {
+        let mut providers: Providers = sonames
+            .iter()
+            .map(|s| (s.to_string(), BTreeSet::new()))
+            .collect();
+        match self {
+            FilesDb::Pacman => {
+                let output = Command::new("pacman")
+                    .args(["-F", "--machinereadable", "--"])
+                    .args(sonames)
+                    .output()?;
+                // Exit status is 1 if a soname has no match
+                if output.status.code().is_none_or(|c| c > 1) {
+                    return Err(Box::new(SimpleError::new(format!(
+                        "Failed to search files databases with pacman: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ))));
+                }
+                for (package, path) in parse::files_db_matches(&output.stdout)? {
+                    let path = Path::new(&path);
+                    if !path.parent().is_some_and(is_library_dir) {
+                        continue;
+                    }
+                    let soname = path.file_name().unwrap_or_default().to_string_lossy();
+                    if let Some(packages) = providers.get_mut(soname.as_ref()) {
+                        packages.insert(package);
+                    }
+                }
+            }
+            FilesDb::Pkgfile => {
+                for (soname, packages) in &mut providers {
+                    for dir in LIBRARY_DIRS {
+                        // Exit status is 1 if the path has no match
+                        let output = Command::new("pkgfile")
+                            .arg(format!("/{}/{}", dir, soname))
+                            .output()?;
+                        packages.extend(parse::package_names(&output.stdout)?);
+                    }
+                }
+            }
+        }
+        Ok(providers)
+    }
+}
+
+fn is_library_dir(dir: &Path) -> bool {
+    LIBRARY_DIRS.iter().any(|d| dir == Path::new(d))
+}
+
+/// Return true if a pacman files database is in a sync database directory
+fn has_pacman_files_dbs(sync_dir: &Path) -> bool {
+    fs::read_dir(sync_dir).is_ok_and(|mut e| {
+        e.any(|e| e.is_ok_and(|e| e.path().extension().is_some_and(|e| e == "files")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_files_dbs() {
+        let tmp_dir = TempDir::new("").unwrap();
+        assert!(!has_pacman_files_dbs(tmp_dir.path()));
+        fs::write(tmp_dir.path().join("core.db"), b"").unwrap();
+        assert!(!has_pacman_files_dbs(tmp_dir.path()));
+        fs::write(tmp_dir.path().join("core.files"), b"").unwrap();
+        assert!(has_pacman_files_dbs(tmp_dir.path()));
+
+        assert!(is_library_dir(Path::new("usr/lib32")));
+        assert!(!is_library_dir(Path::new("usr/lib/foo")));
+    }
+}
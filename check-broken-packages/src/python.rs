@@ -0,0 +1,392 @@
+//! Detection of packages with files ignored by the current Python interpreter
+
+use std::collections::{BTreeSet, HashSet};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::BufRead;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Mutex;
+use std::time;
+
+use crossbeam::thread as cb_thread;
+use glob::glob;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+
+use crate::cache;
+use crate::pacman::FileIndex;
+use crate::walk;
+
+pub struct PythonPackageVersion {
+    pub major: u8,
+    pub minor: u8,
+    release: u8,
+    package: u8,
+}
+
+impl fmt::Display for PythonPackageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}-{}",
+            self.major, self.minor, self.release, self.package
+        )
+    }
+}
+
+#[allow(clippy::needless_borrows_for_generic_args, clippy::lines_filter_map_ok)]
+pub fn get_python_version() -> Result<PythonPackageVersion, Box<dyn error::Error>> {
+    let output = Command::new("pacman")
+        .args(&["-Qi", "python"])
+        .env("LANG", "C")
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Box::new(SimpleError::new(
+            "Failed to query Python version with pacman",
+        )));
+    }
+
+    let version_line = output
+        .stdout
+        .lines()
+        .filter_map(Result::ok)
+        .find(|l| l.starts_with("Version"))
+        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to find version line"))?;
+    let version_str = version_line
+        .split(':')
+        .nth(1)
+        .ok_or_else(|| SimpleError::new("Unexpected pacman output: unable to parse version line"))?
+        .trim_start();
+
+    let mut dot_iter = version_str.split('.');
+    let major = u8::from_str(dot_iter.next().ok_or_else(|| {
+        SimpleError::new("Unexpected pacman output: unable to parse Python version major part")
+    })?)?;
+    let minor = u8::from_str(dot_iter.next().ok_or_else(|| {
+        SimpleError::new("Unexpected pacman output: unable to parse Python version minor part")
+    })?)?;
+    let mut dash_iter = dot_iter
+        .next()
+        .ok_or_else(|| {
+            SimpleError::new(
+                "Unexpected pacman output: unable to parse Python version release/package part",
+            )
+        })?
+        .split('-');
+    let release = u8::from_str(dash_iter.next().ok_or_else(|| {
+        SimpleError::new("Unexpected pacman output: unable to parse Python version release part")
+    })?)?;
+    let package = u8::from_str(dash_iter.next().ok_or_else(|| {
+        SimpleError::new("Unexpected pacman output: unable to parse Python version package part")
+    })?)?;
+
+    Ok(PythonPackageVersion {
+        major,
+        minor,
+        release,
+        package,
+    })
+}
+
+/// Python or Perl module directory ignored by the current interpreter
+#[derive(Serialize, Deserialize)]
+pub struct StaleDir {
+    pub dir: String,
+
+    /// Packages owning files in the directory, with their file count
+    pub owners: Vec<(String, usize)>,
+
+    /// Number of files in the directory not owned by any package, typically installed with pip or
+    /// cpan
+    pub unowned_file_count: usize,
+
+    /// Paths containing all unowned files and no file owned by a package, that can be removed
+    pub removable: Vec<String>,
+}
+
+const PYTHON_CHECK_CACHE_NAME: &str = "python";
+
+/// Format version of the Python check cache, to increase when `PythonCheckCache` changes
+const PYTHON_CHECK_CACHE_VERSION: u32 = 3;
+
+/// What the Python check result depends on, if it is unchanged the previous result is still valid
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct PythonCheckCacheKey {
+    python_version: String,
+
+    /// Checksums of the modification times of Python directories and all their subdirectories,
+    /// which change when files are added or removed anywhere in them
+    dir_checksums: Vec<(String, u32)>,
+}
+
+impl PythonCheckCacheKey {
+    fn new(python_version: &PythonPackageVersion, python_dirs: &[String]) -> PythonCheckCacheKey {
+        PythonCheckCacheKey {
+            python_version: python_version.to_string(),
+            dir_checksums: python_dirs
+                .iter()
+                .map(|d| (d.to_string(), tree_mtimes_checksum(Path::new(d))))
+                .collect(),
+        }
+    }
+}
+
+/// Get checksum of the paths and modification times of a directory and its subdirectories,
+/// without following links
+pub fn tree_mtimes_checksum(dir: &Path) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mtime = fs::symlink_metadata(&dir)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(time::UNIX_EPOCH).ok())
+            .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()));
+        hasher.update(dir.as_os_str().as_bytes());
+        hasher.update(&mtime.0.to_le_bytes());
+        hasher.update(&mtime.1.to_le_bytes());
+        let mut subdirs: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|e| e.path())
+                .collect(),
+            Err(_) => continue,
+        };
+        // Directory entries are listed in no particular order
+        subdirs.sort_unstable_by(|a, b| b.cmp(a));
+        dirs.extend(subdirs);
+    }
+    hasher.finalize()
+}
+
+#[derive(Serialize, Deserialize)]
+struct PythonCheckCache {
+    key: PythonCheckCacheKey,
+    stale_dirs: Vec<StaleDir>,
+}
+
+impl PythonPackageVersion {
+    /// Get directory of the interpreter modules
+    pub fn dir(&self) -> String {
+        format!("/usr/lib/python{}.{}", self.major, self.minor)
+    }
+}
+
+/// Get all Python directories for the same major version as the current interpreter
+pub fn get_python_dirs(
+    current_python_version: &PythonPackageVersion,
+) -> Result<Vec<String>, Box<dyn error::Error>> {
+    let mut python_dirs = Vec::new();
+    for python_dir_entry in glob(&format!("/usr/lib/python{}*", current_python_version.major))? {
+        let python_dir = python_dir_entry?
+            .into_os_string()
+            .into_string()
+            .map_err(|_| SimpleError::new("Failed to convert OS string to native string"))?;
+        python_dirs.push(python_dir);
+    }
+    Ok(python_dirs)
+}
+
+/// Get highest paths under a directory (or the directory itself) containing unowned files and no
+/// file owned by a package
+fn removable_paths(dir: &str, unowned_files: &[String], file_index: &FileIndex) -> Vec<String> {
+    let dir = Path::new(dir);
+    let mut owned_dirs = HashSet::new();
+    for file in file_index.files_under(&dir.to_string_lossy()) {
+        for ancestor in Path::new(file).ancestors().skip(1) {
+            if !owned_dirs.insert(ancestor) || (ancestor == dir) {
+                break;
+            }
+        }
+    }
+
+    let mut removable = BTreeSet::new();
+    for file in unowned_files {
+        let mut top = Path::new(file);
+        for ancestor in top.ancestors().skip(1) {
+            if owned_dirs.contains(ancestor) || !ancestor.starts_with(dir) {
+                break;
+            }
+            top = ancestor;
+        }
+        removable.insert(top.to_string_lossy().to_string());
+    }
+    removable.into_iter().collect()
+}
+
+/// Get Python directories ignored by the current interpreter, with the packages owning files
+/// there, and the files no package owns, walking directories with a number of threads
+pub fn get_broken_python_packages(
+    current_python_version: &PythonPackageVersion,
+    jobs: usize,
+    progress: &AtomicUsize,
+) -> Result<Vec<StaleDir>, Box<dyn error::Error>> {
+    let current_python_dir = current_python_version.dir();
+    let python_dirs = get_python_dirs(current_python_version)?;
+
+    let cache_key = PythonCheckCacheKey::new(current_python_version, &python_dirs);
+    if let Some(cache) =
+        cache::load::<PythonCheckCache>(PYTHON_CHECK_CACHE_NAME, PYTHON_CHECK_CACHE_VERSION)
+    {
+        if cache.key == cache_key {
+            debug!("Using cached Python check result");
+            return Ok(cache.stale_dirs);
+        }
+    }
+
+    let stale_dirs: Vec<String> = python_dirs
+        .into_iter()
+        .filter(|d| d != &current_python_dir)
+        .collect();
+    let stale_dirs = inspect_stale_dirs(stale_dirs, jobs, progress)?;
+
+    let cache = PythonCheckCache {
+        key: cache_key,
+        stale_dirs,
+    };
+    if let Err(err) = cache::store(PYTHON_CHECK_CACHE_NAME, PYTHON_CHECK_CACHE_VERSION, &cache) {
+        debug!("Failed to store Python check result in cache: {}", err);
+    }
+
+    Ok(cache.stale_dirs)
+}
+
+/// Get the packages owning files in directories ignored by the current interpreter, and the files
+/// no package owns, walking directories with a number of threads
+pub fn inspect_stale_dirs(
+    stale_dirs: Vec<String>,
+    jobs: usize,
+    progress: &AtomicUsize,
+) -> Result<Vec<StaleDir>, Box<dyn error::Error>> {
+    if stale_dirs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let file_index = FileIndex::new()?;
+
+    // Find files owned by no package, like pip leftovers
+    let unowned_files = Mutex::new(Vec::new());
+    let roots: Vec<&Path> = stale_dirs.iter().map(Path::new).collect();
+    walk::walk(&roots, jobs, &[], progress, |chunk| {
+        let unowned = chunk
+            .iter()
+            .filter_map(|p| p.to_str())
+            .filter(|p| file_index.owner(p).is_none())
+            .map(str::to_string);
+        unowned_files.lock().unwrap().extend(unowned);
+    });
+    let unowned_files = unowned_files.into_inner().unwrap();
+
+    // Resolve ownership of each directory in parallel
+    let file_index = &file_index;
+    let unowned_files = &unowned_files;
+    let stale_dirs = cb_thread::scope(|scope| {
+        let handles: Vec<_> = stale_dirs
+            .into_iter()
+            .map(|dir| {
+                scope.spawn(move |_| {
+                    let owners = file_index.owners_under(&dir);
+                    let prefix = format!("{}/", dir);
+                    let dir_unowned_files: Vec<String> = unowned_files
+                        .iter()
+                        .filter(|f| f.starts_with(&prefix))
+                        .cloned()
+                        .collect();
+                    let removable = removable_paths(&dir, &dir_unowned_files, file_index);
+                    StaleDir {
+                        dir,
+                        owners,
+                        unowned_file_count: dir_unowned_files.len(),
+                        removable,
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|d| !d.owners.is_empty() || (d.unowned_file_count > 0))
+            .collect()
+    })
+    .map_err(|_| SimpleError::new("Directory ownership worker panicked"))?;
+
+    Ok(stale_dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_tree_mtimes_checksum() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let nested_dir = tmp_dir.path().join("site-packages/foo/bar");
+        fs::create_dir_all(&nested_dir).unwrap();
+        let checksum = tree_mtimes_checksum(tmp_dir.path());
+        assert_eq!(tree_mtimes_checksum(tmp_dir.path()), checksum);
+
+        // Files added deep in the tree change it
+        fs::write(nested_dir.join("baz.py"), "").unwrap();
+        let new_mtime = time::SystemTime::now() + time::Duration::from_secs(10);
+        fs::File::open(&nested_dir)
+            .unwrap()
+            .set_modified(new_mtime)
+            .unwrap();
+        assert_ne!(tree_mtimes_checksum(tmp_dir.path()), checksum);
+    }
+
+    #[test]
+    fn test_removable_paths() {
+        let package = Arc::new("python-foo".to_string());
+        let mut files = BTreeMap::new();
+        for file in &[
+            "/usr/lib/python3.8/site-packages/foo/__init__.py",
+            "/usr/lib/python3.8/site-packages/mixed/a.py",
+        ] {
+            files.insert(file.to_string(), Arc::clone(&package));
+        }
+        let file_index = FileIndex { files };
+        let unowned_files: Vec<String> = [
+            "/usr/lib/python3.8/site-packages/bar/__init__.py",
+            "/usr/lib/python3.8/site-packages/bar/sub/baz.py",
+            "/usr/lib/python3.8/site-packages/bar-1.0.dist-info/RECORD",
+            "/usr/lib/python3.8/site-packages/mixed/b.py",
+        ]
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+
+        // Mixed with packaged files
+        assert_eq!(
+            removable_paths("/usr/lib/python3.8", &unowned_files, &file_index),
+            [
+                "/usr/lib/python3.8/site-packages/bar",
+                "/usr/lib/python3.8/site-packages/bar-1.0.dist-info",
+                "/usr/lib/python3.8/site-packages/mixed/b.py"
+            ]
+        );
+
+        // Only unowned files
+        assert_eq!(
+            removable_paths(
+                "/usr/lib/python3.7",
+                &["/usr/lib/python3.7/site-packages/bar/__init__.py".to_string()],
+                &file_index
+            ),
+            ["/usr/lib/python3.7"]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Generation of a shell script rebuilding packages from their PKGBUILD checkouts, and of lists
+//! of packages to rebuild for AUR helpers
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::format::AtomicFile;
+use crate::pacman::LocalPackage;
+use crate::report::Finding;
+
+/// Findings whose packages are listed by --list-rebuilds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RebuildList {
+    /// Packages with files in stale Python directories
+    Python,
+    /// Packages with files missing shared libraries
+    Libs,
+}
+
+impl FromStr for RebuildList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "python" => Ok(RebuildList::Python),
+            "libs" => Ok(RebuildList::Libs),
+            _ => Err(format!(
+                "Invalid rebuild list {:?}, expected 'python' or 'libs'",
+                s
+            )),
+        }
+    }
+}
+
+impl RebuildList {
+    /// Get package a finding lists, if it is of the listed kind and a rebuild fixes it
+    pub fn package<'a>(&self, finding: &'a Finding) -> Option<&'a str> {
+        match (self, finding) {
+            (RebuildList::Python, Finding::StalePythonDir { .. })
+            | (RebuildList::Libs, Finding::MissingDependency { .. })
+            | (RebuildList::Libs, Finding::AudioPluginMissingDependency { .. }) => {
+                finding.rebuild_package()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Get name of a dependency or provide without its version constraint, like "libfoo" from
+/// "libfoo>=1"
+pub fn unversioned(name: &str) -> &str {
+    name.split(['<', '>', '=']).next().unwrap_or(name)
+}
+
+/// Sort packages so that each comes after the packages of the set it depends on, directly or
+/// through a provide, in name order otherwise. Packages in a dependency cycle come last.
+pub fn rebuild_order(packages: &BTreeSet<String>, local_packages: &[LocalPackage]) -> Vec<String> {
+    // Package of the set for each name it can be depended on with
+    let mut providers: HashMap<&str, &str> = HashMap::new();
+    for package in local_packages.iter().filter(|p| packages.contains(&p.name)) {
+        providers.insert(&package.name, &package.name);
+        for provide in &package.provides {
+            providers.insert(unversioned(provide), &package.name);
+        }
+    }
+
+    let mut depends: BTreeMap<&str, BTreeSet<&str>> = packages
+        .iter()
+        .map(|p| (p.as_str(), BTreeSet::new()))
+        .collect();
+    for package in local_packages.iter().filter(|p| packages.contains(&p.name)) {
+        let package_depends = depends.get_mut(package.name.as_str()).unwrap();
+        for dependency in &package.depends {
+            if let Some(provider) = providers.get(unversioned(dependency)) {
+                if *provider != package.name {
+                    package_depends.insert(provider);
+                }
+            }
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut done = HashSet::new();
+    while done.len() < depends.len() {
+        let ready: Vec<&str> = depends
+            .iter()
+            .filter(|(p, d)| !done.contains(*p) && d.iter().all(|d| done.contains(d)))
+            .map(|(p, _)| *p)
+            .collect();
+        if ready.is_empty() {
+            // Cycle
+            order.extend(
+                depends
+                    .keys()
+                    .filter(|p| !done.contains(*p))
+                    .map(|p| p.to_string()),
+            );
+            break;
+        }
+        for package in ready {
+            done.insert(package);
+            order.push(package.to_string());
+        }
+    }
+    order
+}
+
+/// Quote string for a POSIX shell
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Build script rebuilding packages in order from their checkout directories, packages of the
+/// same checkout (split packages) are built once
+pub fn script(packages: &[String], checkouts: &BTreeMap<String, PathBuf>) -> String {
+    let mut lines = vec![
+        "#!/bin/sh".to_string(),
+        format!(
+            "# Rebuild packages with findings of {}, in dependency order",
+            env!("CARGO_PKG_NAME")
+        ),
+        "set -e".to_string(),
+    ];
+    let mut built_dirs = HashSet::new();
+    let mut missing = Vec::new();
+    for package in packages {
+        match checkouts.get(package) {
+            Some(dir) => {
+                if built_dirs.insert(dir) {
+                    lines.push(format!(
+                        "(cd {} && makepkg -si)  # {}",
+                        shell_quote(&dir.to_string_lossy()),
+                        package
+                    ));
+                }
+            }
+            None => missing.push(package.as_str()),
+        }
+    }
+    if !missing.is_empty() {
+        lines.push(format!(
+            "# No PKGBUILD checkout found for: {}",
+            missing.join(", ")
+        ));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Write executable script file, atomically
+pub fn write_script(filepath: &Path, content: &str) -> Result<(), Box<dyn error::Error>> {
+    let mut file = AtomicFile::create(filepath)?;
+    file.write_all(content.as_bytes())?;
+    file.file()
+        .set_permissions(fs::Permissions::from_mode(0o755))?;
+    file.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuild_list() {
+        let list: RebuildList = "python".parse().unwrap();
+        let stale = Finding::StalePythonDir {
+            package: "python-foo".to_string(),
+            dir: "/usr/lib/python3.12".to_string(),
+            file_count: 3,
+        };
+        assert_eq!(list.package(&stale), Some("python-foo"));
+        let link = Finding::BrokenServiceLink {
+            link: "/etc/systemd/system/foo.service".to_string(),
+        };
+        assert_eq!(list.package(&link), None);
+
+        let list: RebuildList = "libs".parse().unwrap();
+        let missing = |held_back: Option<&str>| Finding::MissingDependency {
+            package: "foo".to_string(),
+            file: "/usr/bin/foo".to_string(),
+            dependency: "libbar.so.1".to_string(),
+            held_back: held_back.map(str::to_string),
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+        assert_eq!(list.package(&missing(None)), Some("foo"));
+        // Fixed by upgrading the held back package instead
+        assert_eq!(list.package(&missing(Some("bar"))), None);
+        assert_eq!(list.package(&stale), None);
+        assert!("perl".parse::<RebuildList>().is_err());
+    }
+
+    #[test]
+    fn test_rebuild_script() {
+        let local_package = |name: &str, provides: &[&str], depends: &[&str]| LocalPackage {
+            name: name.to_string(),
+            version: "1-1".to_string(),
+            arch: String::new(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+            depends: depends.iter().map(|d| d.to_string()).collect(),
+            groups: Vec::new(),
+            installed_size: 0,
+            install_date: 0,
+            files: Vec::new(),
+        };
+        let local_packages = [
+            local_package("app", &[], &["libbar>=2", "glibc"]),
+            local_package("bar", &["libbar=2"], &["foo-cli"]),
+            local_package("foo-cli", &[], &[]),
+            local_package("foo-gui", &[], &["foo-cli"]),
+            local_package("zzz", &[], &[]),
+        ];
+        let packages: BTreeSet<String> = ["app", "bar", "foo-cli", "foo-gui", "zzz"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let order = rebuild_order(&packages, &local_packages);
+        assert_eq!(order, ["foo-cli", "zzz", "bar", "foo-gui", "app"]);
+
+        let checkouts: BTreeMap<String, PathBuf> = vec![
+            ("foo-cli", "/build/foo"),
+            ("foo-gui", "/build/foo"),
+            ("bar", "/build/it's bar"),
+            ("app", "/build/app"),
+        ]
+        .into_iter()
+        .map(|(p, d)| (p.to_string(), PathBuf::from(d)))
+        .collect();
+        let script = script(&order, &checkouts);
+        let lines: Vec<&str> = script.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            [
+                "(cd '/build/foo' && makepkg -si)  # foo-cli",
+                "(cd '/build/it'\\''s bar' && makepkg -si)  # bar",
+                "(cd '/build/app' && makepkg -si)  # app",
+                "# No PKGBUILD checkout found for: zzz",
+            ]
+        );
+    }
+}
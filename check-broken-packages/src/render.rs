@@ -0,0 +1,630 @@
+//! Human readable rendering of findings
+
+use std::io::{self, IsTerminal};
+
+use ansi_term::Colour::*;
+use indicatif::HumanBytes;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::i18n::{self, Catalog};
+use crate::report::{
+    FileDamage, FileKind, Finding, HardeningIssue, ReferenceKind, Severity, TargetProblem,
+};
+use crate::severity::SeverityOverrides;
+
+/// Indentation of wrapped lines
+const WRAP_INDENT: &str = "  ";
+
+/// Minimum width of a value before it gets truncated
+const MIN_VALUE_WIDTH: usize = 24;
+
+/// Maximum number of removable paths in the suggested command of unowned Python files
+const MAX_REMOVABLE_DISPLAY: usize = 3;
+
+/// Build "1 file" / "2 files" like strings
+pub fn plural(count: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", count, if count == 1 { singular } else { plural })
+}
+
+/// Truncate string to a maximum display width by replacing its middle part with an ellipsis
+pub fn truncate_middle(s: &str, max_width: usize) -> String {
+    if (s.width() <= max_width) || (max_width < 3) {
+        return s.to_string();
+    }
+    let side_width = (max_width - 1) / 2;
+
+    let mut head = String::new();
+    let mut head_width = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if head_width + w > side_width {
+            break;
+        }
+        head.push(c);
+        head_width += w;
+    }
+
+    let mut tail = Vec::new();
+    let mut tail_width = 0;
+    for c in s.chars().rev() {
+        let w = c.width().unwrap_or(0);
+        if tail_width + w > max_width - 1 - head_width {
+            break;
+        }
+        tail.push(c);
+        tail_width += w;
+    }
+
+    format!("{}…{}", head, tail.into_iter().rev().collect::<String>())
+}
+
+/// Wrap words of a string to a maximum display width, indenting continuation lines
+pub fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in s.split(' ') {
+        if !line.is_empty() && (line.width() + 1 + word.width() > width) {
+            lines.push(line);
+            line = WRAP_INDENT.to_string();
+        } else if !line.is_empty() && (line != WRAP_INDENT) {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// Renders findings for display
+pub struct Renderer {
+    /// Output width for wrapping and truncation, None to disable both
+    width: Option<usize>,
+
+    /// Prefix findings with a glyph identifying their type
+    glyphs: bool,
+
+    /// Severity of findings, for their color
+    severities: SeverityOverrides,
+
+    /// Translations of the user language, if any
+    catalog: Option<&'static Catalog>,
+}
+
+impl Renderer {
+    /// Create renderer for standard output, wrapping if it is a terminal
+    pub fn new(glyphs: bool, severities: SeverityOverrides) -> Renderer {
+        let width = if io::stdout().is_terminal() {
+            terminal_size::terminal_size().map(|(w, _h)| w.0 as usize)
+        } else {
+            None
+        };
+        Renderer {
+            width,
+            glyphs,
+            severities,
+            catalog: i18n::catalog(),
+        }
+    }
+
+    /// Format value (file path, package name...) for display
+    fn value(&self, s: &str) -> String {
+        let s = match self.width {
+            Some(width) => truncate_middle(s, (width / 2).max(MIN_VALUE_WIDTH)),
+            None => s.to_string(),
+        };
+        format!("'{}'", s)
+    }
+
+    fn glyph(finding: &Finding) -> &'static str {
+        match finding {
+            Finding::MissingDependency { .. } => "🔗",
+            Finding::AudioPluginMissingDependency { .. } => "🎵",
+            Finding::UpgradeRemovesDependency { .. } => "🔮",
+            Finding::UndeclaredDependency { .. } => "📦",
+            Finding::StalePythonDir { .. } => "🐍",
+            Finding::StalePythonLeftovers { .. } | Finding::StalePerlLeftovers { .. } => "🧹",
+            Finding::StalePerlDir { .. } => "🐪",
+            Finding::BrokenServiceLink { .. } => "⚙️",
+            Finding::ShadowingLibraryDir { .. } => "🌘",
+            Finding::CheckTimedOut { .. } => "⏱️",
+            Finding::StaleCache { .. } => "🗃️",
+            Finding::InvalidFile { .. } => "📄",
+            Finding::LostCapabilities { .. } => "🛡️",
+            Finding::DamagedFile { .. } => "💥",
+            Finding::UnreadableFile { .. } => "🔒",
+            Finding::LoaderError { .. } => "🧨",
+            Finding::ForeignArchitecture { .. } | Finding::ArchitectureSpecificFiles { .. } => "🧬",
+            Finding::WeakHardening { .. } => "🪖",
+            Finding::MissingDebugInfo { .. } => "🐞",
+            Finding::DuplicateExecutable { .. } => "👯",
+            Finding::OldPackage { .. } => "⏳",
+            Finding::GpuRuntimeMismatch { .. } | Finding::GpuDriverTooOld { .. } => "🧮",
+            Finding::MissingMultilibLibrary { .. } => "🍷",
+            Finding::BrokenReference { kind, .. } => match kind {
+                ReferenceKind::NativeMessagingHost => "🧩",
+                ReferenceKind::PamModule | ReferenceKind::NssModule => "🔐",
+                ReferenceKind::PreloadedLibrary => "💉",
+                ReferenceKind::EnvironmentPath => "🪧",
+                ReferenceKind::MkinitcpioEntry => "👢",
+                ReferenceKind::ScheduledJob => "⏰",
+                ReferenceKind::AutostartEntry => "🚀",
+                ReferenceKind::ExecutableLink => "🔗",
+                ReferenceKind::JavaExecutableLink | ReferenceKind::JavaRuntime => "☕",
+                ReferenceKind::PolkitAction | ReferenceKind::DBusService => "📨",
+                ReferenceKind::UdevRule => "🔌",
+                ReferenceKind::GraphicsDriver => "🎮",
+                ReferenceKind::LinkerConfig => "🧭",
+            },
+        }
+    }
+
+    /// Get values of the fields of a finding, for the placeholders of translated messages
+    fn fields(&self, finding: &Finding) -> Vec<(String, String)> {
+        fn field_value(renderer: &Renderer, value: &serde_json::Value) -> String {
+            match value {
+                serde_json::Value::String(s) => renderer.value(s),
+                serde_json::Value::Array(a) => a
+                    .iter()
+                    .map(|v| field_value(renderer, v))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                serde_json::Value::Object(o) => o
+                    .values()
+                    .map(|v| field_value(renderer, v))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                serde_json::Value::Null => String::new(),
+                v => v.to_string(),
+            }
+        }
+        match serde_json::to_value(finding) {
+            Ok(serde_json::Value::Object(fields)) => fields
+                .iter()
+                .filter(|(name, _v)| *name != "type")
+                .map(|(name, value)| (name.clone(), field_value(self, value)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get finding message without decoration
+    pub fn message(&self, finding: &Finding) -> String {
+        if let Some(template) = self.catalog.and_then(|c| c.finding(finding.code())) {
+            return i18n::fill(template, &self.fields(finding));
+        }
+        match finding {
+            Finding::MissingDependency {
+                package,
+                file,
+                dependency,
+                held_back,
+                chain,
+                transitive,
+                unshipped,
+            } => {
+                let mut message = format!(
+                    "File {} from package {} is missing dependency {}",
+                    self.value(file),
+                    self.value(package),
+                    self.value(dependency)
+                );
+                if !chain.is_empty() {
+                    message.push_str(&format!(
+                        " through {}",
+                        chain.iter().map(|s| self.value(s)).collect::<Vec<_>>().join(" → ")
+                    ));
+                }
+                if let Some(transitive) = transitive {
+                    message.push_str(&format!(
+                        ", transitively because library {} from package {} is itself missing it, fix that package rather than rebuilding",
+                        self.value(&transitive.file),
+                        self.value(&transitive.package)
+                    ));
+                }
+                if let Some(held_back) = held_back {
+                    message.push_str(&format!(
+                        ", likely because package {} is held back by IgnorePkg or IgnoreGroup (partial upgrade), upgrade it rather than rebuilding",
+                        self.value(held_back)
+                    ));
+                }
+                if *unshipped {
+                    message.push_str(", the upstream binary requires a library Arch no longer ships, rebuilding does not fix it: upgrade to a more recent upstream release, or install a compatibility package of the library");
+                }
+                message
+            }
+            Finding::AudioPluginMissingDependency {
+                package,
+                file,
+                format,
+                dependency,
+            } => format!(
+                "{} audio plugin {} from package {} is missing dependency {}",
+                format,
+                self.value(file),
+                self.value(package),
+                self.value(dependency)
+            ),
+            Finding::UpgradeRemovesDependency {
+                package,
+                file,
+                dependency,
+                provider,
+                provider_version,
+            } => format!(
+                "File {} from package {} will miss dependency {} after package {} is upgraded to {}, rebuild it after the upgrade",
+                self.value(file),
+                self.value(package),
+                self.value(dependency),
+                self.value(provider),
+                provider_version
+            ),
+            Finding::UndeclaredDependency {
+                package,
+                file,
+                dependency,
+                provider,
+            } => format!(
+                "File {} from package {} links {} from package {}, which is not a declared dependency, add it to the package dependencies",
+                self.value(file),
+                self.value(package),
+                self.value(dependency),
+                self.value(provider)
+            ),
+            Finding::StalePythonDir {
+                package,
+                dir,
+                file_count,
+            } => format!(
+                "Package {} has {} in directory {} that {} ignored by the current Python interpreter, rebuild it or remove it",
+                self.value(package),
+                plural(*file_count, "file", "files"),
+                self.value(dir),
+                if *file_count == 1 { "is" } else { "are" }
+            ),
+            Finding::StalePerlDir {
+                package,
+                dir,
+                file_count,
+            } => format!(
+                "Package {} has {} in directory {} of a previous Perl version that {} ignored by the current Perl interpreter, rebuild it or remove it",
+                self.value(package),
+                plural(*file_count, "file", "files"),
+                self.value(dir),
+                if *file_count == 1 { "is" } else { "are" }
+            ),
+            Finding::StalePythonLeftovers {
+                dir,
+                file_count,
+                removable,
+            }
+            | Finding::StalePerlLeftovers {
+                dir,
+                file_count,
+                removable,
+            } => {
+                let (installer, interpreter) = match finding {
+                    Finding::StalePerlLeftovers { .. } => ("cpan", "Perl"),
+                    _ => ("pip", "Python"),
+                };
+                let mut command = format!("rm -ri {}", removable.iter().take(MAX_REMOVABLE_DISPLAY).map(|p| self.value(p)).collect::<Vec<_>>().join(" "));
+                if removable.len() > MAX_REMOVABLE_DISPLAY {
+                    command.push_str(&format!(" … ({} more)", removable.len() - MAX_REMOVABLE_DISPLAY));
+                }
+                format!(
+                    "Directory {} has {} not owned by any package (likely installed with {}) that {} ignored by the current {} interpreter, review and remove {} with {}",
+                    self.value(dir),
+                    plural(*file_count, "file", "files"),
+                    installer,
+                    if *file_count == 1 { "is" } else { "are" },
+                    interpreter,
+                    if removable.first() == Some(dir) { "the directory" } else if *file_count == 1 { "it" } else { "them" },
+                    command
+                )
+            }
+            Finding::ShadowingLibraryDir {
+                source,
+                dir,
+                library,
+                library_count,
+            } => format!(
+                "Directory {} of dynamic linker configuration {} has {} {} system libraries like {}, which it shadows",
+                self.value(dir),
+                self.value(source),
+                library_count,
+                if *library_count > 1 {
+                    "libraries with the same name as"
+                } else {
+                    "library with the same name as"
+                },
+                self.value(library)
+            ),
+            Finding::BrokenServiceLink { link } => {
+                format!("Systemd enabled service has broken link in {}", self.value(link))
+            }
+            Finding::CheckTimedOut { check, timeout } => format!(
+                "Check {} timed out after {}s, its findings are partial",
+                self.value(check),
+                timeout
+            ),
+            Finding::StaleCache {
+                package,
+                cache,
+                command,
+                file_count,
+            } => format!(
+                "Package {} has {} newer than cache {}, run {} to update it",
+                self.value(package),
+                plural(*file_count, "file", "files"),
+                self.value(cache),
+                self.value(command)
+            ),
+            Finding::LostCapabilities { package, file } => format!(
+                "File {} from package {} has lost its file capabilities, reinstall the package to restore them",
+                self.value(file),
+                self.value(package)
+            ),
+            Finding::DamagedFile {
+                package,
+                file,
+                damage,
+            } => format!(
+                "File {} from package {} {}, reinstall the package to restore it",
+                self.value(file),
+                self.value(package),
+                match damage {
+                    FileDamage::Empty => "is empty",
+                    FileDamage::TruncatedElf => "is a truncated ELF file",
+                    FileDamage::CorruptedElf => "is a corrupted ELF file",
+                }
+            ),
+            Finding::UnreadableFile { package, file } => format!(
+                "File {} from package {} can not be read by the current user, it was not inspected",
+                self.value(file),
+                self.value(package)
+            ),
+            Finding::LoaderError {
+                package,
+                file,
+                error,
+            } => format!(
+                "File {} from package {} can not be loaded: {}",
+                self.value(file),
+                self.value(package),
+                self.value(error)
+            ),
+            Finding::ForeignArchitecture {
+                package,
+                file,
+                architecture,
+                package_architecture,
+            } => format!(
+                "File {} from package {} is built for architecture {}{}, the package is likely for the wrong architecture",
+                self.value(file),
+                self.value(package),
+                architecture,
+                match package_architecture {
+                    Some(a) => format!(" while the package is built for {}", a),
+                    None => String::new(),
+                }
+            ),
+            Finding::ArchitectureSpecificFiles {
+                package,
+                file,
+                architecture,
+                file_count,
+            } => format!(
+                "Package {} is declared architecture independent but has {} built for a specific architecture, like {} built for {}, its architecture should be {}",
+                self.value(package),
+                plural(*file_count, "ELF file", "ELF files"),
+                self.value(file),
+                architecture,
+                architecture
+            ),
+            Finding::WeakHardening {
+                package,
+                file,
+                issues,
+            } => format!(
+                "File {} from package {} is built without hardening ({}), check the package build flags",
+                self.value(file),
+                self.value(package),
+                issues
+                    .iter()
+                    .map(|i| match i {
+                        HardeningIssue::ExecutableStack => "executable stack",
+                        HardeningIssue::NoRelro => "no RELRO",
+                        HardeningIssue::NoPie => "not PIE",
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Finding::MissingDebugInfo {
+                package,
+                file,
+                build_id,
+            } => format!(
+                "File {} from package {} has no debug information{}, build the package with the debug option and install its debug package to get symbolized backtraces",
+                self.value(file),
+                self.value(package),
+                match build_id {
+                    Some(id) => format!(" (build-id {})", id),
+                    None => " and no build-id".to_string(),
+                }
+            ),
+            Finding::DuplicateExecutable { size, copies } => format!(
+                "Identical executables {} waste {}, the packages could share a single copy",
+                copies
+                    .iter()
+                    .map(|(p, f)| format!("{} (package {})", self.value(f), self.value(p)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                HumanBytes(size * (copies.len() as u64 - 1))
+            ),
+            Finding::OldPackage { package, age_days } => format!(
+                "Package {} was not rebuilt or upgraded for {}, old builds are the most likely to break, consider rebuilding it",
+                self.value(package),
+                plural(*age_days as usize, "day", "days")
+            ),
+            Finding::GpuRuntimeMismatch {
+                package,
+                file,
+                runtime,
+                dependency,
+                installed,
+            } => format!(
+                "File {} from package {} needs {} library {}, but only {} {} installed, rebuild the package for the installed {} version or install the version it needs",
+                self.value(file),
+                self.value(package),
+                runtime,
+                self.value(dependency),
+                installed
+                    .iter()
+                    .map(|s| self.value(s))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if installed.len() == 1 { "is" } else { "are" },
+                runtime
+            ),
+            Finding::GpuDriverTooOld {
+                package,
+                file,
+                dependency,
+                driver_version,
+                required_version,
+            } => format!(
+                "File {} from package {} links CUDA runtime {} which needs NVIDIA driver {} or newer, but driver {} is installed, upgrade the driver or use a package built for an older CUDA version",
+                self.value(file),
+                self.value(package),
+                self.value(dependency),
+                required_version,
+                driver_version
+            ),
+            Finding::MissingMultilibLibrary {
+                package,
+                file,
+                dependency,
+                suggestion,
+            } => format!(
+                "File {} from package {} needs the 32-bit version of library {}, which is not installed{}",
+                self.value(file),
+                self.value(package),
+                self.value(dependency),
+                match suggestion {
+                    Some(suggestion) => format!(", install package {}", self.value(suggestion)),
+                    None => String::new(),
+                }
+            ),
+            Finding::InvalidFile {
+                kind,
+                package,
+                file,
+                error,
+            } => format!(
+                "{} {} from package {} is invalid: {}",
+                match kind {
+                    FileKind::GSettingsSchema => "GSettings schema",
+                    FileKind::SystemdUnit => "Systemd unit",
+                },
+                self.value(file),
+                self.value(package),
+                error
+            ),
+            Finding::BrokenReference {
+                kind,
+                package,
+                source,
+                target,
+                problem,
+            } => format!(
+                "{} {} from package {} references {} which {}{}",
+                match kind {
+                    ReferenceKind::NativeMessagingHost => "Browser native messaging host manifest",
+                    ReferenceKind::PamModule => "PAM configuration",
+                    ReferenceKind::NssModule => "NSS configuration",
+                    ReferenceKind::MkinitcpioEntry => "Mkinitcpio configuration",
+                    ReferenceKind::ScheduledJob => "Scheduled job",
+                    ReferenceKind::AutostartEntry => "XDG autostart entry",
+                    ReferenceKind::ExecutableLink | ReferenceKind::JavaExecutableLink =>
+                        "Executable symbolic link",
+                    ReferenceKind::JavaRuntime => "Java launcher",
+                    ReferenceKind::PolkitAction => "Polkit policy",
+                    ReferenceKind::DBusService => "D-Bus service",
+                    ReferenceKind::UdevRule => "Udev rules",
+                    ReferenceKind::GraphicsDriver => "Graphics driver configuration",
+                    ReferenceKind::LinkerConfig => "Dynamic linker configuration",
+                    ReferenceKind::PreloadedLibrary => "Library preload configuration",
+                    ReferenceKind::EnvironmentPath => "Environment configuration",
+                },
+                self.value(source),
+                self.value(package),
+                self.value(target),
+                match problem {
+                    TargetProblem::Missing => "does not exist".to_string(),
+                    TargetProblem::NotExecutable => "is not executable".to_string(),
+                    TargetProblem::MissingDependency(dependency) =>
+                        format!("is missing dependency {}", self.value(dependency)),
+                    TargetProblem::MissingInterpreter(interpreter) =>
+                        format!("has missing interpreter {}", self.value(interpreter)),
+                    TargetProblem::NotFound(dirs) =>
+                        format!("was not found in {}", self.value(dirs)),
+                },
+                match kind {
+                    ReferenceKind::JavaExecutableLink =>
+                        ", select an installed Java environment with 'archlinux-java fix'",
+                    ReferenceKind::JavaRuntime =>
+                        ", install the Java environment package providing it",
+                    _ => "",
+                }
+            ),
+        }
+    }
+
+    /// Render finding for display, possibly on several lines
+    pub fn finding(&self, finding: &Finding) -> String {
+        let mut s = format!("[{}] {}", finding.code(), self.message(finding));
+        if self.glyphs {
+            s = format!("{} {}", Self::glyph(finding), s);
+        }
+        let lines = match self.width {
+            Some(width) => wrap(&s, width),
+            None => vec![s],
+        };
+        lines
+            .iter()
+            .map(|l| match self.severities.severity(finding) {
+                Severity::Info => Cyan.paint(l).to_string(),
+                Severity::Warning => Yellow.paint(l).to_string(),
+                Severity::Error => Red.paint(l).to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plural() {
+        assert_eq!(plural(0, "file", "files"), "0 files");
+        assert_eq!(plural(1, "file", "files"), "1 file");
+        assert_eq!(plural(2, "file", "files"), "2 files");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(truncate_middle("/usr/bin/foo", 12), "/usr/bin/foo");
+        assert_eq!(truncate_middle("/usr/bin/foo", 11), "/usr/…n/foo");
+        assert_eq!(truncate_middle("/usr/bin/foo", 6), "/u…foo");
+        assert_eq!(truncate_middle("/usr/bin/foo", 2), "/usr/bin/foo");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("aaa bbb ccc", 20), ["aaa bbb ccc"]);
+        assert_eq!(wrap("aaa bbb ccc", 7), ["aaa bbb", "  ccc"]);
+        assert_eq!(wrap("aaa bbb ccc", 5), ["aaa", "  bbb", "  ccc"]);
+        assert_eq!(wrap("aaaaaaaa bbb", 5), ["aaaaaaaa", "  bbb"]);
+    }
+}
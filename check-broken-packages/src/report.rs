@@ -0,0 +1,1629 @@
+//! Findings and their aggregation into a report
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Filter;
+use crate::format::{self, AtomicFile};
+use crate::ignore::IgnoreRule;
+use crate::plugins;
+use crate::severity::SeverityOverrides;
+
+/// Format version of the report file, to increase on incompatible changes of findings
+const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// JSON Schema of the report file lines, also describing findings printed with --json, versioned
+/// with the report format
+pub const REPORT_SCHEMA: &str = include_str!("../report.schema.json");
+const REPORT_SCHEMA_ID: &str = "urn:check-broken-packages:report:1";
+
+/// Something wrong found by one of the checks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Finding {
+    /// Package executable file with a missing dynamic library
+    MissingDependency {
+        package: String,
+        file: String,
+        dependency: String,
+        /// Package held back from upgrades that likely causes the dependency to be missing
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        held_back: Option<String>,
+        /// Sonames of the libraries through which the dependency is needed, empty if the file
+        /// itself needs it, or if the dependency tree was not resolved
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        chain: Vec<String>,
+        /// Library of another package through which the dependency is needed, that is itself
+        /// missing it: fixing that package fixes this finding
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transitive: Option<BrokenLibrary>,
+        /// The package repackages an upstream binary needing a version of the library that the
+        /// repositories no longer ship: a rebuild does not fix it
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        unshipped: bool,
+    },
+
+    /// Audio plugin from a package with a missing dynamic library
+    AudioPluginMissingDependency {
+        package: String,
+        file: String,
+        format: String,
+        dependency: String,
+    },
+
+    /// Package executable file with a dynamic library that a pending upgrade removes
+    UpgradeRemovesDependency {
+        package: String,
+        file: String,
+        dependency: String,
+        /// Package providing the library, and its version after upgrade
+        provider: String,
+        provider_version: String,
+    },
+
+    /// Package ELF file linking a library of a package that is not a declared dependency
+    UndeclaredDependency {
+        package: String,
+        file: String,
+        dependency: String,
+        /// Package owning the library
+        provider: String,
+    },
+
+    /// Package with files in a Python directory ignored by the current interpreter
+    StalePythonDir {
+        package: String,
+        dir: String,
+        file_count: usize,
+    },
+
+    /// Files owned by no package (typically installed with pip) in a Python directory ignored by
+    /// the current interpreter
+    StalePythonLeftovers {
+        dir: String,
+        file_count: usize,
+        /// Paths containing only those files, that can be removed
+        removable: Vec<String>,
+    },
+
+    /// Package with files in a Perl directory of a previous version, ignored by the current
+    /// interpreter
+    StalePerlDir {
+        package: String,
+        dir: String,
+        file_count: usize,
+    },
+
+    /// Files owned by no package (typically installed with cpan) in a Perl directory of a previous
+    /// version, ignored by the current interpreter
+    StalePerlLeftovers {
+        dir: String,
+        file_count: usize,
+        /// Paths containing only those files, that can be removed
+        removable: Vec<String>,
+    },
+
+    /// Broken symbolic link for an enabled systemd service
+    BrokenServiceLink { link: String },
+
+    /// Directory of the dynamic linker configuration added by the user, with libraries of the
+    /// same name as system ones, that it shadows
+    ShadowingLibraryDir {
+        /// Configuration file declaring the directory
+        source: String,
+        dir: String,
+        /// First shadowed library
+        library: String,
+        library_count: usize,
+    },
+
+    /// Check cancelled because it exceeded its time budget, its other findings are partial
+    CheckTimedOut { check: String, timeout: u64 },
+
+    /// System cache older than files of a package it indexes, because the package does not
+    /// trigger its update
+    StaleCache {
+        package: String,
+        cache: String,
+        /// Command updating the cache
+        command: String,
+        file_count: usize,
+    },
+
+    /// Package executable file that is damaged, typically by a disk full during an upgrade
+    DamagedFile {
+        package: String,
+        file: String,
+        damage: FileDamage,
+    },
+
+    /// Package file that could not be inspected, because the current user can not read it
+    UnreadableFile { package: String, file: String },
+
+    /// Package ELF file on which the dynamic loader fails for another reason than a missing
+    /// library, like a missing symbol version
+    LoaderError {
+        package: String,
+        file: String,
+        /// Error message of the dynamic loader
+        error: String,
+    },
+
+    /// Package file that lost the capabilities it has in the package archive
+    LostCapabilities { package: String, file: String },
+
+    /// Package ELF file built for another architecture than the system one
+    ForeignArchitecture {
+        package: String,
+        file: String,
+        architecture: String,
+        /// Architecture the package declares, if known
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        package_architecture: Option<String>,
+    },
+
+    /// ELF files of a foreign package declared architecture independent ("any")
+    ArchitectureSpecificFiles {
+        package: String,
+        /// First of the files, and the architecture it is built for
+        file: String,
+        architecture: String,
+        file_count: usize,
+    },
+
+    /// ELF file of a foreign package built without usual hardening features
+    WeakHardening {
+        package: String,
+        file: String,
+        issues: Vec<HardeningIssue>,
+    },
+
+    /// ELF file of a foreign package with no debug information, installed or in the file itself,
+    /// so backtraces of its crashes are not symbolized
+    MissingDebugInfo {
+        package: String,
+        file: String,
+        build_id: Option<String>,
+    },
+
+    /// Byte-identical executables of several foreign packages
+    DuplicateExecutable {
+        /// Size of each copy in bytes
+        size: u64,
+        /// Package and path of each copy, at least two packages
+        copies: Vec<(String, String)>,
+    },
+
+    /// Foreign package not installed again (rebuilt or upgraded) for more than the configured age
+    OldPackage { package: String, age_days: u64 },
+
+    /// ELF file of a foreign package needing a version of a GPU compute library (CUDA or ROCm)
+    /// other than the installed ones
+    GpuRuntimeMismatch {
+        package: String,
+        file: String,
+        /// "CUDA" or "ROCm"
+        runtime: String,
+        dependency: String,
+        /// Installed versions of the library, as sonames
+        installed: Vec<String>,
+    },
+
+    /// ELF file of a foreign package linking a CUDA runtime too recent for the installed NVIDIA
+    /// driver
+    GpuDriverTooOld {
+        package: String,
+        file: String,
+        dependency: String,
+        driver_version: String,
+        /// Minimum driver version of the CUDA runtime
+        required_version: String,
+    },
+
+    /// ELF file of a Wine related foreign package needing a library with no 32-bit version
+    MissingMultilibLibrary {
+        package: String,
+        file: String,
+        dependency: String,
+        /// Multilib package likely providing the 32-bit library
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suggestion: Option<String>,
+    },
+
+    /// File rejected by the tool processing it
+    InvalidFile {
+        kind: FileKind,
+        package: String,
+        file: String,
+        error: String,
+    },
+
+    /// System file (manifest, configuration...) referencing a missing or broken file
+    BrokenReference {
+        kind: ReferenceKind,
+        /// Package owning the referencing file
+        package: String,
+        /// Referencing file
+        source: String,
+        /// Referenced file
+        target: String,
+        problem: TargetProblem,
+    },
+}
+
+/// How a file is damaged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileDamage {
+    Empty,
+    /// ELF file shorter than its headers claim
+    TruncatedElf,
+    /// Shared library that is neither a valid ELF file nor a linker script, like one filled with
+    /// zeros by a crash
+    CorruptedElf,
+}
+
+/// Usual hardening feature missing from an ELF file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HardeningIssue {
+    /// Stack mapped executable, so not protected by NX
+    ExecutableStack,
+    /// No read-only relocations
+    NoRelro,
+    /// Executable not position independent, so not fully randomized by ASLR
+    NoPie,
+}
+
+/// Kind of file validated by a tool, each has its own finding code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    GSettingsSchema,
+    SystemdUnit,
+}
+
+/// Kind of system file referencing other files, each has its own finding code
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceKind {
+    NativeMessagingHost,
+    PamModule,
+    NssModule,
+    MkinitcpioEntry,
+    ScheduledJob,
+    AutostartEntry,
+    ExecutableLink,
+    JavaExecutableLink,
+    JavaRuntime,
+    PolkitAction,
+    #[serde(rename = "dbus_service")]
+    DBusService,
+    UdevRule,
+    GraphicsDriver,
+    LinkerConfig,
+    PreloadedLibrary,
+    EnvironmentPath,
+}
+
+/// How serious a finding is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Nothing is broken, but it is worth knowing
+    Info,
+
+    /// Something is broken
+    Warning,
+
+    /// Something is broken in a way that can prevent using the system (for example logging in)
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "Unknown severity {:?}, valid severities are: info, warning, error",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Library of the dependency tree of a file that is itself missing a dependency
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BrokenLibrary {
+    pub file: String,
+    pub package: String,
+}
+
+/// Why a referenced file is broken
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetProblem {
+    Missing,
+    NotExecutable,
+    MissingDependency(String),
+    MissingInterpreter(String),
+    /// Not found in the given directories
+    NotFound(String),
+}
+
+impl Finding {
+    /// Stable codes of all finding types, with their short description
+    pub const CODES: [(&'static str, &'static str); 43] = [
+        ("LIB001", "missing shared library"),
+        ("AUD001", "missing shared library in audio plugin"),
+        ("LIB002", "shared library removed by a pending upgrade"),
+        ("DEP001", "linked library of an undeclared dependency"),
+        ("LDR001", "dynamic loader error"),
+        ("PY001", "files in stale Python directory"),
+        ("PY002", "unowned files in stale Python directory"),
+        ("PL001", "files in stale Perl directory"),
+        ("PL002", "unowned files in stale Perl directory"),
+        ("SD001", "broken systemd service link"),
+        ("NMH001", "broken browser native messaging host"),
+        ("PAM001", "broken PAM module"),
+        ("NSS001", "broken NSS module"),
+        ("INIT001", "missing mkinitcpio hook, binary or module"),
+        ("LD001", "missing dynamic linker configuration directory"),
+        ("LD002", "library directory shadowing system libraries"),
+        ("PRELOAD001", "missing or broken preloaded library"),
+        ("ENV001", "missing directory of PATH or LD_LIBRARY_PATH"),
+        ("CRON001", "broken scheduled job executable"),
+        ("XDG001", "broken XDG autostart entry"),
+        ("LNK001", "broken executable symbolic link"),
+        ("LNK002", "broken Java executable symbolic link"),
+        ("JAVA001", "missing required Java environment"),
+        (
+            "CACHE001",
+            "stale font, icon, MIME or GSettings schema cache",
+        ),
+        ("GS001", "invalid GSettings schema"),
+        ("UNIT001", "invalid systemd unit"),
+        ("POLKIT001", "broken polkit action executable"),
+        ("DBUS001", "broken D-Bus service executable"),
+        ("CAP001", "lost file capabilities"),
+        (
+            "BIN001",
+            "empty, truncated or corrupted executable or library",
+        ),
+        ("UDEV001", "broken udev rule program"),
+        ("PERM001", "file not readable by the current user"),
+        ("ARCH001", "ELF file of a foreign architecture"),
+        (
+            "ARCH002",
+            "ELF files in an architecture independent package",
+        ),
+        ("HARD001", "ELF file without usual hardening"),
+        ("DBG001", "ELF file without available debug information"),
+        ("DUP001", "identical executables in several packages"),
+        ("AGE001", "foreign package not rebuilt for a long time"),
+        ("GFX001", "broken graphics driver configuration"),
+        ("GPU001", "GPU compute library of another version installed"),
+        ("GPU002", "NVIDIA driver too old for the CUDA runtime"),
+        ("CHK001", "check timed out"),
+        ("WINE001", "missing 32-bit library of a Wine package"),
+    ];
+
+    /// Get stable code identifying the finding type
+    pub fn code(&self) -> &'static str {
+        match self {
+            Finding::MissingDependency { .. } => "LIB001",
+            Finding::AudioPluginMissingDependency { .. } => "AUD001",
+            Finding::UpgradeRemovesDependency { .. } => "LIB002",
+            Finding::UndeclaredDependency { .. } => "DEP001",
+            Finding::StalePythonDir { .. } => "PY001",
+            Finding::StalePythonLeftovers { .. } => "PY002",
+            Finding::StalePerlDir { .. } => "PL001",
+            Finding::StalePerlLeftovers { .. } => "PL002",
+            Finding::BrokenServiceLink { .. } => "SD001",
+            Finding::ShadowingLibraryDir { .. } => "LD002",
+            Finding::CheckTimedOut { .. } => "CHK001",
+            Finding::StaleCache { .. } => "CACHE001",
+            Finding::LostCapabilities { .. } => "CAP001",
+            Finding::DamagedFile { .. } => "BIN001",
+            Finding::UnreadableFile { .. } => "PERM001",
+            Finding::LoaderError { .. } => "LDR001",
+            Finding::ForeignArchitecture { .. } => "ARCH001",
+            Finding::ArchitectureSpecificFiles { .. } => "ARCH002",
+            Finding::WeakHardening { .. } => "HARD001",
+            Finding::MissingDebugInfo { .. } => "DBG001",
+            Finding::DuplicateExecutable { .. } => "DUP001",
+            Finding::OldPackage { .. } => "AGE001",
+            Finding::GpuRuntimeMismatch { .. } => "GPU001",
+            Finding::GpuDriverTooOld { .. } => "GPU002",
+            Finding::MissingMultilibLibrary { .. } => "WINE001",
+            Finding::InvalidFile { kind, .. } => match kind {
+                FileKind::GSettingsSchema => "GS001",
+                FileKind::SystemdUnit => "UNIT001",
+            },
+            Finding::BrokenReference { kind, .. } => match kind {
+                ReferenceKind::NativeMessagingHost => "NMH001",
+                ReferenceKind::PamModule => "PAM001",
+                ReferenceKind::NssModule => "NSS001",
+                ReferenceKind::MkinitcpioEntry => "INIT001",
+                ReferenceKind::ScheduledJob => "CRON001",
+                ReferenceKind::AutostartEntry => "XDG001",
+                ReferenceKind::ExecutableLink => "LNK001",
+                ReferenceKind::JavaExecutableLink => "LNK002",
+                ReferenceKind::JavaRuntime => "JAVA001",
+                ReferenceKind::PolkitAction => "POLKIT001",
+                ReferenceKind::DBusService => "DBUS001",
+                ReferenceKind::UdevRule => "UDEV001",
+                ReferenceKind::GraphicsDriver => "GFX001",
+                ReferenceKind::LinkerConfig => "LD001",
+                ReferenceKind::PreloadedLibrary => "PRELOAD001",
+                ReferenceKind::EnvironmentPath => "ENV001",
+            },
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::BrokenReference {
+                kind:
+                    ReferenceKind::PamModule
+                    | ReferenceKind::NssModule
+                    | ReferenceKind::PreloadedLibrary,
+                ..
+            } => Severity::Error,
+            Finding::WeakHardening { .. }
+            | Finding::MissingDebugInfo { .. }
+            | Finding::DuplicateExecutable { .. }
+            | Finding::OldPackage { .. } => Severity::Info,
+            _ => Severity::Warning,
+        }
+    }
+
+    /// Get check of the finding, the code family in lowercase, like "lib" for LIB001
+    pub fn check(&self) -> String {
+        self.code()
+            .chars()
+            .take_while(char::is_ascii_alphabetic)
+            .collect::<String>()
+            .to_ascii_lowercase()
+    }
+
+    /// Get package that a rebuild fixes, if the finding suggests one
+    pub fn rebuild_package(&self) -> Option<&str> {
+        let package = match self {
+            // Fixed by upgrading the held back package, or fixing the broken library, instead, and
+            // not fixed by repackaging the same upstream binary
+            Finding::MissingDependency {
+                held_back: Some(_), ..
+            }
+            | Finding::MissingDependency {
+                transitive: Some(_),
+                ..
+            }
+            | Finding::MissingDependency {
+                unshipped: true, ..
+            } => return None,
+            Finding::MissingDependency { package, .. }
+            | Finding::AudioPluginMissingDependency { package, .. }
+            | Finding::UpgradeRemovesDependency { package, .. }
+            | Finding::StalePythonDir { package, .. }
+            | Finding::StalePerlDir { package, .. }
+            | Finding::GpuRuntimeMismatch { package, .. } => package,
+            _ => return None,
+        };
+        Some(package.as_str()).filter(|p| *p != plugins::UNOWNED_PACKAGE)
+    }
+
+    /// Get what the finding is about, used to match ignore rules: the package name if any, or path
+    pub fn subject(&self) -> &str {
+        match self {
+            Finding::MissingDependency { package, .. } => package,
+            Finding::AudioPluginMissingDependency { package, .. } => package,
+            Finding::UpgradeRemovesDependency { package, .. } => package,
+            Finding::UndeclaredDependency { package, .. } => package,
+            Finding::StalePythonDir { package, .. } => package,
+            Finding::StalePythonLeftovers { dir, .. } => dir,
+            Finding::StalePerlDir { package, .. } => package,
+            Finding::StalePerlLeftovers { dir, .. } => dir,
+            Finding::BrokenServiceLink { link } => link,
+            Finding::ShadowingLibraryDir { source, .. } => source,
+            Finding::CheckTimedOut { check, .. } => check,
+            Finding::StaleCache { package, .. } => package,
+            Finding::LostCapabilities { package, .. } => package,
+            Finding::DamagedFile { package, .. } => package,
+            Finding::UnreadableFile { package, .. } => package,
+            Finding::LoaderError { package, .. } => package,
+            Finding::ForeignArchitecture { package, .. } => package,
+            Finding::ArchitectureSpecificFiles { package, .. } => package,
+            Finding::WeakHardening { package, .. } => package,
+            Finding::MissingDebugInfo { package, .. } => package,
+            Finding::DuplicateExecutable { copies, .. } => &copies[0].0,
+            Finding::OldPackage { package, .. } => package,
+            Finding::GpuRuntimeMismatch { package, .. } => package,
+            Finding::GpuDriverTooOld { package, .. } => package,
+            Finding::MissingMultilibLibrary { package, .. } => package,
+            Finding::InvalidFile { package, .. } => package,
+            Finding::BrokenReference { package, .. } => package,
+        }
+    }
+
+    /// Approximate memory used by this finding
+    fn memory_size(&self) -> usize {
+        let heap_size = match self {
+            Finding::MissingDependency {
+                package,
+                file,
+                dependency,
+                held_back,
+                chain,
+                transitive,
+                ..
+            } => {
+                package.len()
+                    + file.len()
+                    + dependency.len()
+                    + held_back.as_ref().map_or(0, String::len)
+                    + chain.iter().map(String::len).sum::<usize>()
+                    + transitive
+                        .as_ref()
+                        .map_or(0, |t| t.file.len() + t.package.len())
+            }
+            Finding::AudioPluginMissingDependency {
+                package,
+                file,
+                format,
+                dependency,
+            } => package.len() + file.len() + format.len() + dependency.len(),
+            Finding::UpgradeRemovesDependency {
+                package,
+                file,
+                dependency,
+                provider,
+                provider_version,
+            } => {
+                package.len()
+                    + file.len()
+                    + dependency.len()
+                    + provider.len()
+                    + provider_version.len()
+            }
+            Finding::UndeclaredDependency {
+                package,
+                file,
+                dependency,
+                provider,
+            } => package.len() + file.len() + dependency.len() + provider.len(),
+            Finding::StalePythonDir { package, dir, .. }
+            | Finding::StalePerlDir { package, dir, .. } => package.len() + dir.len(),
+            Finding::StalePythonLeftovers { dir, removable, .. }
+            | Finding::StalePerlLeftovers { dir, removable, .. } => {
+                dir.len() + removable.iter().map(String::len).sum::<usize>()
+            }
+            Finding::BrokenServiceLink { link } => link.len(),
+            Finding::ShadowingLibraryDir {
+                source,
+                dir,
+                library,
+                ..
+            } => source.len() + dir.len() + library.len(),
+            Finding::CheckTimedOut { check, .. } => check.len(),
+            Finding::StaleCache {
+                package,
+                cache,
+                command,
+                ..
+            } => package.len() + cache.len() + command.len(),
+            Finding::LostCapabilities { package, file } => package.len() + file.len(),
+            Finding::DamagedFile { package, file, .. } => package.len() + file.len(),
+            Finding::UnreadableFile { package, file } => package.len() + file.len(),
+            Finding::LoaderError {
+                package,
+                file,
+                error,
+            } => package.len() + file.len() + error.len(),
+            Finding::ForeignArchitecture {
+                package,
+                file,
+                architecture,
+                package_architecture,
+            } => {
+                package.len()
+                    + file.len()
+                    + architecture.len()
+                    + package_architecture.as_ref().map_or(0, String::len)
+            }
+            Finding::ArchitectureSpecificFiles {
+                package,
+                file,
+                architecture,
+                ..
+            } => package.len() + file.len() + architecture.len(),
+            Finding::WeakHardening {
+                package,
+                file,
+                issues,
+            } => package.len() + file.len() + issues.len() * mem::size_of::<HardeningIssue>(),
+            Finding::MissingDebugInfo {
+                package,
+                file,
+                build_id,
+            } => package.len() + file.len() + build_id.as_ref().map_or(0, String::len),
+            Finding::DuplicateExecutable { copies, .. } => copies
+                .iter()
+                .map(|(p, f)| mem::size_of::<(String, String)>() + p.len() + f.len())
+                .sum(),
+            Finding::OldPackage { package, .. } => package.len(),
+            Finding::GpuRuntimeMismatch {
+                package,
+                file,
+                runtime,
+                dependency,
+                installed,
+            } => {
+                package.len()
+                    + file.len()
+                    + runtime.len()
+                    + dependency.len()
+                    + installed.iter().map(String::len).sum::<usize>()
+            }
+            Finding::GpuDriverTooOld {
+                package,
+                file,
+                dependency,
+                driver_version,
+                required_version,
+            } => {
+                package.len()
+                    + file.len()
+                    + dependency.len()
+                    + driver_version.len()
+                    + required_version.len()
+            }
+            Finding::MissingMultilibLibrary {
+                package,
+                file,
+                dependency,
+                suggestion,
+            } => {
+                package.len()
+                    + file.len()
+                    + dependency.len()
+                    + suggestion.as_ref().map_or(0, String::len)
+            }
+            Finding::InvalidFile {
+                package,
+                file,
+                error,
+                ..
+            } => package.len() + file.len() + error.len(),
+            Finding::BrokenReference {
+                package,
+                source,
+                target,
+                problem,
+                ..
+            } => {
+                package.len()
+                    + source.len()
+                    + target.len()
+                    + match problem {
+                        TargetProblem::MissingDependency(s)
+                        | TargetProblem::MissingInterpreter(s)
+                        | TargetProblem::NotFound(s) => s.len(),
+                        TargetProblem::Missing | TargetProblem::NotExecutable => 0,
+                    }
+            }
+        };
+        mem::size_of::<Finding>() + heap_size
+    }
+}
+
+/// Report file line that is not a finding
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ReportMarker {
+    /// First line
+    Header {
+        format_version: u32,
+        /// Identifier of the JSON Schema of the report lines, absent in older reports
+        #[serde(default)]
+        schema: Option<String>,
+        /// Transaction analyzed by a hook run
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transaction: Option<Transaction>,
+    },
+    /// Last line, written when the analysis ends
+    Footer { finding_count: usize, checksum: u32 },
+}
+
+/// Pacman transaction analyzed by a hook run, to correlate reports with what was installed or
+/// removed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    /// Start of the analysis, in seconds since the epoch
+    pub time: u64,
+    /// Packages of the transaction, and of the transactions whose analysis was deferred to it
+    pub targets: Vec<String>,
+}
+
+/// Collects findings as they are produced, streaming them to the report file, and keeping them in
+/// memory for display until a memory ceiling is reached
+pub struct Aggregator {
+    /// Findings kept in memory, in production order
+    findings: Vec<Finding>,
+
+    /// Rules of findings to drop
+    ignore_rules: Vec<IgnoreRule>,
+
+    /// Expression of the findings to keep
+    filter: Option<Filter>,
+
+    /// Severity of findings, and the minimum one of findings to keep
+    severities: SeverityOverrides,
+    min_severity: Option<Severity>,
+
+    /// Highest severity of kept findings, None if there is none
+    max_severity: Option<Severity>,
+
+    /// Approximate memory used by in memory findings
+    memory_used: usize,
+
+    max_memory: usize,
+
+    /// Report file path and writer, None if it could not be written. The file replaces the
+    /// report of the previous analysis only once complete.
+    report: Option<(PathBuf, AtomicFile)>,
+
+    /// Number of findings written to the report file, and checksum of their lines
+    report_count: usize,
+    report_hasher: crc32fast::Hasher,
+
+    /// Number of in memory findings when spilling started, number of findings only available in
+    /// the report file, and checksum of their lines
+    spill_start: usize,
+    spilled_count: usize,
+    spill_hasher: crc32fast::Hasher,
+
+    /// Temporary report file kept after failing to write it, with the spilled findings
+    spill_filepath: Option<PathBuf>,
+}
+
+impl Aggregator {
+    pub fn new(
+        report_filepath: Option<&Path>,
+        max_memory: usize,
+        ignore_rules: Vec<IgnoreRule>,
+        filter: Option<Filter>,
+        severities: SeverityOverrides,
+        min_severity: Option<Severity>,
+        transaction: Option<Transaction>,
+    ) -> Aggregator {
+        let report = report_filepath.and_then(|p| match Self::create_report(p, transaction) {
+            Ok(w) => Some((p.to_path_buf(), w)),
+            Err(err) => {
+                eprintln!("Failed to create report file {:?}: {}", p, err);
+                None
+            }
+        });
+        Aggregator {
+            findings: Vec::new(),
+            ignore_rules,
+            filter,
+            severities,
+            min_severity,
+            max_severity: None,
+            memory_used: 0,
+            max_memory,
+            report,
+            report_count: 0,
+            report_hasher: crc32fast::Hasher::new(),
+            spill_start: 0,
+            spilled_count: 0,
+            spill_hasher: crc32fast::Hasher::new(),
+            spill_filepath: None,
+        }
+    }
+
+    fn create_report(
+        filepath: &Path,
+        transaction: Option<Transaction>,
+    ) -> Result<AtomicFile, Box<dyn error::Error>> {
+        let mut writer = AtomicFile::create(filepath)?;
+        serde_json::to_writer(
+            &mut writer,
+            &ReportMarker::Header {
+                format_version: REPORT_FORMAT_VERSION,
+                schema: Some(REPORT_SCHEMA_ID.to_string()),
+                transaction,
+            },
+        )?;
+        writeln!(writer)?;
+        Ok(writer)
+    }
+
+    pub fn add(&mut self, finding: Finding) {
+        if let Some(rule) = self.ignore_rules.iter().find(|r| r.matches(&finding)) {
+            debug!("Ignoring {:?} due to rule {}", finding, rule);
+            return;
+        }
+        let severity = self.severities.severity(&finding);
+        if self.min_severity.is_some_and(|s| severity < s)
+            || self
+                .filter
+                .as_ref()
+                .is_some_and(|f| !f.matches(&finding, severity))
+        {
+            return;
+        }
+        self.max_severity = self.max_severity.max(Some(severity));
+
+        let size = finding.memory_size();
+        let spill = (self.spilled_count > 0) || (self.memory_used + size > self.max_memory);
+        let mut line = serde_json::to_vec(&finding).unwrap();
+        line.push(b'\n');
+        if let Some((filepath, writer)) = &mut self.report {
+            self.report_hasher.update(&line);
+            self.report_count += 1;
+            // Spilled findings are flushed, so that those written before a write error can still
+            // be read back
+            let res = writer
+                .write_all(&line)
+                .and_then(|_| if spill { writer.flush() } else { Ok(()) });
+            if let Err(err) = res {
+                eprintln!("Failed to write report file {:?}: {}", filepath, err);
+                self.abandon_report();
+            }
+        }
+
+        if spill {
+            if self.report.is_some() {
+                if self.spilled_count == 0 {
+                    debug!(
+                        "Memory ceiling of {} bytes reached, spilling to report file",
+                        self.max_memory
+                    );
+                    self.spill_start = self.findings.len();
+                }
+                self.spilled_count += 1;
+                self.spill_hasher.update(&line);
+                return;
+            } else if self.memory_used <= self.max_memory {
+                eprintln!(
+                    "Memory ceiling reached but report file is unavailable, ignoring ceiling"
+                );
+            }
+        }
+        self.memory_used += size;
+        self.findings.push(finding);
+    }
+
+    /// Stop writing the report file after an error, keeping its temporary file if it has spilled
+    /// findings
+    fn abandon_report(&mut self) {
+        if let Some((filepath, writer)) = self.report.take() {
+            if self.spilled_count > 0 {
+                let spill_filepath = writer.keep();
+                eprintln!(
+                    "Report file {:?} not written, its findings are in {:?}",
+                    filepath, spill_filepath
+                );
+                self.spill_filepath = Some(spill_filepath);
+            }
+        }
+    }
+
+    /// Flush report file, and return the final report
+    pub fn finish(mut self) -> Report {
+        let report_filepath = match self.report.take() {
+            Some((filepath, mut writer)) => {
+                let tmp_filepath = writer.tmp_filepath().to_path_buf();
+                match Self::write_footer(
+                    &mut writer,
+                    self.report_count,
+                    self.report_hasher.clone().finalize(),
+                )
+                .and_then(|_| writer.commit())
+                {
+                    Ok(_) => Some(filepath),
+                    Err(err) => {
+                        eprintln!("Failed to write report file {:?}: {}", filepath, err);
+                        // A failed commit leaves the temporary file, unless it was renamed
+                        if self.spilled_count > 0 {
+                            let spill_filepath = if tmp_filepath.exists() {
+                                tmp_filepath
+                            } else {
+                                filepath
+                            };
+                            eprintln!("Findings of the analysis are in {:?}", spill_filepath);
+                            self.spill_filepath = Some(spill_filepath);
+                        }
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        Report {
+            findings: self.findings,
+            spill_start: self.spill_start,
+            spilled_count: self.spilled_count,
+            spill_checksum: self.spill_hasher.finalize(),
+            spill_filepath: self.spill_filepath.or_else(|| report_filepath.clone()),
+            report_filepath,
+            max_severity: self.max_severity,
+        }
+    }
+
+    fn write_footer(
+        writer: &mut AtomicFile,
+        finding_count: usize,
+        checksum: u32,
+    ) -> io::Result<()> {
+        serde_json::to_writer(
+            &mut *writer,
+            &ReportMarker::Footer {
+                finding_count,
+                checksum,
+            },
+        )?;
+        writeln!(writer)
+    }
+}
+
+/// All findings of a run
+pub struct Report {
+    findings: Vec<Finding>,
+    spill_start: usize,
+    spilled_count: usize,
+    spill_checksum: u32,
+    /// File with the spilled findings, the report file unless it could not be written
+    spill_filepath: Option<PathBuf>,
+    report_filepath: Option<PathBuf>,
+    max_severity: Option<Severity>,
+}
+
+impl Report {
+    /// Get highest severity of the findings, None if there is none
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.max_severity
+    }
+
+    /// Iterate over all findings in production order, reading back those spilled to the report
+    /// file. Findings kept in memory after the report file failed to be written come last.
+    pub fn for_each<F: FnMut(&Finding)>(&self, mut f: F) -> Result<(), Box<dyn error::Error>> {
+        let (before_spill, after_spill) = self.findings.split_at(self.spill_start);
+        before_spill.iter().for_each(&mut f);
+
+        if self.spilled_count > 0 {
+            let spill_filepath = self
+                .spill_filepath
+                .as_ref()
+                .ok_or("Spilled findings are lost")?;
+            // Check the file was not changed since written before displaying anything from it
+            if self.report_filepath.as_ref() == Some(spill_filepath) {
+                Self::verify(spill_filepath)
+                    .map_err(|e| format!("Report file {:?}: {}", spill_filepath, e))?;
+            }
+            let spilled_lines = || -> io::Result<_> {
+                Ok(BufReader::new(fs::File::open(spill_filepath)?)
+                    .lines()
+                    .skip(1 + self.spill_start)
+                    .take(self.spilled_count))
+            };
+            let mut hasher = crc32fast::Hasher::new();
+            let mut count = 0;
+            for line in spilled_lines()? {
+                hasher.update(line?.as_bytes());
+                hasher.update(b"\n");
+                count += 1;
+            }
+            if (count != self.spilled_count) || (hasher.finalize() != self.spill_checksum) {
+                return Err(format!(
+                    "File {:?}: {}",
+                    spill_filepath,
+                    format::FormatError::Corrupted
+                )
+                .into());
+            }
+            for line in spilled_lines()? {
+                let finding: Finding = serde_json::from_str(&line?)?;
+                f(&finding);
+            }
+        }
+
+        after_spill.iter().for_each(&mut f);
+        Ok(())
+    }
+
+    /// Check report file format version and checksum
+    fn verify(report_filepath: &Path) -> Result<(), Box<dyn error::Error>> {
+        let mut lines = BufReader::new(fs::File::open(report_filepath)?).lines();
+        match lines.next().transpose()?.map(|l| serde_json::from_str(&l)) {
+            Some(Ok(ReportMarker::Header { format_version, .. })) => {
+                if format_version != REPORT_FORMAT_VERSION {
+                    return Err(format::FormatError::UnsupportedVersion(format_version).into());
+                }
+            }
+            Some(_) => return Err(format::FormatError::UnsupportedVersion(0).into()),
+            None => return Err(format::FormatError::Truncated.into()),
+        }
+        let mut hasher = crc32fast::Hasher::new();
+        for (count, line) in lines.enumerate() {
+            let line = line?;
+            if let Ok(ReportMarker::Footer {
+                finding_count,
+                checksum,
+            }) = serde_json::from_str(&line)
+            {
+                if (finding_count != count) || (checksum != hasher.finalize()) {
+                    return Err(format::FormatError::Corrupted.into());
+                }
+                return Ok(());
+            }
+            hasher.update(line.as_bytes());
+            hasher.update(b"\n");
+        }
+        Err(format::FormatError::Truncated.into())
+    }
+}
+
+/// Read findings of a complete report file, written by a previous analysis. Older versions wrote
+/// the report file in place, so the complete findings of a report file they left truncated when
+/// interrupted are recovered.
+pub fn read_findings(report_filepath: &Path) -> Result<Vec<Finding>, Box<dyn error::Error>> {
+    match Report::verify(report_filepath) {
+        Ok(()) => {}
+        Err(err) if matches!(err.downcast_ref(), Some(format::FormatError::Truncated)) => {
+            warn!(
+                "Report file {:?} is truncated, recovering its complete findings",
+                report_filepath
+            );
+        }
+        Err(err) => return Err(err),
+    }
+    let mut reader = BufReader::new(fs::File::open(report_filepath)?);
+    let mut findings = Vec::new();
+    let mut line = Vec::new();
+    // Header
+    reader.read_until(b'\n', &mut line)?;
+    loop {
+        line.clear();
+        // Last line of a truncated file can be partially written
+        if (reader.read_until(b'\n', &mut line)? == 0) || (line.last() != Some(&b'\n')) {
+            break;
+        }
+        match serde_json::from_slice(&line) {
+            Ok(f) => findings.push(f),
+            // Footer
+            Err(_) => break,
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_aggregator_spill() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let report_filepath = tmp_dir.path().join("report.jsonl");
+        let findings: Vec<Finding> = (0..10)
+            .map(|i| Finding::BrokenServiceLink {
+                link: format!("/etc/systemd/system/multi-user.target.wants/{}.service", i),
+            })
+            .collect();
+
+        let mut aggregator = Aggregator::new(
+            Some(&report_filepath),
+            3 * findings[0].memory_size(),
+            vec!["SD001:*/9.service".parse().unwrap()],
+            None,
+            SeverityOverrides::default(),
+            None,
+            None,
+        );
+        for finding in &findings {
+            aggregator.add(finding.clone());
+        }
+        let report = aggregator.finish();
+        assert_eq!(report.findings.len(), 3);
+        assert_eq!(report.spilled_count, 6);
+
+        let mut all_findings = Vec::new();
+        report.for_each(|f| all_findings.push(f.clone())).unwrap();
+        assert_eq!(all_findings, findings[..9]);
+        assert_eq!(read_findings(&report_filepath).unwrap(), findings[..9]);
+
+        // Modified report file is detected
+        let content = fs::read_to_string(&report_filepath).unwrap();
+        fs::write(
+            &report_filepath,
+            content.replace("/8.service", "/0.service"),
+        )
+        .unwrap();
+        assert!(report.for_each(|_| {}).is_err());
+    }
+
+    #[test]
+    fn test_aggregator_spill_commit_failure() {
+        let tmp_dir = TempDir::new("").unwrap();
+        // Renaming the report file over a non empty directory fails
+        let report_filepath = tmp_dir.path().join("report.jsonl");
+        fs::create_dir_all(report_filepath.join("foo")).unwrap();
+        let findings: Vec<Finding> = (0..5)
+            .map(|i| Finding::BrokenServiceLink {
+                link: format!("/etc/systemd/system/multi-user.target.wants/{}.service", i),
+            })
+            .collect();
+
+        let mut aggregator = Aggregator::new(
+            Some(&report_filepath),
+            2 * findings[0].memory_size(),
+            Vec::new(),
+            None,
+            SeverityOverrides::default(),
+            None,
+            None,
+        );
+        for finding in &findings {
+            aggregator.add(finding.clone());
+        }
+        let report = aggregator.finish();
+        assert_eq!(report.spilled_count, 3);
+        assert!(report.report_filepath.is_none());
+        assert_eq!(
+            report.spill_filepath,
+            Some(tmp_dir.path().join("report.jsonl.tmp"))
+        );
+
+        let mut all_findings = Vec::new();
+        report.for_each(|f| all_findings.push(f.clone())).unwrap();
+        assert_eq!(all_findings, findings);
+    }
+
+    #[test]
+    fn test_read_truncated_report() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let report_filepath = tmp_dir.path().join("report.jsonl");
+        let findings: Vec<Finding> = (0..3)
+            .map(|i| Finding::BrokenServiceLink {
+                link: format!("/etc/systemd/system/multi-user.target.wants/{}.service", i),
+            })
+            .collect();
+        let mut aggregator = Aggregator::new(
+            Some(&report_filepath),
+            usize::MAX,
+            Vec::new(),
+            None,
+            SeverityOverrides::default(),
+            None,
+            None,
+        );
+        for finding in &findings {
+            aggregator.add(finding.clone());
+        }
+        aggregator.finish();
+
+        // Written in place by an older version interrupted in the middle of the last finding
+        let content = fs::read_to_string(&report_filepath).unwrap();
+        let end = content.rfind("/2.service").unwrap();
+        fs::write(&report_filepath, &content[..end]).unwrap();
+        assert_eq!(read_findings(&report_filepath).unwrap(), findings[..2]);
+
+        // Interrupted before the header was written
+        fs::write(&report_filepath, "").unwrap();
+        assert!(read_findings(&report_filepath).unwrap().is_empty());
+    }
+
+    /// Check a JSON value against a schema node, supporting the keywords report.schema.json uses
+    fn validate(
+        schema: &serde_json::Value,
+        node: &serde_json::Value,
+        value: &serde_json::Value,
+    ) -> Result<(), String> {
+        use serde_json::Value;
+
+        let node = match node {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => return Err(format!("{} not allowed", value)),
+            _ => node.as_object().unwrap(),
+        };
+        if let Some(reference) = node.get("$ref") {
+            let name = reference.as_str().unwrap().trim_start_matches("#/$defs/");
+            validate(schema, &schema["$defs"][name], value)?;
+        }
+        if let Some(constant) = node.get("const") {
+            if value != constant {
+                return Err(format!("{} is not {}", value, constant));
+            }
+        }
+        if let Some(values) = node.get("enum") {
+            if !values.as_array().unwrap().contains(value) {
+                return Err(format!("{} is not in {}", value, values));
+            }
+        }
+        if let Some(types) = node.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().map(|t| t.as_str().unwrap()).collect(),
+                t => vec![t.as_str().unwrap()],
+            };
+            let value_type = match value {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            if !types.contains(&value_type) {
+                return Err(format!("{} is not of type {:?}", value, types));
+            }
+        }
+        if let Some(minimum) = node.get("minimum") {
+            if value.as_f64().unwrap() < minimum.as_f64().unwrap() {
+                return Err(format!("{} is below {}", value, minimum));
+            }
+        }
+        if let Some(schemas) = node.get("oneOf") {
+            let matches = schemas
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|s| validate(schema, s, value).is_ok())
+                .count();
+            if matches != 1 {
+                return Err(format!("{} matches {} schemas of oneOf", value, matches));
+            }
+        }
+        if let Value::Object(object) = value {
+            let properties = node.get("properties").and_then(Value::as_object);
+            for key in node
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if !object.contains_key(key.as_str().unwrap()) {
+                    return Err(format!("{} misses {}", value, key));
+                }
+            }
+            for (key, property) in object {
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property_schema) => validate(schema, property_schema, property)?,
+                    None if node.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        return Err(format!("{} has unknown property {}", value, key));
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let Value::Array(items) = value {
+            if let Some(min_items) = node.get("minItems") {
+                if (items.len() as u64) < min_items.as_u64().unwrap() {
+                    return Err(format!("{} has less than {} items", value, min_items));
+                }
+            }
+            let prefix_items = node
+                .get("prefixItems")
+                .and_then(Value::as_array)
+                .map_or(&[][..], Vec::as_slice);
+            for (i, item) in items.iter().enumerate() {
+                if let Some(item_schema) = prefix_items.get(i).or_else(|| node.get("items")) {
+                    validate(schema, item_schema, item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get findings of every type, with every finding code, and every value of their enums
+    fn every_finding() -> Vec<Finding> {
+        let s = |s: &str| s.to_string();
+        let mut findings = vec![
+            Finding::MissingDependency {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                dependency: s("libbar.so.1"),
+                held_back: Some(s("bar")),
+                chain: vec![s("libbaz.so.2")],
+                transitive: Some(BrokenLibrary {
+                    file: s("/usr/lib/libbaz.so.2"),
+                    package: s("baz"),
+                }),
+                unshipped: true,
+            },
+            Finding::MissingDependency {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                dependency: s("libbar.so.1"),
+                held_back: None,
+                chain: Vec::new(),
+                transitive: None,
+                unshipped: false,
+            },
+            Finding::AudioPluginMissingDependency {
+                package: s("foo-lv2"),
+                file: s("/usr/lib/lv2/foo.lv2/foo.so"),
+                format: s("LV2"),
+                dependency: s("libbar.so.1"),
+            },
+            Finding::UpgradeRemovesDependency {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                dependency: s("libbar.so.1"),
+                provider: s("bar"),
+                provider_version: s("2-1"),
+            },
+            Finding::UndeclaredDependency {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                dependency: s("libbar.so.1"),
+                provider: s("bar"),
+            },
+            Finding::StalePythonDir {
+                package: s("python-foo"),
+                dir: s("/usr/lib/python3.12"),
+                file_count: 3,
+            },
+            Finding::StalePythonLeftovers {
+                dir: s("/usr/lib/python3.12"),
+                file_count: 2,
+                removable: vec![s("/usr/lib/python3.12/site-packages/foo")],
+            },
+            Finding::StalePerlDir {
+                package: s("perl-foo"),
+                dir: s("/usr/lib/perl5/5.38"),
+                file_count: 3,
+            },
+            Finding::StalePerlLeftovers {
+                dir: s("/usr/lib/perl5/5.38"),
+                file_count: 2,
+                removable: vec![s("/usr/lib/perl5/5.38")],
+            },
+            Finding::BrokenServiceLink {
+                link: s("/etc/systemd/system/multi-user.target.wants/foo.service"),
+            },
+            Finding::ShadowingLibraryDir {
+                source: s("/etc/ld.so.conf.d/foo.conf"),
+                dir: s("/opt/foo/lib"),
+                library: s("libz.so.1"),
+                library_count: 2,
+            },
+            Finding::CheckTimedOut {
+                check: s("debug-info"),
+                timeout: 60,
+            },
+            Finding::StaleCache {
+                package: s("foo"),
+                cache: s("/usr/share/icons/hicolor/icon-theme.cache"),
+                command: s("gtk-update-icon-cache -f /usr/share/icons/hicolor"),
+                file_count: 4,
+            },
+            Finding::UnreadableFile {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+            },
+            Finding::LoaderError {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                error: s("version `GLIBC_2.38' not found"),
+            },
+            Finding::LostCapabilities {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+            },
+            Finding::ForeignArchitecture {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                architecture: s("aarch64"),
+                package_architecture: Some(s("x86_64")),
+            },
+            Finding::ForeignArchitecture {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                architecture: s("aarch64"),
+                package_architecture: None,
+            },
+            Finding::ArchitectureSpecificFiles {
+                package: s("foo"),
+                file: s("/usr/lib/foo/foo.so"),
+                architecture: s("x86_64"),
+                file_count: 2,
+            },
+            Finding::WeakHardening {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                issues: vec![
+                    HardeningIssue::ExecutableStack,
+                    HardeningIssue::NoRelro,
+                    HardeningIssue::NoPie,
+                ],
+            },
+            Finding::MissingDebugInfo {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                build_id: Some(s("0123456789abcdef")),
+            },
+            Finding::MissingDebugInfo {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                build_id: None,
+            },
+            Finding::DuplicateExecutable {
+                size: 1024,
+                copies: vec![
+                    (s("foo"), s("/usr/bin/foo")),
+                    (s("foo-git"), s("/opt/foo/foo")),
+                ],
+            },
+            Finding::OldPackage {
+                package: s("foo"),
+                age_days: 400,
+            },
+            Finding::GpuRuntimeMismatch {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                runtime: s("CUDA"),
+                dependency: s("libcudart.so.11.0"),
+                installed: vec![s("libcudart.so.12")],
+            },
+            Finding::GpuDriverTooOld {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                dependency: s("libcudart.so.12"),
+                driver_version: s("470.256.02"),
+                required_version: s("525.60.13"),
+            },
+            Finding::MissingMultilibLibrary {
+                package: s("wine-foo"),
+                file: s("/usr/bin/wine-foo"),
+                dependency: s("libbar.so.1"),
+                suggestion: Some(s("lib32-bar")),
+            },
+        ];
+        for damage in &[
+            FileDamage::Empty,
+            FileDamage::TruncatedElf,
+            FileDamage::CorruptedElf,
+        ] {
+            findings.push(Finding::DamagedFile {
+                package: s("foo"),
+                file: s("/usr/bin/foo"),
+                damage: *damage,
+            });
+        }
+        for kind in &[FileKind::GSettingsSchema, FileKind::SystemdUnit] {
+            findings.push(Finding::InvalidFile {
+                kind: *kind,
+                package: s("foo"),
+                file: s("/usr/lib/systemd/system/foo.service"),
+                error: s("Unknown key"),
+            });
+        }
+        let problems = [
+            TargetProblem::Missing,
+            TargetProblem::NotExecutable,
+            TargetProblem::MissingDependency(s("libbar.so.1")),
+            TargetProblem::MissingInterpreter(s("/usr/bin/python2")),
+            TargetProblem::NotFound(s("/usr/lib/security")),
+        ];
+        let kinds = [
+            ReferenceKind::NativeMessagingHost,
+            ReferenceKind::PamModule,
+            ReferenceKind::NssModule,
+            ReferenceKind::MkinitcpioEntry,
+            ReferenceKind::ScheduledJob,
+            ReferenceKind::AutostartEntry,
+            ReferenceKind::ExecutableLink,
+            ReferenceKind::JavaExecutableLink,
+            ReferenceKind::JavaRuntime,
+            ReferenceKind::PolkitAction,
+            ReferenceKind::DBusService,
+            ReferenceKind::UdevRule,
+            ReferenceKind::GraphicsDriver,
+            ReferenceKind::LinkerConfig,
+            ReferenceKind::PreloadedLibrary,
+            ReferenceKind::EnvironmentPath,
+        ];
+        for (kind, problem) in kinds.iter().zip(problems.iter().cycle()) {
+            findings.push(Finding::BrokenReference {
+                kind: *kind,
+                package: s("foo"),
+                source: s("/usr/share/foo/foo.conf"),
+                target: s("/usr/bin/foo"),
+                problem: problem.clone(),
+            });
+        }
+        findings
+    }
+
+    #[test]
+    fn test_report_schema() {
+        let schema: serde_json::Value = serde_json::from_str(REPORT_SCHEMA).unwrap();
+        assert_eq!(schema["$id"], REPORT_SCHEMA_ID);
+        assert_eq!(
+            schema["$defs"]["header"]["properties"]["format_version"]["const"],
+            REPORT_FORMAT_VERSION
+        );
+
+        // Every finding code is covered
+        let findings = every_finding();
+        for (code, _description) in Finding::CODES.iter() {
+            assert!(findings.iter().any(|f| f.code() == *code), "{}", code);
+        }
+
+        // Every line of a report is valid
+        let tmp_dir = TempDir::new("").unwrap();
+        let report_filepath = tmp_dir.path().join("report.jsonl");
+        let mut aggregator = Aggregator::new(
+            Some(&report_filepath),
+            usize::MAX,
+            Vec::new(),
+            None,
+            SeverityOverrides::default(),
+            None,
+            Some(Transaction {
+                time: 1_700_000_000,
+                targets: vec!["foo".to_string(), "qt6-base".to_string()],
+            }),
+        );
+        for finding in &findings {
+            aggregator.add(finding.clone());
+        }
+        aggregator.finish();
+        let content = fs::read_to_string(&report_filepath).unwrap();
+        assert_eq!(content.lines().count(), findings.len() + 2);
+        for line in content.lines() {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            if let Err(err) = validate(&schema, &schema, &value) {
+                panic!("{}: {}", line, err);
+            }
+        }
+
+        // Public names of types are not derived from word boundaries in acronyms
+        let value = serde_json::to_value(ReferenceKind::DBusService).unwrap();
+        assert_eq!(value, "dbus_service");
+        assert!(schema["$defs"]["reference_kind"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&value));
+
+        // The validator rejects invalid findings
+        let finding = |f: &Finding| serde_json::to_value(f).unwrap();
+        let mut value = finding(&findings[0]);
+        value["unknown"] = serde_json::Value::Bool(true);
+        assert!(validate(&schema, &schema, &value).is_err());
+        let mut value = finding(&findings[0]);
+        value.as_object_mut().unwrap().remove("file");
+        assert!(validate(&schema, &schema, &value).is_err());
+        let mut value = finding(&findings[findings.len() - 1]);
+        value["kind"] = serde_json::Value::String("unknown".to_string());
+        assert!(validate(&schema, &schema, &value).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Sandbox for programs that can execute code of analyzed files, like ldd which runs the dynamic
+//! loader on them, so that a malicious foreign package can not change the system or reach the
+//! network when analyzed by a hook running as root
+
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use ansi_term::Colour::Yellow;
+use log::debug;
+
+use crate::paths;
+use crate::tools;
+
+/// Read only view of the system, without network nor capabilities
+const BWRAP_ARGS: [&str; 13] = [
+    "--ro-bind",
+    "/",
+    "/",
+    "--dev",
+    "/dev",
+    "--proc",
+    "/proc",
+    "--tmpfs",
+    "/tmp",
+    "--unshare-all",
+    "--die-with-parent",
+    "--new-session",
+    "--",
+];
+
+/// Same restrictions as with bubblewrap, in a transient service
+const SYSTEMD_RUN_ARGS: [&str; 21] = [
+    "--pipe",
+    "--quiet",
+    "--wait",
+    "--collect",
+    "--service-type=exec",
+    "-p",
+    "ProtectSystem=strict",
+    "-p",
+    "ProtectHome=read-only",
+    "-p",
+    "PrivateTmp=yes",
+    "-p",
+    "PrivateDevices=yes",
+    "-p",
+    "PrivateNetwork=yes",
+    "-p",
+    "NoNewPrivileges=yes",
+    "-p",
+    "CapabilityBoundingSet=",
+    "-E",
+    "LD_LIBRARY_PATH",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sandbox {
+    Bubblewrap,
+    /// Transient service of the systemd system manager, only when running as root
+    SystemdRun,
+    /// Programs run directly
+    None,
+}
+
+impl Sandbox {
+    /// Get sandbox of the running system, the first working one of bubblewrap and systemd-run,
+    /// detected once
+    pub fn get() -> Sandbox {
+        static SANDBOX: OnceLock<Sandbox> = OnceLock::new();
+        *SANDBOX.get_or_init(|| {
+            let sandbox = [Sandbox::Bubblewrap, Sandbox::SystemdRun]
+                .iter()
+                .copied()
+                .find(|s| s.is_available())
+                .unwrap_or_else(|| {
+                    if paths::is_root() {
+                        eprintln!(
+                            "{}",
+                            Yellow.paint(
+                                "Neither bubblewrap nor systemd-run is usable, ldd is not run as \
+                                 root without a sandbox, files are parsed instead"
+                            )
+                        );
+                    } else {
+                        eprintln!(
+                            "{}",
+                            Yellow.paint(
+                                "Neither bubblewrap nor systemd-run is usable, ldd runs without a \
+                                 sandbox"
+                            )
+                        );
+                    }
+                    Sandbox::None
+                });
+            debug!("Sandbox: {:?}", sandbox);
+            sandbox
+        })
+    }
+
+    /// Return true if programs that can execute code of analyzed files may run in the sandbox,
+    /// never without one when running as root
+    pub fn allows_untrusted(self) -> bool {
+        (self != Sandbox::None) || !paths::is_root()
+    }
+
+    /// Return true if the sandbox works, by running a program that does nothing in it
+    fn is_available(self) -> bool {
+        let usable = match self {
+            Sandbox::Bubblewrap => tools::is_in_path("bwrap"),
+            Sandbox::SystemdRun => {
+                paths::is_root()
+                    && tools::is_in_path("systemd-run")
+                    && Path::new("/run/systemd/system").is_dir()
+            }
+            Sandbox::None => true,
+        };
+        usable
+            && self
+                .command("true", &[] as &[&str])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+    }
+
+    /// Build command running a program in the sandbox
+    pub fn command<S: AsRef<OsStr>>(self, program: &str, args: &[S]) -> Command {
+        let mut command = match self {
+            Sandbox::Bubblewrap => {
+                let mut command = Command::new("bwrap");
+                command.args(BWRAP_ARGS).arg(program);
+                command
+            }
+            Sandbox::SystemdRun => {
+                let mut command = Command::new("systemd-run");
+                if env::var_os("LD_LIBRARY_PATH").is_some() {
+                    command.args(SYSTEMD_RUN_ARGS);
+                } else {
+                    command.args(&SYSTEMD_RUN_ARGS[..SYSTEMD_RUN_ARGS.len() - 2]);
+                }
+                command.arg("--").arg(program);
+                command
+            }
+            Sandbox::None => Command::new(program),
+        };
+        command.args(args);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let command = Sandbox::Bubblewrap.command("ldd", &["/usr/bin/foo"]);
+        assert_eq!(command.get_program(), "bwrap");
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(&args[..3], ["--ro-bind", "/", "/"]);
+        assert_eq!(&args[args.len() - 3..], ["--", "ldd", "/usr/bin/foo"]);
+
+        let command = Sandbox::None.command("ldd", &["/usr/bin/foo"]);
+        assert_eq!(command.get_program(), "ldd");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["/usr/bin/foo"]);
+
+        assert!(Sandbox::None.is_available());
+
+        assert!(Sandbox::Bubblewrap.allows_untrusted());
+        assert!(Sandbox::SystemdRun.allows_untrusted());
+        assert_eq!(Sandbox::None.allows_untrusted(), !paths::is_root());
+    }
+}
@@ -0,0 +1,131 @@
+//! Severity of findings overridden by the configuration, to encode a local policy of what is a
+//! failure, like `severity."LIB001:zoom*" = "info"`
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ignore::IgnoreRule;
+use crate::report::{Finding, Severity};
+
+/// Severity of the findings a rule matches, with the same "CODE[:PATTERN]" syntax as ignore rules
+#[derive(Clone, Debug)]
+pub struct SeverityOverride {
+    rule: IgnoreRule,
+    severity: Severity,
+}
+
+impl FromStr for SeverityOverride {
+    type Err = String;
+
+    /// Parse override from "CODE[:PATTERN]=SEVERITY" string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rule, severity) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected CODE[:PATTERN]=SEVERITY, got {:?}", s))?;
+        Ok(SeverityOverride {
+            rule: rule.parse()?,
+            severity: severity.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for SeverityOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.rule, self.severity)
+    }
+}
+
+/// Severity overrides, those with a pattern take precedence over those for a whole code
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, Severity>",
+    into = "BTreeMap<String, Severity>"
+)]
+pub struct SeverityOverrides(Vec<SeverityOverride>);
+
+impl SeverityOverrides {
+    /// Get severity of a finding, overridden or its default one
+    pub fn severity(&self, finding: &Finding) -> Severity {
+        self.0
+            .iter()
+            .filter(|o| o.rule.matches(finding))
+            .max_by_key(|o| o.rule.has_pattern())
+            .map_or_else(|| finding.severity(), |o| o.severity)
+    }
+
+    /// Add overrides, replacing existing ones for the same rule
+    pub fn extend(&mut self, overrides: impl IntoIterator<Item = SeverityOverride>) {
+        for o in overrides {
+            let rule = o.rule.to_string();
+            self.0.retain(|e| e.rule.to_string() != rule);
+            self.0.push(o);
+        }
+    }
+}
+
+impl TryFrom<BTreeMap<String, Severity>> for SeverityOverrides {
+    type Error = String;
+
+    fn try_from(map: BTreeMap<String, Severity>) -> Result<Self, Self::Error> {
+        map.into_iter()
+            .map(|(rule, severity)| {
+                Ok(SeverityOverride {
+                    rule: rule.parse()?,
+                    severity,
+                })
+            })
+            .collect::<Result<_, String>>()
+            .map(SeverityOverrides)
+    }
+}
+
+impl From<SeverityOverrides> for BTreeMap<String, Severity> {
+    fn from(overrides: SeverityOverrides) -> BTreeMap<String, Severity> {
+        overrides
+            .0
+            .into_iter()
+            .map(|o| (o.rule.to_string(), o.severity))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_overrides() {
+        let missing = |package: &str| Finding::MissingDependency {
+            package: package.to_string(),
+            file: format!("/usr/bin/{}", package),
+            dependency: "libfoo.so.1".to_string(),
+            held_back: None,
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+        let overrides = SeverityOverrides::try_from(
+            toml::from_str::<BTreeMap<String, Severity>>(
+                "\"LIB001:zoom*\" = \"info\"\nLIB001 = \"error\"\n",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(overrides.severity(&missing("zoom")), Severity::Info);
+        assert_eq!(overrides.severity(&missing("foo")), Severity::Error);
+        assert_eq!(
+            SeverityOverrides::default().severity(&missing("foo")),
+            Severity::Warning
+        );
+
+        let mut overrides = overrides;
+        overrides.extend(vec!["lib001=warning".parse().unwrap()]);
+        assert_eq!(overrides.severity(&missing("foo")), Severity::Warning);
+        assert!("LIB001".parse::<SeverityOverride>().is_err());
+        assert!("LIB001=fatal".parse::<SeverityOverride>().is_err());
+    }
+}
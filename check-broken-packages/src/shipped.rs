@@ -0,0 +1,87 @@
+//! Sonames shipped by the repositories, from the soname provides of their packages, to tell
+//! prebuilt packages needing a library version that Arch no longer ships, which a rebuild of the
+//! package (that only repackages the upstream binary) does not fix
+
+use std::collections::HashSet;
+use std::error;
+use std::path::Path;
+
+use crate::elf;
+use crate::pacman::{self, provided_sonames};
+
+/// Suffix of the names of AUR packages repackaging upstream binaries
+const PREBUILT_PACKAGE_SUFFIX: &str = "-bin";
+
+/// Sonames provided by packages of the sync databases
+#[derive(Default)]
+pub struct ShippedSonames {
+    sonames: HashSet<String>,
+    /// Library names without version, like "libfoo.so"
+    libraries: HashSet<String>,
+}
+
+impl ShippedSonames {
+    /// Load sonames of the sync databases of a system at a root directory
+    pub fn load(root: &Path) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        let sync_packages =
+            pacman::read_sync_db(&elf::rooted(root, Path::new(pacman::SYNC_DB_DIR)))?;
+        Ok(ShippedSonames::new(
+            sync_packages
+                .values()
+                .flat_map(|p| provided_sonames(&p.provides)),
+        ))
+    }
+
+    pub fn new(sonames: impl IntoIterator<Item = String>) -> ShippedSonames {
+        let sonames: HashSet<String> = sonames.into_iter().collect();
+        let libraries = sonames
+            .iter()
+            .filter_map(|s| library_name(s))
+            .map(str::to_string)
+            .collect();
+        ShippedSonames { sonames, libraries }
+    }
+
+    /// Return true if the repositories ship other versions of a library, but not this one.
+    /// Libraries that no repository package provides as a soname are unknown, not unshipped.
+    pub fn is_unshipped(&self, soname: &str) -> bool {
+        !self.sonames.contains(soname)
+            && library_name(soname).is_some_and(|l| self.libraries.contains(l))
+    }
+}
+
+/// Get library name of a soname, like "libfoo.so" from "libfoo.so.1"
+fn library_name(soname: &str) -> Option<&str> {
+    soname
+        .find(".so.")
+        .map(|i| &soname[..i + 3])
+        .or_else(|| soname.ends_with(".so").then_some(soname))
+}
+
+/// Return true if a package repackages an upstream binary, instead of building it
+pub fn is_prebuilt(package: &str) -> bool {
+    package.ends_with(PREBUILT_PACKAGE_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_unshipped() {
+        let shipped = ShippedSonames::new(vec![
+            "libicuuc.so.75".to_string(),
+            "libssl.so.3".to_string(),
+            "libssl.so.1.1".to_string(),
+        ]);
+        assert!(shipped.is_unshipped("libicuuc.so.72"));
+        assert!(!shipped.is_unshipped("libicuuc.so.75"));
+        assert!(!shipped.is_unshipped("libssl.so.1.1"));
+        assert!(!shipped.is_unshipped("libfoo.so.1"));
+
+        assert_eq!(library_name("libssl.so.1.1"), Some("libssl.so"));
+        assert_eq!(library_name("libfoo.so"), Some("libfoo.so"));
+        assert!(is_prebuilt("zoom-bin"));
+        assert!(!is_prebuilt("binutils"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Index of the shared libraries of system library directories by soname, which is expensive to
+//! build, so it can be exported to a file once and imported by later runs or other tools
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::elf;
+use crate::format;
+
+/// Format version of exported indexes
+const FORMAT_VERSION: u32 = 1;
+
+/// Shared library of an indexed directory
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexedLibrary {
+    pub dir: PathBuf,
+    /// ELF class (32 or 64 bits) and machine
+    pub class: u8,
+    pub machine: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SonameIndex {
+    /// Indexed directories, with their modification time in nanoseconds since the epoch when
+    /// indexed, a directory changes when files are added or removed in it
+    dirs: BTreeMap<PathBuf, u128>,
+
+    /// Soname to libraries with this name
+    libraries: BTreeMap<String, Vec<IndexedLibrary>>,
+}
+
+/// Get modification time of a directory, in nanoseconds since the epoch
+fn dir_mtime(dir: &Path) -> Option<u128> {
+    fs::metadata(dir)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+}
+
+impl SonameIndex {
+    /// Index directories of a system mounted at a root directory
+    pub fn build(root: &Path, dirs: &[PathBuf]) -> SonameIndex {
+        let mut index = SonameIndex::default();
+        for dir in dirs {
+            let rooted_dir = elf::rooted(root, dir);
+            let mtime = match dir_mtime(&rooted_dir) {
+                Some(t) => t,
+                None => continue,
+            };
+            let entries = match fs::read_dir(&rooted_dir) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let soname = entry.file_name().to_string_lossy().to_string();
+                if !soname.contains(".so") {
+                    continue;
+                }
+                // Follows symbolic links, like from the soname to the versioned file
+                if let Some((class, machine)) = elf::class_machine(&entry.path()) {
+                    index
+                        .libraries
+                        .entry(soname)
+                        .or_default()
+                        .push(IndexedLibrary {
+                            dir: dir.clone(),
+                            class,
+                            machine,
+                        });
+                }
+            }
+            index.dirs.insert(dir.clone(), mtime);
+        }
+        index
+    }
+
+    /// Load index exported to a file, for a system mounted at a root directory, directories that
+    /// changed since are not used
+    pub fn load(filepath: &Path, root: &Path) -> Result<SonameIndex, format::FormatError> {
+        // Indexes exported before formats were versioned have the same content
+        let mut index: SonameIndex = format::read(filepath, FORMAT_VERSION, |version, value| {
+            (version == 0).then_some(value)
+        })?;
+        index
+            .dirs
+            .retain(|d, mtime| dir_mtime(&elf::rooted(root, d)) == Some(*mtime));
+        let dirs = &index.dirs;
+        for libraries in index.libraries.values_mut() {
+            libraries.retain(|l| dirs.contains_key(&l.dir));
+        }
+        Ok(index)
+    }
+
+    /// Export index to a file
+    pub fn save(&self, filepath: &Path) -> Result<(), Box<dyn error::Error>> {
+        format::write(filepath, FORMAT_VERSION, self)
+    }
+
+    /// Look up a library in a directory, optionally of an ELF class and machine, None if the
+    /// directory is not indexed
+    pub fn lookup(
+        &self,
+        dir: &Path,
+        soname: &str,
+        class_machine: Option<(u8, u16)>,
+    ) -> Option<bool> {
+        if !self.dirs.contains_key(dir) {
+            return None;
+        }
+        Some(self.libraries.get(soname).is_some_and(|l| {
+            l.iter().any(|l| {
+                (l.dir == dir) && class_machine.is_none_or(|cm| cm == (l.class, l.machine))
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_soname_index() {
+        let root = TempDir::new("").unwrap();
+        let lib_dir = root.path().join("usr/lib");
+        fs::create_dir_all(&lib_dir).unwrap();
+        let mut header = b"\x7fELF\x02\x01\x01".to_vec();
+        header.resize(16, 0);
+        // ET_DYN, x86-64
+        header.extend([3, 0, 62, 0, 1, 0, 0, 0]);
+        header.resize(64, 0);
+        fs::write(lib_dir.join("libfoo.so.1"), &header).unwrap();
+        fs::write(lib_dir.join("libbar.so.1"), "not an ELF file").unwrap();
+
+        let dirs = [PathBuf::from("/usr/lib"), PathBuf::from("/opt/lib")];
+        let index = SonameIndex::build(root.path(), &dirs);
+        let index_filepath = root.path().join("index.json");
+        index.save(&index_filepath).unwrap();
+
+        let index = SonameIndex::load(&index_filepath, root.path()).unwrap();
+        let usr_lib = Path::new("/usr/lib");
+        assert_eq!(
+            index.lookup(usr_lib, "libfoo.so.1", Some((2, 62))),
+            Some(true)
+        );
+        assert_eq!(
+            index.lookup(usr_lib, "libfoo.so.1", Some((1, 3))),
+            Some(false)
+        );
+        assert_eq!(index.lookup(usr_lib, "libbar.so.1", None), Some(false));
+        assert_eq!(
+            index.lookup(Path::new("/opt/lib"), "libfoo.so.1", None),
+            None
+        );
+
+        // Directories changed since export are not used
+        fs::write(lib_dir.join("libbaz.so.1"), &header).unwrap();
+        let index = SonameIndex::load(&index_filepath, root.path()).unwrap();
+        assert_eq!(index.lookup(usr_lib, "libfoo.so.1", None), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Summary of missing libraries by soname, the natural unit to plan rebuilds after a soname bump
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::report::Finding;
+
+/// Packages and number of files missing a soname
+#[derive(Debug, Default)]
+struct SonameUsers {
+    packages: BTreeSet<String>,
+    file_count: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SonameSummary {
+    sonames: BTreeMap<String, SonameUsers>,
+}
+
+/// Normalize a missing dependency to a soname, dependencies can also be paths
+fn normalize_soname(dependency: &str) -> &str {
+    let dependency = dependency.trim();
+    Path::new(dependency)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(dependency)
+}
+
+impl SonameSummary {
+    pub fn add(&mut self, finding: &Finding) {
+        if let Finding::MissingDependency {
+            package,
+            dependency,
+            ..
+        }
+        | Finding::AudioPluginMissingDependency {
+            package,
+            dependency,
+            ..
+        }
+        | Finding::UpgradeRemovesDependency {
+            package,
+            dependency,
+            ..
+        } = finding
+        {
+            let users = self
+                .sonames
+                .entry(normalize_soname(dependency).to_string())
+                .or_default();
+            users.packages.insert(package.clone());
+            users.file_count += 1;
+        }
+    }
+
+    /// Return true if several files miss the same soname, otherwise the findings say it all
+    pub fn is_relevant(&self) -> bool {
+        self.sonames.values().any(|u| u.file_count > 1)
+    }
+
+    /// Get sonames with their number of packages and files, most packages first
+    pub fn groups(&self) -> Vec<(&str, usize, usize)> {
+        let mut groups: Vec<(&str, usize, usize)> = self
+            .sonames
+            .iter()
+            .map(|(s, u)| (s.as_str(), u.packages.len(), u.file_count))
+            .collect();
+        groups.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(b.0)));
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soname_summary() {
+        let missing = |package: &str, file: &str, dependency: &str| Finding::MissingDependency {
+            package: package.to_string(),
+            file: file.to_string(),
+            dependency: dependency.to_string(),
+            held_back: None,
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+        let mut summary = SonameSummary::default();
+        summary.add(&missing("foo", "/usr/bin/foo", "libicuuc.so.72"));
+        assert!(!summary.is_relevant());
+        summary.add(&missing("foo", "/usr/bin/foo-cli", "libicuuc.so.72"));
+        summary.add(&missing("bar", "/usr/bin/bar", "/usr/lib/libicuuc.so.72"));
+        summary.add(&missing("bar", "/usr/bin/bar", "libbar.so.1"));
+        summary.add(&Finding::BrokenServiceLink {
+            link: "/etc/systemd/system/foo.service".to_string(),
+        });
+        assert!(summary.is_relevant());
+        assert_eq!(
+            summary.groups(),
+            [("libicuuc.so.72", 2, 3), ("libbar.so.1", 1, 1)]
+        );
+    }
+}
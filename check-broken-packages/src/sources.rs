@@ -0,0 +1,201 @@
+//! Sources of the set of packages to analyze, foreign packages by default
+
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::system::SystemProvider;
+
+/// Where to get the names of the packages to analyze
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PackageSource {
+    /// Packages not in any sync database, from `pacman -Qqm`
+    #[default]
+    Foreign,
+
+    /// Packages listed in a file, one per line
+    List(PathBuf),
+
+    /// Packages built from the PKGBUILD checkouts of an AUR helper clone directory, with one
+    /// subdirectory per package base
+    CloneDir(PathBuf),
+}
+
+impl PackageSource {
+    /// Get names of installed packages of the source
+    pub fn packages(
+        &self,
+        system: &dyn SystemProvider,
+    ) -> Result<Vec<String>, Box<dyn error::Error>> {
+        let names: Vec<String> = match self {
+            PackageSource::Foreign => return system.foreign_packages(),
+            PackageSource::List(filepath) => fs::read_to_string(filepath)
+                .map_err(|e| format!("{:?}: {}", filepath, e))?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+            PackageSource::CloneDir(_) => self.checkouts()?.into_keys().collect(),
+        };
+
+        // Packages built long ago, or listed by mistake, may not be installed
+        let installed: HashSet<String> = system.packages()?.into_iter().collect();
+        let (packages, not_installed): (Vec<String>, Vec<String>) =
+            names.into_iter().partition(|n| installed.contains(n));
+        if let PackageSource::List(filepath) = self {
+            for package in not_installed {
+                eprintln!(
+                    "Ignoring package '{}' listed in {:?}, it is not installed",
+                    package, filepath
+                );
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Get PKGBUILD checkout directory of each package of the source, if it has any
+    pub fn checkouts(&self) -> Result<BTreeMap<String, PathBuf>, Box<dyn error::Error>> {
+        match self {
+            PackageSource::CloneDir(clone_dir) => checkouts(clone_dir),
+            _ => Ok(BTreeMap::new()),
+        }
+    }
+}
+
+/// Get PKGBUILD checkout directory of each package built by the checkouts of a directory, with
+/// one subdirectory per package base
+pub fn checkouts(dir: &Path) -> Result<BTreeMap<String, PathBuf>, Box<dyn error::Error>> {
+    let mut checkouts = BTreeMap::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("{:?}: {}", dir, e))? {
+        let checkout_dir = entry?.path();
+        if !checkout_dir.join("PKGBUILD").is_file() {
+            continue;
+        }
+        for package in checkout_packages(&checkout_dir) {
+            checkouts.insert(package, checkout_dir.clone());
+        }
+    }
+    Ok(checkouts)
+}
+
+/// Get names of the packages built by a PKGBUILD checkout, from its .SRCINFO file if any
+/// (split packages), otherwise the package base is the directory name
+fn checkout_packages(checkout_dir: &Path) -> Vec<String> {
+    let names: Vec<String> = fs::read_to_string(checkout_dir.join(".SRCINFO"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("pkgname = "))
+        .map(str::to_string)
+        .collect();
+    if !names.is_empty() {
+        return names;
+    }
+    checkout_dir
+        .file_name()
+        .map(|n| vec![n.to_string_lossy().to_string()])
+        .unwrap_or_default()
+}
+
+/// Get clone directory of an AUR helper for the current user
+fn helper_clone_dir(subdir: &str) -> Result<PathBuf, String> {
+    let cache_dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => PathBuf::from(env::var_os("HOME").ok_or("HOME is not set")?).join(".cache"),
+    };
+    Ok(cache_dir.join(subdir))
+}
+
+impl FromStr for PackageSource {
+    type Err = String;
+
+    /// Parse source from "foreign", "list:FILE", "clone-dir:DIR", or "paru"/"yay" for the clone
+    /// directory of these AUR helpers for the current user
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("list", filepath)) => Ok(PackageSource::List(PathBuf::from(filepath))),
+            Some(("clone-dir", dir)) => Ok(PackageSource::CloneDir(PathBuf::from(dir))),
+            None if s == "foreign" => Ok(PackageSource::Foreign),
+            None if s == "paru" => helper_clone_dir("paru/clone").map(PackageSource::CloneDir),
+            None if s == "yay" => helper_clone_dir("yay").map(PackageSource::CloneDir),
+            _ => Err(format!(
+                "Invalid package source {:?}, expected 'foreign', 'list:FILE', 'clone-dir:DIR', \
+                 'paru' or 'yay'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PackageSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackageSource::Foreign => write!(f, "foreign"),
+            PackageSource::List(filepath) => write!(f, "list:{}", filepath.display()),
+            PackageSource::CloneDir(dir) => write!(f, "clone-dir:{}", dir.display()),
+        }
+    }
+}
+
+impl TryFrom<String> for PackageSource {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PackageSource> for String {
+    fn from(source: PackageSource) -> String {
+        source.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_clone_dir() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let clone_dir = tmp_dir.path();
+        fs::create_dir_all(clone_dir.join("foo")).unwrap();
+        fs::write(clone_dir.join("foo/PKGBUILD"), "").unwrap();
+        fs::create_dir_all(clone_dir.join("bar")).unwrap();
+        fs::write(clone_dir.join("bar/PKGBUILD"), "").unwrap();
+        fs::write(
+            clone_dir.join("bar/.SRCINFO"),
+            "pkgbase = bar\n\tpkgver = 1\n\npkgname = bar-cli\n\npkgname = bar-gui\n",
+        )
+        .unwrap();
+        // Not a PKGBUILD checkout
+        fs::create_dir_all(clone_dir.join("repo")).unwrap();
+
+        let source: PackageSource = format!("clone-dir:{}", clone_dir.display())
+            .parse()
+            .unwrap();
+        assert_eq!(
+            source.to_string().parse::<PackageSource>(),
+            Ok(source.clone())
+        );
+        let checkouts = source.checkouts().unwrap();
+        assert_eq!(
+            checkouts.keys().collect::<Vec<_>>(),
+            ["bar-cli", "bar-gui", "foo"]
+        );
+        assert_eq!(checkouts["bar-gui"], clone_dir.join("bar"));
+
+        assert_eq!("foreign".parse(), Ok(PackageSource::Foreign));
+        assert!("aur".parse::<PackageSource>().is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Anonymized statistics of findings, without package names or paths, that users can share when
+//! reporting a breakage affecting many systems (like after a soname bump)
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::report::Finding;
+
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    /// Version of the tool, findings depend on it
+    version: &'static str,
+
+    /// Number of findings by code
+    finding_counts: BTreeMap<&'static str, usize>,
+
+    /// Number of packages with findings
+    package_count: usize,
+
+    /// Sonames of missing libraries (or removed ones by a pending upgrade), with the number of
+    /// packages linking them
+    missing_sonames: BTreeMap<String, usize>,
+
+    /// Packages with findings, and with each missing soname, only counted
+    #[serde(skip)]
+    packages: HashSet<String>,
+    #[serde(skip)]
+    soname_packages: HashSet<(String, String)>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            version: env!("CARGO_PKG_VERSION"),
+            finding_counts: BTreeMap::new(),
+            package_count: 0,
+            missing_sonames: BTreeMap::new(),
+            packages: HashSet::new(),
+            soname_packages: HashSet::new(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn add(&mut self, finding: &Finding) {
+        *self.finding_counts.entry(finding.code()).or_default() += 1;
+        let subject = finding.subject();
+        // Subjects that are paths are not about a package
+        if !subject.starts_with('/') && self.packages.insert(subject.to_string()) {
+            self.package_count += 1;
+        }
+        if let Finding::MissingDependency {
+            package,
+            dependency,
+            ..
+        }
+        | Finding::AudioPluginMissingDependency {
+            package,
+            dependency,
+            ..
+        }
+        | Finding::UpgradeRemovesDependency {
+            package,
+            dependency,
+            ..
+        } = finding
+        {
+            if self
+                .soname_packages
+                .insert((dependency.clone(), package.clone()))
+            {
+                *self.missing_sonames.entry(dependency.clone()).or_default() += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let missing = |package: &str, file: &str, dependency: &str| Finding::MissingDependency {
+            package: package.to_string(),
+            file: file.to_string(),
+            dependency: dependency.to_string(),
+            held_back: None,
+            chain: Vec::new(),
+            transitive: None,
+            unshipped: false,
+        };
+        let mut stats = Stats::default();
+        stats.add(&missing("foo", "/usr/bin/foo", "libicuuc.so.74"));
+        stats.add(&missing("foo", "/usr/bin/foo-cli", "libicuuc.so.74"));
+        stats.add(&missing("bar", "/usr/bin/bar", "libicuuc.so.74"));
+        stats.add(&missing("bar", "/usr/bin/bar", "libbaz.so.1"));
+        stats.add(&Finding::BrokenServiceLink {
+            link: "/etc/systemd/system/multi-user.target.wants/foo.service".to_string(),
+        });
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "finding_counts": {"LIB001": 4, "SD001": 1},
+                "package_count": 2,
+                "missing_sonames": {"libbaz.so.1": 1, "libicuuc.so.74": 2},
+            })
+        );
+        let json = json.to_string();
+        assert!(!json.contains("foo") && !json.contains("/usr"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Live analysis status, dumped on SIGUSR1, and analysis time of packages
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::signal::SIGUSR1;
+use signal_hook::iterator::Signals;
+
+/// Maximum number of in-flight files to display
+const MAX_IN_FLIGHT_DISPLAY: usize = 5;
+
+/// Package, filepath and analysis start time of a file being analyzed
+type InFlightFile = (Arc<String>, Arc<String>, Instant);
+
+/// Shared analysis status, updated by workers
+#[derive(Default)]
+pub struct Status {
+    phase: Mutex<&'static str>,
+
+    package_count: AtomicUsize,
+
+    package_done_count: AtomicUsize,
+
+    /// Directories walked by parallel tree walks
+    walked_dir_count: AtomicUsize,
+
+    /// Files being analyzed, by worker thread
+    in_flight: Mutex<HashMap<thread::ThreadId, InFlightFile>>,
+
+    /// Total analysis time of the files of packages, summed over worker threads
+    package_times: Mutex<HashMap<Arc<String>, Duration>>,
+}
+
+impl Status {
+    pub fn set_phase(&self, phase: &'static str) {
+        *self.phase.lock().unwrap() = phase;
+    }
+
+    pub fn set_package_count(&self, count: usize) {
+        self.package_count.store(count, Ordering::SeqCst);
+    }
+
+    pub fn package_done(&self) {
+        self.package_done_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Get counter of directories walked, to pass to tree walks
+    pub fn walk_progress(&self) -> &AtomicUsize {
+        &self.walked_dir_count
+    }
+
+    /// Mark file as being analyzed by the current thread, until the returned guard is dropped
+    pub fn file_in_flight(
+        &self,
+        package: &Arc<String>,
+        filepath: &Arc<String>,
+    ) -> InFlightGuard<'_> {
+        self.in_flight.lock().unwrap().insert(
+            thread::current().id(),
+            (Arc::clone(package), Arc::clone(filepath), Instant::now()),
+        );
+        InFlightGuard(self)
+    }
+
+    /// Get packages whose files took the longest to analyze, slowest first
+    pub fn slowest_packages(&self, count: usize) -> Vec<(Arc<String>, Duration)> {
+        let mut times: Vec<(Arc<String>, Duration)> = self
+            .package_times
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(p, d)| (Arc::clone(p), *d))
+            .collect();
+        times.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        times.truncate(count);
+        times
+    }
+
+    /// Build human readable status dump
+    fn dump(&self) -> String {
+        let package_count = self.package_count.load(Ordering::SeqCst);
+        let package_done_count = self.package_done_count.load(Ordering::SeqCst);
+        let mut lines = vec![format!(
+            "Phase: {}, packages completed: {}/{}, pending: {}",
+            self.phase.lock().unwrap(),
+            package_done_count,
+            package_count,
+            package_count.saturating_sub(package_done_count)
+        )];
+        let walked_dir_count = self.walked_dir_count.load(Ordering::SeqCst);
+        if walked_dir_count > 0 {
+            lines.push(format!("Directories walked: {}", walked_dir_count));
+        }
+
+        let now = Instant::now();
+        let mut in_flight: Vec<(Arc<String>, Arc<String>, Duration)> = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(p, f, t)| (Arc::clone(p), Arc::clone(f), now - *t))
+            .collect();
+        in_flight.sort_unstable_by_key(|f| cmp::Reverse(f.2));
+        if !in_flight.is_empty() {
+            lines.push("Slowest in-flight files:".to_string());
+        }
+        for (package, filepath, duration) in in_flight.iter().take(MAX_IN_FLIGHT_DISPLAY) {
+            lines.push(format!(
+                "  {:.1}s '{}' from package '{}'",
+                duration.as_secs_f32(),
+                filepath,
+                package
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Spawn thread dumping status to stderr each time SIGUSR1 is received
+    pub fn dump_on_signal(status: &Arc<Status>) -> Result<(), std::io::Error> {
+        let mut signals = Signals::new([SIGUSR1])?;
+        let status = Arc::clone(status);
+        thread::Builder::new().spawn(move || {
+            for _ in signals.forever() {
+                eprintln!("{}", status.dump());
+            }
+        })?;
+        Ok(())
+    }
+}
+
+/// Removes file from in-flight files when dropped, adding its analysis time to its package
+pub struct InFlightGuard<'a>(&'a Status);
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let file = self
+            .0
+            .in_flight
+            .lock()
+            .unwrap()
+            .remove(&thread::current().id());
+        if let Some((package, _filepath, start)) = file {
+            *self
+                .0
+                .package_times
+                .lock()
+                .unwrap()
+                .entry(package)
+                .or_default() += start.elapsed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let status = Status::default();
+        status.set_phase("analyzing packages");
+        status.set_package_count(3);
+        status.package_done();
+        let package = Arc::new("foo".to_string());
+        let filepath = Arc::new("/usr/bin/foo".to_string());
+
+        {
+            let _guard = status.file_in_flight(&package, &filepath);
+            let dump = status.dump();
+            assert!(dump.starts_with(
+                "Phase: analyzing packages, packages completed: 1/3, pending: 2\nSlowest in-flight files:\n  "
+            ));
+            assert!(dump.ends_with("s '/usr/bin/foo' from package 'foo'"));
+        }
+
+        assert_eq!(
+            status.dump(),
+            "Phase: analyzing packages, packages completed: 1/3, pending: 2"
+        );
+    }
+
+    #[test]
+    fn test_slowest_packages() {
+        let status = Status::default();
+        let packages: Vec<Arc<String>> = ["foo", "bar", "baz"]
+            .iter()
+            .map(|p| Arc::new(p.to_string()))
+            .collect();
+        let filepath = Arc::new("/usr/bin/foo".to_string());
+        for (package, duration) in packages.iter().zip([5, 30, 10]) {
+            let _guard = status.file_in_flight(package, &filepath);
+            thread::sleep(Duration::from_millis(duration));
+        }
+        {
+            let _guard = status.file_in_flight(&packages[0], &filepath);
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let slowest = status.slowest_packages(2);
+        assert_eq!(
+            slowest.iter().map(|(p, _d)| p.as_str()).collect::<Vec<_>>(),
+            vec!["bar", "foo"]
+        );
+        assert!(slowest[1].1 >= Duration::from_millis(25));
+    }
+}
@@ -0,0 +1,676 @@
+//! Access to the system being analyzed (pacman database, files, ELF dependencies), behind a trait
+//! so that the analysis can be tested without a real Arch Linux system
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use log::debug;
+
+use crate::elf::{self, DebugInfo, ElfInfo, MissingLibrary, Resolver};
+use crate::held::HeldPackages;
+use crate::pacman::{self, FileIndex, LocalPackage};
+use crate::report::{FileDamage, HardeningIssue};
+use crate::sandbox::Sandbox;
+use crate::shipped::ShippedSonames;
+use crate::soname_index::SonameIndex;
+
+/// File metadata, following symbolic links
+#[derive(Debug, Clone, Copy)]
+pub struct FileMetadata {
+    pub is_file: bool,
+    /// Permission bits
+    pub mode: u32,
+    pub len: u64,
+}
+
+/// Soname of a direct dynamic dependency, with the path it resolves to if found
+pub type LinkedLibrary = (String, Option<PathBuf>);
+
+/// Error of the dynamic loader on a file, other than missing libraries
+#[derive(Debug)]
+pub struct LoaderError(pub String);
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dynamic loader error: {}", self.0)
+    }
+}
+
+impl error::Error for LoaderError {}
+
+/// Source of everything the analysis reads from the system
+pub trait SystemProvider: Sync {
+    /// Get names of foreign packages (not from a sync repository, typically from the AUR)
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>>;
+
+    /// Get names of all installed packages
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>>;
+
+    /// Get installed packages with their dependencies and provides
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>>;
+
+    /// Get files owned by a package, excluding directories
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>>;
+
+    /// Build index of files owned by all installed packages
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>>;
+
+    /// Get metadata of a file, following symbolic links
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Read at most len bytes from the start of a file
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Get sonames of missing dynamic dependencies of an ELF file
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>>;
+
+    /// Get sonames of the missing libraries of the whole dependency tree of an ELF file, with the
+    /// chain of libraries through which each one is needed. Without a chain by default.
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        Ok(self
+            .missing_dependencies(path)?
+            .into_iter()
+            .map(|soname| MissingLibrary {
+                soname,
+                chain: Vec::new(),
+                needed_by: None,
+            })
+            .collect())
+    }
+
+    /// Get sonames of the direct dynamic dependencies of an ELF file, with the path they resolve
+    /// to if found
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>>;
+
+    /// Detect if a file is empty or a truncated ELF file
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>>;
+
+    /// Get dynamic linking information of an ELF file, None if it is not an ELF file
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>>;
+
+    /// Get hardening features missing from an ELF executable or shared library, None if it is not
+    /// such a file
+    fn hardening_issues(&self, path: &Path) -> io::Result<Option<Vec<HardeningIssue>>> {
+        Ok(elf::hardening_issues(
+            &self.read_head(path, elf::HARDENING_HEAD_LEN)?,
+        ))
+    }
+
+    /// Get debugging information of an ELF executable or shared library, None if it is not such
+    /// a file
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>>;
+
+    /// Get packages held back from upgrades by the pacman configuration
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>>;
+
+    /// Get sonames shipped by the repositories
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>>;
+}
+
+/// The system we are running on
+#[derive(Default)]
+pub struct RealSystem {
+    /// Find missing libraries with ldd instead of parsing ELF files in process. ldd runs the
+    /// dynamic loader on files, which can execute code of untrusted ones, so it runs in a
+    /// sandbox, like for files that can not be parsed.
+    pub ldd: bool,
+}
+
+impl SystemProvider for RealSystem {
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        pacman::get_aur_packages()
+    }
+
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        Ok(pacman::get_installed_packages()?
+            .into_iter()
+            .map(|(n, _v)| n)
+            .collect())
+    }
+
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+        pacman::read_local_db(Path::new(pacman::LOCAL_DB_DIR))
+    }
+
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+        pacman::get_package_files(package)
+    }
+
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+        FileIndex::new()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileMetadata {
+            is_file: metadata.is_file(),
+            mode: metadata.permissions().mode(),
+            len: metadata.len(),
+        })
+    }
+
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(len);
+        fs::File::open(path)?
+            .take(len as u64)
+            .read_to_end(&mut head)?;
+        Ok(head)
+    }
+
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+        // Without a sandbox, ldd never runs as root, files are parsed instead
+        if !self.ldd || !Sandbox::get().allows_untrusted() {
+            match ElfInfo::parse(path) {
+                Ok(Some(elf_info)) => {
+                    // Files of a foreign architecture are reported by their own check
+                    if !elf::is_native_machine(elf_info.machine) {
+                        return Ok(Vec::new());
+                    }
+                    // Like ldd, report missing libraries of the whole dependency tree
+                    return Ok(real_resolver()
+                        .resolve_tree(elf_info, path)
+                        .into_iter()
+                        .map(|m| m.soname)
+                        .collect());
+                }
+                Ok(None) => return Ok(Vec::new()),
+                // Can not be resolved statically, let the dynamic loader decide
+                Err(err)
+                    if err.is::<goblin::error::Error>() && Sandbox::get().allows_untrusted() =>
+                {
+                    debug!("Failed to parse {:?}, running ldd: {}", path, err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let path = path
+            .to_str()
+            .ok_or_else(|| format!("Non UTF-8 path {:?}", path))?;
+        crate::get_missing_dependencies(path, Sandbox::get())
+    }
+
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        let elf_info = match ElfInfo::parse(path)? {
+            Some(e) => e,
+            None => return Ok(Vec::new()),
+        };
+        Ok(real_resolver().resolve_tree(elf_info, path))
+    }
+
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+        let elf_info = match ElfInfo::parse(path)? {
+            Some(e) => e,
+            None => return Ok(Vec::new()),
+        };
+        Ok(real_resolver()
+            .resolve(&elf_info, path)
+            .into_iter()
+            .map(|r| (r.soname, r.found.map(|f| f.0)))
+            .collect())
+    }
+
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+        elf::damage(path)
+    }
+
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        ElfInfo::parse(path)
+    }
+
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+        elf::debug_info(&mut fs::File::open(path)?)
+    }
+
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+        HeldPackages::load(Path::new("/"))
+    }
+
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        ShippedSonames::load(Path::new("/"))
+    }
+}
+
+/// Get resolver of the running system, reading the dynamic linker configuration once is enough
+fn real_resolver() -> &'static Resolver {
+    static RESOLVER: OnceLock<Resolver> = OnceLock::new();
+    RESOLVER.get_or_init(Resolver::new)
+}
+
+/// A system mounted at a root directory (a chroot, container or backup), or the running system
+/// while its database is locked, its databases are read directly and dependencies are resolved
+/// without running anything from it
+pub struct RootSystem {
+    root: PathBuf,
+    packages: Vec<LocalPackage>,
+    foreign_packages: Vec<String>,
+    resolver: Resolver,
+    /// Find missing libraries with ldd like the running system does, only for the running system
+    /// since ldd runs its dynamic loader
+    ldd: bool,
+}
+
+impl RootSystem {
+    pub fn new(
+        root: &Path,
+        soname_index: Option<Arc<SonameIndex>>,
+        ldd: bool,
+    ) -> Result<RootSystem, Box<dyn error::Error>> {
+        let packages =
+            pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR)))
+                .map_err(|e| format!("Failed to read local database of {:?}: {}", root, e))?;
+        // Like pacman, packages are foreign if they are in no sync database
+        let sync_packages =
+            pacman::read_sync_db_packages(&elf::rooted(root, Path::new(pacman::SYNC_DB_DIR)))?;
+        let foreign_packages = packages
+            .iter()
+            .filter(|p| !sync_packages.contains(&p.name))
+            .map(|p| p.name.clone())
+            .collect();
+        Ok(RootSystem {
+            packages,
+            foreign_packages,
+            ..RootSystem::without_databases(root, soname_index, ldd)
+        })
+    }
+
+    /// Get system only analyzing files, without any package, like the unprivileged helpers do
+    pub fn without_databases(
+        root: &Path,
+        soname_index: Option<Arc<SonameIndex>>,
+        ldd: bool,
+    ) -> RootSystem {
+        let mut resolver = Resolver::with_root(root);
+        if let Some(soname_index) = soname_index {
+            resolver = resolver.with_soname_index(soname_index);
+        }
+        RootSystem {
+            root: root.to_path_buf(),
+            packages: Vec::new(),
+            foreign_packages: Vec::new(),
+            resolver,
+            ldd: ldd && (root == Path::new("/")),
+        }
+    }
+
+    fn rooted(&self, path: &Path) -> PathBuf {
+        elf::rooted(&self.root, path)
+    }
+}
+
+impl SystemProvider for RootSystem {
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        Ok(self.foreign_packages.clone())
+    }
+
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        Ok(self.packages.iter().map(|p| p.name.clone()).collect())
+    }
+
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+        Ok(self.packages.clone())
+    }
+
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.packages
+            .iter()
+            .find(|p| p.name == package)
+            .map(|p| p.files.clone())
+            .ok_or_else(|| format!("Package '{}' is not installed", package).into())
+    }
+
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+        let mut files = BTreeMap::new();
+        for package in &self.packages {
+            let name = Arc::new(package.name.clone());
+            for file in &package.files {
+                files.insert(file.clone(), Arc::clone(&name));
+            }
+        }
+        Ok(FileIndex { files })
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        RealSystem::default().metadata(&self.rooted(path))
+    }
+
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        RealSystem::default().read_head(&self.rooted(path), len)
+    }
+
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+        if self.ldd {
+            return RealSystem { ldd: true }.missing_dependencies(path);
+        }
+        // Like the running system, report missing libraries of the whole dependency tree
+        Ok(self
+            .missing_dependency_chains(path)?
+            .into_iter()
+            .map(|m| m.soname)
+            .collect())
+    }
+
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        let elf_info = match ElfInfo::parse(&self.rooted(path))? {
+            Some(e) => e,
+            None => return Ok(Vec::new()),
+        };
+        Ok(self.resolver.resolve_tree(elf_info, path))
+    }
+
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+        let elf_info = match ElfInfo::parse(&self.rooted(path))? {
+            Some(e) => e,
+            None => return Ok(Vec::new()),
+        };
+        Ok(self
+            .resolver
+            .resolve(&elf_info, path)
+            .into_iter()
+            .map(|r| (r.soname, r.found.map(|f| f.0)))
+            .collect())
+    }
+
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+        elf::damage(&self.rooted(path))
+    }
+
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        ElfInfo::parse(&self.rooted(path))
+    }
+
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+        elf::debug_info(&mut fs::File::open(self.rooted(path))?)
+    }
+
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+        HeldPackages::load(&self.root)
+    }
+
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        ShippedSonames::load(&self.root)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use goblin::elf::header;
+
+    use super::*;
+
+    /// A file of a mock system
+    struct MockFile {
+        mode: u32,
+        content: Vec<u8>,
+        missing_deps: Vec<String>,
+        /// Direct dependencies, with the path they resolve to
+        linked: Vec<LinkedLibrary>,
+    }
+
+    /// In memory system, files not explicitly added do not exist
+    #[derive(Default)]
+    pub struct MockSystem {
+        /// Package name to files, and whether the package is foreign
+        packages: BTreeMap<String, (Vec<String>, bool)>,
+        files: BTreeMap<PathBuf, MockFile>,
+        /// Files that exist, but can not be read or stat'ed
+        unreadable_files: Vec<PathBuf>,
+        /// Package name to declared dependencies
+        depends: BTreeMap<String, Vec<String>>,
+        /// Package name to install date, in seconds since the epoch
+        install_dates: BTreeMap<String, u64>,
+        /// Package name to version, "1-1" by default
+        versions: BTreeMap<String, String>,
+        /// Package name to architecture, unknown by default
+        architectures: BTreeMap<String, String>,
+        /// Sonames provided by packages of the repositories
+        shipped_sonames: Vec<String>,
+    }
+
+    impl MockSystem {
+        /// Add a package with its files, which are not created
+        pub fn package(mut self, name: &str, foreign: bool, files: &[&str]) -> Self {
+            let files = files.iter().map(|f| f.to_string()).collect();
+            self.packages.insert(name.to_string(), (files, foreign));
+            self
+        }
+
+        /// Add a file with permission bits, content, and the missing dependencies it would have
+        /// if it is an ELF file
+        pub fn file(
+            mut self,
+            path: &str,
+            mode: u32,
+            content: &[u8],
+            missing_deps: &[&str],
+        ) -> Self {
+            self.files.insert(
+                PathBuf::from(path),
+                MockFile {
+                    mode,
+                    content: content.to_vec(),
+                    missing_deps: missing_deps.iter().map(|d| d.to_string()).collect(),
+                    linked: Vec::new(),
+                },
+            );
+            self
+        }
+
+        /// Set direct dependencies of an added file, with the library path they resolve to, empty
+        /// if not found
+        pub fn linked(mut self, path: &str, libraries: &[(&str, &str)]) -> Self {
+            self.files.get_mut(Path::new(path)).unwrap().linked = libraries
+                .iter()
+                .map(|(s, p)| {
+                    (
+                        s.to_string(),
+                        Some(PathBuf::from(p)).filter(|_| !p.is_empty()),
+                    )
+                })
+                .collect();
+            self
+        }
+
+        /// Set declared dependencies of an added package
+        pub fn depends(mut self, package: &str, depends: &[&str]) -> Self {
+            self.depends.insert(
+                package.to_string(),
+                depends.iter().map(|d| d.to_string()).collect(),
+            );
+            self
+        }
+
+        /// Set install date of an added package, in seconds since the epoch
+        pub fn install_date(mut self, package: &str, date: u64) -> Self {
+            self.install_dates.insert(package.to_string(), date);
+            self
+        }
+
+        /// Set version of an added package
+        pub fn version(mut self, package: &str, version: &str) -> Self {
+            self.versions
+                .insert(package.to_string(), version.to_string());
+            self
+        }
+
+        /// Set architecture of an added package
+        pub fn arch(mut self, package: &str, arch: &str) -> Self {
+            self.architectures
+                .insert(package.to_string(), arch.to_string());
+            self
+        }
+
+        /// Add a file that exists, but that the current user can not access
+        pub fn unreadable_file(mut self, path: &str) -> Self {
+            self.unreadable_files.push(PathBuf::from(path));
+            self
+        }
+
+        pub fn shipped(mut self, sonames: &[&str]) -> Self {
+            self.shipped_sonames
+                .extend(sonames.iter().map(|s| s.to_string()));
+            self
+        }
+
+        fn get(&self, path: &Path) -> io::Result<&MockFile> {
+            if self.unreadable_files.iter().any(|p| p == path) {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            self.files
+                .get(path)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    impl SystemProvider for MockSystem {
+        fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+            Ok(self
+                .packages
+                .iter()
+                .filter(|(_n, (_f, foreign))| *foreign)
+                .map(|(n, _)| n.clone())
+                .collect())
+        }
+
+        fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+            Ok(self.packages.keys().cloned().collect())
+        }
+
+        fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+            Ok(self
+                .packages
+                .iter()
+                .map(|(name, (files, _foreign))| LocalPackage {
+                    name: name.clone(),
+                    version: self
+                        .versions
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_else(|| "1-1".to_string()),
+                    arch: self.architectures.get(name).cloned().unwrap_or_default(),
+                    provides: Vec::new(),
+                    depends: self.depends.get(name).cloned().unwrap_or_default(),
+                    groups: Vec::new(),
+                    installed_size: 0,
+                    install_date: self.install_dates.get(name).copied().unwrap_or(0),
+                    files: files.clone(),
+                })
+                .collect())
+        }
+
+        fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+            self.packages
+                .get(package)
+                .map(|(f, _)| f.clone())
+                .ok_or_else(|| format!("Unknown package {:?}", package).into())
+        }
+
+        fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+            let mut files = BTreeMap::new();
+            for (package, (package_files, _foreign)) in &self.packages {
+                let package = Arc::new(package.clone());
+                for file in package_files {
+                    files.insert(file.clone(), Arc::clone(&package));
+                }
+            }
+            Ok(FileIndex { files })
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+            let file = self.get(path)?;
+            Ok(FileMetadata {
+                is_file: true,
+                mode: file.mode,
+                len: file.content.len() as u64,
+            })
+        }
+
+        fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+            let content = &self.get(path)?.content;
+            Ok(content[..len.min(content.len())].to_vec())
+        }
+
+        fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+            Ok(self.get(path)?.missing_deps.clone())
+        }
+
+        fn linked_libraries(
+            &self,
+            path: &Path,
+        ) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+            Ok(self.get(path)?.linked.clone())
+        }
+
+        fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+            let empty = self.get(path)?.content.is_empty();
+            Ok(empty.then_some(FileDamage::Empty))
+        }
+
+        fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+            let file = self.get(path)?;
+            if !file.content.starts_with(b"\x7fELF") {
+                return Ok(None);
+            }
+            Ok(Some(ElfInfo {
+                class: header::ELFCLASS64,
+                machine: header::EM_X86_64,
+                needed: file
+                    .linked
+                    .iter()
+                    .map(|(s, _p)| s.clone())
+                    .chain(file.missing_deps.iter().cloned())
+                    .collect(),
+                rpath: Vec::new(),
+                runpath: Vec::new(),
+            }))
+        }
+
+        fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+            elf::debug_info(&mut io::Cursor::new(&self.get(path)?.content))
+        }
+
+        fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+            Ok(HeldPackages::default())
+        }
+
+        fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+            Ok(ShippedSonames::new(self.shipped_sonames.iter().cloned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_real_system() {
+        let system = RealSystem::default();
+        let exe = env::current_exe().unwrap();
+        assert!(system.missing_dependencies(&exe).unwrap().is_empty());
+        assert!(system.missing_dependency_chains(&exe).unwrap().is_empty());
+        let libraries = system.linked_libraries(&exe).unwrap();
+        assert!(!libraries.is_empty());
+        assert!(libraries.iter().all(|(_soname, path)| path.is_some()));
+
+        let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert!(system.missing_dependencies(&script).unwrap().is_empty());
+        assert!(system.linked_libraries(&script).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! Detection of the optional external programs some checks rely on, so that a check whose program
+//! is absent is disabled with a note at startup, instead of failing in the middle of a run
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+/// External program, and the package providing it
+#[derive(Debug, PartialEq, Eq)]
+pub struct Tool {
+    pub program: &'static str,
+    pub package: &'static str,
+}
+
+pub const BSDTAR: Tool = Tool {
+    program: "bsdtar",
+    package: "libarchive",
+};
+
+pub const GLIB_COMPILE_SCHEMAS: Tool = Tool {
+    program: "glib-compile-schemas",
+    package: "glib2",
+};
+
+pub const PKGFILE: Tool = Tool {
+    program: "pkgfile",
+    package: "pkgfile",
+};
+
+pub const SYSTEMD_ANALYZE: Tool = Tool {
+    program: "systemd-analyze",
+    package: "systemd",
+};
+
+impl Tool {
+    /// Return true if the program is installed, looked up once
+    pub fn is_available(&self) -> bool {
+        static AVAILABLE: OnceLock<Mutex<HashMap<&'static str, bool>>> = OnceLock::new();
+        *AVAILABLE
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(self.program)
+            .or_insert_with(|| is_in_path(self.program))
+    }
+}
+
+/// Return true if an executable program is in PATH
+pub fn is_in_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|p| env::split_paths(&p).any(|d| d.join(program).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_available() {
+        assert!(is_in_path("sh"));
+        assert!(!is_in_path("doesnotexist-program"));
+        let tool = Tool {
+            program: "doesnotexist-program",
+            package: "doesnotexist",
+        };
+        assert!(!tool.is_available());
+    }
+}
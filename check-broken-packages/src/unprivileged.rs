@@ -0,0 +1,485 @@
+//! Analysis of package files by helper processes running as an unprivileged user when running as
+//! root (like pacman hooks and the daemon do), so that files of foreign packages are never parsed,
+//! nor given to ldd, with root privileges
+
+use std::env;
+use std::error;
+use std::ffi::{CString, OsString};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::elf::{DebugInfo, ElfInfo, MissingLibrary};
+use crate::held::HeldPackages;
+use crate::pacman::{FileIndex, LocalPackage};
+use crate::report::{FileDamage, HardeningIssue};
+use crate::shipped::ShippedSonames;
+use crate::soname_index::SonameIndex;
+use crate::system::{
+    FileMetadata, LinkedLibrary, LoaderError, RealSystem, RootSystem, SystemProvider,
+};
+
+/// User analyzing package files by default
+pub const DEFAULT_USER: &str = "nobody";
+
+/// Unprivileged user of the helpers
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+impl User {
+    /// Get user from the user database
+    pub fn lookup(name: &str) -> Result<User, Box<dyn error::Error>> {
+        let c_name = CString::new(name)?;
+        // Only called before worker threads are started, getpwnam is not thread safe
+        let passwd = unsafe { libc::getpwnam(c_name.as_ptr()) };
+        if passwd.is_null() {
+            return Err(format!("No user '{}'", name).into());
+        }
+        let (uid, gid) = unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) };
+        if uid == 0 {
+            return Err(format!("User '{}' is privileged", name).into());
+        }
+        Ok(User {
+            name: name.to_string(),
+            uid,
+            gid,
+        })
+    }
+}
+
+/// System provider the helpers analyze files with, the same as the one of the analysis
+#[derive(Debug, Clone, Default)]
+pub struct HelperSystem {
+    /// Root directory of a system whose databases are read directly, None for the running system
+    pub root: Option<PathBuf>,
+    /// Soname index file dependencies are resolved with
+    pub soname_index: Option<PathBuf>,
+    pub ldd: bool,
+}
+
+impl HelperSystem {
+    /// Get command line arguments of the helper building this system provider
+    fn args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if self.ldd {
+            args.push(OsString::from("--ldd"));
+        }
+        if let Some(root) = &self.root {
+            args.extend([OsString::from("--root"), root.clone().into_os_string()]);
+        }
+        if let Some(soname_index) = &self.soname_index {
+            args.extend([
+                OsString::from("--import-soname-index"),
+                soname_index.clone().into_os_string(),
+            ]);
+        }
+        args
+    }
+
+    /// Build system provider in a helper
+    fn build(&self) -> Result<Box<dyn SystemProvider>, Box<dyn error::Error>> {
+        if self.root.is_none() && self.soname_index.is_none() {
+            return Ok(Box::new(RealSystem { ldd: self.ldd }));
+        }
+        let root = self.root.as_deref().unwrap_or_else(|| Path::new("/"));
+        let soname_index = self
+            .soname_index
+            .as_ref()
+            .map(|f| SonameIndex::load(f, root).map(Arc::new))
+            .transpose()?;
+        Ok(Box::new(RootSystem::without_databases(
+            root,
+            soname_index,
+            self.ldd,
+        )))
+    }
+}
+
+/// Query of a file sent to a helper, one JSON line each
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Query {
+    Damage(PathBuf),
+    MissingDependencies(PathBuf),
+    MissingDependencyChains(PathBuf),
+    LinkedLibraries(PathBuf),
+    ElfInfo(PathBuf),
+    HardeningIssues(PathBuf),
+    DebugInfo(PathBuf),
+}
+
+/// Answer of a helper to a query, one JSON line each
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Answer {
+    Damage(Option<FileDamage>),
+    MissingDependencies(Vec<String>),
+    MissingDependencyChains(Vec<MissingLibrary>),
+    LinkedLibraries(Vec<LinkedLibrary>),
+    ElfInfo(Option<ElfInfo>),
+    HardeningIssues(Option<Vec<HardeningIssue>>),
+    DebugInfo(Option<DebugInfo>),
+    /// The helper user can not read the file
+    PermissionDenied,
+    LoaderError(String),
+    Failed(String),
+}
+
+impl Answer {
+    fn from_error(err: Box<dyn error::Error>) -> Answer {
+        match err.downcast::<LoaderError>() {
+            Ok(err) => Answer::LoaderError(err.0),
+            Err(err) => match err.downcast_ref::<io::Error>() {
+                Some(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                    Answer::PermissionDenied
+                }
+                _ => Answer::Failed(err.to_string()),
+            },
+        }
+    }
+
+    /// Get error of an answer that is not the one expected for its query
+    fn into_error(self) -> Box<dyn error::Error> {
+        match self {
+            Answer::PermissionDenied => io::Error::from(io::ErrorKind::PermissionDenied).into(),
+            Answer::LoaderError(error) => LoaderError(error).into(),
+            Answer::Failed(error) => error.into(),
+            answer => format!("Unexpected helper answer {:?}", answer).into(),
+        }
+    }
+
+    /// Get I/O error of an answer that is not the one expected for its query
+    fn into_io_error(self) -> io::Error {
+        match self {
+            Answer::PermissionDenied => io::Error::from(io::ErrorKind::PermissionDenied),
+            answer => io::Error::other(answer.into_error().to_string()),
+        }
+    }
+}
+
+/// Helper process answering the queries sent to its standard input, one at a time
+struct Helper {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Helper {
+    /// Start helper running as a user, without supplementary groups (dropped by the standard
+    /// library when changing user as root)
+    fn spawn(user: &User, system: &HelperSystem) -> io::Result<Helper> {
+        let mut child = Command::new(env::current_exe()?)
+            .args(system.args())
+            .arg("unprivileged-helper")
+            .uid(user.uid)
+            .gid(user.gid)
+            .current_dir("/")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Helper {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn query(&mut self, query: &Query) -> Result<Answer, Box<dyn error::Error>> {
+        let stdin = self.stdin.as_mut().ok_or("Helper is stopped")?;
+        writeln!(stdin, "{}", serde_json::to_string(query)?)?;
+        stdin.flush()?;
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err("Helper exited".into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        // Closing its standard input stops the helper
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+/// System provider parsing files in helpers running as an unprivileged user, and reading
+/// everything else from an inner provider. Helpers are started as needed, one per thread querying
+/// at the same time, and kept for later queries. A helper that fails is replaced, files are never
+/// parsed with the privileges of the current user instead.
+pub struct UnprivilegedSystem {
+    inner: Box<dyn SystemProvider>,
+    user: User,
+    system: HelperSystem,
+    /// Helpers waiting for a query
+    idle: Mutex<Vec<Helper>>,
+}
+
+impl UnprivilegedSystem {
+    /// Wrap a system provider, with helpers building the same provider, failing if a helper can
+    /// not be started
+    pub fn start(
+        inner: Box<dyn SystemProvider>,
+        user: User,
+        system: HelperSystem,
+    ) -> io::Result<UnprivilegedSystem> {
+        let helper = Helper::spawn(&user, &system)?;
+        Ok(UnprivilegedSystem {
+            inner,
+            user,
+            system,
+            idle: Mutex::new(vec![helper]),
+        })
+    }
+
+    fn query(&self, query: &Query) -> Result<Answer, Box<dyn error::Error>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let mut helper = match idle {
+            Some(h) => h,
+            None => Helper::spawn(&self.user, &self.system).map_err(|e| {
+                format!(
+                    "Failed to start analysis helper as user '{}': {}",
+                    self.user.name, e
+                )
+            })?,
+        };
+        // A helper that failed is dropped, and replaced by the next query
+        let answer = helper.query(query)?;
+        self.idle.lock().unwrap().push(helper);
+        Ok(answer)
+    }
+
+    /// Send a query, with errors of queries answered with I/O results
+    fn query_io(&self, query: &Query) -> io::Result<Answer> {
+        self.query(query)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+impl SystemProvider for UnprivilegedSystem {
+    fn foreign_packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.foreign_packages()
+    }
+
+    fn packages(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.packages()
+    }
+
+    fn local_packages(&self) -> Result<Vec<LocalPackage>, Box<dyn error::Error>> {
+        self.inner.local_packages()
+    }
+
+    fn package_files(&self, package: &str) -> Result<Vec<String>, Box<dyn error::Error>> {
+        self.inner.package_files(package)
+    }
+
+    fn file_index(&self) -> Result<FileIndex, Box<dyn error::Error>> {
+        self.inner.file_index()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.inner.metadata(path)
+    }
+
+    fn read_head(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        self.inner.read_head(path, len)
+    }
+
+    fn missing_dependencies(&self, path: &Path) -> Result<Vec<String>, Box<dyn error::Error>> {
+        match self.query(&Query::MissingDependencies(path.to_path_buf()))? {
+            Answer::MissingDependencies(d) => Ok(d),
+            answer => Err(answer.into_error()),
+        }
+    }
+
+    fn missing_dependency_chains(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<MissingLibrary>, Box<dyn error::Error>> {
+        match self.query(&Query::MissingDependencyChains(path.to_path_buf()))? {
+            Answer::MissingDependencyChains(c) => Ok(c),
+            answer => Err(answer.into_error()),
+        }
+    }
+
+    fn linked_libraries(&self, path: &Path) -> Result<Vec<LinkedLibrary>, Box<dyn error::Error>> {
+        match self.query(&Query::LinkedLibraries(path.to_path_buf()))? {
+            Answer::LinkedLibraries(l) => Ok(l),
+            answer => Err(answer.into_error()),
+        }
+    }
+
+    fn damage(&self, path: &Path) -> io::Result<Option<FileDamage>> {
+        match self.query_io(&Query::Damage(path.to_path_buf()))? {
+            Answer::Damage(d) => Ok(d),
+            answer => Err(answer.into_io_error()),
+        }
+    }
+
+    fn elf_info(&self, path: &Path) -> Result<Option<ElfInfo>, Box<dyn error::Error>> {
+        match self.query(&Query::ElfInfo(path.to_path_buf()))? {
+            Answer::ElfInfo(i) => Ok(i),
+            answer => Err(answer.into_error()),
+        }
+    }
+
+    fn hardening_issues(&self, path: &Path) -> io::Result<Option<Vec<HardeningIssue>>> {
+        match self.query_io(&Query::HardeningIssues(path.to_path_buf()))? {
+            Answer::HardeningIssues(i) => Ok(i),
+            answer => Err(answer.into_io_error()),
+        }
+    }
+
+    fn debug_info(&self, path: &Path) -> io::Result<Option<DebugInfo>> {
+        match self.query_io(&Query::DebugInfo(path.to_path_buf()))? {
+            Answer::DebugInfo(i) => Ok(i),
+            answer => Err(answer.into_io_error()),
+        }
+    }
+
+    fn held_packages(&self) -> Result<HeldPackages, Box<dyn error::Error>> {
+        self.inner.held_packages()
+    }
+
+    fn shipped_sonames(&self) -> Result<ShippedSonames, Box<dyn error::Error>> {
+        self.inner.shipped_sonames()
+    }
+}
+
+/// Answer a query of a file with a system provider
+fn answer(system: &dyn SystemProvider, query: Query) -> Answer {
+    let res = match query {
+        Query::Damage(path) => system
+            .damage(&path)
+            .map(Answer::Damage)
+            .map_err(|e| e.into()),
+        Query::MissingDependencies(path) => system
+            .missing_dependencies(&path)
+            .map(Answer::MissingDependencies),
+        Query::MissingDependencyChains(path) => system
+            .missing_dependency_chains(&path)
+            .map(Answer::MissingDependencyChains),
+        Query::LinkedLibraries(path) => system.linked_libraries(&path).map(Answer::LinkedLibraries),
+        Query::ElfInfo(path) => system.elf_info(&path).map(Answer::ElfInfo),
+        Query::HardeningIssues(path) => system
+            .hardening_issues(&path)
+            .map(Answer::HardeningIssues)
+            .map_err(|e| e.into()),
+        Query::DebugInfo(path) => system
+            .debug_info(&path)
+            .map(Answer::DebugInfo)
+            .map_err(|e| e.into()),
+    };
+    res.unwrap_or_else(Answer::from_error)
+}
+
+/// Answer queries read from standard input as the unprivileged helper, one JSON line each
+pub fn run_helper(system: &HelperSystem) -> Result<(), Box<dyn error::Error>> {
+    let system = system.build()?;
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let query: Query = serde_json::from_str(&line?)?;
+        writeln!(
+            stdout,
+            "{}",
+            serde_json::to_string(&answer(system.as_ref(), query))?
+        )?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::mock::MockSystem;
+
+    #[test]
+    fn test_lookup_user() {
+        assert!(User::lookup("root").is_err());
+        assert!(User::lookup("no-such-user-for-sure").is_err());
+    }
+
+    #[test]
+    fn test_helper_system() {
+        use clap::Parser;
+
+        use crate::cl::{Command, CommandLineOpts};
+
+        assert!(HelperSystem::default().args().is_empty());
+        let system = HelperSystem {
+            root: Some(PathBuf::from("/mnt")),
+            soname_index: Some(PathBuf::from("/var/cache/index")),
+            ldd: true,
+        };
+        let mut args = vec![OsString::from("check-broken-packages")];
+        args.extend(system.args());
+        args.push(OsString::from("unprivileged-helper"));
+        let cl_opts = CommandLineOpts::try_parse_from(args).unwrap();
+        assert!(matches!(cl_opts.command, Some(Command::UnprivilegedHelper)));
+        assert_eq!(cl_opts.root, system.root);
+        assert_eq!(cl_opts.import_soname_index, system.soname_index);
+        assert!(cl_opts.ldd);
+
+        let tmp_dir = tempdir::TempDir::new("").unwrap();
+        let system = HelperSystem {
+            root: Some(tmp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(system.build().is_ok());
+        let system = HelperSystem {
+            soname_index: Some(tmp_dir.path().join("missing")),
+            ..system
+        };
+        assert!(system.build().is_err());
+    }
+
+    #[test]
+    fn test_answer() {
+        let system = MockSystem::default()
+            .package("app", true, &["/usr/bin/app"])
+            .file("/usr/bin/app", 0o755, b"\x7fELF", &["libfoo.so.1"]);
+        let path = PathBuf::from("/usr/bin/app");
+
+        let query = Query::MissingDependencies(path.clone());
+        let line = serde_json::to_string(&query).unwrap();
+        assert_eq!(line, r#"{"missing_dependencies":"/usr/bin/app"}"#);
+        assert_eq!(
+            answer(&system, serde_json::from_str(&line).unwrap()),
+            Answer::MissingDependencies(vec!["libfoo.so.1".to_string()])
+        );
+
+        assert!(matches!(
+            answer(&system, Query::ElfInfo(path.clone())),
+            Answer::ElfInfo(Some(i)) if i.needed == ["libfoo.so.1"]
+        ));
+        assert_eq!(
+            answer(&system, Query::HardeningIssues(path)),
+            Answer::HardeningIssues(None)
+        );
+        assert!(matches!(
+            answer(&system, Query::DebugInfo(PathBuf::from("/usr/bin/other"))),
+            Answer::Failed(_)
+        ));
+
+        assert_eq!(
+            Answer::from_error(io::Error::from(io::ErrorKind::PermissionDenied).into()),
+            Answer::PermissionDenied
+        );
+        assert!(Answer::LoaderError("foo".to_string())
+            .into_error()
+            .downcast_ref::<LoaderError>()
+            .is_some());
+    }
+}
@@ -0,0 +1,143 @@
+//! Prediction of the dependencies a pending upgrade will break, from sync databases synchronized
+//! before the upgrade is applied (in a PreTransaction hook)
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::elf;
+use crate::pacman::{self, provided_sonames, LocalPackage, SyncPackage};
+use crate::plugins;
+use crate::report::Finding;
+use crate::system::SystemProvider;
+
+/// Get sonames no longer provided by any package after upgrade, with the package that provided
+/// it and its sync version
+fn removed_sonames(
+    local_packages: &[LocalPackage],
+    sync_packages: &HashMap<String, SyncPackage>,
+) -> HashMap<String, (String, String)> {
+    let mut provided_after = HashSet::new();
+    let mut candidates = HashMap::new();
+    for package in local_packages {
+        match sync_packages.get(&package.name) {
+            Some(sync_package) if sync_package.version != package.version => {
+                let new_sonames: HashSet<String> =
+                    provided_sonames(&sync_package.provides).collect();
+                for soname in provided_sonames(&package.provides) {
+                    if !new_sonames.contains(&soname) {
+                        candidates
+                            .insert(soname, (package.name.clone(), sync_package.version.clone()));
+                    }
+                }
+                provided_after.extend(new_sonames);
+            }
+            _ => provided_after.extend(provided_sonames(&package.provides)),
+        }
+    }
+    candidates.retain(|soname, _| !provided_after.contains(soname));
+    candidates
+}
+
+/// Predict files of foreign packages of a system at a root directory that will miss a shared
+/// library after the pending upgrade, their files being parsed by a system provider
+pub fn predict_breakage(
+    root: &Path,
+    config: &Config,
+    system: &dyn SystemProvider,
+) -> Result<Vec<Finding>, Box<dyn error::Error>> {
+    let local_packages =
+        pacman::read_local_db(&elf::rooted(root, Path::new(pacman::LOCAL_DB_DIR)))?;
+    let sync_packages = pacman::read_sync_db(&elf::rooted(root, Path::new(pacman::SYNC_DB_DIR)))?;
+    let removed = removed_sonames(&local_packages, &sync_packages);
+
+    let mut findings = Vec::new();
+    if removed.is_empty() {
+        return Ok(findings);
+    }
+    for package in local_packages
+        .iter()
+        .filter(|p| !sync_packages.contains_key(&p.name))
+    {
+        for file in &package.files {
+            let path = Path::new(file);
+            if config.ignore_path.iter().any(|p| p.matches(path)) {
+                continue;
+            }
+            let rooted_path = elf::rooted(root, path);
+            let is_shared_object = path
+                .file_name()
+                .is_some_and(|f| plugins::is_shared_object_name(&f.to_string_lossy()));
+            let is_executable = fs::metadata(&rooted_path)
+                .is_ok_and(|m| m.is_file() && (m.permissions().mode() & 0o111 != 0));
+            if !is_shared_object && !is_executable {
+                continue;
+            }
+            let elf_info = match system.elf_info(&rooted_path) {
+                Ok(Some(e)) => e,
+                // Not an ELF file, or the post transaction analysis will report it
+                Ok(None) | Err(_) => continue,
+            };
+            for dependency in elf_info.needed {
+                if let Some((provider, provider_version)) = removed.get(&dependency) {
+                    findings.push(Finding::UpgradeRemovesDependency {
+                        package: package.name.clone(),
+                        file: file.clone(),
+                        dependency,
+                        provider: provider.clone(),
+                        provider_version: provider_version.clone(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removed_sonames() {
+        let local_package = |name: &str, version: &str, provides: &[&str]| LocalPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: String::new(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+            depends: Vec::new(),
+            groups: Vec::new(),
+            installed_size: 0,
+            install_date: 0,
+            files: Vec::new(),
+        };
+        let sync_package = |name: &str, version: &str, provides: &[&str]| SyncPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+        };
+        let local_packages = [
+            local_package("foo", "1-1", &["libfoo.so=1-64", "libfoo-extra.so=1-64"]),
+            local_package("foo-compat", "1-1", &["libfoo-extra.so=1-64"]),
+            local_package("bar", "1-1", &["libbar.so=1-64"]),
+        ];
+        let sync_packages: HashMap<String, SyncPackage> = vec![
+            sync_package("foo", "2-1", &["libfoo.so=2-64"]),
+            sync_package("foo-compat", "1-1", &["libfoo-extra.so=1-64"]),
+            sync_package("bar", "1-1", &["libbar.so=1-64"]),
+        ]
+        .into_iter()
+        .map(|p| (p.name.clone(), p))
+        .collect();
+
+        let removed = removed_sonames(&local_packages, &sync_packages);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            removed["libfoo.so.1"],
+            ("foo".to_string(), "2-1".to_string())
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Parallel walk of huge directory trees (plugin roots, Python site-packages...), where a
+//! sequential walk would dominate the analysis time
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crossbeam::thread as cb_thread;
+
+use crate::ignore::IgnorePath;
+
+/// Maximum number of files passed at once to the visit function, so that a directory with a huge
+/// number of files does not delay the others
+const CHUNK_SIZE: usize = 256;
+
+/// How long an idle worker waits for a directory before checking whether the walk is done
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Walk directory trees with a number of worker threads, calling a function (from any worker)
+/// with chunks of the regular files found, excluding ignored paths. Each walked directory
+/// increments a progress counter. Symbolic links are not followed.
+pub fn walk<F: Fn(&[PathBuf]) + Sync>(
+    roots: &[&Path],
+    jobs: usize,
+    ignore_paths: &[IgnorePath],
+    progress: &AtomicUsize,
+    visit: F,
+) {
+    let (dir_tx, dir_rx) = crossbeam::unbounded::<PathBuf>();
+
+    // Directories sent and not walked yet, the walk is done when it drops to 0
+    let pending = AtomicUsize::new(roots.len());
+    for root in roots {
+        dir_tx.send(root.to_path_buf()).unwrap();
+    }
+
+    let pending = &pending;
+    let visit = &visit;
+    cb_thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            let dir_tx = dir_tx.clone();
+            let dir_rx = dir_rx.clone();
+            scope.spawn(move |_| loop {
+                let dir = match dir_rx.recv_timeout(IDLE_POLL_INTERVAL) {
+                    Ok(d) => d,
+                    Err(_) if pending.load(Ordering::SeqCst) == 0 => break,
+                    Err(_) => continue,
+                };
+                let mut files = Vec::new();
+                for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                    let path = entry.path();
+                    if ignore_paths.iter().any(|p| p.matches(&path)) {
+                        continue;
+                    }
+                    match entry.file_type() {
+                        Ok(t) if t.is_dir() => {
+                            pending.fetch_add(1, Ordering::SeqCst);
+                            dir_tx.send(path).unwrap();
+                        }
+                        Ok(t) if t.is_file() => {
+                            files.push(path);
+                            if files.len() == CHUNK_SIZE {
+                                visit(&files);
+                                files.clear();
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                if !files.is_empty() {
+                    visit(&files);
+                }
+                progress.fetch_add(1, Ordering::SeqCst);
+                pending.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_walk() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let root = tmp_dir.path();
+        let mut expected = Vec::new();
+        for i in 0..10 {
+            let dir = root.join(format!("{}/sub", i));
+            fs::create_dir_all(&dir).unwrap();
+            // One directory has more files than a chunk
+            for j in 0..(if i == 0 { CHUNK_SIZE + 1 } else { 2 }) {
+                let filepath = dir.join(format!("{}.py", j));
+                fs::write(&filepath, "").unwrap();
+                expected.push(filepath);
+            }
+        }
+        fs::create_dir_all(root.join("ignored")).unwrap();
+        fs::write(root.join("ignored/foo.py"), "").unwrap();
+        let ignore_paths = vec![format!("{}/ignored", root.display()).parse().unwrap()];
+
+        let files = Mutex::new(Vec::new());
+        let progress = AtomicUsize::new(0);
+        walk(&[root], 4, &ignore_paths, &progress, |chunk| {
+            assert!(chunk.len() <= CHUNK_SIZE);
+            files.lock().unwrap().extend_from_slice(chunk);
+        });
+        let mut files = files.into_inner().unwrap();
+        files.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(files, expected);
+        assert_eq!(progress.load(Ordering::SeqCst), 21);
+    }
+}
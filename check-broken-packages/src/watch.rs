@@ -0,0 +1,169 @@
+//! Watch mode for package developers: the ELF files of a build output directory are analyzed again
+//! each time they change, for instant feedback while iterating on a PKGBUILD
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use ansi_term::Colour::Green;
+
+use crate::elf::Resolver;
+use crate::render::Renderer;
+use crate::report::Finding;
+use crate::system::SystemProvider;
+
+/// Interval between two scans of the watched directory, files are analyzed once unchanged for a
+/// whole interval, when the build stopped writing them
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Modification time and size of the regular files under a directory
+type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+fn snapshot(dir: &Path) -> Snapshot {
+    let pattern = format!("{}/**/*", glob::Pattern::escape(&dir.to_string_lossy()));
+    glob::glob(&pattern)
+        .map(|paths| {
+            paths
+                .flatten()
+                .filter_map(|p| {
+                    let metadata = fs::symlink_metadata(&p).ok()?;
+                    let modified = metadata.modified().ok()?;
+                    metadata
+                        .is_file()
+                        .then_some((p, (modified, metadata.len())))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get sonames missing for an ELF file, libraries of the watched directory being found by
+/// filename, None if it is not an ELF file
+fn missing_dependencies(
+    system: &dyn SystemProvider,
+    resolver: &Resolver,
+    path: &Path,
+    bundled: &HashSet<OsString>,
+) -> Option<Vec<String>> {
+    let elf_info = system.elf_info(path).ok()??;
+    Some(
+        resolver
+            .resolve(&elf_info, path)
+            .into_iter()
+            .filter(|r| r.found.is_none() && !bundled.contains(&OsString::from(&r.soname)))
+            .map(|r| r.soname)
+            .collect(),
+    )
+}
+
+/// Analyze the ELF files of a directory, parsed by a system provider, and again each time they
+/// change, until cancelled
+pub fn watch(
+    dir: &Path,
+    renderer: &Renderer,
+    json: bool,
+    cancelled: &AtomicBool,
+    system: &dyn SystemProvider,
+) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:?} is not a directory", dir),
+        ));
+    }
+    let package = dir
+        .file_name()
+        .map_or_else(|| dir.to_string_lossy(), |n| n.to_string_lossy())
+        .to_string();
+    let resolver = Resolver::new();
+    let mut previous = Snapshot::new();
+    let mut pending: BTreeSet<PathBuf> = BTreeSet::new();
+    while !cancelled.load(Ordering::SeqCst) {
+        let current = snapshot(dir);
+        let changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(p, s)| previous.get(*p) != Some(*s))
+            .map(|(p, _s)| p.clone())
+            .collect();
+        if changed.is_empty() && !pending.is_empty() {
+            let bundled: HashSet<OsString> = current
+                .keys()
+                .filter_map(|p| p.file_name())
+                .map(OsString::from)
+                .collect();
+            for path in &pending {
+                let missing_deps = match missing_dependencies(system, &resolver, path, &bundled) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                let file = path.to_string_lossy().to_string();
+                if missing_deps.is_empty() && !json {
+                    println!("{}", Green.paint(format!("✓ {}", file)));
+                }
+                for dependency in missing_deps {
+                    let finding = Finding::MissingDependency {
+                        package: package.clone(),
+                        file: file.clone(),
+                        dependency,
+                        held_back: None,
+                        chain: Vec::new(),
+                        transitive: None,
+                        unshipped: false,
+                    };
+                    if json {
+                        println!("{}", serde_json::to_string(&finding).unwrap());
+                    } else {
+                        println!("{}", renderer.finding(&finding));
+                    }
+                }
+            }
+            pending.clear();
+        }
+        pending.extend(changed);
+        previous = current;
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::system::RealSystem;
+
+    #[test]
+    fn test_watch_helpers() {
+        let tmp_dir = TempDir::new("").unwrap();
+        fs::create_dir_all(tmp_dir.path().join("usr/bin")).unwrap();
+        let exe_filepath = tmp_dir.path().join("usr/bin/foo");
+        fs::copy(env::current_exe().unwrap(), &exe_filepath).unwrap();
+        fs::write(tmp_dir.path().join("README"), "foo").unwrap();
+
+        let files = snapshot(tmp_dir.path());
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [&tmp_dir.path().join("README"), &exe_filepath]
+        );
+
+        let system = RealSystem::default();
+        let resolver = Resolver::new();
+        let bundled = HashSet::new();
+        assert_eq!(
+            missing_dependencies(&system, &resolver, &exe_filepath, &bundled),
+            Some(Vec::new())
+        );
+        assert_eq!(
+            missing_dependencies(&system, &resolver, &tmp_dir.path().join("README"), &bundled),
+            None
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Discovery of the library directories that shell wrapper scripts add to LD_LIBRARY_PATH before
+//! running the real executable, typical of big proprietary applications
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Read a shell word at the start of a string, with its quotes removed, and the rest of the string
+fn shell_word(s: &str) -> (String, &str) {
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    // Nesting depth of command substitutions, like "$(dirname "$0")"
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, c) if (c.is_whitespace() || c == ';') && depth == 0 => {
+                return (word, &s[i..]);
+            }
+            (None, '\'') | (None, '"') if depth == 0 => quote = Some(c),
+            (Some(q), c) if c == q && depth == 0 => quote = None,
+            (_, '(') if word.ends_with('$') => {
+                depth += 1;
+                word.push(c);
+            }
+            (_, ')') if depth > 0 => {
+                depth -= 1;
+                word.push(c);
+            }
+            (_, c) => word.push(c),
+        }
+    }
+    (word, "")
+}
+
+/// Expand references to known variables in a value, like "$HERE" or "${HERE}"
+fn expand(value: &str, variables: &HashMap<String, String>) -> String {
+    let mut expanded = value.to_string();
+    // Longest names first, so that "$APP" is not expanded in "$APP_DIR"
+    let mut variables: Vec<(&String, &String)> = variables.iter().collect();
+    variables.sort_unstable_by_key(|(n, _v)| std::cmp::Reverse(n.len()));
+    for (name, variable_value) in variables {
+        expanded = expanded
+            .replace(&format!("${{{}}}", name), variable_value)
+            .replace(&format!("${}", name), variable_value);
+    }
+    expanded
+}
+
+/// Get directories a shell script adds to LD_LIBRARY_PATH, with variables assigned by the script
+/// expanded, and the script directory for the usual "$(dirname "$0")" idioms. Directories that
+/// can not be determined statically are skipped.
+pub fn library_path_dirs(script: &str, script_path: &Path) -> Vec<PathBuf> {
+    let script_dir = script_path
+        .parent()
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut dirs = Vec::new();
+    for line in script.lines() {
+        let mut statement = line.trim();
+        // Assignments at the start of the line, possibly prefixing a command
+        loop {
+            statement = statement
+                .strip_prefix("export ")
+                .unwrap_or(statement)
+                .trim();
+            let (name, value) = match statement.split_once('=') {
+                Some((n, v))
+                    if !n.is_empty()
+                        && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+                {
+                    (n, v)
+                }
+                _ => break,
+            };
+            let (value, rest) = shell_word(value);
+            statement = rest.trim_start_matches([';', ' ', '\t']);
+            let value = if value.contains("dirname") && value.contains("$0") {
+                script_dir.clone()
+            } else {
+                expand(&value, &variables)
+            };
+            if name == "LD_LIBRARY_PATH" {
+                // Previous value, like "${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}"
+                let mut value = value;
+                while let Some(start) = value.find("${LD_LIBRARY_PATH") {
+                    let end = value[start..]
+                        .find('}')
+                        .map_or(value.len(), |e| start + e + 1);
+                    value.replace_range(start..end, "");
+                }
+                for dir in value
+                    .split(':')
+                    .filter(|d| d.starts_with('/') && !d.contains('$'))
+                    .map(PathBuf::from)
+                {
+                    if !dirs.contains(&dir) {
+                        dirs.push(dir);
+                    }
+                }
+            } else {
+                variables.insert(name.to_string(), value);
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_path_dirs() {
+        let script = r#"#!/bin/bash
+HERE="$(dirname "$(readlink -f "$0")")"
+APP_DIR=/opt/app
+export LD_LIBRARY_PATH="$HERE/lib:${APP_DIR}/cef${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}"
+LD_LIBRARY_PATH=/opt/app/plugins:$UNKNOWN/lib exec "$HERE/app.bin" "$@"
+"#;
+        assert_eq!(
+            library_path_dirs(script, Path::new("/opt/app/app")),
+            [
+                PathBuf::from("/opt/app/lib"),
+                PathBuf::from("/opt/app/cef"),
+                PathBuf::from("/opt/app/plugins"),
+            ]
+        );
+        assert!(
+            library_path_dirs("#!/bin/sh\nexec /opt/app/app.bin\n", Path::new("/app")).is_empty()
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Run the analysis against fixture systems with --root, and compare findings with golden files
+//!
+//! Golden files are in tests/golden, run with UPDATE_GOLDEN=1 to regenerate them.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tempdir::TempDir;
+
+/// Fixture system, with a local pacman database, under a temporary root directory
+struct Fixture {
+    root: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Fixture {
+        let root = TempDir::new("fixture").unwrap();
+        let local_db_dir = root.path().join("var/lib/pacman/local");
+        fs::create_dir_all(&local_db_dir).unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        fs::write(local_db_dir.join("ALPM_DB_VERSION"), "9\n").unwrap();
+        // Empty configuration, so that the one of the running system is not used
+        fs::write(root.path().join("etc/check-broken-packages.toml"), "").unwrap();
+        Fixture { root }
+    }
+
+    /// Create a file in the fixture root, not owned by any package
+    fn file(&self, path: &str, mode: u32, content: &[u8]) -> &Fixture {
+        let filepath = self.root.path().join(path.trim_start_matches('/'));
+        fs::create_dir_all(filepath.parent().unwrap()).unwrap();
+        fs::write(&filepath, content).unwrap();
+        fs::set_permissions(&filepath, fs::Permissions::from_mode(mode)).unwrap();
+        self
+    }
+
+    /// Install a package with its files, each with permission bits and content
+    fn package(&self, name: &str, files: &[(&str, u32, Vec<u8>)]) -> &Fixture {
+        let entry_dir = self
+            .root
+            .path()
+            .join("var/lib/pacman/local")
+            .join(format!("{}-1.0-1", name));
+        fs::create_dir(&entry_dir).unwrap();
+        fs::write(
+            entry_dir.join("desc"),
+            format!("%NAME%\n{}\n\n%VERSION%\n1.0-1\n\n", name),
+        )
+        .unwrap();
+        let mut files_content = "%FILES%\n".to_string();
+        for (path, mode, content) in files {
+            self.file(path, *mode, content);
+            files_content.push_str(path.trim_start_matches('/'));
+            files_content.push('\n');
+        }
+        fs::write(entry_dir.join("files"), files_content).unwrap();
+        self
+    }
+
+    /// Run analysis with extra arguments, and get JSON findings sorted, since their order depends
+    /// on thread scheduling
+    fn findings(&self, args: &[&str]) -> Vec<String> {
+        let report_filepath = self.root.path().join("report.jsonl");
+        let output = Command::new(env!("CARGO_BIN_EXE_check-broken-packages"))
+            .args(args)
+            .arg("--json")
+            .arg("--root")
+            .arg(self.root.path())
+            .arg("--report")
+            .arg(&report_filepath)
+            .arg("--config")
+            .arg(self.root.path().join("etc/check-broken-packages.toml"))
+            // The build directory may not be readable by the unprivileged user, when running as
+            // root
+            .arg("--analysis-user")
+            .arg("root")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let mut findings: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        findings.sort_unstable();
+        findings
+    }
+}
+
+/// Build a minimal x86-64 ELF shared object, only with what is needed to read its DT_NEEDED entries
+fn elf(needed: &[&str]) -> Vec<u8> {
+    const EHDR_SIZE: u64 = 64;
+    const PHDR_SIZE: u64 = 56;
+    let dyn_offset = EHDR_SIZE + 2 * PHDR_SIZE;
+    let dyn_size = (needed.len() as u64 + 3) * 16;
+    let strtab_offset = dyn_offset + dyn_size;
+    let mut strtab = vec![0];
+    let mut needed_offsets = Vec::new();
+    for soname in needed {
+        needed_offsets.push(strtab.len() as u64);
+        strtab.extend(soname.as_bytes());
+        strtab.push(0);
+    }
+    let size = strtab_offset + strtab.len() as u64;
+
+    let mut data = Vec::new();
+    let u16 = |d: &mut Vec<u8>, v: u16| d.extend(v.to_le_bytes());
+    let u32 = |d: &mut Vec<u8>, v: u32| d.extend(v.to_le_bytes());
+    let u64 = |d: &mut Vec<u8>, v: u64| d.extend(v.to_le_bytes());
+    // ELF header: 64 bits, little endian, shared object, x86-64
+    data.extend(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+    u16(&mut data, 3);
+    u16(&mut data, 62);
+    u32(&mut data, 1);
+    u64(&mut data, 0);
+    u64(&mut data, EHDR_SIZE);
+    u64(&mut data, 0);
+    u32(&mut data, 0);
+    u16(&mut data, EHDR_SIZE as u16);
+    u16(&mut data, PHDR_SIZE as u16);
+    u16(&mut data, 2);
+    u16(&mut data, 64);
+    u16(&mut data, 0);
+    u16(&mut data, 0);
+    // PT_LOAD of the whole file, and PT_DYNAMIC
+    for (p_type, p_flags, offset, filesz) in [(1, 4, 0, size), (2, 6, dyn_offset, dyn_size)] {
+        u32(&mut data, p_type);
+        u32(&mut data, p_flags);
+        u64(&mut data, offset);
+        u64(&mut data, offset);
+        u64(&mut data, offset);
+        u64(&mut data, filesz);
+        u64(&mut data, filesz);
+        u64(&mut data, 8);
+    }
+    // Dynamic section: DT_NEEDED entries, DT_STRTAB, DT_STRSZ, DT_NULL
+    for offset in needed_offsets {
+        u64(&mut data, 1);
+        u64(&mut data, offset);
+    }
+    for (tag, value) in [(5, strtab_offset), (10, strtab.len() as u64), (0, 0)] {
+        u64(&mut data, tag);
+        u64(&mut data, value);
+    }
+    data.extend(strtab);
+    data
+}
+
+/// Compare findings with a golden file, or update it
+fn check_golden(name: &str, findings: &[String]) {
+    let golden_filepath: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.jsonl", name));
+    let actual: String = findings.iter().map(|f| format!("{}\n", f)).collect();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_filepath, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden_filepath).unwrap();
+    assert_eq!(
+        actual, expected,
+        "golden file {:?} mismatch",
+        golden_filepath
+    );
+}
+
+/// Fixture with missing libraries, directly and through another library
+fn dependencies_fixture() -> Fixture {
+    let fixture = Fixture::new();
+    fixture
+        .file(
+            "/etc/ld.so.conf",
+            0o644,
+            b"include /etc/ld.so.conf.d/*.conf\n",
+        )
+        .file("/etc/ld.so.conf.d/foo.conf", 0o644, b"/opt/foo/lib\n")
+        .package(
+            "foo",
+            &[
+                (
+                    "/usr/bin/foo",
+                    0o755,
+                    elf(&["libbar.so.2", "libfoo-private.so.1", "libgone.so.1"]),
+                ),
+                ("/opt/foo/lib/libfoo-private.so.1", 0o755, elf(&[])),
+                ("/usr/bin/foo-empty", 0o755, Vec::new()),
+                (
+                    "/usr/bin/foo-truncated",
+                    0o755,
+                    elf(&["libbar.so.2"])[..40].to_vec(),
+                ),
+                ("/usr/bin/foo-script", 0o755, b"#!/bin/sh\n".to_vec()),
+                ("/usr/share/foo/README", 0o644, b"foo".to_vec()),
+            ],
+        )
+        .package(
+            "bar",
+            &[("/usr/lib/libbar.so.2", 0o755, elf(&["libbaz.so.3"]))],
+        );
+    fixture
+}
+
+#[test]
+fn test_golden_dependencies() {
+    check_golden("dependencies", &dependencies_fixture().findings(&[]));
+}
+
+#[test]
+fn test_golden_dependency_tree() {
+    check_golden(
+        "dependency_tree",
+        &dependencies_fixture().findings(&["--resolve-tree"]),
+    );
+}